@@ -0,0 +1,28 @@
+use std::{
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// Embed the git commit and build time for the /status endpoint. Both can be
+// passed in from the environment, e.g. as Docker build args where .git is missing.
+fn main() {
+    let git_commit = std::env::var("GIT_COMMIT").ok().filter(|commit| !commit.is_empty()).or_else(|| {
+        Command::new("git")
+            .args(["rev-parse", "--short", "HEAD"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+    });
+    let build_time = std::env::var("BUILD_TIME").ok().filter(|time| !time.is_empty()).unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs().to_string())
+            .unwrap_or_default()
+    });
+    println!("cargo:rustc-env=GIT_COMMIT={}", git_commit.unwrap_or_else(|| "unknown".to_string()));
+    println!("cargo:rustc-env=BUILD_TIME={}", build_time);
+    println!("cargo:rerun-if-env-changed=GIT_COMMIT");
+    println!("cargo:rerun-if-env-changed=BUILD_TIME");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+}
@@ -0,0 +1,91 @@
+use axum::{
+    body::{self, Body, HttpBody},
+    extract::{ConnectInfo, Request},
+    http::{header, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+use std::{env, net::SocketAddr, sync::OnceLock, time::Instant};
+
+// Same limit axum applies to request bodies by default
+const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+static ENABLED: OnceLock<bool> = OnceLock::new();
+
+// Access logs are on unless ACCESS_LOG is false
+fn enabled() -> bool {
+    *ENABLED.get_or_init(|| {
+        env::var("ACCESS_LOG")
+            .map(|value| value != "false" && value != "0")
+            .unwrap_or(true)
+    })
+}
+
+// Log one line per request; the format follows LOG_FORMAT through the tracing subscriber
+pub async fn log_request(request: Request, next: Next) -> Response {
+    if !enabled() {
+        return next.run(request).await;
+    }
+    let started = Instant::now();
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let client_ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(peer)| peer.ip().to_string());
+
+    let (request, operation) = if method == Method::POST && path.ends_with("graphql") {
+        match operation_name(request).await {
+            Ok(peeked) => peeked,
+            Err(response) => return response,
+        }
+    } else {
+        (request, None)
+    };
+
+    let response = next.run(request).await;
+    let response_bytes = response
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .or_else(|| response.body().size_hint().exact());
+    tracing::info!(
+        target: "access",
+        method = %method,
+        path = %path,
+        operation = operation.as_deref(),
+        status = response.status().as_u16(),
+        latency_ms = started.elapsed().as_secs_f64() * 1000.0,
+        response_bytes,
+        client_ip = client_ip.as_deref(),
+    );
+    response
+}
+
+// Buffer the body to read the GraphQL operationName, then hand it back unchanged
+async fn operation_name(request: Request) -> Result<(Request, Option<String>), Response> {
+    let (parts, body) = request.into_parts();
+    let bytes = match body::to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            return Err((axum::http::StatusCode::PAYLOAD_TOO_LARGE, e.to_string()).into_response())
+        }
+    };
+    let names = match serde_json::from_slice::<Value>(&bytes) {
+        // batched requests log every named operation
+        Ok(Value::Array(operations)) => operations.iter().filter_map(name_of).collect(),
+        Ok(operation) => name_of(&operation).into_iter().collect(),
+        Err(_) => Vec::new(),
+    };
+    let operation = if names.is_empty() { None } else { Some(names.join(",")) };
+    Ok((Request::from_parts(parts, Body::from(bytes)), operation))
+}
+
+fn name_of(operation: &Value) -> Option<String> {
+    operation
+        .get("operationName")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
@@ -0,0 +1,106 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderName, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use ipnet::IpNet;
+use std::{
+    env,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+// Networks allowed to reach the admin (write) endpoints
+#[derive(Clone, Debug)]
+pub struct AdminAllowlist {
+    networks: Vec<IpNet>,
+}
+
+impl AdminAllowlist {
+    // Reads ADMIN_ALLOWED_IPS as a comma separated list of addresses or CIDR ranges.
+    // There is no default: behind a reverse proxy on the same host every request
+    // comes from loopback, so a loopback fallback would open writes to everyone.
+    pub fn from_env() -> Result<Self, String> {
+        let configured = env::var("ADMIN_ALLOWED_IPS")
+            .map_err(|_| "not set, list the addresses or networks allowed to write".to_string())?;
+        let networks = configured
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                entry
+                    .parse::<IpNet>()
+                    .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| format!("{:?} is not an address or CIDR range", entry))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if networks.is_empty() {
+            return Err("is empty, list the addresses or networks allowed to write".to_string());
+        }
+        Ok(Self { networks })
+    }
+
+    pub fn permits(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            v4 => v4,
+        };
+        self.networks.iter().any(|network| network.contains(&ip))
+    }
+}
+
+// Middleware rejecting admin requests coming from outside the allowlist
+pub async fn require_allowed_ip(
+    State(allowlist): State<Arc<AdminAllowlist>>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    match peer {
+        Some(ConnectInfo(peer)) if allowlist.permits(peer.ip()) => Ok(next.run(request).await),
+        Some(ConnectInfo(peer)) => {
+            eprintln!("Rejected admin request from {}", peer.ip());
+            Err(StatusCode::FORBIDDEN)
+        }
+        // without a known peer there is nothing to check against, so fail closed
+        None => {
+            eprintln!("Rejected admin request without a peer address");
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
+}
+
+// Header the reverse proxy in front of the server sets to the client address,
+// TRUSTED_PROXY_HEADER, e.g. X-Real-IP. Only trusted when configured, and only
+// to be configured when every client goes through that proxy: many proxies pass
+// a header the client sent through unchanged, which would let anyone claim to be
+// loopback.
+pub fn trusted_proxy_header() -> Result<Option<HeaderName>, String> {
+    match env::var("TRUSTED_PROXY_HEADER") {
+        Ok(name) => name
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("{:?} is not a header name", name)),
+        Err(_) => Ok(None),
+    }
+}
+
+// Record the client address the reverse proxy sent in the trusted header, in
+// place of the peer address, which is the proxy's, or missing on Unix sockets
+pub async fn connect_info_from_proxy(
+    State(header): State<Arc<HeaderName>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let peer = request
+        .headers()
+        .get(&*header)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<IpAddr>().ok());
+    if let Some(ip) = peer {
+        request.extensions_mut().insert(ConnectInfo(SocketAddr::new(ip, 0)));
+    }
+    next.run(request).await
+}
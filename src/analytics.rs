@@ -0,0 +1,417 @@
+use mongodb::bson::DateTime;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+};
+use tokio_stream::StreamExt;
+
+use crate::{
+    bots::{self, Verdict},
+    config, geoip,
+    rollup::PathViews,
+    store::{DataStore, FindOptions, StoreError},
+    visitor::Visitor,
+};
+
+// Longest path or referrer stored, anything longer is cut
+const MAX_URL_LENGTH: usize = 512;
+// Largest event payload accepted, in bytes of JSON
+const MAX_PAYLOAD_BYTES: usize = 2048;
+
+// One page view in the analytics collection. No cookies are involved: visitors are
+// only known by a hash that changes every day, so views can't be linked across days.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PageView {
+    pub email: String,
+    // Path without the query string
+    pub path: String,
+    #[serde(default)]
+    pub referrer: Option<String>,
+    // Host of the referrer without www., e.g. news.ycombinator.com
+    #[serde(rename = "referrerDomain", default)]
+    pub referrer_domain: Option<String>,
+    #[serde(rename = "utmSource", default)]
+    pub utm_source: Option<String>,
+    #[serde(rename = "utmMedium", default)]
+    pub utm_medium: Option<String>,
+    #[serde(rename = "utmCampaign", default)]
+    pub utm_campaign: Option<String>,
+    // Country code resolved from the IP when GEOIP_DB_PATH is set, the IP itself is never stored
+    #[serde(default)]
+    pub country: Option<String>,
+    // Probably automated, e.g. sent from a datacenter network; left out of the stats
+    #[serde(default)]
+    pub suspect: bool,
+    pub visitor: String,
+    // ISO 8601 date and timestamp of the view, in UTC
+    pub date: String,
+    pub at: String,
+}
+
+fn truncated(value: &str) -> String {
+    value.chars().take(MAX_URL_LENGTH).collect()
+}
+
+// Host part of an absolute URL, lowercased and without a leading www.
+fn domain(url: &str) -> Option<String> {
+    let (_, rest) = url.split_once("://")?;
+    let host = rest.split(['/', '?', '#']).next()?;
+    let host = host.rsplit('@').next()?.split(':').next()?.to_ascii_lowercase();
+    let host = host.strip_prefix("www.").unwrap_or(&host).to_string();
+    if host.is_empty() {
+        None
+    } else {
+        Some(host)
+    }
+}
+
+// Value of a query string parameter, with + and %XX decoded
+fn query_param(query: &str, name: &str) -> Option<String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| decode(value))
+        .filter(|value| !value.is_empty())
+        .map(|value| truncated(&value))
+}
+
+fn decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (_, Some(byte)) => {
+                decoded.push(byte);
+                i += 2;
+            }
+            (b'+', None) => decoded.push(b' '),
+            (byte, None) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+pub async fn record_page_view(
+    store: &dyn DataStore,
+    owner_email: &str,
+    visitor: &Visitor,
+    path: &str,
+    referrer: Option<&str>,
+) -> Result<(), StoreError> {
+    if !path.starts_with('/') {
+        return Err(format!("Expected a path starting with /, got {}", path).into());
+    }
+    let verdict = bots::filter().classify(visitor);
+    if verdict == Verdict::Bot {
+        return Ok(());
+    }
+    let (path, query) = path.split_once('?').unwrap_or((path, ""));
+    let query = query.split('#').next().unwrap_or_default();
+    let referrer = referrer.filter(|referrer| !referrer.is_empty());
+    let at = DateTime::now().try_to_rfc3339_string()?;
+    let date = at[..10].to_string();
+    let view = PageView {
+        email: owner_email.to_string(),
+        path: truncated(path),
+        referrer: referrer.map(truncated),
+        referrer_domain: referrer.and_then(domain),
+        utm_source: query_param(query, "utm_source"),
+        utm_medium: query_param(query, "utm_medium"),
+        utm_campaign: query_param(query, "utm_campaign"),
+        country: visitor.ip().and_then(geoip::country),
+        suspect: verdict == Verdict::Suspect,
+        visitor: visitor.hash(&format!("pageview:{}:{}", owner_email, date)),
+        date,
+        at,
+    };
+    store
+        .insert_one(&config::collections().analytics, serde_json::to_value(&view)?)
+        .await
+}
+
+// First date of a window of `days` days ending today, None means all time
+pub fn window_start(days: Option<i32>) -> Result<Option<String>, StoreError> {
+    let Some(days) = days else {
+        return Ok(None);
+    };
+    let millis = DateTime::now().timestamp_millis() - i64::from(days.max(1) - 1) * 86_400_000;
+    let start = DateTime::from_millis(millis).try_to_rfc3339_string()?;
+    Ok(Some(start[..10].to_string()))
+}
+
+// Streams the matching records and keeps the ones `keep` accepts, so records outside
+// the window are dropped as they arrive instead of being buffered first
+pub async fn collect_matching<T: DeserializeOwned>(
+    store: &dyn DataStore,
+    collection: &str,
+    filter: Value,
+    keep: impl Fn(&T) -> bool,
+) -> Result<Vec<T>, StoreError> {
+    let mut values = store.find_stream(collection, filter, FindOptions::default()).await?;
+    let mut records = Vec::new();
+    while let Some(value) = values.next().await {
+        if let Ok(record) = serde_json::from_value::<T>(value?) {
+            if keep(&record) {
+                records.push(record);
+            }
+        }
+    }
+    Ok(records)
+}
+
+// Distinct daily visitors among the views
+pub fn distinct_visitors(views: &[PageView]) -> usize {
+    let mut visitors: Vec<&str> = views.iter().map(|view| view.visitor.as_str()).collect();
+    visitors.sort_unstable();
+    visitors.dedup();
+    visitors.len()
+}
+
+// Where views came from: the utm_source when tagged, else the referrer domain, else direct
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+pub struct TrafficSource {
+    pub source: String,
+    pub medium: Option<String>,
+    pub campaign: Option<String>,
+    pub views: i32,
+    pub visitors: i32,
+}
+
+// Views and distinct daily visitors per source, busiest first
+pub fn traffic_sources(views: &[PageView]) -> Vec<TrafficSource> {
+    // keyed by source, medium and campaign
+    let mut sources: HashMap<_, (TrafficSource, HashSet<&str>)> = HashMap::new();
+    for view in views {
+        let source = view
+            .utm_source
+            .clone()
+            .or_else(|| view.referrer_domain.clone())
+            .unwrap_or_else(|| "direct".to_string());
+        let key = (source.clone(), view.utm_medium.as_deref(), view.utm_campaign.as_deref());
+        let (entry, visitors) = sources.entry(key).or_insert_with(|| {
+            let source = TrafficSource {
+                source,
+                medium: view.utm_medium.clone(),
+                campaign: view.utm_campaign.clone(),
+                views: 0,
+                visitors: 0,
+            };
+            (source, HashSet::new())
+        });
+        entry.views += 1;
+        if visitors.insert(&view.visitor) {
+            entry.visitors += 1;
+        }
+    }
+    let mut sources: Vec<TrafficSource> = sources.into_values().map(|(source, _)| source).collect();
+    sources.sort_by(|a, b| {
+        b.views
+            .cmp(&a.views)
+            .then_with(|| a.source.cmp(&b.source))
+            .then_with(|| a.medium.cmp(&b.medium))
+            .then_with(|| a.campaign.cmp(&b.campaign))
+    });
+    sources
+}
+
+// Time window of the popularContent and eventCounts queries
+#[derive(Clone, Copy, Debug, juniper::GraphQLEnum)]
+pub enum PopularWindow {
+    Week,
+    Month,
+    AllTime,
+}
+
+impl PopularWindow {
+    pub fn days(self) -> Option<i32> {
+        match self {
+            PopularWindow::Week => Some(7),
+            PopularWindow::Month => Some(30),
+            PopularWindow::AllTime => None,
+        }
+    }
+}
+
+// Path prefix the frontend serves blog posts under, followed by the slug
+pub fn blog_path_prefix() -> String {
+    env::var("BLOG_PATH_PREFIX").unwrap_or_else(|_| "/blog/".to_string())
+}
+
+// Path prefix the frontend serves project pages under, followed by the slug
+pub fn project_path_prefix() -> String {
+    env::var("PROJECT_PATH_PREFIX").unwrap_or_else(|_| "/projects/".to_string())
+}
+
+// Views per slug for the paths under `prefix`, most viewed first
+pub fn views_by_slug(paths: &[PathViews], prefix: &str) -> Vec<(String, i32)> {
+    let mut counts: HashMap<&str, i32> = HashMap::new();
+    for path in paths {
+        let Some(slug) = path.path.strip_prefix(prefix) else {
+            continue;
+        };
+        let slug = slug.trim_end_matches('/');
+        if slug.is_empty() || slug.contains('/') {
+            continue;
+        }
+        *counts.entry(slug).or_default() += path.views;
+    }
+    let mut counts: Vec<(String, i32)> = counts
+        .into_iter()
+        .map(|(slug, views)| (slug.to_string(), views))
+        .collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+// Views and distinct daily visitors from one country
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+pub struct CountryVisitors {
+    // ISO 3166 country code, null for views that couldn't be located
+    pub country: Option<String>,
+    pub views: i32,
+    pub visitors: i32,
+}
+
+// Views and distinct daily visitors per country, most visitors first
+pub fn visitors_by_country(views: &[PageView]) -> Vec<CountryVisitors> {
+    let mut countries: HashMap<Option<&str>, (CountryVisitors, HashSet<&str>)> = HashMap::new();
+    for view in views {
+        let (entry, visitors) = countries.entry(view.country.as_deref()).or_insert_with(|| {
+            let country = CountryVisitors {
+                country: view.country.clone(),
+                views: 0,
+                visitors: 0,
+            };
+            (country, HashSet::new())
+        });
+        entry.views += 1;
+        if visitors.insert(&view.visitor) {
+            entry.visitors += 1;
+        }
+    }
+    let mut countries: Vec<CountryVisitors> = countries.into_values().map(|(country, _)| country).collect();
+    countries.sort_by(|a, b| b.visitors.cmp(&a.visitors).then_with(|| a.country.cmp(&b.country)));
+    countries
+}
+
+// A named event such as a click on "Download resume", stored in the events collection
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Event {
+    pub email: String,
+    pub name: String,
+    #[serde(default)]
+    pub payload: Option<Value>,
+    #[serde(default)]
+    pub suspect: bool,
+    pub visitor: String,
+    pub date: String,
+    pub at: String,
+}
+
+// Event names are short identifiers, e.g. resume_download or cta:contact
+fn valid_event_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | ':' | '.'))
+}
+
+pub async fn record_event(
+    store: &dyn DataStore,
+    owner_email: &str,
+    visitor: &Visitor,
+    name: &str,
+    payload: Option<&str>,
+) -> Result<(), StoreError> {
+    if !valid_event_name(name) {
+        return Err(format!("Invalid event name {}, use up to 64 letters, digits, _ - : or .", name).into());
+    }
+    let payload = match payload {
+        Some(payload) if payload.len() > MAX_PAYLOAD_BYTES => {
+            return Err(format!("Event payload is larger than {} bytes", MAX_PAYLOAD_BYTES).into())
+        }
+        Some(payload) => match serde_json::from_str::<Value>(payload)? {
+            object @ Value::Object(_) => Some(object),
+            _ => return Err("Event payload must be a JSON object".into()),
+        },
+        None => None,
+    };
+    let verdict = bots::filter().classify(visitor);
+    if verdict == Verdict::Bot {
+        return Ok(());
+    }
+    let at = DateTime::now().try_to_rfc3339_string()?;
+    let date = at[..10].to_string();
+    let event = Event {
+        email: owner_email.to_string(),
+        name: name.to_string(),
+        payload,
+        suspect: verdict == Verdict::Suspect,
+        // same daily hash as page views, so events can be related to the visit they happened in
+        visitor: visitor.hash(&format!("pageview:{}:{}", owner_email, date)),
+        date,
+        at,
+    };
+    store
+        .insert_one(&config::collections().events, serde_json::to_value(&event)?)
+        .await
+}
+
+// Occurrences of one event on one day
+#[derive(Clone, Debug, juniper::GraphQLObject)]
+pub struct DailyCount {
+    pub date: String,
+    pub count: i32,
+}
+
+#[derive(Clone, Debug, juniper::GraphQLObject)]
+pub struct EventCounts {
+    pub name: String,
+    pub total: i32,
+    // Distinct daily visitors who triggered the event
+    pub visitors: i32,
+    // Days without occurrences are left out, oldest first
+    pub daily: Vec<DailyCount>,
+}
+
+pub async fn event_counts(
+    store: &dyn DataStore,
+    owner_email: &str,
+    name: &str,
+    since: Option<&str>,
+) -> Result<EventCounts, StoreError> {
+    let filter = json!({ "email": owner_email, "name": name });
+    let events = collect_matching(store, &config::collections().events, filter, |event: &Event| {
+        !event.suspect && since.map_or(true, |since| event.date.as_str() >= since)
+    })
+    .await?;
+    let mut visitors: Vec<&str> = events.iter().map(|event| event.visitor.as_str()).collect();
+    visitors.sort_unstable();
+    visitors.dedup();
+    let mut daily: Vec<DailyCount> = Vec::new();
+    for event in &events {
+        match daily.iter_mut().find(|day| day.date == event.date) {
+            Some(day) => day.count += 1,
+            None => daily.push(DailyCount {
+                date: event.date.clone(),
+                count: 1,
+            }),
+        }
+    }
+    daily.sort_by(|a, b| a.date.cmp(&b.date));
+    Ok(EventCounts {
+        name: name.to_string(),
+        total: events.len() as i32,
+        visitors: visitors.len() as i32,
+        daily,
+    })
+}
@@ -0,0 +1,163 @@
+use axum::http::{HeaderMap, StatusCode};
+use mongodb::bson::oid::ObjectId;
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    env,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    config,
+    store::{DataStore, StoreError},
+};
+
+// Header carrying the API key on GraphQL requests
+pub const API_KEY_HEADER: &str = "x-api-key";
+pub const READ_SCOPE: &str = "read";
+pub const ADMIN_SCOPE: &str = "admin";
+
+// A key issued by a tenant, only the hash of the secret is stored
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ApiKey {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub email: String,
+    pub name: String,
+    #[serde(rename = "keyHash")]
+    pub key_hash: String,
+    pub scopes: Vec<String>,
+    #[serde(rename = "rateLimitPerMinute")]
+    pub rate_limit_per_minute: i32,
+}
+
+impl ApiKey {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|granted| granted == scope || granted == ADMIN_SCOPE)
+    }
+}
+
+// Returned once when a key is issued, the plain key can't be recovered afterwards
+#[derive(Debug, juniper::GraphQLObject)]
+pub struct IssuedApiKey {
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub rate_limit_per_minute: i32,
+    pub key: String,
+}
+
+fn hash_key(key: &str) -> String {
+    format!("{:x}", Sha256::digest(key.as_bytes()))
+}
+
+pub async fn find_by_key(store: &dyn DataStore, key: &str) -> Result<Option<ApiKey>, StoreError> {
+    let found = store
+        .find_one(&config::collections().api_keys, json!({ "keyHash": hash_key(key) }))
+        .await?;
+    Ok(found.map(serde_json::from_value).transpose()?)
+}
+
+pub async fn issue(
+    store: &dyn DataStore,
+    email: &str,
+    name: String,
+    scopes: Vec<String>,
+    rate_limit_per_minute: i32,
+) -> Result<IssuedApiKey, StoreError> {
+    let key: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(40)
+        .map(char::from)
+        .collect();
+    let api_key = ApiKey {
+        id: ObjectId::new(),
+        email: email.to_string(),
+        name,
+        key_hash: hash_key(&key),
+        scopes,
+        rate_limit_per_minute,
+    };
+    store
+        .insert_one(&config::collections().api_keys, serde_json::to_value(&api_key)?)
+        .await?;
+    Ok(IssuedApiKey {
+        id: api_key.id.to_hex(),
+        name: api_key.name,
+        scopes: api_key.scopes,
+        rate_limit_per_minute: api_key.rate_limit_per_minute,
+        key,
+    })
+}
+
+pub async fn revoke(store: &dyn DataStore, email: &str, id: ObjectId) -> Result<bool, StoreError> {
+    // ids are matched through their extended JSON form so every backend stores them alike
+    let filter = json!({ "_id": { "$oid": id.to_hex() }, "email": email });
+    store.delete_one(&config::collections().api_keys, filter).await
+}
+
+// Checks API keys on incoming requests and enforces each key's per minute quota
+pub struct ApiKeyGuard {
+    required: bool,
+    windows: Mutex<HashMap<ObjectId, (Instant, i32)>>,
+}
+
+impl ApiKeyGuard {
+    pub fn from_env() -> Self {
+        let required = env::var("REQUIRE_API_KEY")
+            .map(|value| value == "true" || value == "1")
+            .unwrap_or(false);
+        Self {
+            required,
+            windows: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Key used for the request, if one was sent and it grants the scope
+    pub async fn authorize(
+        &self,
+        store: &dyn DataStore,
+        headers: &HeaderMap,
+        scope: &str,
+    ) -> Result<Option<ApiKey>, (StatusCode, &'static str)> {
+        let key = match headers.get(API_KEY_HEADER).and_then(|value| value.to_str().ok()) {
+            Some(key) => key,
+            None if self.required => return Err((StatusCode::UNAUTHORIZED, "API key required")),
+            None => return Ok(None),
+        };
+        let api_key = match find_by_key(store, key).await {
+            Ok(Some(api_key)) => api_key,
+            Ok(None) => return Err((StatusCode::UNAUTHORIZED, "Unknown API key")),
+            Err(e) => {
+                eprintln!("Error looking up API key: {}", e);
+                return Err((StatusCode::SERVICE_UNAVAILABLE, "Could not verify API key"));
+            }
+        };
+        if !api_key.has_scope(scope) {
+            return Err((StatusCode::FORBIDDEN, "API key is missing the required scope"));
+        }
+        if !self.take_quota(&api_key) {
+            return Err((StatusCode::TOO_MANY_REQUESTS, "API key rate limit exceeded"));
+        }
+        Ok(Some(api_key))
+    }
+
+    // Fixed one minute windows counted per key
+    fn take_quota(&self, api_key: &ApiKey) -> bool {
+        let mut windows = self.windows.lock().unwrap();
+        let now = Instant::now();
+        let window = windows.entry(api_key.id).or_insert((now, 0));
+        if now.duration_since(window.0) >= Duration::from_secs(60) {
+            *window = (now, 0);
+        }
+        if window.1 >= api_key.rate_limit_per_minute {
+            return false;
+        }
+        window.1 += 1;
+        true
+    }
+}
@@ -0,0 +1,200 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{api_keys, store::StoreError, tenant};
+
+#[derive(Debug, clap::Args)]
+pub struct BenchArgs {
+    /// GraphQL endpoint of the running instance
+    #[arg(long, default_value = "http://127.0.0.1:3000/graphql")]
+    url: String,
+    /// Requests to send in total
+    #[arg(long, default_value_t = 500)]
+    requests: usize,
+    /// Requests in flight at once
+    #[arg(long, default_value_t = 10)]
+    concurrency: usize,
+    /// JSON file with the operations to send instead of the built-in mix, a list
+    /// of {"name", "query", "variables", "weight"}
+    #[arg(long)]
+    operations: Option<PathBuf>,
+    /// Portfolio owner, for multi-tenant deployments
+    #[arg(long)]
+    owner: Option<String>,
+    /// API key, for deployments that require one
+    #[arg(long)]
+    api_key: Option<String>,
+}
+
+// One GraphQL operation of the mix, sent `weight` times as often as a weight of 1
+#[derive(Clone, Debug, Deserialize)]
+struct Operation {
+    name: String,
+    query: String,
+    #[serde(default)]
+    variables: Option<Value>,
+    #[serde(default = "default_weight")]
+    weight: usize,
+}
+
+fn default_weight() -> usize {
+    1
+}
+
+// What a portfolio page load asks for
+fn default_operations() -> Vec<Operation> {
+    let operation = |name: &str, query: &str, weight| Operation {
+        name: name.to_string(),
+        query: query.to_string(),
+        variables: None,
+        weight,
+    };
+    vec![
+        operation("introductions", "{ introductions { title icon } }", 3),
+        operation("projects", "{ projects(limit: 6) { title slug description } }", 3),
+        operation("skills", "{ skillsOverview { title icon } }", 2),
+        operation(
+            "blogPostsPage",
+            "{ blogPostsPage(limit: 10) { totalCount items { title slug publishedAt } } }",
+            2,
+        ),
+        operation("theme", "{ theme { palette { primary } layout { name enabled } } }", 1),
+    ]
+}
+
+#[derive(Debug)]
+struct Sample {
+    operation: usize,
+    elapsed: Duration,
+    failed: bool,
+}
+
+// Send the operation mix to a running instance and print latency percentiles
+pub async fn run(args: BenchArgs) -> Result<(), StoreError> {
+    let operations = match &args.operations {
+        Some(path) => serde_json::from_str(&fs::read_to_string(path)?)?,
+        None => default_operations(),
+    };
+    // operations repeated by weight, requests go through it round robin
+    let schedule: Arc<Vec<usize>> = Arc::new(
+        operations
+            .iter()
+            .enumerate()
+            .flat_map(|(index, operation)| std::iter::repeat(index).take(operation.weight))
+            .collect(),
+    );
+    if schedule.is_empty() {
+        return Err("The operation mix is empty".into());
+    }
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Some(owner) = &args.owner {
+        headers.insert(tenant::OWNER_HEADER, owner.parse()?);
+    }
+    if let Some(api_key) = &args.api_key {
+        headers.insert(api_keys::API_KEY_HEADER, api_key.parse()?);
+    }
+    let client = reqwest::Client::builder().default_headers(headers).build()?;
+    let operations = Arc::new(operations);
+    let next = Arc::new(AtomicUsize::new(0));
+
+    println!(
+        "Sending {} requests to {}, {} at a time",
+        args.requests, args.url, args.concurrency
+    );
+    let started = Instant::now();
+    let mut workers = Vec::new();
+    for _ in 0..args.concurrency.max(1) {
+        let (client, url, operations, schedule, next) = (
+            client.clone(),
+            args.url.clone(),
+            operations.clone(),
+            schedule.clone(),
+            next.clone(),
+        );
+        let requests = args.requests;
+        workers.push(tokio::spawn(async move {
+            let mut samples = Vec::new();
+            loop {
+                let request = next.fetch_add(1, Ordering::Relaxed);
+                if request >= requests {
+                    break;
+                }
+                let index = schedule[request % schedule.len()];
+                let operation = &operations[index];
+                let body = json!({ "query": operation.query, "variables": operation.variables });
+                let sent = Instant::now();
+                let response = client.post(&url).json(&body).send().await;
+                // a GraphQL error counts as a failure even with a 200
+                let failed = match response {
+                    Ok(response) if response.status().is_success() => response
+                        .json::<Value>()
+                        .await
+                        .map_or(true, |body| body.get("errors").is_some()),
+                    _ => true,
+                };
+                samples.push(Sample {
+                    operation: index,
+                    elapsed: sent.elapsed(),
+                    failed,
+                });
+            }
+            samples
+        }));
+    }
+    let mut samples = Vec::new();
+    for worker in workers {
+        samples.extend(worker.await?);
+    }
+    let total = started.elapsed();
+
+    println!(
+        "{:<24} {:>8} {:>8} {:>9} {:>9} {:>9} {:>9}",
+        "operation", "requests", "failed", "p50 ms", "p90 ms", "p99 ms", "max ms"
+    );
+    for (index, operation) in operations.iter().enumerate() {
+        let of_operation: Vec<&Sample> = samples.iter().filter(|sample| sample.operation == index).collect();
+        print_row(&operation.name, &of_operation);
+    }
+    print_row("all", &samples.iter().collect::<Vec<_>>());
+    println!(
+        "{} requests in {:.2}s, {:.1} requests/s",
+        samples.len(),
+        total.as_secs_f64(),
+        samples.len() as f64 / total.as_secs_f64()
+    );
+    Ok(())
+}
+
+fn print_row(name: &str, samples: &[&Sample]) {
+    if samples.is_empty() {
+        return;
+    }
+    let mut millis: Vec<f64> = samples.iter().map(|sample| sample.elapsed.as_secs_f64() * 1000.0).collect();
+    millis.sort_by(|a, b| a.total_cmp(b));
+    let failed = samples.iter().filter(|sample| sample.failed).count();
+    println!(
+        "{:<24} {:>8} {:>8} {:>9.1} {:>9.1} {:>9.1} {:>9.1}",
+        name,
+        samples.len(),
+        failed,
+        percentile(&millis, 50.0),
+        percentile(&millis, 90.0),
+        percentile(&millis, 99.0),
+        millis[millis.len() - 1],
+    );
+}
+
+// Nearest rank percentile of sorted values
+fn percentile(sorted: &[f64], percent: f64) -> f64 {
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
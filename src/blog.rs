@@ -0,0 +1,641 @@
+use juniper::{graphql_object, graphql_value, FieldError};
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    config,
+    content::{self, ContentBlock},
+    dates,
+    localized_page_db, rules,
+    store::{FindOptions, SortDirection},
+    syndication::{self, Syndication},
+    validation::{self, Validator, MAX_DESCRIPTION_CHARS, MAX_NAME_CHARS, MAX_SLUG_CHARS},
+    Context, OrderDirection,
+};
+
+// Status of posts visible on the public API, anything else is a draft
+pub const PUBLISHED: &str = "published";
+
+// Where a post is in the authoring workflow: draft, then review, then published
+#[derive(Clone, Copy, Debug, PartialEq, Eq, juniper::GraphQLEnum)]
+pub enum BlogPostStatus {
+    Draft,
+    Review,
+    Published,
+}
+
+impl BlogPostStatus {
+    // Stored statuses other than review and published read as drafts
+    pub fn of(post: &BlogPost) -> Self {
+        match post.status.as_str() {
+            PUBLISHED => BlogPostStatus::Published,
+            "review" => BlogPostStatus::Review,
+            _ => BlogPostStatus::Draft,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BlogPostStatus::Draft => "draft",
+            BlogPostStatus::Review => "review",
+            BlogPostStatus::Published => PUBLISHED,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BlogPost {
+    // Set on posts created through the API, posts seeded into a backend other
+    // than Mongo may have none
+    #[serde(
+        rename = "_id",
+        default,
+        deserialize_with = "object_id",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub id: Option<ObjectId>,
+    pub email: String,
+    pub title: String,
+    pub slug: String,
+    #[serde(default)]
+    pub excerpt: Option<String>,
+    // ISO 8601, e.g. 2024-03-01T09:00:00Z
+    #[serde(rename = "publishedAt")]
+    pub published_at: String,
+    pub status: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default, deserialize_with = "content::blocks")]
+    pub content: Vec<ContentBlock>,
+    // Locale of this variant, unset means the default locale
+    #[serde(default)]
+    pub locale: Option<String>,
+    // ISO 8601 timestamps kept by the authoring mutations, unset on older posts
+    #[serde(rename = "createdAt", default)]
+    pub created_at: Option<String>,
+    #[serde(rename = "updatedAt", default)]
+    pub updated_at: Option<String>,
+    #[serde(rename = "workingCopy", default, skip_serializing_if = "Option::is_none")]
+    pub working_copy: Option<WorkingCopy>,
+    // Announcements of the post on social networks, see syndication::announce
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub syndication: Vec<Syndication>,
+}
+
+// Edits of a post saved by autosaveDraft, kept apart from the post until it is
+// next updated
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+pub struct WorkingCopy {
+    pub title: String,
+    pub excerpt: Option<String>,
+    pub tags: Vec<String>,
+    #[serde(default, deserialize_with = "content::blocks")]
+    pub content: Vec<ContentBlock>,
+    // ISO 8601, e.g. 2024-03-01T09:00:00Z
+    #[serde(rename = "savedAt")]
+    pub saved_at: String,
+}
+
+// Ids other than ObjectIds, e.g. strings in hand written fixtures, read as none
+// rather than failing the post
+fn object_id<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<ObjectId>, D::Error> {
+    Ok(serde_json::from_value(Value::deserialize(deserializer)?).ok())
+}
+
+#[graphql_object(context = Context)]
+impl BlogPost {
+    fn id(&self) -> Option<String> {
+        self.id.map(|id| id.to_hex())
+    }
+    fn email(&self) -> &str {
+        &self.email
+    }
+    fn title(&self) -> &str {
+        &self.title
+    }
+    fn slug(&self) -> &str {
+        &self.slug
+    }
+    fn excerpt(&self) -> Option<&str> {
+        self.excerpt.as_deref()
+    }
+    // timezone and format return the date pre-formatted, e.g. format: "%B %-d, %Y"
+    fn published_at(&self, timezone: Option<String>, format: Option<String>) -> Result<String, FieldError> {
+        dates::format(&self.published_at, timezone.as_deref(), format.as_deref())
+    }
+    fn status(&self) -> &str {
+        &self.status
+    }
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+    fn content(&self) -> &[ContentBlock] {
+        &self.content
+    }
+    fn locale(&self) -> Option<&str> {
+        self.locale.as_deref()
+    }
+    fn created_at(&self) -> Option<&str> {
+        self.created_at.as_deref()
+    }
+    fn updated_at(&self) -> Option<&str> {
+        self.updated_at.as_deref()
+    }
+    // Edits saved by autosaveDraft since the post was last updated
+    fn working_copy(&self) -> Option<&WorkingCopy> {
+        self.working_copy.as_ref()
+    }
+    fn syndication(&self) -> &[Syndication] {
+        &self.syndication
+    }
+    // Footnotes of the content, numbered in reading order
+    fn footnotes(&self) -> Vec<content::Footnote> {
+        content::footnotes(&self.content)
+    }
+    // Headings of the content with the anchors the frontend should give them
+    fn table_of_contents(&self) -> Vec<content::TocEntry> {
+        content::table_of_contents(&self.content)
+    }
+    // The published post that follows this one by publication date
+    async fn next_post(&self, context: &Context) -> Result<Option<BlogPost>, FieldError> {
+        self.neighbour(context, 1).await
+    }
+    // The published post that precedes this one by publication date
+    async fn previous_post(&self, context: &Context) -> Result<Option<BlogPost>, FieldError> {
+        self.neighbour(context, -1).await
+    }
+}
+
+impl BlogPost {
+    // Drafts keep their creation time as publishedAt until they are published.
+    // Posts from before the authoring mutations have no createdAt and count as published.
+    fn ever_published(&self) -> bool {
+        self.created_at.as_deref() != Some(self.published_at.as_str())
+    }
+
+    // Post `step` places away in the owner's published posts in this post's locale, oldest first
+    async fn neighbour(&self, context: &Context, step: isize) -> Result<Option<BlogPost>, FieldError> {
+        let options = FindOptions {
+            sort: vec![("publishedAt".to_string(), SortDirection::Ascending)],
+            ..Default::default()
+        };
+        let filter = published_filter(&self.email);
+        let collection = &config::collections().blog_posts;
+        let locales = context.locales(&self.email, self.locale.clone()).await?;
+        let (values, _) = localized_page_db(&*context.store, collection, filter, options, &locales)
+            .await
+            .map_err(|err| {
+                FieldError::new(
+                    "Failed to fetch blog posts",
+                    graphql_value!({ "details": err.to_string() }),
+                )
+            })?;
+        let posts: Vec<BlogPost> = context.decode_all(collection, values)?;
+        // drafts aren't part of the sequence and have no neighbours
+        let Some(position) = posts.iter().position(|post| post.slug == self.slug) else {
+            return Ok(None);
+        };
+        Ok(position
+            .checked_add_signed(step)
+            .and_then(|index| posts.get(index))
+            .cloned())
+    }
+}
+
+// Filter matching the published posts of an owner
+pub fn published_filter(owner_email: &str) -> Value {
+    json!({ "email": owner_email, "status": PUBLISHED })
+}
+
+// Published posts of one calendar month, for the archive sidebar
+#[derive(Clone, Debug, juniper::GraphQLObject)]
+#[graphql(context = Context)]
+pub struct ArchiveMonth {
+    pub year: i32,
+    pub month: i32,
+    pub count: i32,
+    pub posts: Vec<BlogPost>,
+}
+
+// Year and month of an ISO 8601 publication date
+fn year_month(published_at: &str) -> Option<(i32, i32)> {
+    let year = published_at.get(0..4)?.parse().ok()?;
+    let month = published_at.get(5..7)?.parse().ok()?;
+    Some((year, month))
+}
+
+// Group posts sorted newest first into months, keeping that order
+pub fn archive(posts: Vec<BlogPost>) -> Vec<ArchiveMonth> {
+    let mut months: Vec<ArchiveMonth> = Vec::new();
+    for post in posts {
+        // posts without a usable date have no place in the archive
+        let Some((year, month)) = year_month(&post.published_at) else {
+            continue;
+        };
+        match months.last_mut() {
+            Some(last) if last.year == year && last.month == month => {
+                last.count += 1;
+                last.posts.push(post);
+            }
+            _ => months.push(ArchiveMonth {
+                year,
+                month,
+                count: 1,
+                posts: vec![post],
+            }),
+        }
+    }
+    months
+}
+
+#[derive(Clone, Copy, Debug, juniper::GraphQLEnum)]
+pub enum BlogPostOrderField {
+    PublishedAt,
+    Title,
+}
+
+#[derive(Debug, juniper::GraphQLInputObject)]
+pub struct BlogPostOrder {
+    field: BlogPostOrderField,
+    #[graphql(default = OrderDirection::Asc)]
+    direction: OrderDirection,
+}
+
+impl BlogPostOrder {
+    pub fn sort(&self) -> (String, SortDirection) {
+        let field = match self.field {
+            BlogPostOrderField::PublishedAt => "publishedAt",
+            BlogPostOrderField::Title => "title",
+        };
+        (field.to_string(), self.direction.into())
+    }
+}
+
+// Fields of a post set by createBlogPost and updateBlogPost, fields left out
+// are unchanged and an empty excerpt unsets it. content is the JSON array of
+// blocks as stored, e.g. [{"type": "paragraph", "value": "Hello"}].
+#[derive(Debug, juniper::GraphQLInputObject)]
+pub struct BlogPostInput {
+    title: Option<String>,
+    // Derived from the title when a post is created without one, and can only
+    // change until the post is first published
+    slug: Option<String>,
+    excerpt: Option<String>,
+    tags: Option<Vec<String>>,
+    content: Option<String>,
+}
+
+impl BlogPostInput {
+    // Check the fields given, returning the parsed content blocks
+    fn validate(&self, input: &mut Validator) -> Option<Vec<ContentBlock>> {
+        if let Some(title) = &self.title {
+            input.text("post.title", title, MAX_NAME_CHARS);
+        }
+        if let Some(slug) = &self.slug {
+            input.slug("post.slug", slug);
+        }
+        input.optional_text("post.excerpt", self.excerpt.as_deref(), MAX_DESCRIPTION_CHARS);
+        if let Some(tags) = &self.tags {
+            for tag in tags {
+                input.text("post.tags", tag, MAX_NAME_CHARS);
+            }
+            input.distinct("post.tags", tags.iter().map(String::as_str));
+        }
+        let content = self.content.as_deref()?;
+        match serde_json::from_str(content) {
+            Ok(blocks) => Some(blocks),
+            Err(e) => {
+                input.reject("post.content", format!("is not a list of content blocks: {}", e));
+                None
+            }
+        }
+    }
+}
+
+fn now() -> Result<String, FieldError> {
+    DateTime::now().try_to_rfc3339_string().map_err(|err| {
+        FieldError::new(
+            "Failed to read the current time",
+            graphql_value!({ "details": err.to_string() }),
+        )
+    })
+}
+
+// Filter matching one locale variant of a post, unset locales match the default variant
+fn variant(owner_email: &str, slug: &str, locale: Option<&str>) -> Value {
+    json!({ "email": owner_email, "slug": slug, "locale": locale })
+}
+
+async fn ensure_slug_free(
+    context: &Context,
+    input: &mut Validator,
+    owner_email: &str,
+    slug: &str,
+    locale: Option<&str>,
+) -> Result<(), FieldError> {
+    let collection = &config::collections().blog_posts;
+    if validation::exists(&*context.store, collection, variant(owner_email, slug, locale)).await? {
+        input.reject("post.slug", "is already used by another post");
+    }
+    Ok(())
+}
+
+async fn load(context: &Context, filter: Value) -> Result<BlogPost, FieldError> {
+    let collection = &config::collections().blog_posts;
+    let found = context.store.find_one(collection, filter).await.map_err(|err| {
+        FieldError::new(
+            "Failed to fetch blog post",
+            graphql_value!({ "details": err.to_string() }),
+        )
+    })?;
+    found
+        .and_then(|value| serde_json::from_value(value).ok())
+        .ok_or_else(|| {
+            FieldError::new(
+                "Blog post not found",
+                graphql_value!({ "details": "No blog post with this slug and locale of the owner" }),
+            )
+        })
+}
+
+async fn save(context: &Context, filter: Value, changes: Value) -> Result<(), FieldError> {
+    let collection = &config::collections().blog_posts;
+    context.store.update_one(collection, filter, changes).await.map_err(|err| {
+        FieldError::new(
+            "Failed to update blog post",
+            graphql_value!({ "details": err.to_string() }),
+        )
+    })?;
+    Ok(())
+}
+
+// Start a draft. publishedAt holds the creation time until the post is published.
+pub async fn create(
+    context: &Context,
+    owner_email: String,
+    locale: Option<String>,
+    post: BlogPostInput,
+) -> Result<BlogPost, FieldError> {
+    let mut input = Validator::default();
+    input.optional_text("locale", locale.as_deref(), MAX_NAME_CHARS);
+    let content = post.validate(&mut input);
+    let title = post.title.unwrap_or_default();
+    if title.trim().is_empty() {
+        input.reject("post.title", "must not be blank");
+    }
+    let slug = post.slug.unwrap_or_else(|| validation::slugify(&title, MAX_SLUG_CHARS));
+    if slug.is_empty() {
+        input.reject("post.slug", "can't be derived from the title, pass one");
+    } else {
+        ensure_slug_free(context, &mut input, &owner_email, &slug, locale.as_deref()).await?;
+    }
+    input.finish()?;
+    let now = now()?;
+    let created = BlogPost {
+        // set here as only Mongo assigns ids on insert
+        id: Some(ObjectId::new()),
+        email: owner_email,
+        title,
+        slug,
+        excerpt: post.excerpt.filter(|excerpt| !excerpt.is_empty()),
+        published_at: now.clone(),
+        status: BlogPostStatus::Draft.as_str().to_string(),
+        tags: post.tags.unwrap_or_default(),
+        content: content.unwrap_or_default(),
+        locale,
+        created_at: Some(now.clone()),
+        updated_at: Some(now),
+        working_copy: None,
+        syndication: Vec::new(),
+    };
+    let collection = &config::collections().blog_posts;
+    rules::enforce(&*context.store, &created.email, collection, &created).await?;
+    let inserted = async {
+        let document = serde_json::to_value(&created)?;
+        context.store.insert_one(collection, document).await
+    };
+    inserted.await.map_err(|err| {
+        FieldError::new(
+            "Failed to create blog post",
+            graphql_value!({ "details": err.to_string() }),
+        )
+    })?;
+    Ok(created)
+}
+
+// Change the fields given of one locale variant of a post
+pub async fn update(
+    context: &Context,
+    owner_email: String,
+    slug: String,
+    locale: Option<String>,
+    post: BlogPostInput,
+) -> Result<BlogPost, FieldError> {
+    let mut input = Validator::default();
+    let content = post.validate(&mut input);
+    input.finish()?;
+    let filter = variant(&owner_email, &slug, locale.as_deref());
+    let mut updated = load(context, filter.clone()).await?;
+    let mut input = Validator::default();
+    if let Some(new_slug) = post.slug.filter(|new_slug| *new_slug != slug) {
+        // links to published posts would break, drafts have none yet
+        if updated.ever_published() {
+            input.reject("post.slug", "can't change once the post has been published");
+        } else {
+            ensure_slug_free(context, &mut input, &owner_email, &new_slug, locale.as_deref()).await?;
+        }
+        updated.slug = new_slug;
+    }
+    input.finish()?;
+    if let Some(title) = post.title {
+        updated.title = title;
+    }
+    if let Some(excerpt) = post.excerpt {
+        updated.excerpt = Some(excerpt).filter(|excerpt| !excerpt.is_empty());
+    }
+    if let Some(tags) = post.tags {
+        updated.tags = tags;
+    }
+    let mut changes = json!({
+        "title": updated.title,
+        "slug": updated.slug,
+        "excerpt": updated.excerpt,
+        "tags": updated.tags,
+    });
+    // content is only written when given, rewriting it would drop blocks this
+    // version doesn't know
+    if let Some(content) = content {
+        changes["content"] = json!(content);
+        updated.content = content;
+    }
+    let now = now()?;
+    changes["updatedAt"] = json!(now);
+    updated.updated_at = Some(now);
+    // the update supersedes whatever was autosaved
+    changes["workingCopy"] = Value::Null;
+    updated.working_copy = None;
+    rules::enforce(&*context.store, &owner_email, &config::collections().blog_posts, &updated).await?;
+    save(context, filter, changes).await?;
+    Ok(updated)
+}
+
+// Keep the editor's current state of a post as its working copy. The save only
+// goes through when the post is still at `base_updated_at`, the updatedAt the
+// editor last saw, null for older posts that have none. Otherwise another
+// session changed it in between and an EDIT_CONFLICT error carries the post as
+// stored now in extensions.serverCopy.
+pub async fn autosave(
+    context: &Context,
+    owner_email: String,
+    id: String,
+    base_updated_at: Option<String>,
+    draft: BlogPostInput,
+) -> Result<BlogPost, FieldError> {
+    let id = ObjectId::parse_str(&id).map_err(|err| {
+        FieldError::new(
+            "Invalid blog post id",
+            graphql_value!({ "details": err.to_string() }),
+        )
+    })?;
+    let mut input = Validator::default();
+    let content = draft.validate(&mut input);
+    if draft.slug.is_some() {
+        input.reject("post.slug", "isn't part of a working copy, change it with updateBlogPost");
+    }
+    input.finish()?;
+    // ids are matched through their extended JSON form so every backend stores them alike
+    let filter = json!({ "_id": { "$oid": id.to_hex() }, "email": owner_email });
+    let mut post = load(context, filter.clone()).await?;
+    if post.updated_at != base_updated_at {
+        return Err(edit_conflict(&post));
+    }
+    // fields left out keep what was saved last, or else the post's own
+    let saved = post.working_copy.take();
+    let working_copy = WorkingCopy {
+        title: draft
+            .title
+            .or_else(|| saved.as_ref().map(|saved| saved.title.clone()))
+            .unwrap_or_else(|| post.title.clone()),
+        excerpt: match draft.excerpt {
+            Some(excerpt) => Some(excerpt).filter(|excerpt| !excerpt.is_empty()),
+            None => saved.as_ref().map_or_else(|| post.excerpt.clone(), |saved| saved.excerpt.clone()),
+        },
+        tags: draft
+            .tags
+            .or_else(|| saved.as_ref().map(|saved| saved.tags.clone()))
+            .unwrap_or_else(|| post.tags.clone()),
+        content: content
+            .or_else(|| saved.map(|saved| saved.content))
+            .unwrap_or_else(|| post.content.clone()),
+        saved_at: now()?,
+    };
+    let changes = json!({ "workingCopy": working_copy, "updatedAt": working_copy.saved_at });
+    // the updatedAt check is part of the filter so a save racing this one can't slip in between
+    let mut unchanged = filter.clone();
+    unchanged["updatedAt"] = json!(base_updated_at);
+    let collection = &config::collections().blog_posts;
+    let saved = context.store.update_one(collection, unchanged, changes).await.map_err(|err| {
+        FieldError::new(
+            "Failed to autosave draft",
+            graphql_value!({ "details": err.to_string() }),
+        )
+    })?;
+    if !saved {
+        return Err(edit_conflict(&load(context, filter).await?));
+    }
+    post.updated_at = Some(working_copy.saved_at.clone());
+    post.working_copy = Some(working_copy);
+    Ok(post)
+}
+
+fn edit_conflict(current: &BlogPost) -> FieldError {
+    let server_copy = serde_json::to_string(current).unwrap_or_default();
+    FieldError::new(
+        "Edit conflict",
+        graphql_value!({
+            "code": "EDIT_CONFLICT",
+            "details": "The post was changed by another session, reload it before saving again",
+            "serverCopy": server_copy
+        }),
+    )
+}
+
+// Send a draft to review or a post in review back to draft
+pub async fn set_status(
+    context: &Context,
+    owner_email: String,
+    slug: String,
+    locale: Option<String>,
+    status: BlogPostStatus,
+) -> Result<BlogPost, FieldError> {
+    let from = match status {
+        BlogPostStatus::Draft => BlogPostStatus::Review,
+        BlogPostStatus::Review => BlogPostStatus::Draft,
+        BlogPostStatus::Published => {
+            return Err(FieldError::new(
+                "Invalid status transition",
+                graphql_value!({ "details": "Call publishBlogPost to publish a post" }),
+            ))
+        }
+    };
+    transition(context, owner_email, slug, locale, from, status).await
+}
+
+// Publish a post in review, dated now
+pub async fn publish(
+    context: &Context,
+    owner_email: String,
+    slug: String,
+    locale: Option<String>,
+) -> Result<BlogPost, FieldError> {
+    let (from, next) = (BlogPostStatus::Review, BlogPostStatus::Published);
+    transition(context, owner_email, slug, locale, from, next).await
+}
+
+// Turn a published post back into a draft
+pub async fn unpublish(
+    context: &Context,
+    owner_email: String,
+    slug: String,
+    locale: Option<String>,
+) -> Result<BlogPost, FieldError> {
+    let (from, next) = (BlogPostStatus::Published, BlogPostStatus::Draft);
+    transition(context, owner_email, slug, locale, from, next).await
+}
+
+// Move one locale variant of a post from status `from` to `next`, publishing stamps publishedAt
+async fn transition(
+    context: &Context,
+    owner_email: String,
+    slug: String,
+    locale: Option<String>,
+    from: BlogPostStatus,
+    next: BlogPostStatus,
+) -> Result<BlogPost, FieldError> {
+    let filter = variant(&owner_email, &slug, locale.as_deref());
+    let mut post = load(context, filter.clone()).await?;
+    let current = BlogPostStatus::of(&post);
+    if current != from {
+        let details = format!("A {} post can't become {}", current.as_str(), next.as_str());
+        return Err(FieldError::new(
+            "Invalid status transition",
+            graphql_value!({ "details": details }),
+        ));
+    }
+    let now = now()?;
+    post.status = next.as_str().to_string();
+    post.updated_at = Some(now.clone());
+    let mut changes = json!({ "status": post.status, "updatedAt": now });
+    if next == BlogPostStatus::Published {
+        changes["publishedAt"] = json!(now);
+        post.published_at = now;
+    }
+    rules::enforce(&*context.store, &owner_email, &config::collections().blog_posts, &post).await?;
+    save(context, filter, changes).await?;
+    if next == BlogPostStatus::Published {
+        let (store, published) = (context.store.clone(), post.clone());
+        tokio::spawn(async move { syndication::announce(&*store, &published).await });
+    }
+    Ok(post)
+}
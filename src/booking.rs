@@ -0,0 +1,175 @@
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeMap,
+    env,
+    sync::Mutex,
+    time::{self, Instant},
+};
+
+use crate::{
+    settings,
+    store::{DataStore, StoreError},
+};
+
+const CAL_COM_API_URL: &str = "https://api.cal.com/v2";
+const CAL_COM_API_VERSION: &str = "2024-09-04";
+const CALENDLY_API_URL: &str = "https://api.calendly.com";
+// Calendly answers for a week at most
+pub const MAX_DAYS: i32 = 7;
+
+// Slots fetched per provider, event type and number of days, with when they were fetched
+static CACHE: Mutex<BTreeMap<String, (Instant, Vec<AvailabilitySlot>)>> = Mutex::new(BTreeMap::new());
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, juniper::GraphQLEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum BookingProvider {
+    #[serde(rename = "calcom")]
+    CalCom,
+    Calendly,
+}
+
+// The "book a call" section, stored in the `booking` field of the site settings
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+#[serde(rename_all = "camelCase")]
+pub struct BookingSettings {
+    pub provider: BookingProvider,
+    // Page visitors book on, e.g. https://cal.com/jane/intro
+    pub booking_link: String,
+    // Event type whose open slots availability lists, the numeric id on
+    // Cal.com, the event type URI on Calendly. Without one no slots are listed.
+    #[serde(default)]
+    pub event_type: Option<String>,
+}
+
+#[derive(Debug, juniper::GraphQLInputObject)]
+pub struct BookingSettingsInput {
+    pub provider: BookingProvider,
+    pub booking_link: String,
+    pub event_type: Option<String>,
+}
+
+#[derive(Clone, Debug, juniper::GraphQLObject)]
+pub struct AvailabilitySlot {
+    // ISO 8601, e.g. 2024-03-01T09:00:00Z
+    pub start: String,
+    // Where this slot is booked, when the provider links slots directly
+    pub booking_url: Option<String>,
+}
+
+pub async fn load(store: &dyn DataStore, owner_email: &str) -> Result<Option<BookingSettings>, StoreError> {
+    let booking = settings::load(store, owner_email)
+        .await?
+        .and_then(|mut settings| settings.get_mut("booking").map(Value::take))
+        .filter(|booking| !booking.is_null());
+    Ok(booking.map(serde_json::from_value).transpose()?)
+}
+
+pub async fn save(store: &dyn DataStore, owner_email: &str, booking: &BookingSettings) -> Result<(), StoreError> {
+    settings::save(store, owner_email, json!({ "booking": booking })).await
+}
+
+// Seconds slots are reused for, AVAILABILITY_CACHE_SECS, 5 minutes by default
+fn cache_ttl() -> time::Duration {
+    let seconds = env::var("AVAILABILITY_CACHE_SECS")
+        .ok()
+        .map(|seconds| seconds.parse().expect("AVAILABILITY_CACHE_SECS must be a number of seconds"))
+        .unwrap_or(5 * 60);
+    time::Duration::from_secs(seconds)
+}
+
+fn credential(provider: BookingProvider) -> Result<String, StoreError> {
+    let key = match provider {
+        BookingProvider::CalCom => "CALCOM_API_KEY",
+        BookingProvider::Calendly => "CALENDLY_TOKEN",
+    };
+    env::var(key).map_err(|_| format!("Set {} to list open slots", key).into())
+}
+
+async fn cal_com(event_type: &str, days: i64) -> Result<Vec<AvailabilitySlot>, StoreError> {
+    let now = Utc::now();
+    let response: Value = reqwest::Client::new()
+        .get(format!("{}/slots", CAL_COM_API_URL))
+        .bearer_auth(credential(BookingProvider::CalCom)?)
+        .header("cal-api-version", CAL_COM_API_VERSION)
+        .query(&[
+            ("eventTypeId", event_type.to_string()),
+            ("start", now.to_rfc3339()),
+            ("end", (now + Duration::days(days)).to_rfc3339()),
+            ("timeZone", "UTC".to_string()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    // slots are grouped by day, { "2024-03-01": [{ "start": ... }] }
+    let mut slots: Vec<AvailabilitySlot> = response["data"]
+        .as_object()
+        .into_iter()
+        .flat_map(|days| days.values())
+        .filter_map(Value::as_array)
+        .flatten()
+        .filter_map(|slot| slot["start"].as_str())
+        .map(|start| AvailabilitySlot {
+            start: start.to_string(),
+            booking_url: None,
+        })
+        .collect();
+    slots.sort_by(|a, b| a.start.cmp(&b.start));
+    Ok(slots)
+}
+
+async fn calendly(event_type: &str, days: i64) -> Result<Vec<AvailabilitySlot>, StoreError> {
+    // the range has to start in the future
+    let start = Utc::now() + Duration::minutes(1);
+    let response: Value = reqwest::Client::new()
+        .get(format!("{}/event_type_available_times", CALENDLY_API_URL))
+        .bearer_auth(credential(BookingProvider::Calendly)?)
+        .query(&[
+            ("event_type", event_type.to_string()),
+            ("start_time", start.to_rfc3339()),
+            ("end_time", (start + Duration::days(days)).to_rfc3339()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(response["collection"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|slot| slot["status"] == "available")
+        .filter_map(|slot| {
+            Some(AvailabilitySlot {
+                start: slot["start_time"].as_str()?.to_string(),
+                booking_url: slot["scheduling_url"].as_str().map(str::to_string),
+            })
+        })
+        .collect())
+}
+
+// Open slots of the booking section's event type over the next `days` days,
+// earliest first, so the frontend can list them without the provider's embed
+// script. Answers are cached, visitors don't each cost an API call.
+pub async fn availability(booking: &BookingSettings, days: i32) -> Result<Vec<AvailabilitySlot>, StoreError> {
+    let Some(event_type) = booking.event_type.as_deref() else {
+        return Ok(Vec::new());
+    };
+    let key = format!("{:?}\n{}\n{}", booking.provider, event_type, days);
+    let ttl = cache_ttl();
+    let cached = CACHE.lock().unwrap().get(&key).cloned();
+    if let Some((_, slots)) = cached.filter(|(fetched, _)| fetched.elapsed() < ttl) {
+        return Ok(slots);
+    }
+    let slots = match booking.provider {
+        BookingProvider::CalCom => cal_com(event_type, days.into()).await?,
+        BookingProvider::Calendly => calendly(event_type, days.into()).await?,
+    };
+    let mut cache = CACHE.lock().unwrap();
+    cache.retain(|_, (fetched, _)| fetched.elapsed() < ttl);
+    cache.insert(key, (Instant::now(), slots.clone()));
+    Ok(slots)
+}
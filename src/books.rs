@@ -0,0 +1,398 @@
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{
+    config,
+    store::{DataStore, StoreError},
+};
+
+const HARDCOVER_API_URL: &str = "https://api.hardcover.app/v1/graphql";
+// Goodreads serves shelves 100 books a page, this is 5000 books
+const MAX_GOODREADS_PAGES: u32 = 50;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, juniper::GraphQLEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum ReadingStatus {
+    WantToRead,
+    Reading,
+    Read,
+}
+
+// A book of the reading list, one per ISBN
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+#[serde(rename_all = "camelCase")]
+pub struct Book {
+    #[graphql(ignore)]
+    #[serde(default)]
+    pub email: String,
+    // ISBN-13, digits only
+    pub isbn: String,
+    pub title: String,
+    #[serde(default)]
+    pub authors: Vec<String>,
+    #[serde(default)]
+    pub cover_url: Option<String>,
+    pub status: ReadingStatus,
+    // Stars out of 5, Hardcover allows halves
+    #[serde(default)]
+    pub rating: Option<f64>,
+    // Dates like 2024-03-01
+    #[serde(default)]
+    pub added_at: Option<String>,
+    #[serde(default)]
+    pub started_at: Option<String>,
+    #[serde(default)]
+    pub finished_at: Option<String>,
+    // goodreads or hardcover, unset for books added by hand
+    #[graphql(ignore)]
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+// Where the reading list is pulled from: GOODREADS_RSS_URL is the RSS feed of a
+// Goodreads shelf, #ALL# for every shelf, HARDCOVER_TOKEN the API token of a
+// Hardcover account, either or both. BOOKS_OWNER is the portfolio owner the
+// books are listed for, USER_EMAIL by default.
+#[derive(Clone, Debug)]
+pub struct BooksConfig {
+    goodreads_rss_url: Option<String>,
+    hardcover_token: Option<String>,
+    pub owner_email: String,
+}
+
+impl BooksConfig {
+    // None when neither source is set up
+    pub fn from_env() -> Option<Self> {
+        let goodreads_rss_url = env::var("GOODREADS_RSS_URL").ok();
+        let hardcover_token = env::var("HARDCOVER_TOKEN").ok();
+        if goodreads_rss_url.is_none() && hardcover_token.is_none() {
+            return None;
+        }
+        Some(Self {
+            goodreads_rss_url,
+            hardcover_token,
+            owner_email: env::var("BOOKS_OWNER").or_else(|_| env::var("USER_EMAIL")).ok()?,
+        })
+    }
+}
+
+#[derive(Debug, Default, juniper::GraphQLObject)]
+pub struct BookSyncResult {
+    pub created: i32,
+    pub updated: i32,
+    pub unchanged: i32,
+    // Synced books no source lists anymore
+    pub removed: i32,
+    // Titles of books left out as the source has no ISBN for them
+    pub without_isbn: Vec<String>,
+}
+
+// Digits of an ISBN-13, ISBN-10s are converted so both sources agree.
+// None for anything else.
+fn normalize_isbn(text: &str) -> Option<String> {
+    let isbn: String = text
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == 'X' || *c == 'x')
+        .collect();
+    match isbn.len() {
+        13 if isbn.bytes().all(|byte| byte.is_ascii_digit()) => Some(isbn),
+        10 if isbn[..9].bytes().all(|byte| byte.is_ascii_digit()) => {
+            let digits = format!("978{}", &isbn[..9]);
+            let sum: u32 = digits
+                .bytes()
+                .enumerate()
+                .map(|(index, byte)| (byte - b'0') as u32 * if index % 2 == 0 { 1 } else { 3 })
+                .sum();
+            Some(format!("{}{}", digits, (10 - sum % 10) % 10))
+        }
+        _ => None,
+    }
+}
+
+// A book as a source lists it, its ISBN not checked yet
+struct Listed {
+    isbn: Option<String>,
+    book: Book,
+}
+
+// Text of the first <name> element of `xml`, unwrapped from CDATA or unescaped
+fn xml_text(xml: &str, name: &str) -> Option<String> {
+    let start = xml.find(&format!("<{}>", name))? + name.len() + 2;
+    let end = start + xml[start..].find(&format!("</{}>", name))?;
+    let text = xml[start..end].trim();
+    let text = match text.strip_prefix("<![CDATA[").and_then(|text| text.strip_suffix("]]>")) {
+        Some(text) => text.to_string(),
+        None => text
+            .replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&#39;", "'")
+            .replace("&amp;", "&"),
+    };
+    Some(text.trim().to_string()).filter(|text| !text.is_empty())
+}
+
+// An RFC 2822 date of the feed, e.g. Sat, 12 Mar 2022 00:00:00 -0800, as 2022-03-12
+fn feed_date(text: Option<String>) -> Option<String> {
+    let date = DateTime::parse_from_rfc2822(&text?).ok()?;
+    Some(date.format("%Y-%m-%d").to_string())
+}
+
+fn goodreads_book(item: &str, email: &str) -> Listed {
+    let shelves = xml_text(item, "user_shelves").unwrap_or_default();
+    let on_shelf = |name: &str| shelves.split(',').any(|shelf| shelf.trim() == name);
+    // the read shelf isn't listed, it's what's left of the exclusive shelves
+    let status = if on_shelf("currently-reading") {
+        ReadingStatus::Reading
+    } else if on_shelf("to-read") {
+        ReadingStatus::WantToRead
+    } else {
+        ReadingStatus::Read
+    };
+    Listed {
+        isbn: xml_text(item, "isbn13").or_else(|| xml_text(item, "isbn")),
+        book: Book {
+            email: email.to_string(),
+            isbn: String::new(),
+            title: xml_text(item, "title").unwrap_or_default(),
+            authors: xml_text(item, "author_name").into_iter().collect(),
+            cover_url: xml_text(item, "book_large_image_url").filter(|url| !url.contains("nophoto")),
+            status,
+            rating: xml_text(item, "user_rating")
+                .and_then(|rating| rating.parse().ok())
+                .filter(|rating| *rating > 0.0),
+            added_at: feed_date(xml_text(item, "user_date_added")),
+            started_at: None,
+            finished_at: feed_date(xml_text(item, "user_read_at")),
+            source: Some("goodreads".to_string()),
+        },
+    }
+}
+
+// Every book of the shelf feed, page by page until one comes back empty
+async fn goodreads(client: &reqwest::Client, url: &str, email: &str) -> Result<Vec<Listed>, StoreError> {
+    let separator = if url.contains('?') { '&' } else { '?' };
+    let mut books = Vec::new();
+    for page in 1..=MAX_GOODREADS_PAGES {
+        let feed = client
+            .get(format!("{}{}page={}", url, separator, page))
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        let items: Vec<&str> = feed
+            .split("<item>")
+            .skip(1)
+            .map(|item| item.split("</item>").next().unwrap_or_default())
+            .collect();
+        if items.is_empty() {
+            break;
+        }
+        books.extend(items.into_iter().map(|item| goodreads_book(item, email)));
+    }
+    Ok(books)
+}
+
+const HARDCOVER_QUERY: &str = "{
+  me {
+    user_books {
+      status_id
+      rating
+      date_added
+      user_book_reads(order_by: { started_at: desc_nulls_last }, limit: 1) { started_at finished_at }
+      edition { isbn_13 isbn_10 title image { url } }
+      book { title image { url } contributions { author { name } } }
+    }
+  }
+}";
+
+fn hardcover_book(user_book: &Value, email: &str) -> Option<Listed> {
+    // paused and did not finish books are left off the list
+    let status = match user_book["status_id"].as_i64()? {
+        1 => ReadingStatus::WantToRead,
+        2 => ReadingStatus::Reading,
+        3 => ReadingStatus::Read,
+        _ => return None,
+    };
+    let (edition, book, read) = (&user_book["edition"], &user_book["book"], &user_book["user_book_reads"][0]);
+    let text = |value: &Value| value.as_str().filter(|text| !text.is_empty()).map(str::to_string);
+    Some(Listed {
+        isbn: text(&edition["isbn_13"]).or_else(|| text(&edition["isbn_10"])),
+        book: Book {
+            email: email.to_string(),
+            isbn: String::new(),
+            title: text(&book["title"]).or_else(|| text(&edition["title"])).unwrap_or_default(),
+            authors: book["contributions"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|contribution| text(&contribution["author"]["name"]))
+                .collect(),
+            cover_url: text(&edition["image"]["url"]).or_else(|| text(&book["image"]["url"])),
+            status,
+            rating: user_book["rating"].as_f64(),
+            added_at: text(&user_book["date_added"]),
+            started_at: text(&read["started_at"]),
+            finished_at: text(&read["finished_at"]),
+            source: Some("hardcover".to_string()),
+        },
+    })
+}
+
+async fn hardcover(client: &reqwest::Client, token: &str, email: &str) -> Result<Vec<Listed>, StoreError> {
+    // the token is shown with its Bearer prefix on Hardcover's settings page
+    let authorization = if token.starts_with("Bearer ") {
+        token.to_string()
+    } else {
+        format!("Bearer {}", token)
+    };
+    let response: Value = client
+        .post(HARDCOVER_API_URL)
+        .header(reqwest::header::AUTHORIZATION, authorization)
+        .json(&json!({ "query": HARDCOVER_QUERY }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    if let Some(errors) = response.get("errors") {
+        return Err(format!("Hardcover returned errors: {}", errors).into());
+    }
+    Ok(response["data"]["me"][0]["user_books"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|user_book| hardcover_book(user_book, email))
+        .collect())
+}
+
+// Make the owner's reading list match the configured sources. Books are keyed
+// by ISBN, so a book on both sources is listed once, as Hardcover has it. Synced
+// books that no source lists anymore are removed, books added by hand are
+// never touched. Nothing is written when a source can't be read.
+pub async fn sync(store: &dyn DataStore, config: &BooksConfig) -> Result<BookSyncResult, StoreError> {
+    let client = reqwest::Client::new();
+    let email = config.owner_email.as_str();
+    let mut listed = Vec::new();
+    if let Some(url) = &config.goodreads_rss_url {
+        listed.extend(goodreads(&client, url, email).await?);
+    }
+    if let Some(token) = &config.hardcover_token {
+        listed.extend(hardcover(&client, token, email).await?);
+    }
+    let mut result = BookSyncResult::default();
+    let mut books: BTreeMap<String, Book> = BTreeMap::new();
+    for Listed { isbn, mut book } in listed {
+        match isbn.as_deref().and_then(normalize_isbn) {
+            Some(isbn) => {
+                book.isbn = isbn.clone();
+                books.insert(isbn, book);
+            }
+            None => result.without_isbn.push(book.title),
+        }
+    }
+    let collection = &config::collections().reading_list;
+    let stored: HashMap<String, Value> = store
+        .find(collection, json!({ "email": email }))
+        .await?
+        .into_iter()
+        .filter_map(|document| Some((document["isbn"].as_str()?.to_string(), document)))
+        .collect();
+    for (isbn, book) in &books {
+        let document = serde_json::to_value(book)?;
+        let filter = json!({ "email": email, "isbn": isbn });
+        match stored.get(isbn) {
+            None => {
+                store.insert_one(collection, document).await?;
+                result.created += 1;
+            }
+            // added by hand, the owner's copy wins over the source's
+            Some(existing) if !existing["source"].is_string() => result.unchanged += 1,
+            Some(existing) => {
+                let Value::Object(fields) = &document else {
+                    continue;
+                };
+                if fields.iter().all(|(field, value)| existing.get(field).unwrap_or(&Value::Null) == value) {
+                    result.unchanged += 1;
+                } else {
+                    store.update_one(collection, filter, document).await?;
+                    result.updated += 1;
+                }
+            }
+        }
+    }
+    for (isbn, document) in &stored {
+        if document["source"].is_string() && !books.contains_key(isbn) {
+            store.delete_one(collection, json!({ "email": email, "isbn": isbn })).await?;
+            result.removed += 1;
+        }
+    }
+    Ok(result)
+}
+
+// Sync every BOOKS_SYNC_INTERVAL seconds, an hour by default, when a source is
+// configured. 0 leaves syncing to the syncBooks mutation.
+pub fn spawn(store: Arc<dyn DataStore>) {
+    let Some(config) = BooksConfig::from_env() else {
+        return;
+    };
+    let interval = env::var("BOOKS_SYNC_INTERVAL")
+        .ok()
+        .map(|seconds| seconds.parse().expect("BOOKS_SYNC_INTERVAL must be a number of seconds"))
+        .unwrap_or(60 * 60);
+    if interval == 0 {
+        println!("BOOKS_SYNC_INTERVAL is 0, the reading list is only synced on demand");
+        return;
+    }
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+            match sync(&*store, &config).await {
+                Ok(result) => tracing::info!(
+                    created = result.created,
+                    updated = result.updated,
+                    unchanged = result.unchanged,
+                    removed = result.removed,
+                    without_isbn = result.without_isbn.len(),
+                    "Synced the reading list"
+                ),
+                Err(e) => tracing::error!(error = %e, "Reading list sync failed"),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_isbn_10_to_isbn_13() {
+        assert_eq!(normalize_isbn("0-306-40615-2").as_deref(), Some("9780306406157"));
+        // the ISBN-10 check digit can be an X, it is recomputed anyway
+        assert_eq!(normalize_isbn("080442957X").as_deref(), Some("9780804429573"));
+        assert_eq!(normalize_isbn("080442957x").as_deref(), Some("9780804429573"));
+    }
+
+    #[test]
+    fn keeps_isbn_13_digits() {
+        assert_eq!(normalize_isbn("978-0-306-40615-7").as_deref(), Some("9780306406157"));
+    }
+
+    #[test]
+    fn rejects_other_numbers() {
+        assert_eq!(normalize_isbn(""), None);
+        assert_eq!(normalize_isbn("12345"), None);
+        assert_eq!(normalize_isbn("X0-306-40615-2"), None);
+        assert_eq!(normalize_isbn("978030640615X"), None);
+    }
+}
@@ -0,0 +1,77 @@
+use ipnet::IpNet;
+use std::{env, net::IpAddr, sync::OnceLock};
+
+use crate::visitor::Visitor;
+
+// Substrings of user agents that belong to crawlers, monitors and HTTP libraries
+const DEFAULT_BOT_USER_AGENTS: &[&str] = &[
+    "bot", "crawler", "spider", "slurp", "facebookexternalhit", "embedly", "preview",
+    "headless", "lighthouse", "pingdom", "uptime", "curl", "wget", "python-requests",
+    "go-http-client", "axios", "node-fetch",
+];
+
+// How analytics should treat a request
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Human,
+    // Recorded but tagged, e.g. coming from a datacenter network
+    Suspect,
+    // Not recorded at all
+    Bot,
+}
+
+#[derive(Clone, Debug)]
+pub struct BotFilter {
+    user_agents: Vec<String>,
+    datacenter_networks: Vec<IpNet>,
+}
+
+impl BotFilter {
+    // BOT_USER_AGENTS replaces the default list of user agent substrings and
+    // DATACENTER_IP_RANGES lists CIDR ranges whose traffic is tagged as suspect,
+    // both comma separated
+    pub fn from_env() -> Self {
+        let user_agents = match env::var("BOT_USER_AGENTS") {
+            Ok(configured) => configured
+                .split(',')
+                .map(|entry| entry.trim().to_ascii_lowercase())
+                .filter(|entry| !entry.is_empty())
+                .collect(),
+            Err(_) => DEFAULT_BOT_USER_AGENTS.iter().map(|entry| entry.to_string()).collect(),
+        };
+        let datacenter_networks = env::var("DATACENTER_IP_RANGES")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                entry
+                    .parse::<IpNet>()
+                    .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                    .unwrap_or_else(|_| panic!("Invalid entry in DATACENTER_IP_RANGES: {}", entry))
+            })
+            .collect();
+        Self {
+            user_agents,
+            datacenter_networks,
+        }
+    }
+
+    pub fn classify(&self, visitor: &Visitor) -> Verdict {
+        let user_agent = visitor.user_agent().to_ascii_lowercase();
+        // real browsers always send a user agent
+        if user_agent.is_empty() || self.user_agents.iter().any(|bot| user_agent.contains(bot.as_str())) {
+            return Verdict::Bot;
+        }
+        match visitor.ip() {
+            Some(ip) if self.datacenter_networks.iter().any(|network| network.contains(&ip)) => Verdict::Suspect,
+            _ => Verdict::Human,
+        }
+    }
+}
+
+static BOT_FILTER: OnceLock<BotFilter> = OnceLock::new();
+
+pub fn filter() -> &'static BotFilter {
+    BOT_FILTER.get_or_init(BotFilter::from_env)
+}
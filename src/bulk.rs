@@ -0,0 +1,183 @@
+use juniper::{graphql_value, FieldError};
+use serde_json::{json, Value};
+
+use crate::{
+    config, data_check, rules,
+    store::{DataStore, StoreError},
+    validation::Validator,
+};
+
+// Most documents one bulkUpsert call takes
+pub const MAX_DOCUMENTS: usize = 500;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, juniper::GraphQLEnum)]
+pub enum UpsertStatus {
+    Created,
+    Updated,
+    // Not written, the document isn't valid for the collection
+    Invalid,
+    // Not written, the data store returned an error
+    Failed,
+}
+
+// What happened to one document, index is its position in the documents argument
+#[derive(Debug, juniper::GraphQLObject)]
+pub struct UpsertItem {
+    pub index: i32,
+    pub status: UpsertStatus,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Default, juniper::GraphQLObject)]
+pub struct BulkUpsertResult {
+    pub created: i32,
+    pub updated: i32,
+    pub invalid: i32,
+    pub failed: i32,
+    pub items: Vec<UpsertItem>,
+}
+
+impl BulkUpsertResult {
+    fn push(&mut self, index: usize, status: UpsertStatus, message: Option<String>) {
+        match status {
+            UpsertStatus::Created => self.created += 1,
+            UpsertStatus::Updated => self.updated += 1,
+            UpsertStatus::Invalid => self.invalid += 1,
+            UpsertStatus::Failed => self.failed += 1,
+        }
+        self.items.push(UpsertItem {
+            index: index as i32,
+            status,
+            message,
+        });
+    }
+
+    // Add the outcome of a later call, whose documents start at `offset` of the whole list
+    pub fn extend(&mut self, other: BulkUpsertResult, offset: usize) {
+        for item in other.items {
+            self.push(offset + item.index as usize, item.status, item.message);
+        }
+    }
+}
+
+// Fields identifying a document of a collection among the owner's documents of
+// one locale, for documents given without an _id. Owners have a single document
+// in the collections with no fields listed.
+fn natural_key(collection: &str) -> &'static [&'static str] {
+    let collections = config::collections();
+    match collection {
+        name if name == collections.introductions => &["experimentKey", "variant"],
+        name if name == collections.projects || name == collections.blog_posts || name == collections.talks => &["slug"],
+        name if name == collections.skills || name == collections.soft_skills => &["name"],
+        name if name == collections.skills_overview || name == collections.services => &["title"],
+        name if name == collections.social_media => &["socialMediaType"],
+        name if name == collections.work_experience => &["company", "title", "startDate"],
+        name if name == collections.education => &["school", "degree"],
+        name if name == collections.reading_list => &["isbn"],
+        name if name == collections.uses => &["name"],
+        name if name == collections.feature_flags => &["key"],
+        name if name == collections.navigation => &["menu", "href"],
+        name if name == collections.redirects => &["fromPath"],
+        _ => &[],
+    }
+}
+
+// Filter matching the stored document `document` replaces
+fn key_filter(collection: &str, owner_email: &str, document: &Value) -> Value {
+    if let Some(id) = document.get("_id") {
+        return json!({ "_id": id, "email": owner_email });
+    }
+    let mut filter = json!({ "email": owner_email });
+    let mut fields = natural_key(collection).to_vec();
+    if config::collections().localized().contains(&collection) {
+        fields.push("locale");
+    }
+    for field in fields {
+        filter[field] = document.get(field).cloned().unwrap_or(Value::Null);
+    }
+    filter
+}
+
+// Create or update each of `documents`, JSON objects for one of the portfolio
+// collections. A document replaces the fields of the stored one with the same
+// _id or natural key and is inserted when there is none. Documents are written
+// one by one, so the ones before an invalid or failed document stay written.
+pub async fn upsert(
+    store: &dyn DataStore,
+    owner_email: &str,
+    collection: &str,
+    documents: Vec<String>,
+) -> Result<BulkUpsertResult, FieldError> {
+    let mut input = Validator::default();
+    if !config::collections().portfolio().contains(&collection) {
+        input.reject("collection", "must be one of the portfolio collections");
+    }
+    if documents.len() > MAX_DOCUMENTS {
+        input.reject("documents", format!("must list at most {} documents", MAX_DOCUMENTS));
+    }
+    input.finish()?;
+    let owner_rules = rules::load(store, owner_email).await.map_err(|err| {
+        FieldError::new(
+            "Failed to fetch content rules",
+            graphql_value!({ "details": err.to_string() }),
+        )
+    })?;
+    let mut result = BulkUpsertResult::default();
+    for (index, document) in documents.iter().enumerate() {
+        let mut document: Value = match serde_json::from_str(document) {
+            Ok(document @ Value::Object(_)) => document,
+            Ok(_) => {
+                result.push(index, UpsertStatus::Invalid, Some("not a JSON object".to_string()));
+                continue;
+            }
+            Err(e) => {
+                result.push(index, UpsertStatus::Invalid, Some(format!("not valid JSON: {}", e)));
+                continue;
+            }
+        };
+        match document.get("email").and_then(Value::as_str) {
+            Some(email) if email != owner_email => {
+                let message = format!("belongs to {}, not to the portfolio owner", email);
+                result.push(index, UpsertStatus::Invalid, Some(message));
+                continue;
+            }
+            _ => document["email"] = json!(owner_email),
+        }
+        if let Some(error) = data_check::model_error(collection, &document) {
+            result.push(index, UpsertStatus::Invalid, Some(error));
+            continue;
+        }
+        let broken: Vec<&str> = rules::broken(&owner_rules, collection, &document)
+            .map(|rule| rule.name.as_str())
+            .collect();
+        if !broken.is_empty() {
+            let message = format!("breaks content rules: {}", broken.join(", "));
+            result.push(index, UpsertStatus::Invalid, Some(message));
+            continue;
+        }
+        match write(store, owner_email, collection, document).await {
+            Ok(status) => result.push(index, status, None),
+            Err(e) => result.push(index, UpsertStatus::Failed, Some(e.to_string())),
+        }
+    }
+    Ok(result)
+}
+
+async fn write(
+    store: &dyn DataStore,
+    owner_email: &str,
+    collection: &str,
+    document: Value,
+) -> Result<UpsertStatus, StoreError> {
+    let filter = key_filter(collection, owner_email, &document);
+    let mut changes = document.clone();
+    if let Value::Object(fields) = &mut changes {
+        fields.remove("_id");
+    }
+    if store.update_one(collection, filter, changes).await? {
+        return Ok(UpsertStatus::Updated);
+    }
+    // an _id that matched nothing is kept for the new document
+    store.insert_one(collection, document).await?;
+    Ok(UpsertStatus::Created)
+}
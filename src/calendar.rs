@@ -0,0 +1,77 @@
+use chrono::{DateTime, Datelike, NaiveDate};
+use chrono_tz::Tz;
+use juniper::{graphql_value, FieldError};
+use std::collections::BTreeMap;
+
+use crate::{
+    blog::{BlogPost, BlogPostStatus},
+    Context,
+};
+
+// A post on the day it was published, or for drafts and posts in review the day
+// they were last worked on
+#[derive(Clone, Debug, juniper::GraphQLObject)]
+#[graphql(context = Context)]
+pub struct CalendarEntry {
+    pub status: BlogPostStatus,
+    // ISO 8601 timestamp the entry is placed by
+    pub at: String,
+    pub post: BlogPost,
+}
+
+#[derive(Clone, Debug, juniper::GraphQLObject)]
+#[graphql(context = Context)]
+pub struct CalendarDay {
+    // e.g. 2024-03-01
+    pub date: String,
+    pub entries: Vec<CalendarEntry>,
+}
+
+// First day of a month given like 2024-03
+pub fn parse_month(month: &str) -> Result<NaiveDate, FieldError> {
+    NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d").map_err(|_| {
+        FieldError::new(
+            "Invalid month",
+            graphql_value!({ "details": "month must look like 2024-03" }),
+        )
+    })
+}
+
+// Day of an ISO 8601 timestamp or date, in `timezone` when given and UTC otherwise
+fn day(at: &str, timezone: Option<Tz>) -> Option<NaiveDate> {
+    match (DateTime::parse_from_rfc3339(at), timezone) {
+        (Ok(at), Some(timezone)) => Some(at.with_timezone(&timezone).date_naive()),
+        (Ok(at), None) => Some(at.naive_utc().date()),
+        // plain dates have no time of day to convert
+        (Err(_), _) => NaiveDate::parse_from_str(at, "%Y-%m-%d").ok(),
+    }
+}
+
+// Days of `month` that have entries, in order, each with its entries by time.
+// Posts whose dates can't be read are left out.
+pub fn month(posts: Vec<BlogPost>, month: NaiveDate, timezone: Option<Tz>) -> Vec<CalendarDay> {
+    let mut days: BTreeMap<NaiveDate, Vec<CalendarEntry>> = BTreeMap::new();
+    for post in posts {
+        let status = BlogPostStatus::of(&post);
+        let at = match status {
+            BlogPostStatus::Published => post.published_at.clone(),
+            // drafts keep their creation time as publishedAt
+            _ => post.updated_at.clone().unwrap_or_else(|| post.published_at.clone()),
+        };
+        let Some(date) = day(&at, timezone) else {
+            continue;
+        };
+        if (date.year(), date.month()) == (month.year(), month.month()) {
+            days.entry(date).or_default().push(CalendarEntry { status, at, post });
+        }
+    }
+    days.into_iter()
+        .map(|(date, mut entries)| {
+            entries.sort_by(|a, b| a.at.cmp(&b.at));
+            CalendarDay {
+                date: date.format("%Y-%m-%d").to_string(),
+                entries,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,166 @@
+use clap::{Parser, Subcommand};
+use serde_json::{json, Value};
+use std::{fs, path::PathBuf};
+
+use crate::{
+    bench::BenchArgs,
+    config, data_check, preflight, search,
+    store::{self, DataStore, FileStore, StoreError},
+};
+
+#[derive(Debug, Parser)]
+#[command(name = "portfolio_api", about = "GraphQL API serving portfolio content")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the HTTP server (the default when no subcommand is given)
+    Serve,
+    /// Insert fixtures from a directory of <collection>.json files
+    Seed {
+        #[arg(long, default_value = "data")]
+        dir: PathBuf,
+    },
+    /// Create the indexes the API relies on
+    Migrate,
+    /// Write every collection to <dir>/<collection>.json
+    Export {
+        #[arg(long, default_value = "export")]
+        dir: PathBuf,
+        /// Only export the documents of this owner
+        #[arg(long)]
+        owner: Option<String>,
+    },
+    /// Validate the environment and that the data store has every collection
+    Check,
+    /// Decode every document against its model and check the content rules, summarizing what doesn't match
+    CheckData {
+        /// Only check the documents of this owner
+        #[arg(long)]
+        owner: Option<String>,
+    },
+    /// Summarize finished days of analytics and prune old raw page views now
+    Rollup,
+    /// Rebuild the search engine's index from every published post and project
+    Reindex,
+    /// Send a mix of GraphQL operations to a running instance and report latency percentiles
+    Bench(BenchArgs),
+}
+
+pub async fn seed(store: &dyn DataStore, dir: PathBuf) -> Result<(), StoreError> {
+    let fixtures = FileStore::load_dir(&dir)?;
+    for (collection, documents) in fixtures.collections() {
+        let count = documents.len();
+        let writes = documents
+            .iter()
+            .map(|document| (collection.clone(), document.clone()))
+            .collect();
+        store.insert_many_atomic(writes).await?;
+        println!("Seeded {} documents into {}", count, collection);
+    }
+    Ok(())
+}
+
+pub async fn migrate(store: &dyn DataStore) -> Result<(), StoreError> {
+    store::ensure_indexes(store).await?;
+    println!("Indexes are up to date");
+    Ok(())
+}
+
+pub async fn export(store: &dyn DataStore, dir: PathBuf, owner: Option<String>) -> Result<(), StoreError> {
+    fs::create_dir_all(&dir)?;
+    let filter = match &owner {
+        Some(owner) => json!({ "email": owner }),
+        None => json!({}),
+    };
+    let collections = config::collections();
+    // API keys are left out on purpose, their hashes have no use outside this deployment
+    let mut names = collections.portfolio().to_vec();
+    names.push(collections.tenants.as_str());
+    for name in names {
+        let documents: Vec<Value> = store.find(name, filter.clone()).await?;
+        let path = dir.join(format!("{}.json", name));
+        fs::write(&path, serde_json::to_string_pretty(&documents)?)?;
+        println!("Exported {} documents to {}", documents.len(), path.display());
+    }
+    Ok(())
+}
+
+pub async fn check() -> Result<(), StoreError> {
+    let mut report = preflight::Report::environment();
+    // with a broken environment connecting would only repeat one of its problems
+    if !report.has_errors() {
+        report.connect().await;
+    }
+    report.print();
+    if report.has_errors() {
+        return Err("Configuration has errors".into());
+    }
+    println!("Configuration OK");
+    Ok(())
+}
+
+pub async fn check_data(store: &dyn DataStore, owner: Option<String>) -> Result<(), StoreError> {
+    let reports = data_check::run(store, owner.as_deref()).await?;
+    let (mut invalid, mut breaking) = (0, 0);
+    for report in &reports {
+        if report.is_ok() {
+            println!("{} ({}): {} documents OK", report.collection, report.model, report.documents);
+            continue;
+        }
+        if report.invalid > 0 {
+            println!(
+                "{} ({}): {} of {} documents don't match",
+                report.collection, report.model, report.invalid, report.documents
+            );
+        }
+        for mismatch in &report.mismatches {
+            println!(
+                "  {}: {} documents, e.g. {}: {}",
+                mismatch.field,
+                mismatch.documents,
+                mismatch.example_id.as_deref().unwrap_or("a document without _id"),
+                mismatch.example
+            );
+        }
+        for violation in &report.rule_violations {
+            println!(
+                "{} ({}): {} documents break rule {:?}, e.g. {}",
+                report.collection,
+                report.model,
+                violation.documents,
+                violation.rule,
+                violation.example_id.as_deref().unwrap_or("a document without _id")
+            );
+            breaking += violation.documents;
+        }
+        invalid += report.invalid;
+    }
+    if invalid > 0 || breaking > 0 {
+        return Err(format!(
+            "{} documents don't match their model, {} content rule violations",
+            invalid, breaking
+        )
+        .into());
+    }
+    println!("All documents match their models and content rules");
+    Ok(())
+}
+
+pub async fn reindex(store: &dyn DataStore) -> Result<(), StoreError> {
+    let provider = search::provider();
+    if !provider.keeps_copy() {
+        println!("{} search reads the data store, there is no index to rebuild", provider.name());
+        return Ok(());
+    }
+    if provider.in_memory() {
+        println!("The {} index lives in the server and is rebuilt when it starts", provider.name());
+        return Ok(());
+    }
+    let count = search::reindex(store, provider).await?;
+    println!("Indexed {} documents in {}", count, provider.name());
+    Ok(())
+}
@@ -0,0 +1,413 @@
+use juniper::{graphql_value, FieldError};
+use mongodb::bson::DateTime;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::{
+    bulk::{self, BulkUpsertResult},
+    config,
+    content::{CodeBlock, ContentBlock, HeadingBlock, ImageBlock, ListBlock, ParagraphBlock},
+    store::DataStore,
+    validation::{Validator, MAX_NAME_CHARS},
+};
+
+// Headless CMS an export comes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, juniper::GraphQLEnum)]
+pub enum CmsSource {
+    // The JSON file written by `contentful space export`
+    Contentful,
+    // The NDJSON of data.ndjson in the archive written by `sanity dataset export`
+    Sanity,
+}
+
+// Collection the documents of a content type of the CMS go to, e.g. article to blogposts
+#[derive(Debug, juniper::GraphQLInputObject)]
+pub struct TypeMappingInput {
+    pub source_type: String,
+    pub collection: String,
+}
+
+// Where Sanity serves the dataset's images, image fields are left out without it
+#[derive(Debug, juniper::GraphQLInputObject)]
+pub struct SanityImagesInput {
+    pub project_id: String,
+    pub dataset: String,
+}
+
+#[derive(Debug, juniper::GraphQLObject)]
+pub struct ImportedCollection {
+    pub collection: String,
+    // Item indexes count the collection's documents in the order of the export
+    pub result: BulkUpsertResult,
+}
+
+// A content type of the export no collection is mapped to
+#[derive(Debug, juniper::GraphQLObject)]
+pub struct SkippedType {
+    pub source_type: String,
+    pub documents: i32,
+}
+
+#[derive(Debug, Default, juniper::GraphQLObject)]
+pub struct ImportResult {
+    pub collections: Vec<ImportedCollection>,
+    pub skipped: Vec<SkippedType>,
+}
+
+// A document of the export with its fields named and shaped as stored here,
+// before it is known which collection it goes to
+struct Converted {
+    source_type: String,
+    fields: Map<String, Value>,
+    published: bool,
+    published_at: Option<String>,
+}
+
+// Content types of the usual CMS starters, used unless the types argument maps them
+fn default_collection(source_type: &str) -> Option<&'static str> {
+    let collections = config::collections();
+    match source_type {
+        "post" | "blogPost" | "article" => Some(collections.blog_posts.as_str()),
+        "project" => Some(collections.projects.as_str()),
+        "skill" => Some(collections.skills.as_str()),
+        "service" => Some(collections.services.as_str()),
+        _ => None,
+    }
+}
+
+// Add an item to the list `content` ends with, or start a list when it ends
+// with something else, as both CMSs store list items as separate blocks
+fn push_list_item(content: &mut Vec<ContentBlock>, item: String, ordered: bool) {
+    if let Some(ContentBlock::List(list)) = content.last_mut() {
+        if list.ordered == ordered {
+            list.items.push(item);
+            return;
+        }
+    }
+    content.push(ContentBlock::List(ListBlock { items: vec![item], ordered }));
+}
+
+fn image(src: String, alt: Option<&str>, caption: Option<&str>) -> ContentBlock {
+    ContentBlock::Image(ImageBlock {
+        src,
+        alt: alt.unwrap_or_default().to_string(),
+        caption: caption.filter(|caption| !caption.is_empty()).map(str::to_string),
+        width: None,
+        height: None,
+        blurhash: None,
+    })
+}
+
+// Text of a rich text node and its children, marks like bold are dropped
+fn node_text(node: &Value) -> String {
+    if node["nodeType"] == "text" {
+        return node["value"].as_str().unwrap_or_default().to_string();
+    }
+    node["content"].as_array().into_iter().flatten().map(node_text).collect()
+}
+
+fn rich_text(document: &Value, assets: &HashMap<String, String>) -> Vec<ContentBlock> {
+    let mut content = Vec::new();
+    for node in document["content"].as_array().into_iter().flatten() {
+        let kind = node["nodeType"].as_str().unwrap_or_default();
+        match kind {
+            "paragraph" | "blockquote" => {
+                let text = node_text(node);
+                if !text.trim().is_empty() {
+                    content.push(ContentBlock::Paragraph(ParagraphBlock { text }));
+                }
+            }
+            "unordered-list" | "ordered-list" => {
+                for item in node["content"].as_array().into_iter().flatten() {
+                    push_list_item(&mut content, node_text(item), kind == "ordered-list");
+                }
+            }
+            "embedded-asset-block" => {
+                let id = node["data"]["target"]["sys"]["id"].as_str().unwrap_or_default();
+                if let Some(src) = assets.get(id) {
+                    content.push(image(src.clone(), None, None));
+                }
+            }
+            heading => {
+                if let Some(level) = heading.strip_prefix("heading-").and_then(|level| level.parse().ok()) {
+                    content.push(ContentBlock::Heading(HeadingBlock { text: node_text(node), level }));
+                }
+            }
+        }
+    }
+    content
+}
+
+// A field value with rich text turned into content blocks and links to assets
+// into their URLs. Links to other entries are kept as they are.
+fn contentful_value(value: &Value, assets: &HashMap<String, String>) -> Value {
+    match value {
+        Value::Object(object) if object.get("nodeType").is_some_and(|kind| kind == "document") => {
+            json!(rich_text(value, assets))
+        }
+        Value::Object(_) if value["sys"]["linkType"] == "Asset" => {
+            let id = value["sys"]["id"].as_str().unwrap_or_default();
+            assets.get(id).map_or(Value::Null, |src| json!(src))
+        }
+        Value::Array(values) => values.iter().map(|value| contentful_value(value, assets)).collect(),
+        other => other.clone(),
+    }
+}
+
+// One document per entry and locale, fields without a value in a locale take
+// the default locale's as Contentful does
+fn contentful(payload: &str) -> Result<Vec<Converted>, String> {
+    let export: Value = serde_json::from_str(payload).map_err(|e| format!("not valid JSON: {}", e))?;
+    let default_locale = export["locales"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|locale| locale["default"] == true)
+        .and_then(|locale| locale["code"].as_str())
+        .unwrap_or("en-US");
+    // asset URLs are protocol relative, e.g. //images.ctfassets.net/...
+    let assets: HashMap<String, String> = export["assets"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|asset| {
+            let id = asset["sys"]["id"].as_str()?;
+            let url = asset["fields"]["file"][default_locale]["url"].as_str()?;
+            let url = if url.starts_with("//") { format!("https:{}", url) } else { url.to_string() };
+            Some((id.to_string(), url))
+        })
+        .collect();
+    let Some(entries) = export["entries"].as_array() else {
+        return Err("has no entries, expected the output of contentful space export".to_string());
+    };
+    let mut converted = Vec::new();
+    for entry in entries {
+        let Some(source_type) = entry["sys"]["contentType"]["sys"]["id"].as_str() else {
+            continue;
+        };
+        let Some(fields) = entry["fields"].as_object() else {
+            continue;
+        };
+        let mut locales: Vec<&str> = vec![default_locale];
+        for values in fields.values().filter_map(Value::as_object) {
+            for locale in values.keys() {
+                if !locales.contains(&locale.as_str()) {
+                    locales.push(locale);
+                }
+            }
+        }
+        let sys = &entry["sys"];
+        for locale in locales {
+            let mut document = Map::new();
+            for (name, values) in fields {
+                if let Some(value) = values.get(locale).or_else(|| values.get(default_locale)) {
+                    document.insert(name.clone(), contentful_value(value, &assets));
+                }
+            }
+            if locale != default_locale {
+                document.insert("locale".to_string(), json!(locale));
+            }
+            converted.push(Converted {
+                source_type: source_type.to_string(),
+                fields: document,
+                published: sys["publishedVersion"].is_number(),
+                published_at: sys["firstPublishedAt"].as_str().or(sys["createdAt"].as_str()).map(str::to_string),
+            });
+        }
+    }
+    Ok(converted)
+}
+
+// CDN URL of an image whose asset is referenced like image-<id>-800x600-png
+fn sanity_image(image: &Value, images: Option<&SanityImagesInput>) -> Option<String> {
+    let images = images?;
+    let reference = image["asset"]["_ref"].as_str()?.strip_prefix("image-")?;
+    let (name, extension) = reference.rsplit_once('-')?;
+    Some(format!(
+        "https://cdn.sanity.io/images/{}/{}/{}.{}",
+        images.project_id, images.dataset, name, extension
+    ))
+}
+
+fn portable_text(blocks: &[Value], images: Option<&SanityImagesInput>) -> Vec<ContentBlock> {
+    let mut content = Vec::new();
+    for block in blocks {
+        match block["_type"].as_str().unwrap_or_default() {
+            "block" => {
+                let text: String = block["children"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|span| span["text"].as_str())
+                    .collect();
+                let style = block["style"].as_str().unwrap_or("normal");
+                if let Some(list) = block["listItem"].as_str() {
+                    push_list_item(&mut content, text, list == "number");
+                } else if let Some(level) = style.strip_prefix('h').and_then(|level| level.parse().ok()) {
+                    content.push(ContentBlock::Heading(HeadingBlock { text, level }));
+                } else if !text.trim().is_empty() {
+                    content.push(ContentBlock::Paragraph(ParagraphBlock { text }));
+                }
+            }
+            "image" => {
+                if let Some(src) = sanity_image(block, images) {
+                    content.push(image(src, block["alt"].as_str(), block["caption"].as_str()));
+                }
+            }
+            "code" => content.push(ContentBlock::Code(CodeBlock {
+                code: block["code"].as_str().unwrap_or_default().to_string(),
+                language: block["language"].as_str().map(str::to_string),
+            })),
+            _ => {}
+        }
+    }
+    content
+}
+
+fn is_portable_text(values: &[Value]) -> bool {
+    values.iter().any(|value| value["_type"] == "block")
+}
+
+// A field value with slugs, images and portable text in the shape stored here,
+// and Sanity's own _key, _type and _ref fields left out of other objects
+fn sanity_value(value: &Value, images: Option<&SanityImagesInput>) -> Value {
+    match value {
+        Value::Object(object) => match object.get("_type").and_then(Value::as_str) {
+            Some("slug") => object.get("current").cloned().unwrap_or(Value::Null),
+            Some("image") => sanity_image(value, images).map_or(Value::Null, |src| json!(src)),
+            _ => object
+                .iter()
+                .filter(|(name, _)| !name.starts_with('_'))
+                .map(|(name, value)| (name.clone(), sanity_value(value, images)))
+                .collect(),
+        },
+        Value::Array(values) if is_portable_text(values) => json!(portable_text(values, images)),
+        Value::Array(values) => values.iter().map(|value| sanity_value(value, images)).collect(),
+        other => other.clone(),
+    }
+}
+
+// One document per published document, or per draft of a document never published
+fn sanity(payload: &str, images: Option<&SanityImagesInput>) -> Result<Vec<Converted>, String> {
+    let mut documents = Vec::new();
+    for (line, text) in payload.lines().enumerate().filter(|(_, text)| !text.trim().is_empty()) {
+        let document: Value =
+            serde_json::from_str(text).map_err(|e| format!("line {} is not valid JSON: {}", line + 1, e))?;
+        documents.push(document);
+    }
+    let published: HashSet<&str> = documents
+        .iter()
+        .filter_map(|document| document["_id"].as_str())
+        .filter(|id| !id.starts_with("drafts."))
+        .collect();
+    let mut converted = Vec::new();
+    for document in &documents {
+        let (Some(id), Some(source_type)) = (document["_id"].as_str(), document["_type"].as_str()) else {
+            continue;
+        };
+        // assets and other documents Sanity keeps for itself
+        if source_type.starts_with("sanity.") || source_type.starts_with("system.") {
+            continue;
+        }
+        let draft_of = id.strip_prefix("drafts.");
+        if draft_of.is_some_and(|id| published.contains(id)) {
+            continue;
+        }
+        let Value::Object(fields) = sanity_value(document, images) else {
+            continue;
+        };
+        converted.push(Converted {
+            source_type: source_type.to_string(),
+            fields,
+            published: draft_of.is_none(),
+            published_at: document["publishedAt"]
+                .as_str()
+                .or(document["_createdAt"].as_str())
+                .map(str::to_string),
+        });
+    }
+    Ok(converted)
+}
+
+// Map the documents of a CMS export into the owner's collections and write them
+// through bulk::upsert, so they are checked and keyed as bulkUpsert documents
+// are. Rich text becomes content blocks and a body field becomes content.
+// Blog posts keep whether they were published, with their first publication date.
+pub async fn import(
+    store: &dyn DataStore,
+    owner_email: &str,
+    source: CmsSource,
+    payload: &str,
+    types: Vec<TypeMappingInput>,
+    sanity_images: Option<SanityImagesInput>,
+) -> Result<ImportResult, FieldError> {
+    let collections = config::collections();
+    let mut input = Validator::default();
+    for mapping in &types {
+        input.text("types.sourceType", &mapping.source_type, MAX_NAME_CHARS);
+        if !collections.portfolio().contains(&mapping.collection.as_str()) {
+            input.reject("types.collection", "must be one of the portfolio collections");
+        }
+    }
+    let converted = match source {
+        CmsSource::Contentful => contentful(payload),
+        CmsSource::Sanity => sanity(payload, sanity_images.as_ref()),
+    };
+    let converted = converted.unwrap_or_else(|message| {
+        input.reject("payload", message);
+        Vec::new()
+    });
+    input.finish()?;
+    let types: HashMap<String, String> = types
+        .into_iter()
+        .map(|mapping| (mapping.source_type, mapping.collection))
+        .collect();
+    let now = DateTime::now().try_to_rfc3339_string().map_err(|err| {
+        FieldError::new(
+            "Failed to read the current time",
+            graphql_value!({ "details": err.to_string() }),
+        )
+    })?;
+    let mut documents: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    let mut skipped: BTreeMap<String, i32> = BTreeMap::new();
+    for Converted { source_type, mut fields, published, published_at } in converted {
+        let collection = types.get(&source_type).map(String::as_str).or_else(|| default_collection(&source_type));
+        let Some(collection) = collection else {
+            *skipped.entry(source_type).or_default() += 1;
+            continue;
+        };
+        if !fields.contains_key("content") {
+            if let Some(body) = fields.remove("body") {
+                fields.insert("content".to_string(), body);
+            }
+        }
+        if collection == collections.blog_posts {
+            let published_at = published_at.unwrap_or_else(|| now.clone());
+            let status = if published { "published" } else { "draft" };
+            fields.entry("status").or_insert_with(|| json!(status));
+            // drafts keep their creation time as publishedAt, see blog::BlogPost
+            if !published {
+                fields.entry("createdAt").or_insert_with(|| json!(published_at));
+            }
+            fields.entry("publishedAt").or_insert_with(|| json!(published_at));
+        }
+        documents.entry(collection).or_default().push(Value::Object(fields).to_string());
+    }
+    let mut result = ImportResult::default();
+    for (collection, documents) in documents {
+        let mut written = BulkUpsertResult::default();
+        for (chunk, documents) in documents.chunks(bulk::MAX_DOCUMENTS).enumerate() {
+            let part = bulk::upsert(store, owner_email, collection, documents.to_vec()).await?;
+            written.extend(part, chunk * bulk::MAX_DOCUMENTS);
+        }
+        result.collections.push(ImportedCollection {
+            collection: collection.to_string(),
+            result: written,
+        });
+    }
+    result.skipped = skipped
+        .into_iter()
+        .map(|(source_type, documents)| SkippedType { source_type, documents })
+        .collect();
+    Ok(result)
+}
@@ -0,0 +1,471 @@
+use serde::Deserialize;
+use std::{collections::HashMap, env, fs, sync::OnceLock};
+
+// Names of every collection the API reads or writes
+#[derive(Clone, Debug)]
+pub struct CollectionNames {
+    pub introductions: String,
+    pub personals: String,
+    pub projects: String,
+    pub skills_overview: String,
+    pub skills: String,
+    pub social_media: String,
+    pub soft_skills: String,
+    pub users: String,
+    pub blog_posts: String,
+    pub services: String,
+    // Resume sections, positions held and schools attended
+    pub work_experience: String,
+    pub education: String,
+    // Books read, being read and to read, one per ISBN
+    pub reading_list: String,
+    // Talks given, and the gear and software of the /uses page
+    pub talks: String,
+    pub uses: String,
+    pub feature_flags: String,
+    // Header and footer menu items
+    pub navigation: String,
+    // Redirects from old paths, served for paths the API doesn't handle
+    pub redirects: String,
+    // One content version per owner, raised on every write to their portfolio
+    pub content_versions: String,
+    pub settings: String,
+    pub tenants: String,
+    pub api_keys: String,
+    // Visitor endorsements of skills, keyed by an anonymous visitor hash
+    pub endorsements: String,
+    // Page views recorded by trackPageView
+    pub analytics: String,
+    // Visitor likes of projects, keyed by an anonymous visitor hash
+    pub likes: String,
+    // Exposures and conversions of introduction experiments
+    pub experiments: String,
+    // Named events recorded by trackEvent
+    pub events: String,
+    // One summary per owner and day, written by the nightly rollup
+    pub analytics_daily: String,
+    // Portfolio documents deleted through the admin API, until restored or purged
+    pub trash: String,
+    // Records of uploaded images and files, the files themselves live elsewhere
+    pub media: String,
+}
+
+impl CollectionNames {
+    // Each name can be overridden with COLLECTION_<NAME>, e.g. COLLECTION_PROJECTS=portfolio_projects
+    pub fn from_env() -> Self {
+        Self {
+            introductions: name_from_env("INTRODUCTIONS", "introductions"),
+            personals: name_from_env("PERSONALS", "personals"),
+            projects: name_from_env("PROJECTS", "projects"),
+            skills_overview: name_from_env("SKILLS_OVERVIEW", "skillsoverview"),
+            skills: name_from_env("SKILLS", "skills"),
+            social_media: name_from_env("SOCIAL_MEDIA", "socialmedias"),
+            soft_skills: name_from_env("SOFT_SKILLS", "softskills"),
+            users: name_from_env("USERS", "users"),
+            blog_posts: name_from_env("BLOG_POSTS", "blogposts"),
+            services: name_from_env("SERVICES", "services"),
+            work_experience: name_from_env("WORK_EXPERIENCE", "workexperiences"),
+            education: name_from_env("EDUCATION", "educations"),
+            reading_list: name_from_env("READING_LIST", "readinglist"),
+            talks: name_from_env("TALKS", "talks"),
+            uses: name_from_env("USES", "uses"),
+            feature_flags: name_from_env("FEATURE_FLAGS", "featureflags"),
+            navigation: name_from_env("NAVIGATION", "navigation"),
+            redirects: name_from_env("REDIRECTS", "redirects"),
+            content_versions: name_from_env("CONTENT_VERSIONS", "contentversions"),
+            settings: name_from_env("SETTINGS", "settings"),
+            tenants: name_from_env("TENANTS", "tenants"),
+            api_keys: name_from_env("API_KEYS", "api_keys"),
+            endorsements: name_from_env("ENDORSEMENTS", "endorsements"),
+            analytics: name_from_env("ANALYTICS", "analytics"),
+            likes: name_from_env("LIKES", "likes"),
+            experiments: name_from_env("EXPERIMENTS", "experiments"),
+            events: name_from_env("EVENTS", "events"),
+            analytics_daily: name_from_env("ANALYTICS_DAILY", "analytics_daily"),
+            trash: name_from_env("TRASH", "trash"),
+            media: name_from_env("MEDIA", "media"),
+        }
+    }
+
+    // Collections holding per owner portfolio documents, all filtered by email
+    pub fn portfolio(&self) -> [&str; 19] {
+        [
+            self.introductions.as_str(),
+            self.personals.as_str(),
+            self.projects.as_str(),
+            self.skills_overview.as_str(),
+            self.skills.as_str(),
+            self.social_media.as_str(),
+            self.soft_skills.as_str(),
+            self.users.as_str(),
+            self.blog_posts.as_str(),
+            self.services.as_str(),
+            self.work_experience.as_str(),
+            self.education.as_str(),
+            self.reading_list.as_str(),
+            self.talks.as_str(),
+            self.uses.as_str(),
+            self.feature_flags.as_str(),
+            self.navigation.as_str(),
+            self.redirects.as_str(),
+            self.settings.as_str(),
+        ]
+    }
+
+    // Collections whose documents can have per-locale variants
+    pub fn localized(&self) -> [&str; 12] {
+        [
+            self.introductions.as_str(),
+            self.personals.as_str(),
+            self.projects.as_str(),
+            self.skills_overview.as_str(),
+            self.skills.as_str(),
+            self.soft_skills.as_str(),
+            self.blog_posts.as_str(),
+            self.services.as_str(),
+            self.work_experience.as_str(),
+            self.education.as_str(),
+            self.talks.as_str(),
+            self.uses.as_str(),
+        ]
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DatabaseConfig {
+    pub database_name: String,
+    pub collections: CollectionNames,
+}
+
+impl DatabaseConfig {
+    pub fn from_env() -> Self {
+        Self {
+            database_name: env::var("MONGO_DB_NAME").unwrap_or_else(|_| "personal".to_string()),
+            collections: CollectionNames::from_env(),
+        }
+    }
+}
+
+static DATABASE_CONFIG: OnceLock<DatabaseConfig> = OnceLock::new();
+
+// Database settings, read from the environment on first use
+pub fn database() -> &'static DatabaseConfig {
+    DATABASE_CONFIG.get_or_init(DatabaseConfig::from_env)
+}
+
+pub fn collections() -> &'static CollectionNames {
+    &database().collections
+}
+
+fn name_from_env(suffix: &str, default: &str) -> String {
+    env::var(format!("COLLECTION_{}", suffix)).unwrap_or_else(|_| default.to_string())
+}
+
+// Deployment profile picked with APP_ENV
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Profile {
+    Dev,
+    Staging,
+    Production,
+}
+
+impl Profile {
+    pub fn from_env() -> Self {
+        match env::var("APP_ENV").unwrap_or_else(|_| "dev".to_string()).as_str() {
+            "dev" | "development" => Profile::Dev,
+            "staging" => Profile::Staging,
+            "prod" | "production" => Profile::Production,
+            other => panic!("Unknown APP_ENV {}, expected dev, staging or production", other),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Profile::Dev => "dev",
+            Profile::Staging => "staging",
+            Profile::Production => "production",
+        }
+    }
+}
+
+// Load .env.<profile> before .env; dotenv never overrides variables that are
+// already set, so the process environment wins, then the profile file, then .env
+pub fn load_env_files() {
+    let profile = Profile::from_env();
+    dotenv::from_filename(format!(".env.{}", profile.name())).ok();
+    dotenv::dotenv().ok();
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+// What to do with a stored document that doesn't match its GraphQL model
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DecodeMode {
+    // Leave it out of the result and report it in the response's warnings
+    Lenient,
+    // Fail the field with the document's id and the decode error
+    Strict,
+}
+
+// Per profile behaviour of the HTTP layer
+#[derive(Clone, Debug)]
+pub struct AppSettings {
+    pub profile: Profile,
+    pub introspection: bool,
+    pub playground: bool,
+    // "*" allows any origin
+    pub cors_origins: Vec<String>,
+    pub log_format: LogFormat,
+    // Syntect theme used to highlight code blocks, e.g. InspiredGitHub or base16-ocean.dark
+    pub syntax_theme: String,
+    // Seconds store reads are cached for, 0 turns the cache off
+    pub cache_ttl: u64,
+    // Seconds expired reads are still served while they refresh, per collection
+    // name with "default" for the others
+    pub cache_swr: HashMap<String, u64>,
+    // Results cached per collection, the least recently read are dropped beyond it
+    pub cache_max_entries: usize,
+    // Attempts at a store operation failing on transient errors, 1 turns retries off
+    pub retry_attempts: u32,
+    // Upper bound of the first wait between attempts in milliseconds, doubled each retry
+    pub retry_backoff_ms: u64,
+    // Failures among the last 20 store calls that open the circuit breaker, 0 turns it off
+    pub breaker_threshold: u32,
+    // Seconds the open breaker fails calls before letting one through to probe the store
+    pub breaker_cooldown: u64,
+    // Serve cached reads whatever their age while the breaker is open
+    pub breaker_serve_stale: bool,
+    // Milliseconds a store call of a request may take before it fails, 0 is unlimited.
+    // Mongo is also asked to give up on the query server side after as long.
+    pub store_timeout_ms: u64,
+    // Seconds an idle HTTP/1 connection waits for its next request, 0 turns keep-alive off
+    pub keep_alive_timeout: u64,
+    // Open connections served at once, further ones wait in the listen backlog. 0 is unlimited
+    pub max_connections: usize,
+    // Pending connections the kernel queues on listeners this server binds
+    pub listen_backlog: u32,
+    // Requests handled at once before new ones are answered 503, 0 is unlimited
+    pub max_in_flight_requests: usize,
+    // Documents one GraphQL operation may read before it fails, 0 is unlimited
+    pub max_documents_per_operation: usize,
+    // Largest GraphQL response body in bytes, larger ones fail. 0 is unlimited
+    pub max_response_bytes: usize,
+    pub decode_mode: DecodeMode,
+    // Create missing indexes when the server starts, as the migrate subcommand does
+    pub ensure_indexes: bool,
+}
+
+// One table of config.toml, e.g. [default] or [production]
+#[derive(Debug, Default, Deserialize)]
+struct SettingsOverrides {
+    introspection: Option<bool>,
+    playground: Option<bool>,
+    cors_origins: Option<Vec<String>>,
+    log_format: Option<LogFormat>,
+    syntax_theme: Option<String>,
+    cache_ttl: Option<u64>,
+    cache_swr: Option<HashMap<String, u64>>,
+    cache_max_entries: Option<usize>,
+    retry_attempts: Option<u32>,
+    retry_backoff_ms: Option<u64>,
+    breaker_threshold: Option<u32>,
+    breaker_cooldown: Option<u64>,
+    breaker_serve_stale: Option<bool>,
+    store_timeout_ms: Option<u64>,
+    keep_alive_timeout: Option<u64>,
+    max_connections: Option<usize>,
+    listen_backlog: Option<u32>,
+    max_in_flight_requests: Option<usize>,
+    max_documents_per_operation: Option<usize>,
+    max_response_bytes: Option<usize>,
+    decode_mode: Option<DecodeMode>,
+    ensure_indexes: Option<bool>,
+}
+
+impl AppSettings {
+    fn defaults(profile: Profile) -> Self {
+        let development = profile != Profile::Production;
+        Self {
+            profile,
+            introspection: development,
+            playground: development,
+            cors_origins: vec!["*".to_string()],
+            log_format: if profile == Profile::Dev { LogFormat::Text } else { LogFormat::Json },
+            syntax_theme: "InspiredGitHub".to_string(),
+            cache_ttl: 0,
+            cache_swr: HashMap::new(),
+            cache_max_entries: 1000,
+            retry_attempts: 3,
+            retry_backoff_ms: 100,
+            breaker_threshold: 10,
+            breaker_cooldown: 30,
+            breaker_serve_stale: true,
+            store_timeout_ms: 5000,
+            keep_alive_timeout: 75,
+            max_connections: 0,
+            listen_backlog: 1024,
+            max_in_flight_requests: 0,
+            max_documents_per_operation: 10_000,
+            max_response_bytes: 8 * 1024 * 1024,
+            decode_mode: DecodeMode::Lenient,
+            ensure_indexes: true,
+        }
+    }
+
+    fn apply(&mut self, overrides: SettingsOverrides) {
+        if let Some(introspection) = overrides.introspection {
+            self.introspection = introspection;
+        }
+        if let Some(playground) = overrides.playground {
+            self.playground = playground;
+        }
+        if let Some(cors_origins) = overrides.cors_origins {
+            self.cors_origins = cors_origins;
+        }
+        if let Some(log_format) = overrides.log_format {
+            self.log_format = log_format;
+        }
+        if let Some(syntax_theme) = overrides.syntax_theme {
+            self.syntax_theme = syntax_theme;
+        }
+        if let Some(cache_ttl) = overrides.cache_ttl {
+            self.cache_ttl = cache_ttl;
+        }
+        // windows are merged so a profile can override single collections
+        if let Some(cache_swr) = overrides.cache_swr {
+            self.cache_swr.extend(cache_swr);
+        }
+        if let Some(cache_max_entries) = overrides.cache_max_entries {
+            self.cache_max_entries = cache_max_entries;
+        }
+        if let Some(retry_attempts) = overrides.retry_attempts {
+            self.retry_attempts = retry_attempts;
+        }
+        if let Some(retry_backoff_ms) = overrides.retry_backoff_ms {
+            self.retry_backoff_ms = retry_backoff_ms;
+        }
+        if let Some(breaker_threshold) = overrides.breaker_threshold {
+            self.breaker_threshold = breaker_threshold;
+        }
+        if let Some(breaker_cooldown) = overrides.breaker_cooldown {
+            self.breaker_cooldown = breaker_cooldown;
+        }
+        if let Some(breaker_serve_stale) = overrides.breaker_serve_stale {
+            self.breaker_serve_stale = breaker_serve_stale;
+        }
+        if let Some(store_timeout_ms) = overrides.store_timeout_ms {
+            self.store_timeout_ms = store_timeout_ms;
+        }
+        if let Some(keep_alive_timeout) = overrides.keep_alive_timeout {
+            self.keep_alive_timeout = keep_alive_timeout;
+        }
+        if let Some(max_connections) = overrides.max_connections {
+            self.max_connections = max_connections;
+        }
+        if let Some(listen_backlog) = overrides.listen_backlog {
+            self.listen_backlog = listen_backlog;
+        }
+        if let Some(max_in_flight_requests) = overrides.max_in_flight_requests {
+            self.max_in_flight_requests = max_in_flight_requests;
+        }
+        if let Some(max_documents_per_operation) = overrides.max_documents_per_operation {
+            self.max_documents_per_operation = max_documents_per_operation;
+        }
+        if let Some(max_response_bytes) = overrides.max_response_bytes {
+            self.max_response_bytes = max_response_bytes;
+        }
+        if let Some(decode_mode) = overrides.decode_mode {
+            self.decode_mode = decode_mode;
+        }
+        if let Some(ensure_indexes) = overrides.ensure_indexes {
+            self.ensure_indexes = ensure_indexes;
+        }
+    }
+
+    // Layered as profile defaults, then [default] and [<profile>] from CONFIG_FILE
+    // (config.toml), then GRAPHQL_INTROSPECTION, GRAPHQL_PLAYGROUND,
+    // CORS_ALLOWED_ORIGINS, LOG_FORMAT, SYNTAX_THEME, CACHE_TTL, CACHE_SWR (the
+    // default stale window), RETRY_ATTEMPTS, RETRY_BACKOFF_MS, BREAKER_THRESHOLD,
+    // BREAKER_COOLDOWN, BREAKER_SERVE_STALE, STORE_TIMEOUT_MS, KEEP_ALIVE_TIMEOUT,
+    // MAX_CONNECTIONS, LISTEN_BACKLOG, MAX_IN_FLIGHT_REQUESTS,
+    // MAX_DOCUMENTS_PER_OPERATION, MAX_RESPONSE_BYTES, DECODE_MODE and
+    // ENSURE_INDEXES from the environment
+    pub fn load() -> Self {
+        let profile = Profile::from_env();
+        let mut settings = Self::defaults(profile);
+
+        let path = env::var("CONFIG_FILE").unwrap_or_else(|_| "config.toml".to_string());
+        if let Ok(contents) = fs::read_to_string(&path) {
+            let mut tables: HashMap<String, SettingsOverrides> = toml::from_str(&contents)
+                .unwrap_or_else(|e| panic!("Invalid config file {}: {}", path, e));
+            if let Some(overrides) = tables.remove("default") {
+                settings.apply(overrides);
+            }
+            if let Some(overrides) = tables.remove(profile.name()) {
+                settings.apply(overrides);
+            }
+        }
+
+        settings.apply(SettingsOverrides {
+            introspection: bool_from_env("GRAPHQL_INTROSPECTION"),
+            playground: bool_from_env("GRAPHQL_PLAYGROUND"),
+            cors_origins: env::var("CORS_ALLOWED_ORIGINS").ok().map(|origins| {
+                origins.split(',').map(|origin| origin.trim().to_string()).collect()
+            }),
+            log_format: env::var("LOG_FORMAT").ok().map(|format| match format.as_str() {
+                "json" => LogFormat::Json,
+                "text" => LogFormat::Text,
+                other => panic!("Unknown LOG_FORMAT {}, expected text or json", other),
+            }),
+            syntax_theme: env::var("SYNTAX_THEME").ok(),
+            cache_ttl: env::var("CACHE_TTL")
+                .ok()
+                .map(|ttl| ttl.parse().expect("CACHE_TTL must be a number of seconds")),
+            cache_swr: env::var("CACHE_SWR").ok().map(|swr| {
+                let seconds = swr.parse().expect("CACHE_SWR must be a number of seconds");
+                HashMap::from([("default".to_string(), seconds)])
+            }),
+            retry_attempts: number_from_env("RETRY_ATTEMPTS"),
+            retry_backoff_ms: number_from_env("RETRY_BACKOFF_MS"),
+            breaker_threshold: number_from_env("BREAKER_THRESHOLD"),
+            breaker_cooldown: number_from_env("BREAKER_COOLDOWN"),
+            breaker_serve_stale: bool_from_env("BREAKER_SERVE_STALE"),
+            store_timeout_ms: number_from_env("STORE_TIMEOUT_MS"),
+            keep_alive_timeout: number_from_env("KEEP_ALIVE_TIMEOUT"),
+            max_connections: number_from_env("MAX_CONNECTIONS"),
+            listen_backlog: number_from_env("LISTEN_BACKLOG"),
+            max_in_flight_requests: number_from_env("MAX_IN_FLIGHT_REQUESTS"),
+            max_documents_per_operation: number_from_env("MAX_DOCUMENTS_PER_OPERATION"),
+            max_response_bytes: number_from_env("MAX_RESPONSE_BYTES"),
+            decode_mode: env::var("DECODE_MODE").ok().map(|mode| match mode.as_str() {
+                "lenient" => DecodeMode::Lenient,
+                "strict" => DecodeMode::Strict,
+                other => panic!("Unknown DECODE_MODE {}, expected lenient or strict", other),
+            }),
+            ensure_indexes: bool_from_env("ENSURE_INDEXES"),
+        });
+        settings
+    }
+}
+
+static APP_SETTINGS: OnceLock<AppSettings> = OnceLock::new();
+
+pub fn app() -> &'static AppSettings {
+    APP_SETTINGS.get_or_init(AppSettings::load)
+}
+
+fn bool_from_env(key: &str) -> Option<bool> {
+    env::var(key).ok().map(|value| value == "true" || value == "1")
+}
+
+fn number_from_env<T: std::str::FromStr>(key: &str) -> Option<T> {
+    env::var(key)
+        .ok()
+        .map(|value| value.parse().unwrap_or_else(|_| panic!("{} must be a number", key)))
+}
@@ -0,0 +1,294 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use crate::highlight;
+
+// One block of long-form content, stored as { "type": ..., "value": ... }
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLUnion)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ContentBlock {
+    Paragraph(ParagraphBlock),
+    Heading(HeadingBlock),
+    List(ListBlock),
+    Code(CodeBlock),
+    Image(ImageBlock),
+    Footnote(FootnoteBlock),
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+pub struct ParagraphBlock {
+    #[serde(rename = "value")]
+    pub text: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+pub struct HeadingBlock {
+    #[serde(rename = "value")]
+    pub text: String,
+    #[serde(default = "default_heading_level")]
+    pub level: i32,
+}
+
+fn default_heading_level() -> i32 {
+    2
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+pub struct ListBlock {
+    #[serde(rename = "value")]
+    pub items: Vec<String>,
+    #[serde(default)]
+    pub ordered: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CodeBlock {
+    #[serde(rename = "value")]
+    pub code: String,
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+#[juniper::graphql_object]
+impl CodeBlock {
+    fn code(&self) -> &str {
+        &self.code
+    }
+    fn language(&self) -> Option<&str> {
+        self.language.as_deref()
+    }
+    // Highlighted HTML of the code, null when the language isn't set or recognised
+    fn highlighted_code(&self) -> Option<String> {
+        highlight::highlight(&self.code, self.language.as_deref()?)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+#[serde(from = "StoredImage", into = "StoredImage")]
+pub struct ImageBlock {
+    pub src: String,
+    pub alt: String,
+    pub caption: Option<String>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    // Placeholder shown while the image loads, see https://blurha.sh
+    pub blurhash: Option<String>,
+}
+
+// Image blocks used to hold only the url, newer ones carry the metadata as an object
+#[derive(Clone, Deserialize, Serialize)]
+struct StoredImage {
+    value: ImageValue,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+enum ImageValue {
+    Src(String),
+    Meta(ImageMeta),
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct ImageMeta {
+    src: String,
+    #[serde(default)]
+    alt: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    caption: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    width: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    height: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    blurhash: Option<String>,
+}
+
+impl From<StoredImage> for ImageBlock {
+    fn from(stored: StoredImage) -> Self {
+        match stored.value {
+            ImageValue::Src(src) => ImageBlock {
+                src,
+                alt: String::new(),
+                caption: None,
+                width: None,
+                height: None,
+                blurhash: None,
+            },
+            ImageValue::Meta(meta) => ImageBlock {
+                src: meta.src,
+                alt: meta.alt,
+                caption: meta.caption,
+                width: meta.width,
+                height: meta.height,
+                blurhash: meta.blurhash,
+            },
+        }
+    }
+}
+
+impl From<ImageBlock> for StoredImage {
+    fn from(image: ImageBlock) -> Self {
+        StoredImage {
+            value: ImageValue::Meta(ImageMeta {
+                src: image.src,
+                alt: image.alt,
+                caption: image.caption,
+                width: image.width,
+                height: image.height,
+                blurhash: image.blurhash,
+            }),
+        }
+    }
+}
+
+// Definition of a footnote, referenced from text as [^id]
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+pub struct FootnoteBlock {
+    pub id: String,
+    #[serde(rename = "value")]
+    pub text: String,
+    #[serde(default)]
+    pub url: Option<String>,
+}
+
+// A footnote numbered in the order it is first referenced
+#[derive(Clone, Debug, juniper::GraphQLObject)]
+pub struct Footnote {
+    pub number: i32,
+    pub id: String,
+    pub text: String,
+    pub url: Option<String>,
+}
+
+// Ids of the [^id] references in a piece of text, in order
+fn references(text: &str) -> Vec<&str> {
+    let mut ids = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("[^") {
+        rest = &rest[start + 2..];
+        match rest.find(']') {
+            Some(end) => {
+                let id = &rest[..end];
+                if !id.is_empty() && !id.contains(char::is_whitespace) {
+                    ids.push(id);
+                }
+                rest = &rest[end + 1..];
+            }
+            None => break,
+        }
+    }
+    ids
+}
+
+// Footnote definitions numbered by first reference, unreferenced ones go last
+pub fn footnotes(blocks: &[ContentBlock]) -> Vec<Footnote> {
+    let definitions: Vec<&FootnoteBlock> = blocks
+        .iter()
+        .filter_map(|block| match block {
+            ContentBlock::Footnote(footnote) => Some(footnote),
+            _ => None,
+        })
+        .collect();
+    let mut order: Vec<&str> = Vec::new();
+    for block in blocks {
+        let texts: Vec<&str> = match block {
+            ContentBlock::Paragraph(paragraph) => vec![&paragraph.text],
+            ContentBlock::Heading(heading) => vec![&heading.text],
+            ContentBlock::List(list) => list.items.iter().map(String::as_str).collect(),
+            ContentBlock::Image(image) => image.caption.as_deref().into_iter().collect(),
+            ContentBlock::Code(_) | ContentBlock::Footnote(_) => Vec::new(),
+        };
+        for id in texts.into_iter().flat_map(references) {
+            if !order.contains(&id) {
+                order.push(id);
+            }
+        }
+    }
+    for definition in &definitions {
+        if !order.contains(&definition.id.as_str()) {
+            order.push(&definition.id);
+        }
+    }
+    // references without a definition are left for the frontend to show as plain text
+    order
+        .into_iter()
+        .filter_map(|id| definitions.iter().find(|definition| definition.id == id))
+        .enumerate()
+        .map(|(index, definition)| Footnote {
+            number: index as i32 + 1,
+            id: definition.id.clone(),
+            text: definition.text.clone(),
+            url: definition.url.clone(),
+        })
+        .collect()
+}
+
+// A heading of the content, for rendering a table of contents
+#[derive(Clone, Debug, juniper::GraphQLObject)]
+pub struct TocEntry {
+    pub text: String,
+    pub level: i32,
+    pub anchor: String,
+}
+
+// Url fragment for a heading, e.g. "Why Rust?" -> "why-rust"
+fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if (c.is_whitespace() || c == '-' || c == '_') && !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+// Table of contents built from the heading blocks, anchors are unique within the post
+pub fn table_of_contents(blocks: &[ContentBlock]) -> Vec<TocEntry> {
+    let mut anchors: Vec<String> = Vec::new();
+    blocks
+        .iter()
+        .filter_map(|block| match block {
+            ContentBlock::Heading(heading) => Some(heading),
+            _ => None,
+        })
+        .map(|heading| {
+            let mut text = heading.text.clone();
+            // footnote markers belong to the heading in the body, not in the ToC
+            for id in references(&heading.text) {
+                text = text.replace(&format!("[^{}]", id), "");
+            }
+            let text = text.trim().to_string();
+            let base = match slugify(&text) {
+                slug if slug.is_empty() => "section".to_string(),
+                slug => slug,
+            };
+            let mut anchor = base.clone();
+            let mut suffix = 1;
+            while anchors.contains(&anchor) {
+                anchor = format!("{}-{}", base, suffix);
+                suffix += 1;
+            }
+            anchors.push(anchor.clone());
+            TocEntry {
+                text,
+                level: heading.level,
+                anchor,
+            }
+        })
+        .collect()
+}
+
+// Deserialize a content array, dropping blocks of unknown or malformed types
+// instead of failing the whole document
+pub fn blocks<'de, D>(deserializer: D) -> Result<Vec<ContentBlock>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let values = Vec::<Value>::deserialize(deserializer)?;
+    Ok(values
+        .into_iter()
+        .filter_map(|value| serde_json::from_value(value).ok())
+        .collect())
+}
@@ -0,0 +1,206 @@
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use std::{
+    any::type_name,
+    collections::{BTreeMap, HashMap},
+};
+use tokio_stream::StreamExt;
+
+use crate::{
+    blog::BlogPost,
+    books::Book,
+    config,
+    flags::FeatureFlag,
+    i18n::LocaleSettings,
+    navigation::NavigationItem,
+    redirects::Redirect,
+    resume::{Education, WorkExperience},
+    rules::{self, ContentRule},
+    store::{DataStore, FindOptions, StoreError, StoredDocument},
+    talks::Talk,
+    uses::UsesItem,
+    Introduction, Personal, Project, Service, Skills, SkillsOverview, SocialMedia, SoftSkills, User,
+};
+
+// Documents of a collection that failed to decode on the same field
+#[derive(Clone, Debug, Serialize, juniper::GraphQLObject)]
+#[serde(rename_all = "camelCase")]
+pub struct FieldMismatch {
+    // Path of the field, e.g. screenshots[0].url
+    pub field: String,
+    pub documents: i32,
+    // Error of the first such document, e.g. missing field `title`
+    pub example: String,
+    // _id of the first such document, when it has one
+    pub example_id: Option<String>,
+}
+
+// Documents of a collection breaking the same content rule of their owner
+#[derive(Clone, Debug, Serialize, juniper::GraphQLObject)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleViolation {
+    pub rule: String,
+    pub documents: i32,
+    // _id of the first such document, when it has one
+    pub example_id: Option<String>,
+}
+
+// How the documents of one collection compare against its model and the
+// content rules of their owners
+#[derive(Clone, Debug, Serialize, juniper::GraphQLObject)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionReport {
+    pub collection: String,
+    pub model: String,
+    pub documents: i32,
+    pub invalid: i32,
+    pub mismatches: Vec<FieldMismatch>,
+    pub rule_violations: Vec<RuleViolation>,
+}
+
+impl CollectionReport {
+    pub fn is_ok(&self) -> bool {
+        self.invalid == 0 && self.rule_violations.is_empty()
+    }
+}
+
+// Decode every document of the portfolio collections, or only the owner's,
+// against the model the API reads it into and check it against its owner's
+// content rules
+pub async fn run(store: &dyn DataStore, owner: Option<&str>) -> Result<Vec<CollectionReport>, StoreError> {
+    let collections = config::collections();
+    let (filter, rules) = match owner {
+        Some(owner) => {
+            let rules = HashMap::from([(owner.to_string(), rules::load(store, owner).await?)]);
+            (json!({ "email": owner }), rules)
+        }
+        None => (json!({}), rules::load_all(store).await?),
+    };
+    Ok(vec![
+        check::<Introduction>(store, &collections.introductions, &filter, &rules).await?,
+        check::<Personal>(store, &collections.personals, &filter, &rules).await?,
+        check::<Project>(store, &collections.projects, &filter, &rules).await?,
+        check::<SkillsOverview>(store, &collections.skills_overview, &filter, &rules).await?,
+        check::<Skills>(store, &collections.skills, &filter, &rules).await?,
+        check::<SocialMedia>(store, &collections.social_media, &filter, &rules).await?,
+        check::<SoftSkills>(store, &collections.soft_skills, &filter, &rules).await?,
+        check::<User>(store, &collections.users, &filter, &rules).await?,
+        check::<BlogPost>(store, &collections.blog_posts, &filter, &rules).await?,
+        check::<Service>(store, &collections.services, &filter, &rules).await?,
+        check::<WorkExperience>(store, &collections.work_experience, &filter, &rules).await?,
+        check::<Education>(store, &collections.education, &filter, &rules).await?,
+        check::<Book>(store, &collections.reading_list, &filter, &rules).await?,
+        check::<Talk>(store, &collections.talks, &filter, &rules).await?,
+        check::<UsesItem>(store, &collections.uses, &filter, &rules).await?,
+        check::<FeatureFlag>(store, &collections.feature_flags, &filter, &rules).await?,
+        check::<NavigationItem>(store, &collections.navigation, &filter, &rules).await?,
+        check::<Redirect>(store, &collections.redirects, &filter, &rules).await?,
+        check::<LocaleSettings>(store, &collections.settings, &filter, &rules).await?,
+    ])
+}
+
+async fn check<T: DeserializeOwned>(
+    store: &dyn DataStore,
+    collection: &str,
+    filter: &Value,
+    rules: &HashMap<String, Vec<ContentRule>>,
+) -> Result<CollectionReport, StoreError> {
+    // streamed so collections over STORE_MAX_DOCUMENTS are checked in full
+    let mut documents = store.find_stream(collection, filter.clone(), FindOptions::default()).await?;
+    let mut mismatches: BTreeMap<String, FieldMismatch> = BTreeMap::new();
+    let mut violations: BTreeMap<String, RuleViolation> = BTreeMap::new();
+    let (mut total, mut invalid) = (0, 0);
+    while let Some(document) = documents.next().await {
+        let document = document?;
+        total += 1;
+        let owner_rules = document
+            .get("email")
+            .and_then(Value::as_str)
+            .and_then(|email| rules.get(email))
+            .map_or(&[][..], Vec::as_slice);
+        for rule in rules::broken(owner_rules, collection, &document) {
+            violations
+                .entry(rule.name.clone())
+                .or_insert_with(|| RuleViolation {
+                    rule: rule.name.clone(),
+                    documents: 0,
+                    example_id: StoredDocument::from(document.clone()).id(),
+                })
+                .documents += 1;
+        }
+        let error = match serde_path_to_error::deserialize::<_, T>(&document) {
+            Ok(_) => continue,
+            Err(error) => error,
+        };
+        invalid += 1;
+        let example = error.inner().to_string();
+        let field = field_path(&error.path().to_string(), &example);
+        mismatches
+            .entry(field.clone())
+            .or_insert_with(|| FieldMismatch {
+                field,
+                documents: 0,
+                example,
+                example_id: StoredDocument::from(document).id(),
+            })
+            .documents += 1;
+    }
+    let model = type_name::<T>();
+    Ok(CollectionReport {
+        collection: collection.to_string(),
+        model: model.rsplit("::").next().unwrap_or(model).to_string(),
+        documents: total,
+        invalid,
+        mismatches: mismatches.into_values().collect(),
+        rule_violations: violations.into_values().collect(),
+    })
+}
+
+// Why `document` doesn't decode into the model of `collection`, e.g.
+// "title: missing field `title`". None when it does or the collection isn't a
+// portfolio collection.
+pub fn model_error(collection: &str, document: &Value) -> Option<String> {
+    let collections = config::collections();
+    let mismatch: fn(&Value) -> Option<String> = match collection {
+        name if name == collections.introductions => mismatch::<Introduction>,
+        name if name == collections.personals => mismatch::<Personal>,
+        name if name == collections.projects => mismatch::<Project>,
+        name if name == collections.skills_overview => mismatch::<SkillsOverview>,
+        name if name == collections.skills => mismatch::<Skills>,
+        name if name == collections.social_media => mismatch::<SocialMedia>,
+        name if name == collections.soft_skills => mismatch::<SoftSkills>,
+        name if name == collections.users => mismatch::<User>,
+        name if name == collections.blog_posts => mismatch::<BlogPost>,
+        name if name == collections.services => mismatch::<Service>,
+        name if name == collections.work_experience => mismatch::<WorkExperience>,
+        name if name == collections.education => mismatch::<Education>,
+        name if name == collections.reading_list => mismatch::<Book>,
+        name if name == collections.talks => mismatch::<Talk>,
+        name if name == collections.uses => mismatch::<UsesItem>,
+        name if name == collections.feature_flags => mismatch::<FeatureFlag>,
+        name if name == collections.navigation => mismatch::<NavigationItem>,
+        name if name == collections.redirects => mismatch::<Redirect>,
+        name if name == collections.settings => mismatch::<LocaleSettings>,
+        _ => return None,
+    };
+    mismatch(document)
+}
+
+fn mismatch<T: DeserializeOwned>(document: &Value) -> Option<String> {
+    let error = serde_path_to_error::deserialize::<_, T>(document).err()?;
+    let message = error.inner().to_string();
+    Some(format!("{}: {}", field_path(&error.path().to_string(), &message), message))
+}
+
+// The path serde stopped at is the struct holding a missing field, so the
+// field's name is taken from the message and appended to it
+fn field_path(path: &str, message: &str) -> String {
+    let missing = message
+        .strip_prefix("missing field `")
+        .and_then(|rest| rest.split('`').next());
+    match (path, missing) {
+        (".", Some(field)) => field.to_string(),
+        (path, Some(field)) => format!("{}.{}", path, field),
+        (path, None) => path.to_string(),
+    }
+}
@@ -0,0 +1,53 @@
+use chrono::{DateTime, NaiveDate};
+use chrono_tz::Tz;
+use juniper::{graphql_value, FieldError};
+use std::fmt::Write;
+
+// Format an ISO 8601 timestamp or date for display. Timestamps are converted to
+// `timezone` (an IANA name like Asia/Manila) when given, `format` is a strftime
+// pattern. Without either the stored value is returned as is.
+pub fn format(value: &str, timezone: Option<&str>, format: Option<&str>) -> Result<String, FieldError> {
+    if timezone.is_none() && format.is_none() {
+        return Ok(value.to_string());
+    }
+    let timezone = parse_timezone(timezone)?;
+    let mut formatted = String::new();
+    // writing into a String only fails on an invalid format pattern
+    let written = if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        match (timezone, format) {
+            (Some(timezone), Some(format)) => write!(formatted, "{}", at.with_timezone(&timezone).format(format)),
+            (Some(timezone), None) => write!(formatted, "{}", at.with_timezone(&timezone).to_rfc3339()),
+            (None, Some(format)) => write!(formatted, "{}", at.format(format)),
+            (None, None) => unreachable!(),
+        }
+    } else if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        // plain dates have no time of day to convert
+        write!(formatted, "{}", date.format(format.unwrap_or("%Y-%m-%d")))
+    } else {
+        return Err(FieldError::new(
+            "Stored date is not ISO 8601",
+            graphql_value!({ "details": value }),
+        ));
+    };
+    written.map_err(|_| {
+        FieldError::new(
+            "Invalid date format",
+            graphql_value!({ "details": "format must be a strftime pattern, e.g. %B %-d, %Y" }),
+        )
+    })?;
+    Ok(formatted)
+}
+
+// An IANA timezone name like Asia/Manila, None when not given
+pub fn parse_timezone(timezone: Option<&str>) -> Result<Option<Tz>, FieldError> {
+    timezone
+        .map(|timezone| {
+            timezone.parse().map_err(|_| {
+                FieldError::new(
+                    "Unknown timezone",
+                    graphql_value!({ "details": "Use an IANA timezone name, e.g. Asia/Manila" }),
+                )
+            })
+        })
+        .transpose()
+}
@@ -0,0 +1,42 @@
+use juniper::{graphql_value, FieldError};
+use serde::Serialize;
+use std::any::type_name;
+
+use crate::store::StoreError;
+
+// A stored document that didn't match the model it was read into
+#[derive(Clone, Debug, Serialize, juniper::GraphQLObject)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodeWarning {
+    pub collection: String,
+    // _id of the document, when it has one
+    pub id: Option<String>,
+    pub model: String,
+    // What didn't match, e.g. missing field `title`
+    pub error: String,
+}
+
+impl DecodeWarning {
+    pub fn new<T>(collection: &str, id: Option<String>, error: &StoreError) -> Self {
+        let model = type_name::<T>();
+        Self {
+            collection: collection.to_string(),
+            id,
+            model: model.rsplit("::").next().unwrap_or(model).to_string(),
+            error: error.to_string(),
+        }
+    }
+
+    pub fn into_field_error(self) -> FieldError {
+        let message = format!("Failed to decode a {} document", self.model);
+        let id = self.id.unwrap_or_default();
+        FieldError::new(
+            message,
+            graphql_value!({
+                "collection": self.collection,
+                "id": id,
+                "details": self.error,
+            }),
+        )
+    }
+}
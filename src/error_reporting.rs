@@ -0,0 +1,58 @@
+use juniper::http::GraphQLBatchRequest;
+use std::{any::type_name, env, error::Error as StdError};
+
+use crate::config;
+
+// Start the Sentry client when SENTRY_DSN is set; the guard flushes pending
+// events on drop, so it has to live as long as main
+pub fn init() -> Option<sentry::ClientInitGuard> {
+    let dsn = env::var("SENTRY_DSN").ok()?;
+    let guard = sentry::init((
+        dsn,
+        sentry::ClientOptions {
+            release: sentry::release_name!(),
+            environment: Some(config::app().profile.name().into()),
+            ..Default::default()
+        },
+    ));
+    println!("Reporting errors to Sentry");
+    Some(guard)
+}
+
+// Attach the GraphQL operation and owner to events raised while handling the request
+pub fn tag_request(request: &GraphQLBatchRequest, owner_email: Option<&str>) {
+    let operation = match request {
+        GraphQLBatchRequest::Single(request) => request.operation_name().map(str::to_string),
+        GraphQLBatchRequest::Batch(requests) => Some(
+            requests
+                .iter()
+                .filter_map(|request| request.operation_name())
+                .collect::<Vec<_>>()
+                .join(","),
+        ),
+    };
+    sentry::configure_scope(|scope| {
+        if let Some(operation) = operation {
+            scope.set_tag("graphql.operation", operation);
+        }
+        if let Some(owner_email) = owner_email {
+            scope.set_tag("portfolio.owner", owner_email);
+        }
+    });
+}
+
+// Report a data store failure, a no-op without a configured DSN
+pub fn capture_store_error(collection: &str, error: &(dyn StdError + Send + Sync)) {
+    sentry::with_scope(
+        |scope| scope.set_tag("collection", collection),
+        || sentry::capture_error(error),
+    );
+}
+
+// Report a document that didn't match the GraphQL model it was read into
+pub fn capture_deserialization_error<T>(error: &(dyn StdError + Send + Sync)) {
+    sentry::with_scope(
+        |scope| scope.set_tag("model", type_name::<T>()),
+        || sentry::capture_error(error),
+    );
+}
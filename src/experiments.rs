@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    config,
+    store::{DataStore, StoreError},
+    visitor,
+};
+
+// Whether a visitor saw a variant or went on to convert
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, juniper::GraphQLEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ExperimentEvent {
+    Exposure,
+    Conversion,
+}
+
+// Index of the variant a visitor is assigned to, the same visitor always gets
+// the same variant of an experiment as long as the variants don't change
+pub fn assign(experiment_key: &str, visitor_id: &str, variants: usize) -> usize {
+    let digest = Sha256::digest(format!("{}|{}", experiment_key, visitor_id).as_bytes());
+    let mut bucket = [0u8; 8];
+    bucket.copy_from_slice(&digest[..8]);
+    (u64::from_be_bytes(bucket) % variants.max(1) as u64) as usize
+}
+
+// Record an exposure or conversion, each visitor counts once per variant and event
+pub async fn record(
+    store: &dyn DataStore,
+    owner_email: &str,
+    experiment_key: &str,
+    variant: &str,
+    visitor_id: &str,
+    event: ExperimentEvent,
+) -> Result<(), StoreError> {
+    let collection = &config::collections().experiments;
+    let document = json!({
+        "email": owner_email,
+        "experimentKey": experiment_key,
+        "variant": variant,
+        "event": event,
+        // visitor ids come from the client, only a salted hash is kept
+        "visitor": visitor::hash_id(&format!("experiment:{}", experiment_key), visitor_id),
+    });
+    if store.find_one(collection, document.clone()).await?.is_none() {
+        store.insert_one(collection, document).await?;
+    }
+    Ok(())
+}
+
+// Exposures and conversions of one variant
+#[derive(Clone, Debug, juniper::GraphQLObject)]
+pub struct VariantResult {
+    pub variant: String,
+    pub exposures: i32,
+    pub conversions: i32,
+}
+
+pub async fn results(
+    store: &dyn DataStore,
+    owner_email: &str,
+    experiment_key: &str,
+    variants: &[String],
+) -> Result<Vec<VariantResult>, StoreError> {
+    let collection = &config::collections().experiments;
+    let mut results = Vec::new();
+    for variant in variants {
+        let filter = |event: ExperimentEvent| {
+            json!({ "email": owner_email, "experimentKey": experiment_key, "variant": variant, "event": event })
+        };
+        let exposures = store.count(collection, filter(ExperimentEvent::Exposure)).await?;
+        let conversions = store.count(collection, filter(ExperimentEvent::Conversion)).await?;
+        results.push(VariantResult {
+            variant: variant.clone(),
+            exposures: exposures as i32,
+            conversions: conversions as i32,
+        });
+    }
+    Ok(results)
+}
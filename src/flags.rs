@@ -0,0 +1,38 @@
+use serde::Deserialize;
+
+use crate::experiments;
+
+// A switch for an experimental part of the frontend, e.g. the new blog design
+#[derive(Debug, Deserialize)]
+pub struct FeatureFlag {
+    pub key: String,
+    #[serde(default)]
+    pub enabled: bool,
+    // Share of visitors the flag is on for, 0 to 100, everyone when unset
+    #[serde(default)]
+    pub rollout: Option<i32>,
+}
+
+#[derive(Clone, Debug, juniper::GraphQLObject)]
+pub struct FlagValue {
+    pub key: String,
+    pub enabled: bool,
+}
+
+impl FeatureFlag {
+    // Whether the flag is on for a visitor, a visitor stays in or out of a
+    // rollout as long as its percentage doesn't shrink below their bucket
+    pub fn evaluate(&self, visitor_id: &str) -> FlagValue {
+        let enabled = self.enabled
+            && match self.rollout {
+                Some(rollout) => {
+                    (experiments::assign(&format!("flag:{}", self.key), visitor_id, 100) as i32) < rollout
+                }
+                None => true,
+            };
+        FlagValue {
+            key: self.key.clone(),
+            enabled,
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use maxminddb::{geoip2, Reader};
+use std::{env, net::IpAddr, sync::OnceLock};
+
+static READER: OnceLock<Option<Reader<Vec<u8>>>> = OnceLock::new();
+
+// MaxMind country (or city) database at GEOIP_DB_PATH, lookups are skipped without one
+fn reader() -> Option<&'static Reader<Vec<u8>>> {
+    READER
+        .get_or_init(|| {
+            let path = env::var("GEOIP_DB_PATH").ok()?;
+            match Reader::open_readfile(&path) {
+                Ok(reader) => {
+                    println!("Using GeoIP database at {}", path);
+                    Some(reader)
+                }
+                Err(e) => {
+                    eprintln!("Error opening GeoIP database {}: {}", path, e);
+                    None
+                }
+            }
+        })
+        .as_ref()
+}
+
+// ISO 3166 country code of an address, e.g. PH
+pub fn country(ip: IpAddr) -> Option<String> {
+    let country: geoip2::Country = reader()?.lookup(ip).ok()?;
+    country.country?.iso_code.map(str::to_string)
+}
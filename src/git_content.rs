@@ -0,0 +1,569 @@
+use axum::{
+    body::Bytes,
+    http::{HeaderMap, StatusCode},
+    Extension,
+};
+use chrono::{DateTime as ChronoDateTime, NaiveDate};
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{process::Command, sync::Mutex};
+
+use crate::{
+    blog::{BlogPost, BlogPostStatus},
+    config,
+    content::{CodeBlock, ContentBlock, FootnoteBlock, HeadingBlock, ImageBlock, ListBlock, ParagraphBlock},
+    rules::{self, ContentRule},
+    signing::{constant_time_eq, hex, hmac_sha256},
+    store::{DataStore, StoreError},
+    syndication,
+    validation::{self, MAX_SLUG_CHARS},
+};
+
+// Syncs run one at a time, a push arriving mid-sync waits for it
+static SYNCING: Mutex<()> = Mutex::const_new(());
+
+// Where posts are pulled from: CONTENT_GIT_URL is the repository, with
+// credentials in the URL for a private one, CONTENT_GIT_BRANCH its branch (main
+// by default) and CONTENT_GIT_PATH the directory holding the posts (the whole
+// repository by default). It's checked out to CONTENT_GIT_DIR, content-repo by
+// default, for CONTENT_GIT_OWNER, USER_EMAIL by default.
+// CONTENT_GIT_WEBHOOK_SECRET turns on the push webhook.
+#[derive(Clone, Debug)]
+pub struct GitContentConfig {
+    url: String,
+    branch: String,
+    path: String,
+    checkout: PathBuf,
+    pub owner_email: String,
+    webhook_secret: Option<String>,
+}
+
+impl GitContentConfig {
+    // None when Git sync isn't set up
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            url: env::var("CONTENT_GIT_URL").ok()?,
+            branch: env::var("CONTENT_GIT_BRANCH").unwrap_or_else(|_| "main".to_string()),
+            path: env::var("CONTENT_GIT_PATH").unwrap_or_default(),
+            checkout: env::var("CONTENT_GIT_DIR").unwrap_or_else(|_| "content-repo".to_string()).into(),
+            owner_email: env::var("CONTENT_GIT_OWNER").or_else(|_| env::var("USER_EMAIL")).ok()?,
+            webhook_secret: env::var("CONTENT_GIT_WEBHOOK_SECRET").ok(),
+        })
+    }
+}
+
+// A markdown file that couldn't be imported
+#[derive(Debug, juniper::GraphQLObject)]
+pub struct GitSyncFailure {
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(Debug, Default, juniper::GraphQLObject)]
+pub struct GitSyncResult {
+    // Commit the posts were read from
+    pub commit: String,
+    pub created: i32,
+    pub updated: i32,
+    // Files not changed since they were last imported
+    pub unchanged: i32,
+    pub failed: Vec<GitSyncFailure>,
+}
+
+// Frontmatter fields the sync reads, others are left alone
+#[derive(Debug, Default, Deserialize)]
+struct Frontmatter {
+    title: Option<String>,
+    slug: Option<String>,
+    #[serde(alias = "description", alias = "summary")]
+    excerpt: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    // publication date, e.g. 2024-03-01 or 2024-03-01T09:00:00Z
+    date: Option<String>,
+    #[serde(default)]
+    draft: bool,
+    locale: Option<String>,
+}
+
+// YAML scalars as written, quotes removed. Numbers stay strings, every field read is one.
+fn yaml_scalar(text: &str) -> Value {
+    let text = text.trim();
+    for quote in ['"', '\''] {
+        if text.len() >= 2 && text.starts_with(quote) && text.ends_with(quote) {
+            return Value::String(text[1..text.len() - 1].to_string());
+        }
+    }
+    match text {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        "" | "null" | "~" => Value::Null,
+        _ => Value::String(text.to_string()),
+    }
+}
+
+// The YAML frontmatter editors write: `key: value` lines, lists inline as
+// [a, b] or as `- item` lines below their key. Multi-line strings aren't supported.
+fn parse_yaml(text: &str) -> Result<Map<String, Value>, String> {
+    let mut fields = Map::new();
+    let mut list_key: Option<String> = None;
+    for (number, line) in text.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if let Some(item) = trimmed.strip_prefix('-') {
+            let Some(key) = &list_key else {
+                return Err(format!("line {}: list item without a key", number + 1));
+            };
+            match fields.get_mut(key) {
+                Some(Value::Array(items)) => items.push(yaml_scalar(item)),
+                Some(value) => *value = Value::Array(vec![yaml_scalar(item)]),
+                None => {}
+            }
+            continue;
+        }
+        let Some((key, value)) = trimmed.split_once(':') else {
+            return Err(format!("line {}: expected key: value", number + 1));
+        };
+        let key = key.trim().to_string();
+        let value = value.trim();
+        list_key = value.is_empty().then(|| key.clone());
+        let value = match value.strip_prefix('[').and_then(|items| items.strip_suffix(']')) {
+            Some(items) => Value::Array(
+                items
+                    .split(',')
+                    .filter(|item| !item.trim().is_empty())
+                    .map(yaml_scalar)
+                    .collect(),
+            ),
+            None => yaml_scalar(value),
+        };
+        fields.insert(key, value);
+    }
+    Ok(fields)
+}
+
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(text) => Value::String(text),
+        toml::Value::Integer(number) => json!(number),
+        toml::Value::Float(number) => json!(number),
+        toml::Value::Boolean(flag) => Value::Bool(flag),
+        toml::Value::Datetime(datetime) => Value::String(datetime.to_string()),
+        toml::Value::Array(values) => Value::Array(values.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(key, value)| (key, toml_to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+// Frontmatter and body of a markdown file. Frontmatter is YAML between --- lines
+// or TOML between +++ lines, files without any only have a body.
+fn split_frontmatter(text: &str) -> Result<(Frontmatter, &str), StoreError> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let Some(delimiter) = ["---", "+++"].into_iter().find(|delimiter| text.starts_with(delimiter)) else {
+        return Ok((Frontmatter::default(), text));
+    };
+    let rest = &text[delimiter.len()..];
+    let Some(end) = rest.find(&format!("\n{}", delimiter)) else {
+        return Err(format!("The frontmatter has no closing {}", delimiter).into());
+    };
+    let (header, body) = (&rest[..end], &rest[end + 1 + delimiter.len()..]);
+    let mut fields = match delimiter {
+        "+++" => toml_to_json(toml::Value::Table(toml::from_str(header)?)),
+        _ => Value::Object(parse_yaml(header).map_err(|e| format!("Invalid frontmatter, {}", e))?),
+    };
+    // keys left empty count as missing
+    if let Value::Object(fields) = &mut fields {
+        fields.retain(|_, value| !value.is_null());
+    }
+    Ok((serde_json::from_value(fields)?, body))
+}
+
+// Dates as stored, dates without a time are taken at midnight UTC
+fn parse_date(date: &str) -> Result<String, StoreError> {
+    if let Ok(date) = NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        return Ok(format!("{}T00:00:00Z", date));
+    }
+    let date = ChronoDateTime::parse_from_rfc3339(date)
+        .map_err(|_| format!("The date {:?} isn't YYYY-MM-DD or RFC 3339", date))?;
+    Ok(date.to_utc().to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+}
+
+fn heading(line: &str) -> Option<ContentBlock> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let text = line[level..].strip_prefix(' ')?;
+    (1..=6).contains(&level).then(|| {
+        ContentBlock::Heading(HeadingBlock {
+            text: text.trim().trim_end_matches('#').trim_end().to_string(),
+            level: level as i32,
+        })
+    })
+}
+
+// `- item`, `* item`, `+ item` or `1. item`, with whether the list is ordered
+fn list_item(line: &str) -> Option<(bool, &str)> {
+    if let Some(item) = ["- ", "* ", "+ "].iter().find_map(|marker| line.strip_prefix(marker)) {
+        return Some((false, item));
+    }
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    let item = line[digits..].strip_prefix(". ").or_else(|| line[digits..].strip_prefix(") "))?;
+    (digits > 0).then_some((true, item))
+}
+
+// An image alone on its line, ![alt](src) or ![alt](src "caption")
+fn image(line: &str) -> Option<ContentBlock> {
+    let (alt, target) = line.strip_prefix("![")?.strip_suffix(')')?.split_once("](")?;
+    let (src, caption) = match target.split_once(' ') {
+        Some((src, title)) => (src, Some(title.trim().trim_matches('"').to_string())),
+        None => (target, None),
+    };
+    Some(ContentBlock::Image(ImageBlock {
+        src: src.to_string(),
+        alt: alt.to_string(),
+        caption: caption.filter(|caption| !caption.is_empty()),
+        width: None,
+        height: None,
+        blurhash: None,
+    }))
+}
+
+// [^id]: text of the footnote
+fn footnote(line: &str) -> Option<ContentBlock> {
+    let (id, text) = line.strip_prefix("[^")?.split_once("]:")?;
+    Some(ContentBlock::Footnote(FootnoteBlock {
+        id: id.to_string(),
+        text: text.trim().to_string(),
+        url: None,
+    }))
+}
+
+fn end_paragraph(paragraph: &mut Vec<&str>, blocks: &mut Vec<ContentBlock>) {
+    if !paragraph.is_empty() {
+        blocks.push(ContentBlock::Paragraph(ParagraphBlock { text: paragraph.join(" ") }));
+        paragraph.clear();
+    }
+}
+
+// Content blocks of a markdown body: headings, paragraphs, lists, fenced code,
+// images on their own line and footnotes. Inline markup stays in the text, quotes
+// become paragraphs and other constructs, e.g. tables or HTML, are kept as text.
+fn content(markdown: &str) -> Vec<ContentBlock> {
+    let mut blocks = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut lines = markdown.lines();
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || ["---", "***", "___"].contains(&trimmed) {
+            end_paragraph(&mut paragraph, &mut blocks);
+            continue;
+        }
+        if let Some(info) = trimmed.strip_prefix("```") {
+            end_paragraph(&mut paragraph, &mut blocks);
+            let code: Vec<&str> = lines.by_ref().take_while(|line| !line.trim_start().starts_with("```")).collect();
+            blocks.push(ContentBlock::Code(CodeBlock {
+                code: code.join("\n"),
+                language: Some(info.trim()).filter(|language| !language.is_empty()).map(str::to_string),
+            }));
+            continue;
+        }
+        // indented lines right after a list item continue it
+        if line.starts_with([' ', '\t']) && paragraph.is_empty() {
+            if let Some(ContentBlock::List(list)) = blocks.last_mut() {
+                if let Some(item) = list.items.last_mut() {
+                    item.push(' ');
+                    item.push_str(trimmed);
+                    continue;
+                }
+            }
+        }
+        let block = heading(trimmed).or_else(|| image(trimmed)).or_else(|| footnote(trimmed));
+        if let Some(block) = block {
+            end_paragraph(&mut paragraph, &mut blocks);
+            blocks.push(block);
+            continue;
+        }
+        if let Some((ordered, item)) = list_item(trimmed) {
+            end_paragraph(&mut paragraph, &mut blocks);
+            if let Some(ContentBlock::List(list)) = blocks.last_mut() {
+                if list.ordered == ordered {
+                    list.items.push(item.to_string());
+                    continue;
+                }
+            }
+            blocks.push(ContentBlock::List(ListBlock {
+                items: vec![item.to_string()],
+                ordered,
+            }));
+            continue;
+        }
+        paragraph.push(trimmed.strip_prefix('>').map_or(trimmed, str::trim));
+    }
+    end_paragraph(&mut paragraph, &mut blocks);
+    blocks
+}
+
+async fn git(args: &[&str]) -> Result<String, StoreError> {
+    let output = Command::new("git")
+        .args(args)
+        // fail rather than wait for credentials nobody will type
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("git {} failed: {}", args.join(" "), stderr.trim()).into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// Bring the checkout to the branch's latest commit, cloning it the first time.
+// Returns the commit.
+async fn pull(config: &GitContentConfig) -> Result<String, StoreError> {
+    let checkout = config.checkout.to_string_lossy();
+    if config.checkout.join(".git").exists() {
+        git(&["-C", &checkout, "fetch", "--depth", "1", "origin", &config.branch]).await?;
+        git(&["-C", &checkout, "reset", "--hard", "FETCH_HEAD"]).await?;
+    } else {
+        git(&["clone", "--depth", "1", "--branch", &config.branch, &config.url, &checkout]).await?;
+    }
+    git(&["-C", &checkout, "rev-parse", "HEAD"]).await
+}
+
+// Markdown files under `dir`, in path order
+fn markdown_files(dir: &Path) -> Result<Vec<PathBuf>, StoreError> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.')) {
+            continue;
+        }
+        if path.is_dir() {
+            files.extend(markdown_files(&path)?);
+        } else if path.extension().is_some_and(|extension| extension == "md" || extension == "markdown") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// Import the markdown files of the configured repository as blog posts of its
+// owner, found again by path on later syncs. Frontmatter gives the title,
+// excerpt, tags, date and locale, `draft: true` keeps a post a draft and any
+// other post is published. A post's slug comes from the frontmatter or the file
+// name when it's first imported and never changes afterwards, renaming a file
+// makes a new post. Files deleted from the repository leave their posts alone.
+pub async fn sync(store: &dyn DataStore, config: &GitContentConfig) -> Result<GitSyncResult, StoreError> {
+    let _syncing = SYNCING.lock().await;
+    let commit = pull(config).await?;
+    let root = config.checkout.join(&config.path);
+    let owner_rules = rules::load(store, &config.owner_email).await?;
+    let mut result = GitSyncResult {
+        commit,
+        ..GitSyncResult::default()
+    };
+    for file in markdown_files(&root)? {
+        let path = file.strip_prefix(&root).unwrap_or(&file).to_string_lossy().replace('\\', "/");
+        match import(store, config, &owner_rules, &path, &file).await {
+            Ok(Imported::Created) => result.created += 1,
+            Ok(Imported::Updated) => result.updated += 1,
+            Ok(Imported::Unchanged) => result.unchanged += 1,
+            Err(e) => result.failed.push(GitSyncFailure {
+                path,
+                message: e.to_string(),
+            }),
+        }
+    }
+    Ok(result)
+}
+
+enum Imported {
+    Created,
+    Updated,
+    Unchanged,
+}
+
+async fn import(
+    store: &dyn DataStore,
+    config: &GitContentConfig,
+    owner_rules: &[ContentRule],
+    path: &str,
+    file: &Path,
+) -> Result<Imported, StoreError> {
+    let text = tokio::fs::read_to_string(file).await?;
+    let hash = format!("{:x}", Sha256::digest(text.as_bytes()));
+    let collection = &config::collections().blog_posts;
+    let filter = json!({ "email": config.owner_email, "gitPath": path });
+    let existing = store.find_one(collection, filter.clone()).await?;
+    if existing.as_ref().is_some_and(|post| post["gitHash"] == hash.as_str()) {
+        return Ok(Imported::Unchanged);
+    }
+    let (frontmatter, body) = split_frontmatter(&text)?;
+    let Some(title) = frontmatter.title.filter(|title| !title.trim().is_empty()) else {
+        return Err("The frontmatter has no title".into());
+    };
+    let date = frontmatter.date.as_deref().map(parse_date).transpose()?;
+    let status = if frontmatter.draft { BlogPostStatus::Draft } else { BlogPostStatus::Published };
+    let now = DateTime::now().try_to_rfc3339_string()?;
+    let (mut post, created): (BlogPost, bool) = match existing {
+        Some(existing) => (serde_json::from_value(existing)?, false),
+        None => {
+            let name = file.file_stem().unwrap_or_default().to_string_lossy();
+            let slug = validation::slugify(frontmatter.slug.as_deref().unwrap_or(&name), MAX_SLUG_CHARS);
+            let variant = json!({ "email": config.owner_email, "slug": slug, "locale": frontmatter.locale });
+            if slug.is_empty() || store.find_one(collection, variant).await?.is_some() {
+                return Err(format!("The slug {:?} is empty or used by another post, set a slug", slug).into());
+            }
+            let post = BlogPost {
+                id: Some(ObjectId::new()),
+                email: config.owner_email.clone(),
+                title: String::new(),
+                slug,
+                excerpt: None,
+                published_at: now.clone(),
+                status: BlogPostStatus::Draft.as_str().to_string(),
+                tags: Vec::new(),
+                content: Vec::new(),
+                locale: frontmatter.locale.clone(),
+                created_at: Some(now.clone()),
+                updated_at: None,
+                working_copy: None,
+                syndication: Vec::new(),
+            };
+            (post, true)
+        }
+    };
+    // posts published without a date are published as of this sync
+    let publishing = status == BlogPostStatus::Published && BlogPostStatus::of(&post) != status;
+    match date {
+        Some(date) => post.published_at = date,
+        None if publishing => post.published_at = now.clone(),
+        None => {}
+    }
+    post.title = title;
+    post.excerpt = frontmatter.excerpt.filter(|excerpt| !excerpt.trim().is_empty());
+    post.tags = frontmatter.tags;
+    post.content = content(body);
+    post.status = status.as_str().to_string();
+    post.updated_at = Some(now);
+    post.working_copy = None;
+    let mut document = serde_json::to_value(&post)?;
+    let broken: Vec<&str> = rules::broken(owner_rules, collection, &document)
+        .map(|rule| rule.name.as_str())
+        .collect();
+    if !broken.is_empty() {
+        return Err(format!("The post would break content rules: {}", broken.join(", ")).into());
+    }
+    document["gitPath"] = json!(path);
+    document["gitHash"] = json!(hash);
+    let imported = if created {
+        store.insert_one(collection, document).await?;
+        Imported::Created
+    } else {
+        let changes = json!({
+            "title": post.title,
+            "excerpt": post.excerpt,
+            "tags": post.tags,
+            "content": post.content,
+            "status": post.status,
+            "publishedAt": post.published_at,
+            "updatedAt": post.updated_at,
+            "workingCopy": null,
+            "gitHash": hash,
+        });
+        store.update_one(collection, filter, changes).await?;
+        Imported::Updated
+    };
+    if publishing {
+        syndication::announce(store, &post).await;
+    }
+    Ok(imported)
+}
+
+async fn sync_and_log(store: &dyn DataStore, config: &GitContentConfig) {
+    match sync(store, config).await {
+        Ok(result) => tracing::info!(
+            commit = result.commit,
+            created = result.created,
+            updated = result.updated,
+            unchanged = result.unchanged,
+            failed = result.failed.len(),
+            "Synced blog posts from Git"
+        ),
+        Err(e) => tracing::error!(error = %e, "Git content sync failed"),
+    }
+}
+
+// Sync every CONTENT_GIT_SYNC_INTERVAL seconds, 15 minutes by default, when a
+// repository is configured. 0 leaves syncing to the webhook and the
+// syncGitContent mutation.
+pub fn spawn(store: Arc<dyn DataStore>) {
+    let Some(config) = GitContentConfig::from_env() else {
+        return;
+    };
+    let interval = env::var("CONTENT_GIT_SYNC_INTERVAL")
+        .ok()
+        .map(|seconds| seconds.parse().expect("CONTENT_GIT_SYNC_INTERVAL must be a number of seconds"))
+        .unwrap_or(15 * 60);
+    if interval == 0 {
+        println!("CONTENT_GIT_SYNC_INTERVAL is 0, Git content is only synced on push or demand");
+        return;
+    }
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+            sync_and_log(&*store, &config).await;
+        }
+    });
+}
+
+// GitHub signs the body with the secret, GitLab sends the secret itself
+fn authentic(secret: &str, headers: &HeaderMap, body: &[u8]) -> bool {
+    if let Some(signature) = headers.get("x-hub-signature-256").and_then(|value| value.to_str().ok()) {
+        let expected = format!("sha256={}", hex(&hmac_sha256(secret.as_bytes(), body)));
+        return constant_time_eq(signature.as_bytes(), expected.as_bytes());
+    }
+    headers
+        .get("x-gitlab-token")
+        .is_some_and(|token| constant_time_eq(token.as_bytes(), secret.as_bytes()))
+}
+
+// Push webhook of the content repository, for GitHub or GitLab with
+// CONTENT_GIT_WEBHOOK_SECRET as the secret. Pushes to other branches are
+// ignored. Forges give up on slow webhooks, so the sync runs in the background.
+pub async fn webhook(
+    Extension(store): Extension<Arc<dyn DataStore>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    let Some(config) = GitContentConfig::from_env() else {
+        return StatusCode::NOT_FOUND;
+    };
+    let Some(secret) = &config.webhook_secret else {
+        return StatusCode::NOT_FOUND;
+    };
+    if !authentic(secret, &headers, &body) {
+        return StatusCode::UNAUTHORIZED;
+    }
+    let pushed_ref = serde_json::from_slice::<Value>(&body)
+        .ok()
+        .and_then(|push| push["ref"].as_str().map(str::to_string));
+    if pushed_ref.is_some_and(|pushed_ref| pushed_ref != format!("refs/heads/{}", config.branch)) {
+        return StatusCode::NO_CONTENT;
+    }
+    tokio::spawn(async move { sync_and_log(&*store, &config).await });
+    StatusCode::ACCEPTED
+}
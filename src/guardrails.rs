@@ -0,0 +1,343 @@
+use async_trait::async_trait;
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use juniper::{graphql_value, FieldError, GraphQLBatchResponse, GraphQLResponse};
+use serde_json::{json, Value};
+use std::{
+    fmt,
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use crate::{
+    config,
+    store::{
+        self, CacheStats, DataStore, DocumentStream, FindOptions, StoreError, StoreTimeout,
+        StoredDocument,
+    },
+    Context,
+};
+
+// Error returned by reads once an operation has used up its document budget
+#[derive(Debug)]
+pub struct DocumentLimitExceeded {
+    limit: usize,
+}
+
+impl fmt::Display for DocumentLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Operation read more than {} documents", self.limit)
+    }
+}
+
+impl std::error::Error for DocumentLimitExceeded {}
+
+// Documents read by one GraphQL operation, against MAX_DOCUMENTS_PER_OPERATION
+#[derive(Debug, Default)]
+pub struct DocumentBudget {
+    read: AtomicUsize,
+    exceeded: AtomicBool,
+}
+
+impl DocumentBudget {
+    fn take(&self, count: usize) -> Result<(), StoreError> {
+        let limit = config::app().max_documents_per_operation;
+        let read = self.read.fetch_add(count, Ordering::Relaxed) + count;
+        if limit > 0 && read > limit {
+            self.exceeded.store(true, Ordering::Relaxed);
+            return Err(DocumentLimitExceeded { limit }.into());
+        }
+        Ok(())
+    }
+
+    pub fn exceeded(&self) -> bool {
+        self.exceeded.load(Ordering::Relaxed)
+    }
+}
+
+// Store calls of a request that the open circuit breaker turned away or that
+// timed out
+#[derive(Debug, Default)]
+pub struct Outage {
+    retry_after: Mutex<Option<Duration>>,
+    timeouts: Mutex<Vec<StoreTimeout>>,
+}
+
+impl Outage {
+    fn note<T>(&self, result: Result<T, StoreError>) -> Result<T, StoreError> {
+        if let Err(e) = &result {
+            if let Some(open) = store::circuit_open(e) {
+                *self.retry_after.lock().unwrap() = Some(open.retry_after);
+            }
+            if let Some(timeout) = e.downcast_ref::<StoreTimeout>() {
+                self.timeouts.lock().unwrap().push(timeout.clone());
+            }
+        }
+        result
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        *self.retry_after.lock().unwrap()
+    }
+
+    pub fn timeouts(&self) -> Vec<StoreTimeout> {
+        self.timeouts.lock().unwrap().clone()
+    }
+}
+
+// Store counting the documents a request reads against its budget, failing calls
+// slower than store_timeout_ms and noting calls the circuit breaker turned away.
+// Streams aren't counted, their documents are folded one at a time rather than kept.
+#[derive(Debug)]
+pub struct BudgetedStore {
+    inner: Arc<dyn DataStore>,
+    budget: Arc<DocumentBudget>,
+    outage: Arc<Outage>,
+}
+
+impl BudgetedStore {
+    pub fn new(inner: Arc<dyn DataStore>, budget: Arc<DocumentBudget>, outage: Arc<Outage>) -> Self {
+        Self { inner, budget, outage }
+    }
+
+    async fn guarded<T>(
+        &self,
+        operation: &str,
+        collection: &str,
+        call: impl Future<Output = Result<T, StoreError>>,
+    ) -> Result<T, StoreError> {
+        self.outage.note(store::within(operation, collection, call).await)
+    }
+}
+
+#[async_trait]
+impl DataStore for BudgetedStore {
+    fn backend_name(&self) -> &'static str {
+        self.inner.backend_name()
+    }
+
+    async fn ping(&self) -> Result<(), StoreError> {
+        self.guarded("ping", "", self.inner.ping()).await
+    }
+
+    async fn find_with(
+        &self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<Vec<Value>, StoreError> {
+        let find = self.inner.find_with(collection, filter, options);
+        let documents = self.guarded("find", collection, find).await?;
+        self.budget.take(documents.len())?;
+        Ok(documents)
+    }
+
+    async fn find_raw(
+        &self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<Vec<StoredDocument>, StoreError> {
+        let find = self.inner.find_raw(collection, filter, options);
+        let documents = self.guarded("find", collection, find).await?;
+        self.budget.take(documents.len())?;
+        Ok(documents)
+    }
+
+    async fn find_stream<'a>(
+        &'a self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<DocumentStream<'a>, StoreError> {
+        self.guarded("find", collection, self.inner.find_stream(collection, filter, options)).await
+    }
+
+    async fn count(&self, collection: &str, filter: Value) -> Result<u64, StoreError> {
+        self.guarded("count", collection, self.inner.count(collection, filter)).await
+    }
+
+    async fn find_one(&self, collection: &str, filter: Value) -> Result<Option<Value>, StoreError> {
+        let find = self.inner.find_one(collection, filter);
+        let document = self.guarded("find_one", collection, find).await?;
+        self.budget.take(usize::from(document.is_some()))?;
+        Ok(document)
+    }
+
+    async fn insert_one(&self, collection: &str, document: Value) -> Result<(), StoreError> {
+        self.guarded("insert_one", collection, self.inner.insert_one(collection, document)).await
+    }
+
+    async fn delete_one(&self, collection: &str, filter: Value) -> Result<bool, StoreError> {
+        self.guarded("delete_one", collection, self.inner.delete_one(collection, filter)).await
+    }
+
+    async fn delete_many(&self, collection: &str, filter: Value) -> Result<u64, StoreError> {
+        self.guarded("delete_many", collection, self.inner.delete_many(collection, filter)).await
+    }
+
+    async fn update_one(&self, collection: &str, filter: Value, changes: Value) -> Result<bool, StoreError> {
+        self.guarded("update_one", collection, self.inner.update_one(collection, filter, changes)).await
+    }
+
+    async fn insert_if_absent(
+        &self,
+        collection: &str,
+        filter: Value,
+        document: Value,
+    ) -> Result<bool, StoreError> {
+        let inserted = self.inner.insert_if_absent(collection, filter, document);
+        self.guarded("insert_if_absent", collection, inserted).await
+    }
+
+    async fn insert_many_atomic(&self, documents: Vec<(String, Value)>) -> Result<(), StoreError> {
+        self.guarded("insert_many_atomic", "", self.inner.insert_many_atomic(documents)).await
+    }
+
+    async fn ensure_index(&self, collection: &str, fields: &[&str], unique: bool) -> Result<(), StoreError> {
+        self.inner.ensure_index(collection, fields, unique).await
+    }
+
+    async fn ensure_text_index(&self, collection: &str, fields: &[&str]) -> Result<(), StoreError> {
+        self.inner.ensure_text_index(collection, fields).await
+    }
+
+    async fn text_search(
+        &self,
+        collection: &str,
+        filter: Value,
+        fields: &[&str],
+        query: &str,
+        limit: u64,
+    ) -> Result<Vec<(f64, Value)>, StoreError> {
+        let search = self.inner.text_search(collection, filter, fields, query, limit);
+        let documents = self.guarded("find", collection, search).await?;
+        self.budget.take(documents.len())?;
+        Ok(documents)
+    }
+
+    async fn collection_names(&self) -> Result<Vec<String>, StoreError> {
+        self.inner.collection_names().await
+    }
+
+    fn cache_stats(&self) -> Vec<CacheStats> {
+        self.inner.cache_stats()
+    }
+
+    fn purge_cache(&self, collection: Option<&str>) -> u64 {
+        self.inner.purge_cache(collection)
+    }
+}
+
+// A serialized GraphQL response, replaced by a single error with a `code`
+// extension when the operation went over one of the limits or the circuit
+// breaker turned one of its reads away. Documents left out because they didn't
+// match their model are listed in extensions.warnings, and store calls that
+// timed out in extensions.timeouts.
+pub struct GuardedResponse {
+    ok: bool,
+    body: Vec<u8>,
+    // Set when the data store was unavailable, answered 503 with a Retry-After
+    retry_after: Option<Duration>,
+}
+
+impl GuardedResponse {
+    pub fn new(response: GraphQLBatchResponse, context: &Context) -> Self {
+        if let Some(retry_after) = context.outage.retry_after() {
+            let seconds = i32::try_from(retry_after.as_secs().max(1)).unwrap_or(i32::MAX);
+            return Self {
+                retry_after: Some(retry_after),
+                ..Self::error(FieldError::new(
+                    "Data store unavailable",
+                    graphql_value!({ "code": "DATA_UNAVAILABLE", "retryAfter": seconds }),
+                ))
+            };
+        }
+        if context.budget.exceeded() {
+            let limit = i32::try_from(config::app().max_documents_per_operation).unwrap_or(i32::MAX);
+            return Self::error(FieldError::new(
+                format!("Operation read more than {} documents", limit),
+                graphql_value!({ "code": "DOCUMENT_LIMIT_EXCEEDED", "limit": limit }),
+            ));
+        }
+        let ok = response.is_ok();
+        let mut extensions = serde_json::Map::new();
+        let warnings = context.decode_warnings.lock().unwrap().clone();
+        if !warnings.is_empty() {
+            extensions.insert("warnings".to_string(), json!(warnings));
+        }
+        let timeouts = context.outage.timeouts();
+        if !timeouts.is_empty() {
+            extensions.insert("timeouts".to_string(), json!(timeouts));
+        }
+        let body = if extensions.is_empty() {
+            serde_json::to_vec(&response)
+        } else {
+            with_extensions(&response, Value::Object(extensions))
+        }
+        .unwrap_or_default();
+        let limit = config::app().max_response_bytes;
+        if limit > 0 && body.len() > limit {
+            let limit = i32::try_from(limit).unwrap_or(i32::MAX);
+            return Self::error(FieldError::new(
+                format!("Response is larger than {} bytes", limit),
+                graphql_value!({ "code": "RESPONSE_TOO_LARGE", "limit": limit }),
+            ));
+        }
+        Self {
+            ok,
+            body,
+            retry_after: None,
+        }
+    }
+
+    fn error(error: FieldError) -> Self {
+        let response = GraphQLBatchResponse::Single(GraphQLResponse::error(error));
+        Self {
+            ok: false,
+            body: serde_json::to_vec(&response).unwrap_or_default(),
+            retry_after: None,
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.ok
+    }
+}
+
+fn with_extensions(response: &GraphQLBatchResponse, extensions: Value) -> serde_json::Result<Vec<u8>> {
+    let mut body = serde_json::to_value(response)?;
+    // a batch gets the extensions of all its operations on each response
+    let responses = match &mut body {
+        Value::Array(responses) => responses.iter_mut().collect(),
+        single => vec![single],
+    };
+    for response in responses {
+        if let Value::Object(fields) = response {
+            fields.insert("extensions".to_string(), extensions.clone());
+        }
+    }
+    serde_json::to_vec(&body)
+}
+
+// Same status codes as juniper_axum's responses, apart from 503 while the data
+// store is unavailable
+impl IntoResponse for GuardedResponse {
+    fn into_response(self) -> Response {
+        if let Some(retry_after) = self.retry_after {
+            let headers = [
+                (header::CONTENT_TYPE, "application/json".to_string()),
+                (header::RETRY_AFTER, retry_after.as_secs().max(1).to_string()),
+            ];
+            return (StatusCode::SERVICE_UNAVAILABLE, headers, self.body).into_response();
+        }
+        let status = if self.ok { StatusCode::OK } else { StatusCode::BAD_REQUEST };
+        (status, [(header::CONTENT_TYPE, "application/json")], self.body).into_response()
+    }
+}
@@ -0,0 +1,38 @@
+use std::sync::OnceLock;
+use syntect::{
+    highlighting::{Theme, ThemeSet},
+    html::highlighted_html_for_string,
+    parsing::SyntaxSet,
+};
+
+use crate::config;
+
+struct Highlighter {
+    syntaxes: SyntaxSet,
+    theme: Theme,
+}
+
+static HIGHLIGHTER: OnceLock<Highlighter> = OnceLock::new();
+
+// Loading the bundled syntaxes and themes takes a while, so it happens once on first use
+fn highlighter() -> &'static Highlighter {
+    HIGHLIGHTER.get_or_init(|| {
+        let mut themes = ThemeSet::load_defaults().themes;
+        let name = &config::app().syntax_theme;
+        let theme = themes.remove(name).unwrap_or_else(|| {
+            println!("Unknown syntax theme {}, using default value", name);
+            themes.remove("InspiredGitHub").expect("InspiredGitHub is a bundled theme")
+        });
+        Highlighter {
+            syntaxes: SyntaxSet::load_defaults_newlines(),
+            theme,
+        }
+    })
+}
+
+// Code as highlighted HTML (a <pre> with inline styles), None when the language is unknown
+pub fn highlight(code: &str, language: &str) -> Option<String> {
+    let highlighter = highlighter();
+    let syntax = highlighter.syntaxes.find_syntax_by_token(language)?;
+    highlighted_html_for_string(code, &highlighter.syntaxes, syntax, &highlighter.theme).ok()
+}
@@ -0,0 +1,337 @@
+use axum::http::{header, HeaderMap};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{
+    collections::{hash_map::Entry, BTreeMap, HashMap},
+    env,
+};
+use tokio_stream::StreamExt;
+
+use crate::{
+    config, settings,
+    store::{DataStore, DocumentFields, DocumentStream, StoreError},
+};
+
+// Field holding the locale of a document, documents without one are in the default locale
+pub const LOCALE_FIELD: &str = "locale";
+// Documents sharing a translationKey are translations of each other
+pub const TRANSLATION_KEY_FIELD: &str = "translationKey";
+
+// Default locale for owners whose settings don't pick one
+fn default_locale() -> String {
+    env::var("DEFAULT_LOCALE").unwrap_or_else(|_| "en".to_string())
+}
+
+// Preferred language of an Accept-Language header, e.g. "fil-PH,fil;q=0.9,en;q=0.8" -> fil-ph
+pub fn accept_language(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(header::ACCEPT_LANGUAGE)?.to_str().ok()?;
+    let mut languages: Vec<(f32, String)> = value
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';');
+            let tag = parts.next()?.trim().to_ascii_lowercase();
+            let quality = parts
+                .find_map(|part| part.trim().strip_prefix("q="))
+                .and_then(|quality| quality.parse().ok())
+                .unwrap_or(1.0);
+            (!tag.is_empty() && tag != "*").then_some((quality, tag))
+        })
+        .collect();
+    // stable, so equal weights keep the client's order
+    languages.sort_by(|a, b| b.0.total_cmp(&a.0));
+    languages.into_iter().next().map(|(_, tag)| tag)
+}
+
+// Locale settings of an owner, read from their site settings document
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct LocaleSettings {
+    #[serde(rename = "defaultLocale", default)]
+    default_locale: Option<String>,
+    // Ordered fallbacks tried after a locale, e.g. { "fil": ["en"] }
+    #[serde(rename = "localeFallbacks", default)]
+    fallbacks: HashMap<String, Vec<String>>,
+    // Display names overriding the built in ones, e.g. { "fil": "Tagalog" }
+    #[serde(rename = "localeNames", default)]
+    names: HashMap<String, String>,
+}
+
+impl LocaleSettings {
+    pub async fn load(store: &dyn DataStore, owner_email: &str) -> Result<Self, StoreError> {
+        let found = settings::load(store, owner_email).await?;
+        Ok(found.map(serde_json::from_value).transpose()?.unwrap_or_default())
+    }
+
+    pub fn default_locale(&self) -> String {
+        self.default_locale
+            .as_deref()
+            .map(str::to_ascii_lowercase)
+            .unwrap_or_else(default_locale)
+    }
+
+    // Locales to try in order for a requested language: the tag, its primary
+    // language (fil-ph -> fil), their configured fallbacks, then the default locale
+    pub fn chain(&self, lang: Option<&str>) -> Vec<String> {
+        let mut locales = Vec::new();
+        if let Some(lang) = lang.map(|lang| lang.trim().to_ascii_lowercase()).filter(|lang| !lang.is_empty()) {
+            if let Some((primary, _)) = lang.split_once('-') {
+                let primary = primary.to_string();
+                locales.push(lang);
+                locales.push(primary);
+            } else {
+                locales.push(lang);
+            }
+        }
+        let fallbacks: Vec<String> = locales
+            .iter()
+            .filter_map(|locale| self.fallbacks.get(locale))
+            .flatten()
+            .map(|locale| locale.to_ascii_lowercase())
+            .collect();
+        locales.extend(fallbacks);
+        locales.push(self.default_locale());
+        dedup(locales)
+    }
+
+    // Every locale the owner has configured, the default locale first
+    pub fn locales(&self) -> Vec<String> {
+        let mut locales = vec![self.default_locale()];
+        let mut configured: Vec<String> = self
+            .fallbacks
+            .iter()
+            .flat_map(|(locale, fallbacks)| std::iter::once(locale).chain(fallbacks))
+            .chain(self.names.keys())
+            .map(|locale| locale.to_ascii_lowercase())
+            .collect();
+        configured.sort();
+        locales.extend(configured);
+        dedup(locales)
+    }
+
+    // Name of a locale in its own language, for language switchers
+    pub fn name(&self, code: &str) -> String {
+        if let Some(name) = self.names.get(code) {
+            return name.clone();
+        }
+        let primary = code.split('-').next().unwrap_or(code);
+        let name = match primary {
+            "ar" => "العربية",
+            "ceb" => "Cebuano",
+            "de" => "Deutsch",
+            "en" => "English",
+            "es" => "Español",
+            "fa" => "فارسی",
+            "fil" | "tl" => "Filipino",
+            "fr" => "Français",
+            "he" => "עברית",
+            "id" => "Bahasa Indonesia",
+            "it" => "Italiano",
+            "ja" => "日本語",
+            "ko" => "한국어",
+            "pt" => "Português",
+            "ur" => "اردو",
+            "zh" => "中文",
+            _ => code,
+        };
+        name.to_string()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, juniper::GraphQLEnum)]
+pub enum TextDirection {
+    Ltr,
+    Rtl,
+}
+
+// Scripts of these languages are written right to left
+const RTL_LANGUAGES: [&str; 8] = ["ar", "dv", "fa", "he", "ps", "ur", "yi", "ckb"];
+
+pub fn direction(code: &str) -> TextDirection {
+    let primary = code.split('-').next().unwrap_or(code);
+    if RTL_LANGUAGES.contains(&primary) {
+        TextDirection::Rtl
+    } else {
+        TextDirection::Ltr
+    }
+}
+
+// A locale the owner publishes in, for the frontend language switcher
+#[derive(Clone, Debug, juniper::GraphQLObject)]
+pub struct LocaleInfo {
+    pub code: String,
+    pub name: String,
+    pub direction: TextDirection,
+    // Share of documents with a variant in this locale, 0 to 100
+    pub completeness: f64,
+}
+
+// The owner's configured locales, default locale first
+pub fn available_locales(settings: &LocaleSettings, groups: &[MissingTranslation]) -> Vec<LocaleInfo> {
+    settings
+        .locales()
+        .into_iter()
+        .map(|code| {
+            let translated = groups.iter().filter(|group| group.locales.contains(&code)).count();
+            let completeness = if groups.is_empty() {
+                100.0
+            } else {
+                (translated * 100) as f64 / groups.len() as f64
+            };
+            LocaleInfo {
+                name: settings.name(&code),
+                direction: direction(&code),
+                code,
+                completeness,
+            }
+        })
+        .collect()
+}
+
+fn dedup(locales: Vec<String>) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::new();
+    for locale in locales {
+        if !unique.contains(&locale) {
+            unique.push(locale);
+        }
+    }
+    unique
+}
+
+fn locale_of(value: &impl DocumentFields, default: &str) -> String {
+    value.get_str(LOCALE_FIELD).unwrap_or(default).to_ascii_lowercase()
+}
+
+fn translation_key(value: &impl DocumentFields) -> Option<String> {
+    value.get_str(TRANSLATION_KEY_FIELD).map(str::to_string)
+}
+
+// Pick one variant per document in the first locale of `locales` it exists in,
+// documents without a locale are in the default locale, the last of `locales`.
+// Documents without a translationKey stand alone and are kept when their locale
+// is in the chain. The order of the first kept variant of each document is kept.
+pub fn localize<D: DocumentFields>(values: Vec<D>, locales: &[String]) -> Vec<D> {
+    let default = locales.last().map(String::as_str).unwrap_or_default();
+    let rank = |value: &D| locales.iter().position(|locale| *locale == locale_of(value, default));
+    let mut picked: Vec<(Option<String>, usize, D)> = Vec::new();
+    for value in values {
+        let Some(value_rank) = rank(&value) else {
+            continue;
+        };
+        let key = translation_key(&value);
+        let existing = key
+            .as_ref()
+            .and_then(|key| picked.iter().position(|(picked_key, _, _)| picked_key.as_ref() == Some(key)));
+        match existing {
+            Some(index) if value_rank < picked[index].1 => picked[index] = (key, value_rank, value),
+            Some(_) => {}
+            None => picked.push((key, value_rank, value)),
+        }
+    }
+    picked.into_iter().map(|(_, _, value)| value).collect()
+}
+
+// One page of what localize keeps from `values`, read in order, plus how many
+// documents it keeps overall. Only the page and the translation keys seen are
+// held in memory, so the whole collection can be streamed through it.
+pub async fn localize_page(
+    mut values: DocumentStream<'_>,
+    locales: &[String],
+    offset: u64,
+    limit: Option<u64>,
+) -> Result<(Vec<Value>, u64), StoreError> {
+    let default = locales.last().map(String::as_str).unwrap_or_default();
+    let end = limit.map_or(u64::MAX, |limit| offset.saturating_add(limit));
+    // position of each translation key and rank of the variant kept for it
+    let mut keys: HashMap<String, (u64, usize)> = HashMap::new();
+    let mut page: BTreeMap<u64, Value> = BTreeMap::new();
+    let mut total = 0;
+    while let Some(value) = values.next().await {
+        let value = value?;
+        let Some(rank) = locales.iter().position(|locale| *locale == locale_of(&value, default)) else {
+            continue;
+        };
+        let position = match translation_key(&value).map(|key| keys.entry(key)) {
+            Some(Entry::Occupied(mut kept)) if rank < kept.get().1 => {
+                kept.get_mut().1 = rank;
+                kept.get().0
+            }
+            Some(Entry::Occupied(_)) => continue,
+            Some(Entry::Vacant(key)) => {
+                key.insert((total, rank));
+                total += 1;
+                total - 1
+            }
+            None => {
+                total += 1;
+                total - 1
+            }
+        };
+        if (offset..end).contains(&position) {
+            page.insert(position, value);
+        }
+    }
+    Ok((page.into_values().collect(), total))
+}
+
+// A document that lacks variants in some of the owner's locales
+#[derive(Clone, Debug, juniper::GraphQLObject)]
+pub struct MissingTranslation {
+    pub collection: String,
+    // Unset for documents that aren't linked to any translation
+    pub translation_key: Option<String>,
+    // slug, title, name or school of the document, whichever it has
+    pub label: String,
+    pub locales: Vec<String>,
+    pub missing_locales: Vec<String>,
+}
+
+// Every document of the owner's localized collections, grouped by translationKey,
+// with the locales it has variants in
+pub async fn translation_groups(
+    store: &dyn DataStore,
+    owner_email: &str,
+    default: &str,
+) -> Result<Vec<MissingTranslation>, StoreError> {
+    let mut groups: Vec<MissingTranslation> = Vec::new();
+    for collection in config::collections().localized() {
+        let start = groups.len();
+        for value in store.find(collection, json!({ "email": owner_email })).await? {
+            let locale = locale_of(&value, default);
+            let key = translation_key(&value);
+            let existing = key.as_ref().and_then(|key| {
+                groups[start..]
+                    .iter_mut()
+                    .find(|group| group.translation_key.as_ref() == Some(key))
+            });
+            match existing {
+                Some(group) => group.locales.push(locale),
+                None => groups.push(MissingTranslation {
+                    collection: collection.to_string(),
+                    translation_key: key,
+                    label: ["slug", "title", "name", "school"]
+                        .iter()
+                        .find_map(|field| value.get(*field).and_then(Value::as_str))
+                        .unwrap_or_default()
+                        .to_string(),
+                    locales: vec![locale],
+                    missing_locales: Vec::new(),
+                }),
+            }
+        }
+    }
+    Ok(groups)
+}
+
+// Groups lacking a variant in some of the `expected` locales
+pub fn missing_translations(groups: Vec<MissingTranslation>, expected: &[String]) -> Vec<MissingTranslation> {
+    groups
+        .into_iter()
+        .filter_map(|mut missing| {
+            missing.missing_locales = expected
+                .iter()
+                .filter(|locale| !missing.locales.contains(locale))
+                .cloned()
+                .collect();
+            (!missing.missing_locales.is_empty()).then_some(missing)
+        })
+        .collect()
+}
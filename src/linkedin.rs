@@ -0,0 +1,280 @@
+use juniper::{graphql_value, FieldError};
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    bulk::{self, BulkUpsertResult},
+    config,
+    store::DataStore,
+    validation::{Validator, MAX_NAME_CHARS},
+};
+
+// Turns the rows of a file of the export into documents
+type Converter = fn(&[HashMap<String, String>]) -> Result<Vec<Map<String, Value>>, String>;
+
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+
+#[derive(Debug, Default, juniper::GraphQLObject)]
+pub struct LinkedInImportResult {
+    // Item indexes count the rows of each file, header left out, and for
+    // skills only the rows of skills that were new
+    pub work_experience: BulkUpsertResult,
+    pub education: BulkUpsertResult,
+    pub skills: BulkUpsertResult,
+    // Skills of the file the owner already has, left as they are
+    pub existing_skills: Vec<String>,
+}
+
+// Rows of a CSV file as written by LinkedIn, keyed by the names of the header
+// row. Quoted fields can hold commas, doubled quotes and line breaks.
+fn parse_csv(text: &str) -> Result<Vec<HashMap<String, String>>, String> {
+    let text = text.trim_start_matches('\u{feff}');
+    let mut records: Vec<Vec<String>> = Vec::new();
+    let (mut record, mut field) = (Vec::new(), String::new());
+    let (mut quoted, mut chars) = (false, text.chars().peekable());
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted => {}
+            '\n' if !quoted => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("ends inside a quoted field".to_string());
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    let mut records = records
+        .into_iter()
+        .filter(|record| record.iter().any(|field| !field.trim().is_empty()));
+    let Some(header) = records.next() else {
+        return Ok(Vec::new());
+    };
+    Ok(records
+        .map(|record| {
+            header
+                .iter()
+                .map(|name| name.trim().to_string())
+                .zip(record.into_iter().map(|field| field.trim().to_string()))
+                .filter(|(_, value)| !value.is_empty())
+                .collect()
+        })
+        .collect())
+}
+
+// A LinkedIn date, "Mar 2019" or "2019", as 2019-03 or 2019. None when empty.
+fn parse_date(text: Option<&String>) -> Result<Option<String>, String> {
+    let Some(text) = text else {
+        return Ok(None);
+    };
+    let year = |year: &str| (year.len() == 4 && year.bytes().all(|byte| byte.is_ascii_digit())).then_some(());
+    let date = match text.split_once(' ') {
+        None => year(text).map(|_| text.clone()),
+        Some((month, rest)) => {
+            let month = month.get(..3).map(str::to_lowercase);
+            let month = MONTHS.iter().position(|name| Some(*name) == month.as_deref());
+            month.zip(year(rest)).map(|(month, _)| format!("{}-{:02}", rest, month + 1))
+        }
+    };
+    date.map(Some).ok_or_else(|| format!("{} is not a date like Mar 2019 or 2019", text))
+}
+
+// Fields of a row that are set, renamed from the columns of the export
+fn fields(row: &HashMap<String, String>, columns: &[(&str, &str)]) -> Map<String, Value> {
+    columns
+        .iter()
+        .filter_map(|(column, field)| Some((field.to_string(), json!(row.get(*column)?))))
+        .collect()
+}
+
+// One document per row of Positions.csv
+fn positions(rows: &[HashMap<String, String>]) -> Result<Vec<Map<String, Value>>, String> {
+    let columns = [
+        ("Company Name", "company"),
+        ("Title", "title"),
+        ("Description", "description"),
+        ("Location", "location"),
+    ];
+    let mut documents = Vec::new();
+    for (index, row) in rows.iter().enumerate() {
+        let row_error = |message: String| format!("row {}: {}", index + 1, message);
+        let mut document = fields(row, &columns);
+        let start_date = parse_date(row.get("Started On")).map_err(row_error)?;
+        let Some(start_date) = start_date else {
+            return Err(row_error("Started On is empty".to_string()));
+        };
+        document.insert("startDate".to_string(), json!(start_date));
+        if let Some(end_date) = parse_date(row.get("Finished On")).map_err(row_error)? {
+            document.insert("endDate".to_string(), json!(end_date));
+        }
+        documents.push(document);
+    }
+    Ok(documents)
+}
+
+// One document per row of Education.csv
+fn education(rows: &[HashMap<String, String>]) -> Result<Vec<Map<String, Value>>, String> {
+    let columns = [
+        ("School Name", "school"),
+        ("Degree Name", "degree"),
+        ("Notes", "description"),
+        ("Activities", "activities"),
+    ];
+    let mut documents = Vec::new();
+    for (index, row) in rows.iter().enumerate() {
+        let row_error = |message: String| format!("row {}: {}", index + 1, message);
+        let mut document = fields(row, &columns);
+        for (column, field) in [("Start Date", "startDate"), ("End Date", "endDate")] {
+            if let Some(date) = parse_date(row.get(column)).map_err(row_error)? {
+                document.insert(field.to_string(), json!(date));
+            }
+        }
+        documents.push(document);
+    }
+    Ok(documents)
+}
+
+// Write `documents` through bulk::upsert, in as many calls as it takes
+async fn upsert_all(
+    store: &dyn DataStore,
+    owner_email: &str,
+    collection: &str,
+    locale: Option<&str>,
+    documents: Vec<Map<String, Value>>,
+) -> Result<BulkUpsertResult, FieldError> {
+    let documents: Vec<String> = documents
+        .into_iter()
+        .map(|mut document| {
+            if let Some(locale) = locale {
+                document.insert("locale".to_string(), json!(locale));
+            }
+            Value::Object(document).to_string()
+        })
+        .collect();
+    let mut result = BulkUpsertResult::default();
+    for (chunk, documents) in documents.chunks(bulk::MAX_DOCUMENTS).enumerate() {
+        let part = bulk::upsert(store, owner_email, collection, documents.to_vec()).await?;
+        result.extend(part, chunk * bulk::MAX_DOCUMENTS);
+    }
+    Ok(result)
+}
+
+// Import the Positions.csv, Education.csv and Skills.csv files of a LinkedIn
+// data export into the owner's work experience, education and skills, to start
+// the resume sections from the profile. Positions and schools already imported
+// are updated, matched as bulkUpsert matches them. LinkedIn doesn't rate
+// skills, so only skills the owner doesn't have are added, with mastery 0 and
+// skillType other, to be rated with updateSkill.
+pub async fn import(
+    store: &dyn DataStore,
+    owner_email: &str,
+    locale: Option<String>,
+    positions_csv: Option<String>,
+    education_csv: Option<String>,
+    skills_csv: Option<String>,
+) -> Result<LinkedInImportResult, FieldError> {
+    let collections = config::collections();
+    let mut input = Validator::default();
+    input.optional_text("locale", locale.as_deref(), MAX_NAME_CHARS);
+    if positions_csv.is_none() && education_csv.is_none() && skills_csv.is_none() {
+        input.reject("positions", "education or skills must be given");
+    }
+    let mut read = |argument: &str, csv: Option<String>, convert: Converter| {
+        let converted = parse_csv(csv.as_deref().unwrap_or_default()).and_then(|rows| convert(&rows));
+        converted.unwrap_or_else(|message| {
+            input.reject(argument, message);
+            Vec::new()
+        })
+    };
+    let positions = read("positions", positions_csv, positions);
+    let schools = read("education", education_csv, education);
+    let skill_names = read("skills", skills_csv, |rows| {
+        Ok(rows.iter().map(|row| fields(row, &[("Name", "name")])).collect())
+    });
+    input.finish()?;
+
+    let existing = store
+        .find(&collections.skills, json!({ "email": owner_email, "locale": locale }))
+        .await
+        .map_err(|err| FieldError::new("Failed to fetch skills", graphql_value!({ "details": err.to_string() })))?;
+    let mut known: HashSet<String> = existing
+        .iter()
+        .filter_map(|skill| skill["name"].as_str())
+        .map(str::to_lowercase)
+        .collect();
+    let mut result = LinkedInImportResult::default();
+    let mut skills = Vec::new();
+    for mut skill in skill_names {
+        let Some(name) = skill.get("name").and_then(Value::as_str).map(str::to_string) else {
+            continue;
+        };
+        if !known.insert(name.to_lowercase()) {
+            result.existing_skills.push(name);
+            continue;
+        }
+        skill.insert("mastery".to_string(), json!(0));
+        skill.insert("skillType".to_string(), json!("other"));
+        skills.push(skill);
+    }
+    let locale = locale.as_deref();
+    result.work_experience = upsert_all(store, owner_email, &collections.work_experience, locale, positions).await?;
+    result.education = upsert_all(store, owner_email, &collections.education, locale, schools).await?;
+    result.skills = upsert_all(store, owner_email, &collections.skills, locale, skills).await?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_quoted_fields() {
+        let rows = parse_csv(
+            "\u{feff}Company Name,Title,Description\r\n\
+             \"Acme, Inc.\",\"Engineer \"\"II\"\"\",\"Built things\nand shipped them\"\r\n\
+             Globex,,\r\n",
+        )
+        .unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["Company Name"], "Acme, Inc.");
+        assert_eq!(rows[0]["Title"], "Engineer \"II\"");
+        assert_eq!(rows[0]["Description"], "Built things\nand shipped them");
+        // empty fields are left out
+        assert_eq!(rows[1]["Company Name"], "Globex");
+        assert!(!rows[1].contains_key("Title"));
+    }
+
+    #[test]
+    fn parses_last_row_without_line_break() {
+        let rows = parse_csv("Name\nRust\n\nTypeScript").unwrap();
+        let names: Vec<&str> = rows.iter().map(|row| row["Name"].as_str()).collect();
+        assert_eq!(names, ["Rust", "TypeScript"]);
+    }
+
+    #[test]
+    fn rejects_unterminated_quotes() {
+        assert!(parse_csv("Name\n\"Rust").is_err());
+    }
+
+    #[test]
+    fn parses_dates() {
+        let date = |text: &str| parse_date(Some(&text.to_string()));
+        assert_eq!(date("Mar 2019"), Ok(Some("2019-03".to_string())));
+        assert_eq!(date("December 2021"), Ok(Some("2021-12".to_string())));
+        assert_eq!(date("2019"), Ok(Some("2019".to_string())));
+        assert_eq!(parse_date(None), Ok(None));
+        assert!(date("Smarch 2019").is_err());
+        assert!(date("Mar 19").is_err());
+    }
+}
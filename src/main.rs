@@ -1,66 +1,832 @@
 use axum::{
-    http::{self, Method}, routing::{get, post}, Extension, Router
+    extract::{ConnectInfo, Request},
+    http::{self, HeaderMap, HeaderName, Method, StatusCode}, middleware,
+    response::{IntoResponse, Response}, routing::{get, post}, Extension, Router
 };
-use mongodb::{bson::{self, Document}, error::Error, options::ClientOptions, Client, Collection, Database};
-use dotenv::dotenv;
-use serde_json::Value;
-use tokio::net::TcpListener;
+use mongodb::bson::oid::ObjectId;
+use clap::Parser;
+use serde_json::{json, Value};
 use std::{
-    env, sync::{Arc, Mutex},
-    error::Error as StdError
+    collections::HashMap, env, net::SocketAddr, sync::{Arc, Mutex}, time::Instant,
 };
 use serde::{Deserialize, Serialize};
 use juniper::{
-    graphql_object, graphql_value, EmptyMutation, EmptySubscription, FieldError, RootNode
+    graphql_object, graphql_value, EmptySubscription, FieldError, RootNode
 };
-use juniper_axum::graphql;
-use tower_http::cors::{Any, CorsLayer};
+use juniper_axum::{extract::JuniperRequest, playground};
+use sha2::{Digest, Sha256};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use sentry_tower::{NewSentryLayer, SentryHttpLayer};
+use tracing_subscriber::EnvFilter;
+use validation::{Validator, MAX_NAME_CHARS, MAX_SLUG_CHARS, MAX_TEXT_CHARS, MAX_URL_CHARS};
 
-#[derive(Clone, Copy, Debug, Default)]
-pub struct Context;
+mod access_log;
+mod admin;
+mod analytics;
+mod api_keys;
+mod bench;
+mod blog;
+mod booking;
+mod books;
+mod bots;
+mod bulk;
+mod calendar;
+mod cli;
+mod cms_import;
+mod config;
+mod content;
+mod data_check;
+mod dates;
+mod decode;
+mod error_reporting;
+mod experiments;
+mod flags;
+mod geoip;
+mod git_content;
+mod guardrails;
+mod highlight;
+mod i18n;
+mod linkedin;
+mod media;
+mod money;
+mod navigation;
+mod ndjson;
+mod not_found;
+mod notion;
+mod operation;
+mod preflight;
+mod redirects;
+mod resume;
+mod rollup;
+mod rules;
+mod search;
+mod settings;
+mod signing;
+mod status;
+mod store;
+mod syndication;
+mod systemd;
+mod talks;
+mod tenant;
+mod theme;
+mod tls;
+mod trash;
+mod tuning;
+mod unix_socket;
+mod uses;
+mod validation;
+mod visitor;
+mod warmup;
+
+#[derive(Clone, Debug)]
+pub struct Context {
+    store: Arc<dyn store::DataStore>,
+    // Owner resolved from the deployment config, request headers or Host
+    owner_email: Option<String>,
+    multi_tenant: bool,
+    tenant: Option<tenant::Tenant>,
+    // Set when the owner comes from an API key, which can't be overridden by arguments
+    api_key: Option<api_keys::ApiKey>,
+    visitor: visitor::Visitor,
+    // Whether the request came through /admin/graphql
+    admin: bool,
+    // Preferred language from Accept-Language, used when a query has no lang argument
+    accept_language: Option<String>,
+    // Locale settings of the owners read during this request
+    locale_settings: Arc<Mutex<HashMap<String, i18n::LocaleSettings>>>,
+    // Documents read through `store` during this request
+    budget: Arc<guardrails::DocumentBudget>,
+    // Set when the circuit breaker turned away one of the request's store calls
+    outage: Arc<guardrails::Outage>,
+    // Documents left out of lists because they didn't match their model
+    decode_warnings: Arc<Mutex<Vec<decode::DecodeWarning>>>,
+}
 
 impl juniper::Context for Context {}
 
-type Schema = RootNode<'static, Query, EmptyMutation<Context>, EmptySubscription<Context>>;
+impl Context {
+    // Analytics and other owner-only data is only served on the admin endpoint
+    fn require_admin(&self) -> Result<(), FieldError> {
+        if self.admin {
+            Ok(())
+        } else {
+            Err(FieldError::new(
+                "Only available on the admin endpoint",
+                graphql_value!({ "details": "Query /admin/graphql instead" }),
+            ))
+        }
+    }
+    // Decode stored documents into models. Documents that don't match are left out
+    // and reported in the response's warnings, or fail the field in strict mode.
+    fn decode_all<T, D>(&self, collection: &str, documents: Vec<D>) -> Result<Vec<T>, FieldError>
+    where
+        T: serde::de::DeserializeOwned,
+        D: Into<store::StoredDocument>,
+    {
+        Ok(self.decode_reporting(collection, documents)?.0)
+    }
+    // Like decode_all, also returning the documents that were left out, for
+    // payloads that list them next to their items
+    fn decode_reporting<T, D>(
+        &self,
+        collection: &str,
+        documents: Vec<D>,
+    ) -> Result<(Vec<T>, Vec<decode::DecodeWarning>), FieldError>
+    where
+        T: serde::de::DeserializeOwned,
+        D: Into<store::StoredDocument>,
+    {
+        let mut models = Vec::with_capacity(documents.len());
+        let mut skipped = Vec::new();
+        for document in documents {
+            let document = document.into();
+            let id = document.id();
+            match value_to_type::<T>(document) {
+                Ok(model) => models.push(model),
+                Err(err) => {
+                    let warning = decode::DecodeWarning::new::<T>(collection, id, &err);
+                    if config::app().decode_mode == config::DecodeMode::Strict {
+                        return Err(warning.into_field_error());
+                    }
+                    skipped.push(warning);
+                }
+            }
+        }
+        self.decode_warnings.lock().unwrap().extend(skipped.iter().cloned());
+        Ok((models, skipped))
+    }
+    fn decode_one<T, D>(&self, collection: &str, document: Option<D>) -> Result<Option<T>, FieldError>
+    where
+        T: serde::de::DeserializeOwned,
+        D: Into<store::StoredDocument>,
+    {
+        Ok(self.decode_all(collection, document.into_iter().collect())?.pop())
+    }
+    // Locales to read the owner's content in, the lang argument wins over Accept-Language
+    async fn locales(&self, owner_email: &str, lang: Option<String>) -> Result<Vec<String>, FieldError> {
+        let settings = self.locale_settings(owner_email).await?;
+        Ok(settings.chain(lang.or_else(|| self.accept_language.clone()).as_deref()))
+    }
+    async fn locale_settings(&self, owner_email: &str) -> Result<i18n::LocaleSettings, FieldError> {
+        let cached = self.locale_settings.lock().unwrap().get(owner_email).cloned();
+        if let Some(settings) = cached {
+            return Ok(settings);
+        }
+        let settings = i18n::LocaleSettings::load(&*self.store, owner_email).await.map_err(|err| {
+            error_reporting::capture_store_error(&config::collections().settings, err.as_ref());
+            FieldError::new(
+                "Failed to fetch locale settings",
+                graphql_value!({ "details": err.to_string() }),
+            )
+        })?;
+        self.locale_settings
+            .lock()
+            .unwrap()
+            .insert(owner_email.to_string(), settings.clone());
+        Ok(settings)
+    }
+    // Email whose documents a resolver should read, the owner argument wins in multi-tenant mode
+    fn owner_email(&self, requested: Option<String>) -> Result<String, FieldError> {
+        if self.multi_tenant && self.api_key.is_none() {
+            if let Some(owner) = requested {
+                return Ok(owner);
+            }
+        }
+        self.owner_email.clone().ok_or_else(|| {
+            FieldError::new(
+                "No portfolio owner given",
+                graphql_value!({ "details": "Pass the owner argument or the X-Portfolio-Owner header" }),
+            )
+        })
+    }
+}
+
+type Schema = RootNode<'static, Query, PublicMutation, EmptySubscription<Context>>;
+type AdminSchema = RootNode<'static, Query, Mutation, EmptySubscription<Context>>;
 
 #[derive(Debug, Deserialize, Serialize, juniper::GraphQLObject)]
 struct Introduction {
     title: String,
-    icon: String,
+    #[serde(default)]
+    icon: Option<String>,
+    // Variants of an A/B test share an experimentKey and are served by introductionFor
+    #[serde(rename = "experimentKey", default)]
+    experiment_key: Option<String>,
+    #[serde(default)]
+    variant: Option<String>,
 }
 #[derive(Debug, Deserialize, Serialize, juniper::GraphQLObject)]
 struct Personal {
     email: String,
-    #[serde(rename = "jobDescription")]
-    job_description: String,
-    #[serde(rename = "lifeStory")]
-    life_story: String,
-    #[serde(rename = "whyDothis")]
-    why_do_this: String,
-    #[serde(rename = "backgroundUrl")]
-    background_url: String,
+    // Every section of the about page is optional, the frontend hides the empty ones
+    #[serde(rename = "jobDescription", default)]
+    job_description: Option<String>,
+    #[serde(rename = "lifeStory", default)]
+    life_story: Option<String>,
+    #[serde(rename = "whyDothis", default)]
+    why_do_this: Option<String>,
+    #[serde(rename = "backgroundUrl", default)]
+    background_url: Option<String>,
 }
-#[derive(Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
 struct Project {
     email: String,
     title: String,
+    #[serde(default)]
     description: String,
+    // Unset for projects that aren't deployed anywhere
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(rename = "backgroundImage", default)]
+    background_image: Option<String>,
+    // Case-study fields, older projects only have the card fields above
+    #[serde(default)]
+    slug: Option<String>,
+    #[serde(default, deserialize_with = "content::blocks")]
+    content: Vec<content::ContentBlock>,
+    #[serde(default)]
+    role: Option<String>,
+    // ISO 8601 dates, endDate is unset for ongoing projects
+    #[serde(rename = "startDate", default)]
+    start_date: Option<String>,
+    #[serde(rename = "endDate", default)]
+    end_date: Option<String>,
+    #[serde(rename = "repoUrl", default)]
+    repo_url: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    category: Option<String>,
+    // Projects stored without a status read as active, but only match a status filter once it is set
+    #[serde(default)]
+    status: ProjectStatus,
+    // Names of the skills used, matching Skills.name
+    #[serde(rename = "techStack", default)]
+    tech_stack: Vec<String>,
+    // Featured projects come first, then ascending sortOrder, see default_project_sort
+    #[serde(default)]
+    featured: bool,
+    #[serde(rename = "sortOrder", default)]
+    sort_order: Option<i32>,
+    #[serde(default)]
+    screenshots: Vec<MediaAsset>,
+    // Locale of this variant, unset means the default locale
+    #[serde(default)]
+    locale: Option<String>,
+}
+// An uploaded image, referenced by its public URL
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+struct MediaAsset {
+    url: String,
+    #[serde(default)]
+    alt: String,
+    #[serde(default)]
+    caption: Option<String>,
+}
+#[graphql_object(context = Context)]
+impl Project {
+    fn email(&self) -> &str {
+        &self.email
+    }
+    fn title(&self) -> &str {
+        &self.title
+    }
+    fn description(&self) -> &str {
+        &self.description
+    }
+    fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+    fn background_image(&self) -> Option<&str> {
+        self.background_image.as_deref()
+    }
+    fn slug(&self) -> Option<&str> {
+        self.slug.as_deref()
+    }
+    fn content(&self) -> &[content::ContentBlock] {
+        &self.content
+    }
+    // Case studies are long-form like blog posts, so they get the same navigation aids
+    fn table_of_contents(&self) -> Vec<content::TocEntry> {
+        content::table_of_contents(&self.content)
+    }
+    fn footnotes(&self) -> Vec<content::Footnote> {
+        content::footnotes(&self.content)
+    }
+    fn role(&self) -> Option<&str> {
+        self.role.as_deref()
+    }
+    fn start_date(&self, timezone: Option<String>, format: Option<String>) -> Result<Option<String>, FieldError> {
+        self.start_date
+            .as_deref()
+            .map(|date| dates::format(date, timezone.as_deref(), format.as_deref()))
+            .transpose()
+    }
+    fn end_date(&self, timezone: Option<String>, format: Option<String>) -> Result<Option<String>, FieldError> {
+        self.end_date
+            .as_deref()
+            .map(|date| dates::format(date, timezone.as_deref(), format.as_deref()))
+            .transpose()
+    }
+    fn repo_url(&self) -> Option<&str> {
+        self.repo_url.as_deref()
+    }
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+    fn category(&self) -> Option<&str> {
+        self.category.as_deref()
+    }
+    fn status(&self) -> ProjectStatus {
+        self.status
+    }
+    fn featured(&self) -> bool {
+        self.featured
+    }
+    fn sort_order(&self) -> Option<i32> {
+        self.sort_order
+    }
+    fn screenshots(&self) -> &[MediaAsset] {
+        &self.screenshots
+    }
+    fn locale(&self) -> Option<&str> {
+        self.locale.as_deref()
+    }
+    // How many visitors liked this project
+    async fn likes(&self, context: &Context) -> Result<i32, FieldError> {
+        let collection = &config::collections().likes;
+        let filter = json!({ "email": self.email, "project": self.slug });
+        match context.store.count(collection, filter).await {
+            Ok(count) => Ok(count as i32),
+            Err(err) => {
+                error_reporting::capture_store_error(collection, err.as_ref());
+                Err(FieldError::new(
+                    "Failed to count likes",
+                    graphql_value!({ "details": err.to_string() }),
+                ))
+            }
+        }
+    }
+    // Skills of the owner named in the project's tech stack, in tech stack order
+    async fn tech_stack(&self, context: &Context) -> Result<Vec<Skills>, FieldError> {
+        let locales = context.locales(&self.email, self.locale.clone()).await?;
+        let collection = &config::collections().skills;
+        match get_localized_db(&*context.store, collection, &self.email, &locales).await {
+            Ok(values) => {
+                let mut skills: Vec<Skills> = context
+                    .decode_all(collection, values)?
+                    .into_iter()
+                    .filter(|skill: &Skills| self.tech_stack.contains(&skill.name))
+                    .collect();
+                skills.sort_by_key(|skill| self.tech_stack.iter().position(|name| *name == skill.name));
+                Ok(skills)
+            }
+            Err(err) => Err(FieldError::new(
+                "Failed to fetch skills",
+                graphql_value!({ "details": err.to_string() }),
+            )),
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, juniper::GraphQLEnum)]
+#[serde(rename_all = "lowercase")]
+enum ProjectStatus {
+    #[default]
+    Active,
+    Archived,
+    Wip,
+}
+// Filter for the projects of an owner, a tag matches projects listing it among their tags
+fn project_filter(
+    owner_email: &str,
+    tag: Option<String>,
+    category: Option<String>,
+    status: Option<ProjectStatus>,
+) -> Value {
+    let mut filter = json!({ "email": owner_email });
+    if let Some(tag) = tag {
+        filter["tags"] = json!([tag]);
+    }
+    if let Some(category) = category {
+        filter["category"] = json!(category);
+    }
+    if let Some(status) = status {
+        filter["status"] = json!(status);
+    }
+    filter
+}
+#[derive(Clone, Copy, Debug, juniper::GraphQLEnum)]
+pub enum OrderDirection {
+    Asc,
+    Desc,
+}
+impl From<OrderDirection> for store::SortDirection {
+    fn from(direction: OrderDirection) -> Self {
+        match direction {
+            OrderDirection::Asc => store::SortDirection::Ascending,
+            OrderDirection::Desc => store::SortDirection::Descending,
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, juniper::GraphQLEnum)]
+enum ProjectOrderField {
+    Title,
+}
+#[derive(Debug, juniper::GraphQLInputObject)]
+struct ProjectOrder {
+    field: ProjectOrderField,
+    #[graphql(default = OrderDirection::Asc)]
+    direction: OrderDirection,
+}
+// Order of the projects query unless orderBy is given: featured first, then by sortOrder
+fn project_sort(order_by: Option<ProjectOrder>) -> Vec<(String, store::SortDirection)> {
+    match order_by {
+        Some(order) => vec![order.sort()],
+        None => vec![
+            ("featured".to_string(), store::SortDirection::Descending),
+            ("sortOrder".to_string(), store::SortDirection::Ascending),
+        ],
+    }
+}
+impl ProjectOrder {
+    fn sort(&self) -> (String, store::SortDirection) {
+        let field = match self.field {
+            ProjectOrderField::Title => "title",
+        };
+        (field.to_string(), self.direction.into())
+    }
+}
+// Fields of a project set by createProject and updateProject, fields left out
+// are unchanged and an empty string unsets an optional field. Content blocks
+// are edited separately.
+#[derive(Debug, juniper::GraphQLInputObject)]
+struct ProjectInput {
+    title: Option<String>,
+    description: Option<String>,
+    url: Option<String>,
+    background_image: Option<String>,
+    role: Option<String>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    repo_url: Option<String>,
+    tags: Option<Vec<String>>,
+    category: Option<String>,
+    status: Option<ProjectStatus>,
+    tech_stack: Option<Vec<String>>,
+    featured: Option<bool>,
+    sort_order: Option<i32>,
+    // Replaces the whole screenshot gallery, see attachScreenshot to add one image
+    screenshots: Option<Vec<MediaAssetInput>>,
+}
+#[derive(Debug, juniper::GraphQLInputObject)]
+struct MediaAssetInput {
     url: String,
-    #[serde(rename = "backgroundImage")]
-    background_image: String,
+    alt: Option<String>,
+    caption: Option<String>,
+}
+impl ProjectInput {
+    fn validate(&self, input: &mut Validator) {
+        if let Some(title) = &self.title {
+            input.text("project.title", title, MAX_NAME_CHARS);
+        }
+        input.optional_text("project.description", self.description.as_deref(), validation::MAX_DESCRIPTION_CHARS);
+        input.optional_text("project.role", self.role.as_deref(), MAX_NAME_CHARS);
+        input.optional_text("project.category", self.category.as_deref(), MAX_NAME_CHARS);
+        let links = [
+            ("project.url", &self.url),
+            ("project.backgroundImage", &self.background_image),
+            ("project.repoUrl", &self.repo_url),
+        ];
+        for (field, link) in links {
+            if let Some(link) = link.as_deref().filter(|link| !link.is_empty()) {
+                input.url(field, link);
+            }
+        }
+        for (field, date) in [("project.startDate", &self.start_date), ("project.endDate", &self.end_date)] {
+            if let Some(date) = date.as_deref().filter(|date| !date.is_empty()) {
+                input.date(field, date);
+            }
+        }
+        for (field, names) in [("project.tags", &self.tags), ("project.techStack", &self.tech_stack)] {
+            if let Some(names) = names {
+                for name in names {
+                    input.text(field, name, MAX_NAME_CHARS);
+                }
+                input.distinct(field, names.iter().map(String::as_str));
+            }
+        }
+        if let Some(sort_order) = self.sort_order {
+            input.range("project.sortOrder", sort_order, 0..=i32::MAX);
+        }
+        if let Some(screenshots) = &self.screenshots {
+            for screenshot in screenshots {
+                input.url("project.screenshots.url", &screenshot.url);
+                input.optional_text("project.screenshots.alt", screenshot.alt.as_deref(), MAX_TEXT_CHARS);
+                input.optional_text("project.screenshots.caption", screenshot.caption.as_deref(), MAX_TEXT_CHARS);
+            }
+            input.distinct("project.screenshots.url", screenshots.iter().map(|screenshot| screenshot.url.as_str()));
+        }
+    }
+    // Set the given fields on `project`, returning them as stored for update_one
+    fn apply(self, project: &mut Project) -> serde_json::Map<String, Value> {
+        fn optional(value: String) -> Option<String> {
+            Some(value).filter(|value| !value.is_empty())
+        }
+        let mut changes = serde_json::Map::new();
+        if let Some(title) = self.title {
+            changes.insert("title".to_string(), json!(title));
+            project.title = title;
+        }
+        if let Some(description) = self.description {
+            changes.insert("description".to_string(), json!(description));
+            project.description = description;
+        }
+        let optional_fields = [
+            ("url", self.url, &mut project.url),
+            ("backgroundImage", self.background_image, &mut project.background_image),
+            ("role", self.role, &mut project.role),
+            ("startDate", self.start_date, &mut project.start_date),
+            ("endDate", self.end_date, &mut project.end_date),
+            ("repoUrl", self.repo_url, &mut project.repo_url),
+            ("category", self.category, &mut project.category),
+        ];
+        for (name, value, field) in optional_fields {
+            if let Some(value) = value {
+                *field = optional(value);
+                changes.insert(name.to_string(), json!(field));
+            }
+        }
+        if let Some(tags) = self.tags {
+            changes.insert("tags".to_string(), json!(tags));
+            project.tags = tags;
+        }
+        if let Some(status) = self.status {
+            changes.insert("status".to_string(), json!(status));
+            project.status = status;
+        }
+        if let Some(tech_stack) = self.tech_stack {
+            changes.insert("techStack".to_string(), json!(tech_stack));
+            project.tech_stack = tech_stack;
+        }
+        if let Some(featured) = self.featured {
+            changes.insert("featured".to_string(), json!(featured));
+            project.featured = featured;
+        }
+        if let Some(sort_order) = self.sort_order {
+            changes.insert("sortOrder".to_string(), json!(sort_order));
+            project.sort_order = Some(sort_order);
+        }
+        if let Some(screenshots) = self.screenshots {
+            project.screenshots = screenshots
+                .into_iter()
+                .map(|screenshot| MediaAsset {
+                    url: screenshot.url,
+                    alt: screenshot.alt.unwrap_or_default(),
+                    caption: screenshot.caption.and_then(optional),
+                })
+                .collect();
+            changes.insert("screenshots".to_string(), json!(project.screenshots));
+        }
+        changes
+    }
+}
+// A project ending before it started was most likely entered the wrong way round
+fn check_project_dates(input: &mut Validator, project: &Project) {
+    if let (Some(start), Some(end)) = (&project.start_date, &project.end_date) {
+        if end < start {
+            input.reject("project.endDate", "must not be before startDate");
+        }
+    }
+}
+// Filter matching one locale variant of a project, unset locales match the default variant
+fn project_variant(owner_email: &str, slug: &str, locale: Option<&str>) -> Value {
+    json!({ "email": owner_email, "slug": slug, "locale": locale })
+}
+// One page of projects along with the number of projects overall
+#[derive(Debug, juniper::GraphQLObject)]
+#[graphql(context = Context)]
+struct ProjectPage {
+    items: Vec<Project>,
+    // Documents of this page left out because they didn't match the model,
+    // they still count towards totalCount
+    errors: Vec<decode::DecodeWarning>,
+    total_count: i32,
+    limit: i32,
+    offset: i32,
+}
+#[derive(Debug, juniper::GraphQLObject)]
+#[graphql(context = Context)]
+struct BlogPostPage {
+    items: Vec<blog::BlogPost>,
+    // Documents of this page left out because they didn't match the model,
+    // they still count towards totalCount
+    errors: Vec<decode::DecodeWarning>,
+    total_count: i32,
+    limit: i32,
+    offset: i32,
 }
 #[derive(Debug, Deserialize, Serialize, juniper::GraphQLObject)]
 struct SkillsOverview {
     email: String,
     title: String,
-    icon: String,
+    #[serde(default)]
+    icon: Option<String>,
 }
-#[derive(Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+#[derive(Debug, Deserialize, Serialize)]
 struct Skills {
+    #[serde(default)]
+    email: String,
     name: String,
     mastery: i32,
     #[serde(rename = "skillType")]
     skill_type: String,
+    // Mastery snapshots, oldest first
+    #[serde(default)]
+    history: Vec<MasterySnapshot>,
+    #[serde(default)]
+    locale: Option<String>,
+}
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+struct MasterySnapshot {
+    // ISO 8601 date, e.g. 2024-03-01
+    date: String,
+    mastery: i32,
+}
+impl Skills {
+    // Add a mastery snapshot, one per date, a second one on the same day replaces
+    // the first. The skill's mastery follows the most recent snapshot.
+    fn record(&mut self, date: String, mastery: i32) {
+        self.history.retain(|snapshot| snapshot.date != date);
+        self.history.push(MasterySnapshot { date, mastery });
+        self.history.sort_by(|a, b| a.date.cmp(&b.date));
+        if let Some(latest) = self.history.last() {
+            self.mastery = latest.mastery;
+        }
+    }
+}
+// Kinds of skills the admin mutations accept, stored as the lowercase name.
+// Skills written before are read whatever their skillType.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, juniper::GraphQLEnum)]
+enum SkillType {
+    Frontend,
+    Backend,
+    Database,
+    Devops,
+    Mobile,
+    Language,
+    Tool,
+    Other,
+}
+impl SkillType {
+    fn as_str(self) -> &'static str {
+        match self {
+            SkillType::Frontend => "frontend",
+            SkillType::Backend => "backend",
+            SkillType::Database => "database",
+            SkillType::Devops => "devops",
+            SkillType::Mobile => "mobile",
+            SkillType::Language => "language",
+            SkillType::Tool => "tool",
+            SkillType::Other => "other",
+        }
+    }
+}
+#[graphql_object(context = Context)]
+impl Skills {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn mastery(&self) -> i32 {
+        self.mastery
+    }
+    fn skill_type(&self) -> &str {
+        &self.skill_type
+    }
+    fn history(&self) -> &[MasterySnapshot] {
+        &self.history
+    }
+    fn locale(&self) -> Option<&str> {
+        self.locale.as_deref()
+    }
+    // How many visitors endorsed this skill
+    async fn endorsements(&self, context: &Context) -> Result<i32, FieldError> {
+        let collection = &config::collections().endorsements;
+        let filter = json!({ "email": self.email, "skill": self.name });
+        match context.store.count(collection, filter).await {
+            Ok(count) => Ok(count as i32),
+            Err(err) => {
+                error_reporting::capture_store_error(collection, err.as_ref());
+                Err(FieldError::new(
+                    "Failed to count endorsements",
+                    graphql_value!({ "details": err.to_string() }),
+                ))
+            }
+        }
+    }
+    // Projects of the owner listing this skill in their tech stack
+    async fn projects(&self, context: &Context) -> Result<Vec<Project>, FieldError> {
+        let filter = json!({ "email": self.email, "techStack": [self.name] });
+        let options = store::FindOptions {
+            sort: project_sort(None),
+            ..Default::default()
+        };
+        let locales = context.locales(&self.email, self.locale.clone()).await?;
+        let collection = &config::collections().projects;
+        match localized_page_db(&*context.store, collection, filter, options, &locales).await {
+            Ok((values, _)) => {
+                let projects: Vec<Project> = context.decode_all(collection, values)?;
+                Ok(projects)
+            }
+            Err(err) => Err(FieldError::new(
+                "Failed to fetch projects",
+                graphql_value!({ "details": err.to_string() }),
+            )),
+        }
+    }
+}
+// Most viewed content for the popular sidebar
+#[derive(Debug, juniper::GraphQLObject)]
+#[graphql(context = Context)]
+struct PopularContent {
+    blog_posts: Vec<PopularBlogPost>,
+    projects: Vec<PopularProject>,
+}
+#[derive(Debug, juniper::GraphQLObject)]
+#[graphql(context = Context)]
+struct PopularBlogPost {
+    post: blog::BlogPost,
+    views: i32,
+}
+#[derive(Debug, juniper::GraphQLObject)]
+#[graphql(context = Context)]
+struct PopularProject {
+    project: Project,
+    views: i32,
+}
+// Everything the admin dashboard shows, in one request
+#[derive(Debug, juniper::GraphQLObject)]
+#[graphql(context = Context)]
+struct Dashboard {
+    views: i32,
+    // Distinct daily visitors, a visitor returning on another day counts again
+    visitors: i32,
+    top_posts: Vec<PopularBlogPost>,
+    top_referrers: Vec<analytics::TrafficSource>,
+}
+// Skills sharing a skillType, strongest first
+#[derive(Debug, juniper::GraphQLObject)]
+#[graphql(context = Context)]
+struct SkillGroup {
+    skill_type: String,
+    skills: Vec<Skills>,
+}
+// A service the owner offers, priced in the minor unit of its currency
+#[derive(Debug, Deserialize, Serialize)]
+struct Service {
+    email: String,
+    title: String,
+    #[serde(default)]
+    description: String,
+    // e.g. 150000 with currency PHP for ₱1,500.00
+    amount: i64,
+    // ISO 4217 code
+    currency: String,
+    #[serde(default)]
+    locale: Option<String>,
+}
+#[graphql_object(context = Context)]
+impl Service {
+    fn title(&self) -> &str {
+        &self.title
+    }
+    fn description(&self) -> &str {
+        &self.description
+    }
+    // Amount in major units, e.g. 1500.0
+    fn amount(&self) -> f64 {
+        self.amount as f64 / 10_f64.powi(money::minor_digits(&self.currency) as i32)
+    }
+    fn currency(&self) -> &str {
+        &self.currency
+    }
+    // Price formatted for `locale`, converted to `currency` at the current exchange rate when given
+    async fn formatted_price(
+        &self,
+        context: &Context,
+        locale: Option<String>,
+        currency: Option<String>,
+    ) -> Result<String, FieldError> {
+        let locales = context.locales(&self.email, locale).await?;
+        let currency = currency.map(|currency| currency.to_ascii_uppercase());
+        let currency = currency.as_deref().unwrap_or(&self.currency);
+        let amount = money::convert(self.amount, &self.currency, currency)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to convert price",
+                graphql_value!({ "details": err.to_string() }),
+            ))?;
+        Ok(money::format(amount, currency, &locales[0]))
+    }
 }
 #[derive(Debug, Deserialize, Serialize, juniper::GraphQLObject)]
 struct SocialMedia {
@@ -71,8 +837,40 @@ struct SocialMedia {
 #[derive(Debug, Deserialize, Serialize, juniper::GraphQLObject)]
 struct SoftSkills {
     name: String,
+    #[serde(default)]
     description: String,
-    icon: String,
+    #[serde(default)]
+    icon: Option<String>,
+    // Position in the list, soft skills without one come last
+    #[serde(default)]
+    order: Option<i32>,
+    #[serde(default)]
+    category: Option<String>,
+}
+// Soft skills sharing a category, in list order
+#[derive(Debug, juniper::GraphQLObject)]
+struct SoftSkillGroup {
+    category: Option<String>,
+    soft_skills: Vec<SoftSkills>,
+}
+// Fetch the soft skills of an owner sorted by their order field, ties keep insertion order
+async fn ordered_soft_skills(
+    context: &Context,
+    owner_email: &str,
+    locales: &[String],
+) -> Result<Vec<SoftSkills>, FieldError> {
+    let collection = &config::collections().soft_skills;
+    match get_localized_db(&*context.store, collection, owner_email, locales).await {
+        Ok(values) => {
+            let mut softskills: Vec<SoftSkills> = context.decode_all(collection, values)?;
+            softskills.sort_by_key(|softskill| (softskill.order.is_none(), softskill.order));
+            Ok(softskills)
+        }
+        Err(err) => Err(FieldError::new(
+            "Failed to fetch soft skills",
+            graphql_value!({ "details": err.to_string() }),
+        )),
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize, juniper::GraphQLObject)]
@@ -80,22 +878,160 @@ struct User {
     email: String,
     #[serde(rename = "fullName")]
     full_name: String,
-    #[serde(rename = "contactNumber")]
-    contact_number: String,
-    website: String,
+    #[serde(rename = "contactNumber", default)]
+    contact_number: Option<String>,
+    #[serde(default)]
+    website: Option<String>,
 }
 #[derive(Clone, Copy, Debug)]
 pub struct Query;
 
 #[graphql_object(context = Context)]
 impl Query {
+    // Tenant resolved from the Host header, if any
+    fn tenant(context: &Context) -> Option<tenant::Tenant> {
+        context.tenant.clone()
+    }
+    // Feature flags evaluated for a visitor. visitorId is any id the client keeps stable,
+    // without one the anonymous request visitor is used.
+    async fn flags(
+        context: &Context,
+        owner: Option<String>,
+        visitor_id: Option<String>,
+    ) -> Result<Vec<flags::FlagValue>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let visitor_id = visitor_id.unwrap_or_else(|| context.visitor.hash("flags"));
+        let collection = &config::collections().feature_flags;
+        match get_data_db(&*context.store, collection, &owner_email).await {
+            Ok(values) => Ok(context
+                .decode_all::<flags::FeatureFlag, _>(collection, values)?
+                .into_iter()
+                .map(|flag| flag.evaluate(&visitor_id))
+                .collect()),
+            Err(err) => Err(FieldError::new(
+                "Failed to fetch feature flags",
+                graphql_value!({ "details": err.to_string() }),
+            )),
+        }
+    }
+    // Resolver function to fetch the visible items of a menu, e.g. header or footer, in order
+    async fn navigation(
+        context: &Context,
+        owner: Option<String>,
+        menu: String,
+    ) -> Result<Vec<navigation::NavigationItem>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let options = store::FindOptions {
+            sort: vec![("order".to_string(), store::SortDirection::Ascending)],
+            ..Default::default()
+        };
+        let filter = json!({ "email": owner_email, "menu": menu });
+        let collection = &config::collections().navigation;
+        match get_page_db(&*context.store, collection, filter, options).await {
+            Ok(values) => Ok(context
+                .decode_all::<navigation::NavigationItem, _>(collection, values)?
+                .into_iter()
+                .filter(|item| item.visibility != navigation::Visibility::Hidden)
+                .collect()),
+            Err(err) => Err(FieldError::new(
+                "Failed to fetch navigation",
+                graphql_value!({ "details": err.to_string() }),
+            )),
+        }
+    }
+    // Resolver function to fetch redirect rules, for frontends and edge workers to apply
+    async fn redirects(context: &Context, owner: Option<String>) -> Result<Vec<redirects::Redirect>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let collection = &config::collections().redirects;
+        match get_data_db(&*context.store, collection, &owner_email).await {
+            Ok(values) => context.decode_all(collection, values),
+            Err(err) => Err(FieldError::new(
+                "Failed to fetch redirects",
+                graphql_value!({ "details": err.to_string() }),
+            )),
+        }
+    }
+    // Resolver function to fetch the content of the frontend's 404 page
+    async fn not_found_content(context: &Context, owner: Option<String>) -> Result<not_found::NotFoundContent, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        not_found::content(&*context.store, &owner_email)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to fetch not found content",
+                graphql_value!({ "details": err.to_string() }),
+            ))
+    }
+    // Version of the owner's content, raised by every change to it
+    async fn content_version(context: &Context, owner: Option<String>) -> Result<String, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        match store::content_version(&*context.store, &owner_email).await {
+            Ok(version) => Ok(version.to_string()),
+            Err(err) => Err(FieldError::new(
+                "Failed to fetch content version",
+                graphql_value!({ "details": err.to_string() }),
+            )),
+        }
+    }
+    // Resolver function to fetch the palette, fonts and layout flags of the frontend
+    async fn theme(context: &Context, owner: Option<String>) -> Result<theme::Theme, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        theme::load(&*context.store, &owner_email)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to fetch theme",
+                graphql_value!({ "details": err.to_string() }),
+            ))
+    }
+    // The "book a call" section of the site settings, None until it is set up
+    async fn booking_settings(context: &Context, owner: Option<String>) -> Result<Option<booking::BookingSettings>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        booking::load(&*context.store, &owner_email)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to fetch booking settings",
+                graphql_value!({ "details": err.to_string() }),
+            ))
+    }
+    // Open slots of the booking section's event type over the next days, at
+    // most a week. Empty without a booking section or event type.
+    async fn availability(
+        context: &Context,
+        owner: Option<String>,
+        #[graphql(default = booking::MAX_DAYS)] days: i32,
+    ) -> Result<Vec<booking::AvailabilitySlot>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        input.range("days", days, 1..=booking::MAX_DAYS);
+        input.finish()?;
+        let settings = booking::load(&*context.store, &owner_email)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to fetch booking settings",
+                graphql_value!({ "details": err.to_string() }),
+            ))?;
+        let Some(settings) = settings else {
+            return Ok(Vec::new());
+        };
+        booking::availability(&settings, days).await.map_err(|err| FieldError::new(
+            "Failed to fetch availability",
+            graphql_value!({ "details": err.to_string() }),
+        ))
+    }
     // Resolver function to fetch introductions
-    async fn introductions() -> Result<Vec<Introduction>, FieldError> {
-        match get_data_db(String::from("introductions")).await {
+    async fn introductions(
+        context: &Context,
+        owner: Option<String>,
+        lang: Option<String>,
+    ) -> Result<Vec<Introduction>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let locales = context.locales(&owner_email, lang).await?;
+        let collection = &config::collections().introductions;
+        match get_localized_db(&*context.store, collection, &owner_email, &locales).await {
             Ok(values) => {
-                let introductions: Vec<Introduction> = values
+                let introductions: Vec<Introduction> = context
+                    .decode_all(collection, values)?
                     .into_iter()
-                    .filter_map(|value| value_to_type(value).ok())
+                    .filter(|introduction: &Introduction| introduction.experiment_key.is_none())
                     .collect();
                 Ok(introductions)
             }
@@ -105,14 +1041,54 @@ impl Query {
             )),
         }
     }
+    // Resolver function to pick the introduction variant of an experiment for a visitor.
+    // visitorId is any id the client keeps stable, e.g. a random value in localStorage.
+    async fn introduction_for(
+        context: &Context,
+        owner: Option<String>,
+        experiment_key: String,
+        visitor_id: String,
+    ) -> Result<Option<Introduction>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let variants = experiment_variants(context, &owner_email, &experiment_key).await?;
+        if variants.is_empty() {
+            return Ok(None);
+        }
+        let index = experiments::assign(&experiment_key, &visitor_id, variants.len());
+        Ok(variants.into_iter().nth(index))
+    }
+    // Exposures and conversions per variant of an experiment. Admin endpoint only.
+    async fn experiment_results(
+        context: &Context,
+        owner: Option<String>,
+        experiment_key: String,
+    ) -> Result<Vec<experiments::VariantResult>, FieldError> {
+        context.require_admin()?;
+        let owner_email = context.owner_email(owner)?;
+        let variants: Vec<String> = experiment_variants(context, &owner_email, &experiment_key)
+            .await?
+            .into_iter()
+            .filter_map(|introduction| introduction.variant)
+            .collect();
+        experiments::results(&*context.store, &owner_email, &experiment_key, &variants)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to fetch experiment results",
+                graphql_value!({ "details": err.to_string() }),
+            ))
+    }
     // Resolver function to fetch personals
-    async fn personals() -> Result<Vec<Personal>, FieldError> {
-        match get_data_db(String::from("personals")).await {
+    async fn personals(
+        context: &Context,
+        owner: Option<String>,
+        lang: Option<String>,
+    ) -> Result<Vec<Personal>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let locales = context.locales(&owner_email, lang).await?;
+        let collection = &config::collections().personals;
+        match get_localized_db(&*context.store, collection, &owner_email, &locales).await {
             Ok(values) => {
-                let personals: Vec<Personal> = values
-                    .into_iter()
-                    .filter_map(|value| value_to_type(value).ok())
-                    .collect();
+                let personals: Vec<Personal> = context.decode_all(collection, values)?;
                 Ok(personals)
             }
             Err(err) => Err(FieldError::new(
@@ -121,222 +1097,2565 @@ impl Query {
             )),
         }
     }
-    // Resolver function to fetch projects
-    async fn projects() -> Result<Vec<Project>, FieldError> {
-        match get_data_db(String::from("projects")).await {
-            Ok(values) => {
-                let projects: Vec<Project> = values
-                    .into_iter()
-                    .filter_map(|value| value_to_type(value).ok())
-                    .collect();
+    // Resolver function to fetch projects, optionally a slice of them
+    async fn projects(
+        context: &Context,
+        owner: Option<String>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        order_by: Option<ProjectOrder>,
+        tag: Option<String>,
+        category: Option<String>,
+        status: Option<ProjectStatus>,
+        lang: Option<String>,
+    ) -> Result<Vec<Project>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let locales = context.locales(&owner_email, lang).await?;
+        let options = find_options(limit, offset, project_sort(order_by))?;
+        let filter = project_filter(&owner_email, tag, category, status);
+        let collection = &config::collections().projects;
+        match localized_page_db(&*context.store, collection, filter, options, &locales).await {
+            Ok((values, _)) => {
+                let projects: Vec<Project> = context.decode_all(collection, values)?;
                 Ok(projects)
             }
             Err(err) => Err(FieldError::new(
-                "Failed to fetch personals",
+                "Failed to fetch projects",
                 graphql_value!({ "details": err.to_string() }),
             )),
         }
     }
-    // Resolver function to fetch skills overview
-    async fn skills_overview() -> Result<Vec<SkillsOverview>, FieldError> {
-        match get_data_db(String::from("skillsoverview")).await {
-            Ok(values) => {
-                let skills_overview: Vec<SkillsOverview> = values
-                    .into_iter()
-                    .filter_map(|value| value_to_type(value).ok())
-                    .collect();
-                Ok(skills_overview)
-            }
+    // Resolver function to fetch a single project by its slug, for case-study pages
+    async fn project(
+        context: &Context,
+        owner: Option<String>,
+        slug: String,
+        lang: Option<String>,
+    ) -> Result<Option<Project>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let locales = context.locales(&owner_email, lang).await?;
+        let filter = json!({ "email": owner_email, "slug": slug });
+        let collection = &config::collections().projects;
+        let options = store::FindOptions::default();
+        match localized_page_db(&*context.store, collection, filter, options, &locales).await {
+            Ok((values, _)) => context.decode_one(collection, values.into_iter().next()),
             Err(err) => Err(FieldError::new(
-                "Failed to fetch skills overview",
+                "Failed to fetch project",
                 graphql_value!({ "details": err.to_string() }),
             )),
         }
     }
-    // Resolver function to fetch skills
-    async fn skills() -> Result<Vec<Skills>, FieldError> {
-        match get_data_db(String::from("skills")).await {
-            Ok(values) => {
-                let skills: Vec<Skills> = values
-                    .into_iter()
-                    .filter_map(|value| value_to_type(value).ok())
-                    .collect();
-                Ok(skills)
+    // Resolver function to fetch a page of projects with the total count, for page number pagination
+    async fn projects_page(
+        context: &Context,
+        owner: Option<String>,
+        #[graphql(default = DEFAULT_PAGE_SIZE)] limit: i32,
+        #[graphql(default = 0)] offset: i32,
+        order_by: Option<ProjectOrder>,
+        tag: Option<String>,
+        category: Option<String>,
+        status: Option<ProjectStatus>,
+        lang: Option<String>,
+    ) -> Result<ProjectPage, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let locales = context.locales(&owner_email, lang).await?;
+        let options = find_options(Some(limit), Some(offset), project_sort(order_by))?;
+        let filter = project_filter(&owner_email, tag, category, status);
+        let collection = &config::collections().projects;
+        match localized_page_db(&*context.store, collection, filter, options.clone(), &locales).await {
+            Ok((values, total_count)) => {
+                let (items, errors) = context.decode_reporting(collection, values)?;
+                Ok(ProjectPage {
+                    items,
+                    errors,
+                    total_count: total_count as i32,
+                    limit: options.limit.unwrap_or_default() as i32,
+                    offset,
+                })
             }
             Err(err) => Err(FieldError::new(
-                "Failed to fetch skills",
+                "Failed to fetch projects",
                 graphql_value!({ "details": err.to_string() }),
             )),
         }
     }
-    async fn social_media() -> Result<Vec<SocialMedia>, FieldError> {
-        match get_data_db(String::from("socialmedias")).await {
-            Ok(values) => {
-                let socialmedias: Vec<SocialMedia> = values
-                    .into_iter()
-                    .filter_map(|value| value_to_type(value).ok())
-                    .collect();
-                Ok(socialmedias)
+    // Resolver function to fetch published blog posts, optionally a slice of them
+    async fn blog_posts(
+        context: &Context,
+        owner: Option<String>,
+        limit: Option<i32>,
+        offset: Option<i32>,
+        order_by: Option<blog::BlogPostOrder>,
+        lang: Option<String>,
+    ) -> Result<Vec<blog::BlogPost>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let locales = context.locales(&owner_email, lang).await?;
+        let options = find_options(limit, offset, order_by.map(|order| order.sort()).into_iter().collect())?;
+        let filter = blog::published_filter(&owner_email);
+        let collection = &config::collections().blog_posts;
+        match localized_page_db(&*context.store, collection, filter, options, &locales).await {
+            Ok((values, _)) => {
+                let posts: Vec<blog::BlogPost> = context.decode_all(collection, values)?;
+                Ok(posts)
             }
             Err(err) => Err(FieldError::new(
-                "Failed to fetch social medias",
+                "Failed to fetch blog posts",
                 graphql_value!({ "details": err.to_string() }),
             )),
         }
     }
-    async fn soft_skills() -> Result<Vec<SoftSkills>, FieldError> {
-        match get_data_db(String::from("softskills")).await {
-            Ok(values) => {
-                let softskills: Vec<SoftSkills> = values
-                    .into_iter()
-                    .filter_map(|value| value_to_type(value).ok())
-                    .collect();
-                Ok(softskills)
+    // Resolver function to fetch a page of published blog posts with the total count
+    async fn blog_posts_page(
+        context: &Context,
+        owner: Option<String>,
+        #[graphql(default = DEFAULT_PAGE_SIZE)] limit: i32,
+        #[graphql(default = 0)] offset: i32,
+        order_by: Option<blog::BlogPostOrder>,
+        lang: Option<String>,
+    ) -> Result<BlogPostPage, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let locales = context.locales(&owner_email, lang).await?;
+        let options = find_options(Some(limit), Some(offset), order_by.map(|order| order.sort()).into_iter().collect())?;
+        let filter = blog::published_filter(&owner_email);
+        let collection = &config::collections().blog_posts;
+        match localized_page_db(&*context.store, collection, filter, options.clone(), &locales).await {
+            Ok((values, total_count)) => {
+                let (items, errors) = context.decode_reporting(collection, values)?;
+                Ok(BlogPostPage {
+                    items,
+                    errors,
+                    total_count: total_count as i32,
+                    limit: options.limit.unwrap_or_default() as i32,
+                    offset,
+                })
             }
             Err(err) => Err(FieldError::new(
-                "Failed to fetch soft skills",
+                "Failed to fetch blog posts",
                 graphql_value!({ "details": err.to_string() }),
             )),
         }
     }
-    async fn users() -> Result<Vec<User>, FieldError> {
-        match get_data_db(String::from("users")).await {
-            Ok(values) => {
-                let user: Vec<User> = values
-                    .into_iter()
-                    .filter_map(|value| value_to_type(value).ok())
-                    .collect();
-                Ok(user)
+    // Resolver function to fetch published blog posts grouped by year and month, newest first
+    async fn blog_archive(
+        context: &Context,
+        owner: Option<String>,
+        lang: Option<String>,
+    ) -> Result<Vec<blog::ArchiveMonth>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let locales = context.locales(&owner_email, lang).await?;
+        let options = store::FindOptions {
+            sort: vec![("publishedAt".to_string(), store::SortDirection::Descending)],
+            ..Default::default()
+        };
+        let filter = blog::published_filter(&owner_email);
+        let collection = &config::collections().blog_posts;
+        match localized_page_db(&*context.store, collection, filter, options, &locales).await {
+            Ok((values, _)) => {
+                let posts: Vec<blog::BlogPost> = context.decode_all(collection, values)?;
+                Ok(blog::archive(posts))
             }
             Err(err) => Err(FieldError::new(
-                "Failed to fetch user",
+                "Failed to fetch blog archive",
                 graphql_value!({ "details": err.to_string() }),
             )),
         }
     }
-}
-
-#[tokio::main]
-async fn main() {
-    // Load the .env file
-    dotenv().ok();
-    let cors = CorsLayer::new()
-        .allow_methods(vec![Method::GET, Method::POST])
-        .allow_origin(Any)
-        .allow_headers(vec![http::header::CONTENT_TYPE]);
-    let schema = Schema::new(
-        Query,
-        EmptyMutation::<Context>::new(),
-        EmptySubscription::<Context>::new()
-     );
-    // build our application with a route
-    let app = Router::new()
-        .route("/", get(root))
-        // .route("/introduction", post(create_introduction))
-        // .route("/:collection_name", get(get_handler))
-        .route("/graphql", post(graphql::<Arc<Schema>>))
-        .layer(CorsLayer::permissive())
-        .layer(cors)
-        .layer(Extension(Arc::new(schema)));
-    let axum_address = env::var("AXUM_ADDRESS").expect("AXUM_ADDRESS must be set");
-    let app_port = env::var("PORT").expect("PORT must be set");
-    let axum_listener_address = format!("{}:{}", axum_address, app_port);
-    let listener = TcpListener::bind(&axum_listener_address).await.expect("Failed to bind to address");
-    axum::serve(listener, app).await.unwrap();
-}
-
-// basic handler that responds with a static string
-async fn root() -> &'static str {
-    "Hello, JM AAcera man!"
-}
-
-async fn connect_to_database() -> Result<Database, mongodb::error::Error> {
-    // Create a new MongoConnection instance
-    let connection_result = MongoConnection::new().await;
-    match connection_result {
-        Ok(connection) => {
-            // Connection successful
-            println!("Connected to MongoDB");
-            // Example usage: Get a handle to a database
-            let db = connection.db("personal");
-            Ok(db)
+    // Resolver function to fetch the most viewed published posts and projects in a time window
+    async fn popular_content(
+        context: &Context,
+        owner: Option<String>,
+        #[graphql(default = analytics::PopularWindow::Month)] window: analytics::PopularWindow,
+        #[graphql(default = 5)] limit: i32,
+        lang: Option<String>,
+    ) -> Result<PopularContent, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let limit = limit.clamp(0, MAX_PAGE_SIZE) as usize;
+        let collections = config::collections();
+        let locales = context.locales(&owner_email, lang).await?;
+        let popular = async {
+            let since = analytics::window_start(window.days())?;
+            let views = rollup::window(&*context.store, &owner_email, since.as_deref()).await?;
+            let slugs = analytics::views_by_slug(&views.paths, &analytics::blog_path_prefix());
+            let filter = blog::published_filter(&owner_email);
+            let posts = popular_by_slug(context, &collections.blog_posts, filter, slugs, &locales, limit).await?;
+            let slugs = analytics::views_by_slug(&views.paths, &analytics::project_path_prefix());
+            let filter = json!({ "email": owner_email });
+            let projects = popular_by_slug(context, &collections.projects, filter, slugs, &locales, limit).await?;
+            Ok::<_, store::StoreError>((posts, projects))
+        };
+        let (posts, projects) = popular.await.map_err(|err| {
+            error_reporting::capture_store_error(&collections.analytics, err.as_ref());
+            FieldError::new(
+                "Failed to fetch popular content",
+                graphql_value!({ "details": err.to_string() }),
+            )
+        })?;
+        let mut blog_posts = Vec::new();
+        for (post, views) in posts {
+            if let Some(post) = context.decode_all(&collections.blog_posts, vec![post])?.pop() {
+                blog_posts.push(PopularBlogPost { post, views });
+            }
         }
-        Err(e) => {
-            // Handle connection error
-            eprintln!("Error connecting to MongoDB: {}", e);
-            Err(e)
+        let mut popular_projects = Vec::new();
+        for (project, views) in projects {
+            if let Some(project) = context.decode_all(&collections.projects, vec![project])?.pop() {
+                popular_projects.push(PopularProject { project, views });
+            }
         }
+        Ok(PopularContent {
+            blog_posts,
+            projects: popular_projects,
+        })
     }
-}
-
-async fn find_all(db: &Database, collection_name: &str) -> Result<Vec<Value>, Error> {
-    let collection: Collection<Document> = db.collection(collection_name);
-    // Construct the filter document to match the email field
-    let user_email = env::var("USER_EMAIL")
-            .unwrap_or_else(|_| {
-                println!("USER_EMAIL is not set, using default value");
-                "default_value".to_string()
-            });
-    let filter = bson::doc! { "email": user_email };
-    let mut cursor = collection.find(filter, None).await?;
-    let mut documents = Vec::new();
-
-    while let true = cursor.advance().await? {
-        let doc = cursor.deserialize_current();
-        match doc {
-            Ok(document) => {
-                // Here, document is of type Document
-                // Convert the BSON Document into a serde_json::Value
-                let doc_as_json = bson::Bson::Document(document).into();
-                
-                documents.push(doc_as_json);
-            },
-            Err(e) => eprintln!("Error deserializing document: {}", e),
-        }
+    // Totals for the admin dashboard over the last `days` days, or all time. Admin endpoint only.
+    async fn dashboard(
+        context: &Context,
+        owner: Option<String>,
+        #[graphql(default = 30)] days: i32,
+        #[graphql(default = 5)] limit: i32,
+    ) -> Result<Dashboard, FieldError> {
+        context.require_admin()?;
+        let owner_email = context.owner_email(owner)?;
+        let limit = limit.clamp(0, MAX_PAGE_SIZE) as usize;
+        let collections = config::collections();
+        let fetch = async {
+            let since = analytics::window_start(Some(days))?;
+            let views = rollup::window(&*context.store, &owner_email, since.as_deref()).await?;
+            let posts = context.store.find(&collections.blog_posts, blog::published_filter(&owner_email)).await?;
+            Ok::<_, store::StoreError>((views, posts))
+        };
+        let (views, posts) = fetch.await.map_err(|err| {
+            error_reporting::capture_store_error(&collections.analytics, err.as_ref());
+            FieldError::new(
+                "Failed to fetch dashboard",
+                graphql_value!({ "details": err.to_string() }),
+            )
+        })?;
+        let mut top_referrers = views.sources;
+        top_referrers.retain(|source| source.source != "direct");
+        top_referrers.truncate(limit);
+        let posts = context.decode_all(&collections.blog_posts, posts)?;
+        Ok(Dashboard {
+            views: views.views,
+            visitors: views.visitors,
+            top_posts: popular_blog_posts(&views.paths, posts, limit),
+            top_referrers,
+        })
     }
-    Ok(documents)
-}
-
-fn value_to_type<T>(value: Value) -> Result<T, Box<dyn StdError>>
-where
+    // Decode each of the owner's documents against its model and summarize what
+    // doesn't match, e.g. after editing documents by hand. Admin endpoint only.
+    async fn data_check(
+        context: &Context,
+        owner: Option<String>,
+    ) -> Result<Vec<data_check::CollectionReport>, FieldError> {
+        context.require_admin()?;
+        let owner_email = context.owner_email(owner)?;
+        data_check::run(&*context.store, Some(&owner_email)).await.map_err(|err| {
+            FieldError::new(
+                "Failed to check documents",
+                graphql_value!({ "details": err.to_string() }),
+            )
+        })
+    }
+    // Blog posts of one month (e.g. 2024-03) by day for the editorial calendar:
+    // published posts on their publication day, drafts and posts in review on
+    // the day they were last changed. Days are taken in `timezone` when given,
+    // UTC otherwise. Admin endpoint only.
+    async fn content_calendar(
+        context: &Context,
+        owner: Option<String>,
+        month: String,
+        timezone: Option<String>,
+    ) -> Result<Vec<calendar::CalendarDay>, FieldError> {
+        context.require_admin()?;
+        let owner_email = context.owner_email(owner)?;
+        let first_day = calendar::parse_month(&month)?;
+        let timezone = dates::parse_timezone(timezone.as_deref())?;
+        let collection = &config::collections().blog_posts;
+        let posts = context
+            .store
+            .find(collection, json!({ "email": owner_email }))
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to fetch blog posts",
+                graphql_value!({ "details": err.to_string() }),
+            ))?;
+        let posts = context.decode_all(collection, posts)?;
+        Ok(calendar::month(posts, first_day, timezone))
+    }
+    // Constraints the owner's content is checked against when written, see
+    // rules::ContentRule. Admin endpoint only.
+    async fn content_rules(context: &Context, owner: Option<String>) -> Result<Vec<rules::ContentRule>, FieldError> {
+        context.require_admin()?;
+        let owner_email = context.owner_email(owner)?;
+        rules::load(&*context.store, &owner_email)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to fetch content rules",
+                graphql_value!({ "details": err.to_string() }),
+            ))
+    }
+    // Published posts, projects, talks and uses items of the owner matching
+    // `query`, most relevant first, answered by the search provider picked with
+    // SEARCH_ENGINE. Each hit's item is the document itself, for results of
+    // every kind in one search box.
+    async fn search(
+        context: &Context,
+        owner: Option<String>,
+        query: String,
+        #[graphql(default = 10)] limit: i32,
+    ) -> Result<Vec<search::SearchHit>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        input.text("query", &query, MAX_TEXT_CHARS);
+        input.range("limit", limit, 1..=100);
+        input.finish()?;
+        search::provider()
+            .search(&*context.store, &owner_email, &query, limit as usize)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to search",
+                graphql_value!({ "details": err.to_string() }),
+            ))
+    }
+    // Uploaded files in the media library, of one tag when given, most recently
+    // uploaded first. Admin endpoint only.
+    async fn media(
+        context: &Context,
+        owner: Option<String>,
+        tag: Option<String>,
+    ) -> Result<Vec<media::MediaFile>, FieldError> {
+        context.require_admin()?;
+        let owner_email = context.owner_email(owner)?;
+        media::list(&*context.store, &owner_email, tag)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to fetch media",
+                graphql_value!({ "details": err.to_string() }),
+            ))
+    }
+    // Documents deleted through the admin API, of one collection when given,
+    // most recently deleted first. Admin endpoint only.
+    async fn trash(
+        context: &Context,
+        owner: Option<String>,
+        collection: Option<String>,
+    ) -> Result<Vec<trash::TrashedDocument>, FieldError> {
+        context.require_admin()?;
+        let owner_email = context.owner_email(owner)?;
+        trash::list(&*context.store, &owner_email, collection.as_deref())
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to fetch trash",
+                graphql_value!({ "details": err.to_string() }),
+            ))
+    }
+    // Occurrences of a named event in a time window. Admin endpoint only.
+    async fn event_counts(
+        context: &Context,
+        owner: Option<String>,
+        name: String,
+        #[graphql(default = analytics::PopularWindow::Month)] window: analytics::PopularWindow,
+    ) -> Result<analytics::EventCounts, FieldError> {
+        context.require_admin()?;
+        let owner_email = context.owner_email(owner)?;
+        let counts = async {
+            let since = analytics::window_start(window.days())?;
+            analytics::event_counts(&*context.store, &owner_email, &name, since.as_deref()).await
+        };
+        counts.await.map_err(|err| {
+            error_reporting::capture_store_error(&config::collections().events, err.as_ref());
+            FieldError::new(
+                "Failed to count events",
+                graphql_value!({ "details": err.to_string() }),
+            )
+        })
+    }
+    // Daily analytics summaries over the last `days` days, oldest first. Admin endpoint only.
+    async fn analytics_summaries(
+        context: &Context,
+        owner: Option<String>,
+        #[graphql(default = 30)] days: i32,
+    ) -> Result<Vec<rollup::DailySummary>, FieldError> {
+        context.require_admin()?;
+        let owner_email = context.owner_email(owner)?;
+        let collection = &config::collections().analytics_daily;
+        let summaries = async {
+            let since = analytics::window_start(Some(days))?;
+            let options = store::FindOptions {
+                sort: vec![("date".to_string(), store::SortDirection::Ascending)],
+                ..Default::default()
+            };
+            let values = get_page_db(&*context.store, collection, json!({ "email": owner_email }), options).await?;
+            Ok::<_, store::StoreError>((values, since))
+        };
+        match summaries.await {
+            Ok((values, since)) => Ok(context
+                .decode_all::<rollup::DailySummary, _>(collection, values)?
+                .into_iter()
+                .filter(|summary| since.as_deref().map_or(true, |since| summary.date.as_str() >= since))
+                .collect()),
+            Err(err) => Err(FieldError::new(
+                "Failed to fetch analytics summaries",
+                graphql_value!({ "details": err.to_string() }),
+            )),
+        }
+    }
+    // Locales the owner publishes in with how much of the content is translated to each
+    async fn available_locales(context: &Context, owner: Option<String>) -> Result<Vec<i18n::LocaleInfo>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let settings = context.locale_settings(&owner_email).await?;
+        match i18n::translation_groups(&*context.store, &owner_email, &settings.default_locale()).await {
+            Ok(groups) => Ok(i18n::available_locales(&settings, &groups)),
+            Err(err) => Err(FieldError::new(
+                "Failed to fetch locales",
+                graphql_value!({ "details": err.to_string() }),
+            )),
+        }
+    }
+    // Documents lacking a variant in `locale`, or in any locale of the owner's settings. Admin endpoint only.
+    async fn missing_translations(
+        context: &Context,
+        owner: Option<String>,
+        locale: Option<String>,
+    ) -> Result<Vec<i18n::MissingTranslation>, FieldError> {
+        context.require_admin()?;
+        let owner_email = context.owner_email(owner)?;
+        let settings = context.locale_settings(&owner_email).await?;
+        let expected = match locale {
+            Some(locale) => vec![locale.to_ascii_lowercase()],
+            None => settings.locales(),
+        };
+        match i18n::translation_groups(&*context.store, &owner_email, &settings.default_locale()).await {
+            Ok(groups) => Ok(i18n::missing_translations(groups, &expected)),
+            Err(err) => Err(FieldError::new(
+                "Failed to fetch translations",
+                graphql_value!({ "details": err.to_string() }),
+            )),
+        }
+    }
+    // Visitors per country over the last `days` days, or all time. Admin endpoint only.
+    async fn visitors_by_country(
+        context: &Context,
+        owner: Option<String>,
+        days: Option<i32>,
+    ) -> Result<Vec<analytics::CountryVisitors>, FieldError> {
+        context.require_admin()?;
+        let owner_email = context.owner_email(owner)?;
+        let views = async {
+            let since = analytics::window_start(days)?;
+            rollup::window(&*context.store, &owner_email, since.as_deref()).await
+        };
+        match views.await {
+            Ok(views) => Ok(views.countries),
+            Err(err) => {
+                error_reporting::capture_store_error(&config::collections().analytics, err.as_ref());
+                Err(FieldError::new(
+                    "Failed to fetch visitors by country",
+                    graphql_value!({ "details": err.to_string() }),
+                ))
+            }
+        }
+    }
+    // Where visitors came from over the last `days` days, or all time. Admin endpoint only.
+    async fn traffic_sources(
+        context: &Context,
+        owner: Option<String>,
+        days: Option<i32>,
+    ) -> Result<Vec<analytics::TrafficSource>, FieldError> {
+        context.require_admin()?;
+        let owner_email = context.owner_email(owner)?;
+        let views = async {
+            let since = analytics::window_start(days)?;
+            rollup::window(&*context.store, &owner_email, since.as_deref()).await
+        };
+        match views.await {
+            Ok(views) => Ok(views.sources),
+            Err(err) => {
+                error_reporting::capture_store_error(&config::collections().analytics, err.as_ref());
+                Err(FieldError::new(
+                    "Failed to fetch traffic sources",
+                    graphql_value!({ "details": err.to_string() }),
+                ))
+            }
+        }
+    }
+    // Resolver function to fetch skills overview
+    async fn skills_overview(
+        context: &Context,
+        owner: Option<String>,
+        lang: Option<String>,
+    ) -> Result<Vec<SkillsOverview>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let locales = context.locales(&owner_email, lang).await?;
+        let collection = &config::collections().skills_overview;
+        match get_localized_db(&*context.store, collection, &owner_email, &locales).await {
+            Ok(values) => {
+                let skills_overview: Vec<SkillsOverview> = context.decode_all(collection, values)?;
+                Ok(skills_overview)
+            }
+            Err(err) => Err(FieldError::new(
+                "Failed to fetch skills overview",
+                graphql_value!({ "details": err.to_string() }),
+            )),
+        }
+    }
+    // Resolver function to fetch skills
+    async fn skills(
+        context: &Context,
+        owner: Option<String>,
+        lang: Option<String>,
+    ) -> Result<Vec<Skills>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let locales = context.locales(&owner_email, lang).await?;
+        let collection = &config::collections().skills;
+        match get_localized_db(&*context.store, collection, &owner_email, &locales).await {
+            Ok(values) => {
+                let skills: Vec<Skills> = context.decode_all(collection, values)?;
+                Ok(skills)
+            }
+            Err(err) => Err(FieldError::new(
+                "Failed to fetch skills",
+                graphql_value!({ "details": err.to_string() }),
+            )),
+        }
+    }
+    // Resolver function to fetch skills grouped by skillType, groups sorted by type name
+    async fn skills_by_type(
+        context: &Context,
+        owner: Option<String>,
+        lang: Option<String>,
+    ) -> Result<Vec<SkillGroup>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let locales = context.locales(&owner_email, lang).await?;
+        let options = store::FindOptions {
+            sort: vec![("mastery".to_string(), store::SortDirection::Descending)],
+            ..Default::default()
+        };
+        let filter = json!({ "email": owner_email });
+        let collection = &config::collections().skills;
+        match localized_page_db(&*context.store, collection, filter, options, &locales).await {
+            Ok((values, _)) => {
+                let mut groups: Vec<SkillGroup> = Vec::new();
+                for skill in context.decode_all::<Skills, _>(collection, values)? {
+                    match groups.iter_mut().find(|group| group.skill_type == skill.skill_type) {
+                        Some(group) => group.skills.push(skill),
+                        None => groups.push(SkillGroup {
+                            skill_type: skill.skill_type.clone(),
+                            skills: vec![skill],
+                        }),
+                    }
+                }
+                groups.sort_by(|a, b| a.skill_type.cmp(&b.skill_type));
+                Ok(groups)
+            }
+            Err(err) => Err(FieldError::new(
+                "Failed to fetch skills",
+                graphql_value!({ "details": err.to_string() }),
+            )),
+        }
+    }
+    // Resolver function to fetch services
+    async fn services(
+        context: &Context,
+        owner: Option<String>,
+        lang: Option<String>,
+    ) -> Result<Vec<Service>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let locales = context.locales(&owner_email, lang).await?;
+        let collection = &config::collections().services;
+        match get_localized_db(&*context.store, collection, &owner_email, &locales).await {
+            Ok(values) => {
+                let services: Vec<Service> = context.decode_all(collection, values)?;
+                Ok(services)
+            }
+            Err(err) => Err(FieldError::new(
+                "Failed to fetch services",
+                graphql_value!({ "details": err.to_string() }),
+            )),
+        }
+    }
+    // Positions held, the most recently started first
+    async fn work_experience(
+        context: &Context,
+        owner: Option<String>,
+        lang: Option<String>,
+    ) -> Result<Vec<resume::WorkExperience>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let locales = context.locales(&owner_email, lang).await?;
+        let options = store::FindOptions {
+            sort: vec![("startDate".to_string(), store::SortDirection::Descending)],
+            ..Default::default()
+        };
+        let filter = json!({ "email": owner_email });
+        let collection = &config::collections().work_experience;
+        match localized_page_db(&*context.store, collection, filter, options, &locales).await {
+            Ok((values, _)) => {
+                let positions: Vec<resume::WorkExperience> = context.decode_all(collection, values)?;
+                Ok(positions)
+            }
+            Err(err) => Err(FieldError::new(
+                "Failed to fetch work experience",
+                graphql_value!({ "details": err.to_string() }),
+            )),
+        }
+    }
+    // Schools attended, the most recently started first
+    async fn education(
+        context: &Context,
+        owner: Option<String>,
+        lang: Option<String>,
+    ) -> Result<Vec<resume::Education>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let locales = context.locales(&owner_email, lang).await?;
+        let options = store::FindOptions {
+            sort: vec![("startDate".to_string(), store::SortDirection::Descending)],
+            ..Default::default()
+        };
+        let filter = json!({ "email": owner_email });
+        let collection = &config::collections().education;
+        match localized_page_db(&*context.store, collection, filter, options, &locales).await {
+            Ok((values, _)) => {
+                let education: Vec<resume::Education> = context.decode_all(collection, values)?;
+                Ok(education)
+            }
+            Err(err) => Err(FieldError::new(
+                "Failed to fetch education",
+                graphql_value!({ "details": err.to_string() }),
+            )),
+        }
+    }
+    // Talks given, the most recent first
+    async fn talks(
+        context: &Context,
+        owner: Option<String>,
+        lang: Option<String>,
+    ) -> Result<Vec<talks::Talk>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let locales = context.locales(&owner_email, lang).await?;
+        let options = store::FindOptions {
+            sort: vec![("date".to_string(), store::SortDirection::Descending)],
+            ..Default::default()
+        };
+        let filter = json!({ "email": owner_email });
+        let collection = &config::collections().talks;
+        match localized_page_db(&*context.store, collection, filter, options, &locales).await {
+            Ok((values, _)) => {
+                let talks: Vec<talks::Talk> = context.decode_all(collection, values)?;
+                Ok(talks)
+            }
+            Err(err) => Err(FieldError::new(
+                "Failed to fetch talks",
+                graphql_value!({ "details": err.to_string() }),
+            )),
+        }
+    }
+    // Items of the /uses page by category, in list order within a category
+    async fn uses(
+        context: &Context,
+        owner: Option<String>,
+        lang: Option<String>,
+    ) -> Result<Vec<uses::UsesItem>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let locales = context.locales(&owner_email, lang).await?;
+        let collection = &config::collections().uses;
+        match get_localized_db(&*context.store, collection, &owner_email, &locales).await {
+            Ok(values) => {
+                let mut items: Vec<uses::UsesItem> = context.decode_all(collection, values)?;
+                items.sort_by(|a, b| {
+                    let position = |item: &uses::UsesItem| (item.order.is_none(), item.order);
+                    a.category.cmp(&b.category).then(position(a).cmp(&position(b)))
+                });
+                Ok(items)
+            }
+            Err(err) => Err(FieldError::new(
+                "Failed to fetch uses items",
+                graphql_value!({ "details": err.to_string() }),
+            )),
+        }
+    }
+    // Books of the reading list, or those with one status, the most recently
+    // finished and then the most recently added first
+    async fn reading_list(
+        context: &Context,
+        owner: Option<String>,
+        status: Option<books::ReadingStatus>,
+    ) -> Result<Vec<books::Book>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut filter = json!({ "email": owner_email });
+        if let Some(status) = status {
+            filter["status"] = json!(status);
+        }
+        let options = store::FindOptions {
+            sort: vec![
+                ("finishedAt".to_string(), store::SortDirection::Descending),
+                ("addedAt".to_string(), store::SortDirection::Descending),
+            ],
+            ..Default::default()
+        };
+        let collection = &config::collections().reading_list;
+        match get_page_db(&*context.store, collection, filter, options).await {
+            Ok(values) => {
+                let books: Vec<books::Book> = context.decode_all(collection, values)?;
+                Ok(books)
+            }
+            Err(err) => Err(FieldError::new(
+                "Failed to fetch the reading list",
+                graphql_value!({ "details": err.to_string() }),
+            )),
+        }
+    }
+    async fn social_media(context: &Context, owner: Option<String>) -> Result<Vec<SocialMedia>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let collection = &config::collections().social_media;
+        match get_data_db(&*context.store, collection, &owner_email).await {
+            Ok(values) => {
+                let socialmedias: Vec<SocialMedia> = context.decode_all(collection, values)?;
+                Ok(socialmedias)
+            }
+            Err(err) => Err(FieldError::new(
+                "Failed to fetch social medias",
+                graphql_value!({ "details": err.to_string() }),
+            )),
+        }
+    }
+    async fn soft_skills(
+        context: &Context,
+        owner: Option<String>,
+        lang: Option<String>,
+    ) -> Result<Vec<SoftSkills>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let locales = context.locales(&owner_email, lang).await?;
+        ordered_soft_skills(context, &owner_email, &locales).await
+    }
+    // Resolver function to fetch soft skills grouped by category, groups follow their first soft skill
+    async fn soft_skills_by_category(
+        context: &Context,
+        owner: Option<String>,
+        lang: Option<String>,
+    ) -> Result<Vec<SoftSkillGroup>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let locales = context.locales(&owner_email, lang).await?;
+        let mut groups: Vec<SoftSkillGroup> = Vec::new();
+        for softskill in ordered_soft_skills(context, &owner_email, &locales).await? {
+            match groups.iter_mut().find(|group| group.category == softskill.category) {
+                Some(group) => group.soft_skills.push(softskill),
+                None => groups.push(SoftSkillGroup {
+                    category: softskill.category.clone(),
+                    soft_skills: vec![softskill],
+                }),
+            }
+        }
+        Ok(groups)
+    }
+    async fn users(context: &Context, owner: Option<String>) -> Result<Vec<User>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let collection = &config::collections().users;
+        match get_data_db(&*context.store, collection, &owner_email).await {
+            Ok(values) => {
+                let user: Vec<User> = context.decode_all(collection, values)?;
+                Ok(user)
+            }
+            Err(err) => Err(FieldError::new(
+                "Failed to fetch user",
+                graphql_value!({ "details": err.to_string() }),
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+// Mutations anonymous visitors may call on the public endpoint
+pub struct PublicMutation;
+
+#[graphql_object(context = Context)]
+impl PublicMutation {
+    // Endorse one of the owner's skills, each visitor counts once per skill.
+    // Returns the skill's endorsement count afterwards.
+    async fn endorse_skill(context: &Context, owner: Option<String>, name: String) -> Result<i32, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        input.text("name", &name, MAX_NAME_CHARS);
+        input.finish()?;
+        let collections = config::collections();
+        let skill = get_one_db(&*context.store, &collections.skills, json!({ "email": owner_email, "name": name }))
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to fetch skill",
+                graphql_value!({ "details": err.to_string() }),
+            ))?;
+        if skill.is_none() {
+            return Err(FieldError::new(
+                "Skill not found",
+                graphql_value!({ "details": "No skill with this name" }),
+            ));
+        }
+        let endorsement = json!({
+            "email": owner_email,
+            "skill": name,
+            "visitor": context.visitor.hash(&format!("endorse:{}", owner_email)),
+        });
+        let endorse = async {
+            context
+                .store
+                .insert_if_absent(&collections.endorsements, endorsement.clone(), endorsement)
+                .await?;
+            context
+                .store
+                .count(&collections.endorsements, json!({ "email": owner_email, "skill": name }))
+                .await
+        };
+        match endorse.await {
+            Ok(count) => Ok(count as i32),
+            Err(err) => {
+                error_reporting::capture_store_error(&collections.endorsements, err.as_ref());
+                Err(FieldError::new(
+                    "Failed to endorse skill",
+                    graphql_value!({ "details": err.to_string() }),
+                ))
+            }
+        }
+    }
+    // Like one of the owner's projects, each visitor counts once per project.
+    // Returns the project's like count afterwards.
+    async fn like_project(context: &Context, owner: Option<String>, slug: String) -> Result<i32, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        input.text("slug", &slug, MAX_SLUG_CHARS);
+        input.finish()?;
+        let collections = config::collections();
+        let project = get_one_db(&*context.store, &collections.projects, json!({ "email": owner_email, "slug": slug }))
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to fetch project",
+                graphql_value!({ "details": err.to_string() }),
+            ))?;
+        if project.is_none() {
+            return Err(FieldError::new(
+                "Project not found",
+                graphql_value!({ "details": "No project with this slug" }),
+            ));
+        }
+        let like = json!({
+            "email": owner_email,
+            "project": slug,
+            "visitor": context.visitor.hash(&format!("like:{}", owner_email)),
+        });
+        let record = async {
+            context.store.insert_if_absent(&collections.likes, like.clone(), like).await?;
+            context
+                .store
+                .count(&collections.likes, json!({ "email": owner_email, "project": slug }))
+                .await
+        };
+        match record.await {
+            Ok(count) => Ok(count as i32),
+            Err(err) => {
+                error_reporting::capture_store_error(&collections.likes, err.as_ref());
+                Err(FieldError::new(
+                    "Failed to like project",
+                    graphql_value!({ "details": err.to_string() }),
+                ))
+            }
+        }
+    }
+    // Record that a visitor saw an introduction variant, or converted after seeing it
+    async fn track_experiment(
+        context: &Context,
+        owner: Option<String>,
+        experiment_key: String,
+        variant: String,
+        visitor_id: String,
+        event: experiments::ExperimentEvent,
+    ) -> Result<bool, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        input.text("experimentKey", &experiment_key, MAX_NAME_CHARS);
+        input.text("variant", &variant, MAX_NAME_CHARS);
+        input.text("visitorId", &visitor_id, MAX_NAME_CHARS);
+        input.finish()?;
+        match experiments::record(&*context.store, &owner_email, &experiment_key, &variant, &visitor_id, event).await {
+            Ok(()) => Ok(true),
+            Err(err) => {
+                error_reporting::capture_store_error(&config::collections().experiments, err.as_ref());
+                Err(FieldError::new(
+                    "Failed to track experiment",
+                    graphql_value!({ "details": err.to_string() }),
+                ))
+            }
+        }
+    }
+    // Record a named event such as a CTA click, payload is an optional JSON object of up to 2 KB
+    async fn track_event(
+        context: &Context,
+        owner: Option<String>,
+        name: String,
+        payload: Option<String>,
+    ) -> Result<bool, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        input.text("name", &name, MAX_NAME_CHARS);
+        input.finish()?;
+        match analytics::record_event(&*context.store, &owner_email, &context.visitor, &name, payload.as_deref()).await {
+            Ok(()) => Ok(true),
+            Err(err) => Err(FieldError::new(
+                "Failed to track event",
+                graphql_value!({ "details": err.to_string() }),
+            )),
+        }
+    }
+    // Record a page view for first-party analytics, no cookies are set or read
+    async fn track_page_view(
+        context: &Context,
+        owner: Option<String>,
+        path: String,
+        referrer: Option<String>,
+    ) -> Result<bool, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        // overlong paths and referrers are truncated when recorded rather than rejected
+        if path.trim().is_empty() {
+            input.reject("path", "must not be blank");
+        }
+        input.finish()?;
+        match analytics::record_page_view(&*context.store, &owner_email, &context.visitor, &path, referrer.as_deref()).await {
+            Ok(()) => Ok(true),
+            Err(err) => {
+                error_reporting::capture_store_error(&config::collections().analytics, err.as_ref());
+                Err(FieldError::new(
+                    "Failed to track page view",
+                    graphql_value!({ "details": err.to_string() }),
+                ))
+            }
+        }
+    }
+}
+
+pub struct Mutation;
+
+#[graphql_object(context = Context)]
+impl Mutation {
+    // Onboard a new portfolio owner with default documents and indexes
+    async fn create_tenant(
+        context: &Context,
+        email: String,
+        display_name: String,
+        host: Option<String>,
+    ) -> Result<tenant::Tenant, FieldError> {
+        // tenant keys manage their own portfolio only, onboarding is an operator task
+        if context.api_key.is_some() {
+            return Err(FieldError::new(
+                "Creating tenants is not allowed with an API key",
+                graphql_value!({ "details": "Call createTenant from an allowlisted network without X-Api-Key" }),
+            ));
+        }
+        let mut input = Validator::default();
+        input.email("email", &email);
+        input.text("displayName", &display_name, MAX_NAME_CHARS);
+        let tenants = &config::collections().tenants;
+        if validation::exists(&*context.store, tenants, json!({ "email": email })).await? {
+            input.reject("email", "already has a tenant");
+        }
+        // hosts are stored trimmed and lowercased
+        if let Some(host) = host.as_deref().map(|host| host.trim().to_ascii_lowercase()) {
+            input.host("host", &host);
+            if validation::exists(&*context.store, tenants, json!({ "host": host })).await? {
+                input.reject("host", "is already used by another tenant");
+            }
+        }
+        input.finish()?;
+        tenant::provision(&*context.store, email, display_name, host)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to create tenant",
+                graphql_value!({ "details": err.to_string() }),
+            ))
+    }
+    // Issue a new API key for the owner, the plain key is only returned here
+    async fn issue_api_key(
+        context: &Context,
+        owner: Option<String>,
+        name: String,
+        scopes: Vec<String>,
+        rate_limit_per_minute: i32,
+    ) -> Result<api_keys::IssuedApiKey, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        input.text("name", &name, MAX_NAME_CHARS);
+        if scopes.is_empty() {
+            input.reject("scopes", "must list at least one scope");
+        }
+        if let Some(unknown) = scopes
+            .iter()
+            .find(|scope| ![api_keys::READ_SCOPE, api_keys::ADMIN_SCOPE].contains(&scope.as_str()))
+        {
+            input.reject("scopes", format!("has unknown scope {}, expected read or admin", unknown));
+        }
+        input.distinct("scopes", scopes.iter().map(String::as_str));
+        input.range("rateLimitPerMinute", rate_limit_per_minute, 1..=i32::MAX);
+        let filter = json!({ "email": owner_email, "name": name });
+        if validation::exists(&*context.store, &config::collections().api_keys, filter).await? {
+            input.reject("name", "is already used by another API key");
+        }
+        input.finish()?;
+        api_keys::issue(&*context.store, &owner_email, name, scopes, rate_limit_per_minute)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to issue API key",
+                graphql_value!({ "details": err.to_string() }),
+            ))
+    }
+    async fn revoke_api_key(context: &Context, owner: Option<String>, id: String) -> Result<bool, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let id = ObjectId::parse_str(&id).map_err(|err| FieldError::new(
+            "Invalid API key id",
+            graphql_value!({ "details": err.to_string() }),
+        ))?;
+        api_keys::revoke(&*context.store, &owner_email, id)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to revoke API key",
+                graphql_value!({ "details": err.to_string() }),
+            ))
+    }
+    // Change the sections of the theme given, the rest of the theme is kept
+    async fn update_theme(
+        context: &Context,
+        owner: Option<String>,
+        theme: theme::ThemeInput,
+    ) -> Result<theme::Theme, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        if let Some(fonts) = &theme.fonts {
+            input.optional_text("fonts.heading", fonts.heading.as_deref(), MAX_NAME_CHARS);
+            input.optional_text("fonts.body", fonts.body.as_deref(), MAX_NAME_CHARS);
+            input.optional_text("fonts.mono", fonts.mono.as_deref(), MAX_NAME_CHARS);
+        }
+        if let Some(layout) = &theme.layout {
+            for flag in layout {
+                input.text("layout.name", &flag.name, MAX_NAME_CHARS);
+            }
+            input.distinct("layout.name", layout.iter().map(|flag| flag.name.as_str()));
+        }
+        input.finish()?;
+        let mut current = theme::load(&*context.store, &owner_email)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to fetch theme",
+                graphql_value!({ "details": err.to_string() }),
+            ))?;
+        theme
+            .apply(&mut current)
+            .map_err(|details| FieldError::new("Invalid theme", graphql_value!({ "details": details })))?;
+        theme::save(&*context.store, &owner_email, &current)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to update theme",
+                graphql_value!({ "details": err.to_string() }),
+            ))?;
+        Ok(current)
+    }
+    // Set up the "book a call" section, replacing the one there was
+    async fn update_booking_settings(
+        context: &Context,
+        owner: Option<String>,
+        booking: booking::BookingSettingsInput,
+    ) -> Result<booking::BookingSettings, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        input.url("booking.bookingLink", &booking.booking_link);
+        input.optional_text("booking.eventType", booking.event_type.as_deref(), MAX_URL_CHARS);
+        input.finish()?;
+        let settings = booking::BookingSettings {
+            provider: booking.provider,
+            booking_link: booking.booking_link,
+            event_type: booking.event_type.filter(|event_type| !event_type.trim().is_empty()),
+        };
+        booking::save(&*context.store, &owner_email, &settings)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to update booking settings",
+                graphql_value!({ "details": err.to_string() }),
+            ))?;
+        Ok(settings)
+    }
+    // Add a project, or a variant of one in another locale. Without a sortOrder
+    // it goes to the end of the grid.
+    async fn create_project(
+        context: &Context,
+        owner: Option<String>,
+        slug: String,
+        locale: Option<String>,
+        project: ProjectInput,
+    ) -> Result<Project, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let collection = &config::collections().projects;
+        let mut input = Validator::default();
+        input.slug("slug", &slug);
+        input.optional_text("locale", locale.as_deref(), MAX_NAME_CHARS);
+        if project.title.is_none() {
+            input.reject("project.title", "must not be blank");
+        }
+        project.validate(&mut input);
+        let variant = project_variant(&owner_email, &slug, locale.as_deref());
+        if validation::exists(&*context.store, collection, variant).await? {
+            input.reject("slug", "is already used by another project");
+        }
+        let mut created = Project {
+            email: owner_email.clone(),
+            slug: Some(slug),
+            locale,
+            ..Default::default()
+        };
+        project.apply(&mut created);
+        check_project_dates(&mut input, &created);
+        input.finish()?;
+        if created.sort_order.is_none() {
+            let count = context
+                .store
+                .count(collection, json!({ "email": owner_email }))
+                .await
+                .map_err(|err| FieldError::new(
+                    "Failed to count projects",
+                    graphql_value!({ "details": err.to_string() }),
+                ))?;
+            created.sort_order = Some(i32::try_from(count).unwrap_or(i32::MAX));
+        }
+        rules::enforce(&*context.store, &owner_email, collection, &created).await?;
+        let document = serde_json::to_value(&created).map_err(|err| FieldError::new(
+            "Failed to create project",
+            graphql_value!({ "details": err.to_string() }),
+        ))?;
+        context
+            .store
+            .insert_one(collection, document)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to create project",
+                graphql_value!({ "details": err.to_string() }),
+            ))?;
+        Ok(created)
+    }
+    // Change the fields given of a project, the locale picks which variant.
+    // The slug can't be changed as likes and links refer to it.
+    async fn update_project(
+        context: &Context,
+        owner: Option<String>,
+        slug: String,
+        locale: Option<String>,
+        project: ProjectInput,
+    ) -> Result<Project, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let collection = &config::collections().projects;
+        let mut input = Validator::default();
+        input.text("slug", &slug, MAX_SLUG_CHARS);
+        project.validate(&mut input);
+        input.finish()?;
+        let filter = project_variant(&owner_email, &slug, locale.as_deref());
+        let found: Option<Project> = get_one_db(&*context.store, collection, filter.clone())
+            .await
+            .and_then(|found| found.map(value_to_type).transpose())
+            .map_err(|err| FieldError::new(
+                "Failed to fetch project",
+                graphql_value!({ "details": err.to_string() }),
+            ))?;
+        let mut updated = found
+            .ok_or_else(|| FieldError::new(
+                "Project not found",
+                graphql_value!({ "details": "No project with this slug and locale" }),
+            ))?;
+        let changes = project.apply(&mut updated);
+        let mut input = Validator::default();
+        check_project_dates(&mut input, &updated);
+        input.finish()?;
+        if changes.is_empty() {
+            return Ok(updated);
+        }
+        rules::enforce(&*context.store, &owner_email, collection, &updated).await?;
+        context
+            .store
+            .update_one(collection, filter, Value::Object(changes))
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to update project",
+                graphql_value!({ "details": err.to_string() }),
+            ))?;
+        Ok(updated)
+    }
+    // Move one locale variant of a project to the trash, returning it. Its likes
+    // stay until it is purged.
+    async fn delete_project(
+        context: &Context,
+        owner: Option<String>,
+        slug: String,
+        locale: Option<String>,
+    ) -> Result<Option<Project>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let collection = &config::collections().projects;
+        let mut input = Validator::default();
+        input.text("slug", &slug, MAX_SLUG_CHARS);
+        input.finish()?;
+        let filter = project_variant(&owner_email, &slug, locale.as_deref());
+        let deleted = async {
+            let discarded = trash::discard(&*context.store, &owner_email, collection, filter).await?;
+            let project: Option<Project> = discarded.map(value_to_type).transpose()?;
+            Ok::<_, store::StoreError>(project)
+        };
+        deleted.await.map_err(|err| FieldError::new(
+            "Failed to delete project",
+            graphql_value!({ "details": err.to_string() }),
+        ))
+    }
+    // Pin one locale variant of a project to the top of the projects query or unpin it
+    async fn set_project_featured(
+        context: &Context,
+        owner: Option<String>,
+        slug: String,
+        locale: Option<String>,
+        featured: bool,
+    ) -> Result<bool, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        input.text("slug", &slug, MAX_SLUG_CHARS);
+        input.finish()?;
+        let filter = project_variant(&owner_email, &slug, locale.as_deref());
+        context
+            .store
+            .update_one(&config::collections().projects, filter, json!({ "featured": featured }))
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to update project",
+                graphql_value!({ "details": err.to_string() }),
+            ))
+    }
+    // Add an uploaded image to the end of the screenshot gallery of one locale
+    // variant of a project
+    async fn attach_screenshot(
+        context: &Context,
+        owner: Option<String>,
+        slug: String,
+        locale: Option<String>,
+        url: String,
+        alt: String,
+        caption: Option<String>,
+    ) -> Result<Project, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        input.text("slug", &slug, MAX_SLUG_CHARS);
+        input.url("url", &url);
+        input.optional_text("alt", Some(&alt), MAX_TEXT_CHARS);
+        input.optional_text("caption", caption.as_deref(), MAX_TEXT_CHARS);
+        input.finish()?;
+        update_screenshots(context, &owner_email, &slug, locale.as_deref(), |screenshots| {
+            screenshots.retain(|screenshot| screenshot.url != url);
+            screenshots.push(MediaAsset { url, alt, caption });
+        })
+        .await
+    }
+    // Remove an image from the screenshot gallery of one locale variant of a project
+    async fn detach_screenshot(
+        context: &Context,
+        owner: Option<String>,
+        slug: String,
+        locale: Option<String>,
+        url: String,
+    ) -> Result<Project, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        input.text("slug", &slug, MAX_SLUG_CHARS);
+        input.finish()?;
+        update_screenshots(context, &owner_email, &slug, locale.as_deref(), |screenshots| {
+            screenshots.retain(|screenshot| screenshot.url != url);
+        })
+        .await
+    }
+    // Record a mastery snapshot for a skill, dated today unless a date is given,
+    // the locale picks which variant. The skill's mastery follows the most recent
+    // snapshot.
+    async fn record_skill_mastery(
+        context: &Context,
+        owner: Option<String>,
+        name: String,
+        locale: Option<String>,
+        mastery: i32,
+        date: Option<String>,
+    ) -> Result<Skills, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        input.text("name", &name, MAX_NAME_CHARS);
+        input.range("mastery", mastery, 0..=100);
+        if let Some(date) = &date {
+            input.date("date", date);
+        }
+        input.finish()?;
+        let collection = &config::collections().skills;
+        let filter = json!({ "email": owner_email, "name": name, "locale": locale });
+        let found: Option<Skills> = get_one_db(&*context.store, collection, filter.clone())
+            .await
+            .and_then(|found| found.map(value_to_type).transpose())
+            .map_err(|err| FieldError::new(
+                "Failed to fetch skill",
+                graphql_value!({ "details": err.to_string() }),
+            ))?;
+        let mut skill = found
+            .ok_or_else(|| FieldError::new(
+                "Skill not found",
+                graphql_value!({ "details": "No skill with this name and locale" }),
+            ))?;
+        let date = match date {
+            Some(date) => date,
+            None => today()?,
+        };
+        skill.record(date, mastery);
+        context
+            .store
+            .update_one(collection, filter, json!({ "history": skill.history, "mastery": skill.mastery }))
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to update skill",
+                graphql_value!({ "details": err.to_string() }),
+            ))?;
+        Ok(skill)
+    }
+    // Create or update documents of one portfolio collection in one call, e.g. to
+    // migrate from another CMS. Each document is a JSON object, see bulk::upsert.
+    async fn bulk_upsert(
+        context: &Context,
+        owner: Option<String>,
+        collection: String,
+        documents: Vec<String>,
+    ) -> Result<bulk::BulkUpsertResult, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        bulk::upsert(&*context.store, &owner_email, &collection, documents).await
+    }
+    // Import the pages of the Notion database configured with NOTION_TOKEN and
+    // NOTION_DATABASE_ID as blog posts now, rather than on the next background
+    // sync. See notion::sync.
+    async fn sync_notion(context: &Context, owner: Option<String>) -> Result<notion::NotionSyncResult, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let Some(config) = notion::NotionConfig::from_env() else {
+            return Err(FieldError::new(
+                "Notion sync is not configured",
+                graphql_value!({ "details": "Set NOTION_TOKEN and NOTION_DATABASE_ID" }),
+            ));
+        };
+        if config.owner_email != owner_email {
+            return Err(FieldError::new(
+                "Notion sync imports into another portfolio",
+                graphql_value!({ "details": "NOTION_OWNER names the portfolio posts are imported into" }),
+            ));
+        }
+        notion::sync(&*context.store, &config).await.map_err(|err| FieldError::new(
+            "Failed to sync from Notion",
+            graphql_value!({ "details": err.to_string() }),
+        ))
+    }
+    // Pull the content repository configured with CONTENT_GIT_URL and import its
+    // markdown files as blog posts now, rather than on the next push or
+    // background sync. See git_content::sync.
+    async fn sync_git_content(context: &Context, owner: Option<String>) -> Result<git_content::GitSyncResult, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let Some(config) = git_content::GitContentConfig::from_env() else {
+            return Err(FieldError::new(
+                "Git content sync is not configured",
+                graphql_value!({ "details": "Set CONTENT_GIT_URL" }),
+            ));
+        };
+        if config.owner_email != owner_email {
+            return Err(FieldError::new(
+                "Git content sync imports into another portfolio",
+                graphql_value!({ "details": "CONTENT_GIT_OWNER names the portfolio posts are imported into" }),
+            ));
+        }
+        git_content::sync(&*context.store, &config).await.map_err(|err| FieldError::new(
+            "Failed to sync from Git",
+            graphql_value!({ "details": err.to_string() }),
+        ))
+    }
+    // Pull the reading list from Goodreads and Hardcover, whichever are
+    // configured, now rather than on the next background sync. See books::sync.
+    async fn sync_books(context: &Context, owner: Option<String>) -> Result<books::BookSyncResult, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let Some(config) = books::BooksConfig::from_env() else {
+            return Err(FieldError::new(
+                "Reading list sync is not configured",
+                graphql_value!({ "details": "Set GOODREADS_RSS_URL or HARDCOVER_TOKEN" }),
+            ));
+        };
+        if config.owner_email != owner_email {
+            return Err(FieldError::new(
+                "Reading list sync imports into another portfolio",
+                graphql_value!({ "details": "BOOKS_OWNER names the portfolio books are listed for" }),
+            ));
+        }
+        books::sync(&*context.store, &config).await.map_err(|err| FieldError::new(
+            "Failed to sync the reading list",
+            graphql_value!({ "details": err.to_string() }),
+        ))
+    }
+    // Import the documents of a Contentful or Sanity export, e.g. to move a site
+    // over from either. Content types map to collections through `types`, or by
+    // name for post, blogPost, article, project, skill and service. See
+    // cms_import::import.
+    async fn import_cms_export(
+        context: &Context,
+        owner: Option<String>,
+        source: cms_import::CmsSource,
+        payload: String,
+        #[graphql(default = Vec::new())] types: Vec<cms_import::TypeMappingInput>,
+        sanity_images: Option<cms_import::SanityImagesInput>,
+    ) -> Result<cms_import::ImportResult, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        cms_import::import(&*context.store, &owner_email, source, &payload, types, sanity_images).await
+    }
+    // Import the Positions.csv, Education.csv and Skills.csv files of a LinkedIn
+    // data export, each given as the file's text, into the resume sections. See
+    // linkedin::import.
+    async fn import_linkedin_export(
+        context: &Context,
+        owner: Option<String>,
+        locale: Option<String>,
+        positions: Option<String>,
+        education: Option<String>,
+        skills: Option<String>,
+    ) -> Result<linkedin::LinkedInImportResult, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        linkedin::import(&*context.store, &owner_email, locale, positions, education, skills).await
+    }
+    // Start a blog post as a draft, its slug is derived from the title unless given
+    async fn create_blog_post(
+        context: &Context,
+        owner: Option<String>,
+        locale: Option<String>,
+        post: blog::BlogPostInput,
+    ) -> Result<blog::BlogPost, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        blog::create(context, owner_email, locale, post).await
+    }
+    // Change the fields given of a blog post, the locale picks which variant
+    async fn update_blog_post(
+        context: &Context,
+        owner: Option<String>,
+        slug: String,
+        locale: Option<String>,
+        post: blog::BlogPostInput,
+    ) -> Result<blog::BlogPost, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        blog::update(context, owner_email, slug, locale, post).await
+    }
+    // Save the editor's state of a blog post as its working copy, failing with
+    // EDIT_CONFLICT when the post changed since baseUpdatedAt, see blog::autosave
+    async fn autosave_draft(
+        context: &Context,
+        owner: Option<String>,
+        id: String,
+        base_updated_at: Option<String>,
+        draft: blog::BlogPostInput,
+    ) -> Result<blog::BlogPost, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        blog::autosave(context, owner_email, id, base_updated_at, draft).await
+    }
+    // Send a draft to review or a post in review back to draft
+    async fn set_blog_post_status(
+        context: &Context,
+        owner: Option<String>,
+        slug: String,
+        locale: Option<String>,
+        status: blog::BlogPostStatus,
+    ) -> Result<blog::BlogPost, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        blog::set_status(context, owner_email, slug, locale, status).await
+    }
+    // Publish a blog post in review, dated now
+    async fn publish_blog_post(
+        context: &Context,
+        owner: Option<String>,
+        slug: String,
+        locale: Option<String>,
+    ) -> Result<blog::BlogPost, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        blog::publish(context, owner_email, slug, locale).await
+    }
+    // Take a published blog post off the public API, it becomes a draft again
+    async fn unpublish_blog_post(
+        context: &Context,
+        owner: Option<String>,
+        slug: String,
+        locale: Option<String>,
+    ) -> Result<blog::BlogPost, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        blog::unpublish(context, owner_email, slug, locale).await
+    }
+    // Add a skill, its mastery is recorded as today's snapshot
+    async fn create_skill(
+        context: &Context,
+        owner: Option<String>,
+        name: String,
+        mastery: i32,
+        skill_type: SkillType,
+        locale: Option<String>,
+    ) -> Result<Skills, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let collection = &config::collections().skills;
+        let mut input = Validator::default();
+        input.text("name", &name, MAX_NAME_CHARS);
+        input.range("mastery", mastery, 0..=100);
+        input.optional_text("locale", locale.as_deref(), MAX_NAME_CHARS);
+        let variant = json!({ "email": owner_email, "name": name, "locale": locale });
+        if validation::exists(&*context.store, collection, variant).await? {
+            input.reject("name", "is already used by another skill");
+        }
+        input.finish()?;
+        let mut skill = Skills {
+            email: owner_email,
+            name,
+            mastery,
+            skill_type: skill_type.as_str().to_string(),
+            history: Vec::new(),
+            locale,
+        };
+        skill.record(today()?, mastery);
+        let created = async {
+            context.store.insert_one(collection, serde_json::to_value(&skill)?).await
+        };
+        created.await.map_err(|err| FieldError::new(
+            "Failed to create skill",
+            graphql_value!({ "details": err.to_string() }),
+        ))?;
+        Ok(skill)
+    }
+    // Change the type of a skill or record a new mastery for today, the locale
+    // picks which variant. The name can't be changed as endorsements and project
+    // tech stacks refer to it.
+    async fn update_skill(
+        context: &Context,
+        owner: Option<String>,
+        name: String,
+        locale: Option<String>,
+        mastery: Option<i32>,
+        skill_type: Option<SkillType>,
+    ) -> Result<Skills, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let collection = &config::collections().skills;
+        let mut input = Validator::default();
+        input.text("name", &name, MAX_NAME_CHARS);
+        if let Some(mastery) = mastery {
+            input.range("mastery", mastery, 0..=100);
+        }
+        if mastery.is_none() && skill_type.is_none() {
+            input.reject("mastery", "or skillType must be given");
+        }
+        input.finish()?;
+        let filter = json!({ "email": owner_email, "name": name, "locale": locale });
+        let found: Option<Skills> = get_one_db(&*context.store, collection, filter.clone())
+            .await
+            .and_then(|found| found.map(value_to_type).transpose())
+            .map_err(|err| FieldError::new(
+                "Failed to fetch skill",
+                graphql_value!({ "details": err.to_string() }),
+            ))?;
+        let mut skill = found
+            .ok_or_else(|| FieldError::new(
+                "Skill not found",
+                graphql_value!({ "details": "No skill with this name and locale" }),
+            ))?;
+        if let Some(mastery) = mastery {
+            skill.record(today()?, mastery);
+        }
+        if let Some(skill_type) = skill_type {
+            skill.skill_type = skill_type.as_str().to_string();
+        }
+        let changes = json!({ "history": skill.history, "mastery": skill.mastery, "skillType": skill.skill_type });
+        context
+            .store
+            .update_one(collection, filter, changes)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to update skill",
+                graphql_value!({ "details": err.to_string() }),
+            ))?;
+        Ok(skill)
+    }
+    // Move one locale variant of a skill to the trash, returns whether it existed.
+    // Its endorsements stay until it is purged.
+    async fn delete_skill(
+        context: &Context,
+        owner: Option<String>,
+        name: String,
+        locale: Option<String>,
+    ) -> Result<bool, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        input.text("name", &name, MAX_NAME_CHARS);
+        input.finish()?;
+        let filter = json!({ "email": owner_email, "name": name, "locale": locale });
+        trash::discard(&*context.store, &owner_email, &config::collections().skills, filter)
+            .await
+            .map(|discarded| discarded.is_some())
+            .map_err(|err| FieldError::new(
+                "Failed to delete skill",
+                graphql_value!({ "details": err.to_string() }),
+            ))
+    }
+    // Add an entry to the skills overview
+    async fn create_skills_overview(
+        context: &Context,
+        owner: Option<String>,
+        title: String,
+        icon: Option<String>,
+        locale: Option<String>,
+    ) -> Result<SkillsOverview, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let collection = &config::collections().skills_overview;
+        let mut input = Validator::default();
+        input.text("title", &title, MAX_NAME_CHARS);
+        input.optional_text("icon", icon.as_deref(), MAX_URL_CHARS);
+        input.optional_text("locale", locale.as_deref(), MAX_NAME_CHARS);
+        let variant = json!({ "email": owner_email, "title": title, "locale": locale });
+        if validation::exists(&*context.store, collection, variant).await? {
+            input.reject("title", "is already used by another skills overview entry");
+        }
+        input.finish()?;
+        let document = json!({ "email": owner_email, "title": title, "icon": icon, "locale": locale });
+        context
+            .store
+            .insert_one(collection, document)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to create skills overview entry",
+                graphql_value!({ "details": err.to_string() }),
+            ))?;
+        Ok(SkillsOverview {
+            email: owner_email,
+            title,
+            icon,
+        })
+    }
+    // Rename a skills overview entry or change its icon, an empty icon unsets it.
+    // Returns whether the entry exists.
+    async fn update_skills_overview(
+        context: &Context,
+        owner: Option<String>,
+        title: String,
+        locale: Option<String>,
+        new_title: Option<String>,
+        icon: Option<String>,
+    ) -> Result<bool, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let collection = &config::collections().skills_overview;
+        let mut input = Validator::default();
+        input.text("title", &title, MAX_NAME_CHARS);
+        input.optional_text("icon", icon.as_deref(), MAX_URL_CHARS);
+        let mut changes = json!({});
+        if let Some(new_title) = new_title {
+            input.text("newTitle", &new_title, MAX_NAME_CHARS);
+            let variant = json!({ "email": owner_email, "title": new_title, "locale": locale });
+            if new_title != title && validation::exists(&*context.store, collection, variant).await? {
+                input.reject("newTitle", "is already used by another skills overview entry");
+            }
+            changes["title"] = json!(new_title);
+        }
+        if let Some(icon) = icon {
+            changes["icon"] = json!(Some(icon).filter(|icon| !icon.is_empty()));
+        }
+        if changes.as_object().is_some_and(|changes| changes.is_empty()) {
+            input.reject("newTitle", "or icon must be given");
+        }
+        input.finish()?;
+        let filter = json!({ "email": owner_email, "title": title, "locale": locale });
+        context
+            .store
+            .update_one(collection, filter, changes)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to update skills overview entry",
+                graphql_value!({ "details": err.to_string() }),
+            ))
+    }
+    // Move one locale variant of a skills overview entry to the trash, returns whether it existed
+    async fn delete_skills_overview(
+        context: &Context,
+        owner: Option<String>,
+        title: String,
+        locale: Option<String>,
+    ) -> Result<bool, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        input.text("title", &title, MAX_NAME_CHARS);
+        input.finish()?;
+        let filter = json!({ "email": owner_email, "title": title, "locale": locale });
+        trash::discard(&*context.store, &owner_email, &config::collections().skills_overview, filter)
+            .await
+            .map(|discarded| discarded.is_some())
+            .map_err(|err| FieldError::new(
+                "Failed to delete skills overview entry",
+                graphql_value!({ "details": err.to_string() }),
+            ))
+    }
+    // Set the position and category of a soft skill, omitted arguments are left unchanged
+    async fn update_soft_skill(
+        context: &Context,
+        owner: Option<String>,
+        name: String,
+        order: Option<i32>,
+        category: Option<String>,
+    ) -> Result<bool, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        input.text("name", &name, MAX_NAME_CHARS);
+        if let Some(order) = order {
+            input.range("order", order, 0..=i32::MAX);
+        }
+        if let Some(category) = &category {
+            input.text("category", category, MAX_NAME_CHARS);
+        }
+        if order.is_none() && category.is_none() {
+            input.reject("order", "or category must be given");
+        }
+        input.finish()?;
+        let mut changes = json!({});
+        if let Some(order) = order {
+            changes["order"] = json!(order);
+        }
+        if let Some(category) = category {
+            changes["category"] = json!(category);
+        }
+        let filter = json!({ "email": owner_email, "name": name });
+        context
+            .store
+            .update_one(&config::collections().soft_skills, filter, changes)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to update soft skill",
+                graphql_value!({ "details": err.to_string() }),
+            ))
+    }
+    // Add a menu item, or change the item of the menu with the same href
+    async fn save_navigation_item(
+        context: &Context,
+        owner: Option<String>,
+        menu: String,
+        href: String,
+        label: String,
+        #[graphql(default = 0)] order: i32,
+        #[graphql(default = navigation::Visibility::All)] visibility: navigation::Visibility,
+    ) -> Result<navigation::NavigationItem, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        input.slug("menu", &menu);
+        input.href("href", &href);
+        input.text("label", &label, MAX_NAME_CHARS);
+        input.range("order", order, 0..=i32::MAX);
+        input.finish()?;
+        let collection = &config::collections().navigation;
+        let item = navigation::NavigationItem {
+            email: owner_email.clone(),
+            menu,
+            label,
+            href,
+            order,
+            visibility,
+        };
+        let filter = json!({ "email": owner_email, "menu": item.menu, "href": item.href });
+        let changes = json!({ "label": item.label, "order": item.order, "visibility": item.visibility });
+        let saved = async {
+            if !context.store.update_one(collection, filter, changes).await? {
+                context.store.insert_one(collection, serde_json::to_value(&item)?).await?;
+            }
+            Ok::<_, store::StoreError>(())
+        };
+        saved.await.map_err(|err| FieldError::new(
+            "Failed to save navigation item",
+            graphql_value!({ "details": err.to_string() }),
+        ))?;
+        Ok(item)
+    }
+    // Drop cached reads of one collection, or of every collection when none is
+    // given, so the next reads go to the database. Returns how many were dropped.
+    fn purge_cache(context: &Context, collection: Option<String>) -> Result<i32, FieldError> {
+        // the cache is shared by every tenant, so purging it is an operator task
+        if context.multi_tenant && context.api_key.is_some() {
+            return Err(FieldError::new(
+                "Purging the cache is not allowed with a tenant API key",
+                graphql_value!({ "details": "Call purgeCache from an allowlisted network without X-Api-Key" }),
+            ));
+        }
+        let purged = context.store.purge_cache(collection.as_deref());
+        Ok(i32::try_from(purged).unwrap_or(i32::MAX))
+    }
+    // Remove an item from a menu, returns whether it existed
+    async fn delete_navigation_item(
+        context: &Context,
+        owner: Option<String>,
+        menu: String,
+        href: String,
+    ) -> Result<bool, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        input.slug("menu", &menu);
+        input.finish()?;
+        let filter = json!({ "email": owner_email, "menu": menu, "href": href });
+        trash::discard(&*context.store, &owner_email, &config::collections().navigation, filter)
+            .await
+            .map(|discarded| discarded.is_some())
+            .map_err(|err| FieldError::new(
+                "Failed to delete navigation item",
+                graphql_value!({ "details": err.to_string() }),
+            ))
+    }
+    // Replace the owner's content rules. Documents already stored aren't
+    // checked, the check-data command reports the ones breaking a rule.
+    async fn set_content_rules(
+        context: &Context,
+        owner: Option<String>,
+        rules: Vec<rules::ContentRuleInput>,
+    ) -> Result<Vec<rules::ContentRule>, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        for rule in &rules {
+            rule.validate(&mut input);
+        }
+        input.distinct("rules.name", rules.iter().map(|rule| rule.name.as_str()));
+        input.finish()?;
+        let rules: Vec<rules::ContentRule> = rules.into_iter().map(Into::into).collect();
+        rules::save(&*context.store, &owner_email, &rules)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to save content rules",
+                graphql_value!({ "details": err.to_string() }),
+            ))?;
+        Ok(rules)
+    }
+    // Signed upload of a new file straight to the media storage set with
+    // MEDIA_STORAGE, register the file once it's uploaded
+    async fn create_media_upload(
+        context: &Context,
+        owner: Option<String>,
+        file_name: String,
+        content_type: String,
+    ) -> Result<media::MediaUpload, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let Some(storage) = media::storage() else {
+            return Err(FieldError::new(
+                "Media storage is not configured",
+                graphql_value!({ "details": "MEDIA_STORAGE is not set" }),
+            ));
+        };
+        let mut input = Validator::default();
+        input.text("fileName", &file_name, MAX_NAME_CHARS);
+        input.text("contentType", &content_type, MAX_NAME_CHARS);
+        input.finish()?;
+        storage
+            .upload(&media::upload_key(&owner_email, &file_name), &content_type)
+            .map_err(|err| FieldError::new(
+                "Failed to sign media upload",
+                graphql_value!({ "details": err.to_string() }),
+            ))
+    }
+    // Add an uploaded file to the media library. Files uploaded through
+    // createMediaUpload pass its storageKey, so deleting them from the library
+    // deletes them from the storage too.
+    async fn register_media(
+        context: &Context,
+        owner: Option<String>,
+        url: String,
+        name: String,
+        content_type: Option<String>,
+        #[graphql(default = Vec::new())] tags: Vec<String>,
+        storage_key: Option<String>,
+    ) -> Result<media::MediaFile, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        input.url("url", &url);
+        input.text("name", &name, MAX_NAME_CHARS);
+        input.optional_text("contentType", content_type.as_deref(), MAX_NAME_CHARS);
+        if let Some(storage_key) = &storage_key {
+            input.text("storageKey", storage_key, MAX_URL_CHARS);
+            if media::storage().is_none() {
+                input.reject("storageKey", "needs MEDIA_STORAGE to be set");
+            } else if !media::owns_key(&owner_email, storage_key) {
+                input.reject("storageKey", "is not a key createMediaUpload signed for this owner");
+            }
+        }
+        for tag in &tags {
+            input.text("tags", tag, MAX_NAME_CHARS);
+        }
+        input.distinct("tags", tags.iter().map(String::as_str));
+        let media = &config::collections().media;
+        if validation::exists(&*context.store, media, json!({ "email": owner_email, "url": url })).await? {
+            input.reject("url", "is already in the media library");
+        }
+        input.finish()?;
+        media::register(&*context.store, &owner_email, url, name, content_type, tags, storage_key)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to register media file",
+                graphql_value!({ "details": err.to_string() }),
+            ))
+    }
+    // Replace the tags of a media file
+    async fn tag_media(
+        context: &Context,
+        owner: Option<String>,
+        id: String,
+        tags: Vec<String>,
+    ) -> Result<media::MediaFile, FieldError> {
+        let mut input = Validator::default();
+        for tag in &tags {
+            input.text("tags", tag, MAX_NAME_CHARS);
+        }
+        input.distinct("tags", tags.iter().map(String::as_str));
+        input.finish()?;
+        update_media(context, owner, id, json!({ "tags": tags })).await
+    }
+    // Change the name a media file is listed under, its URL stays the same
+    async fn rename_media(
+        context: &Context,
+        owner: Option<String>,
+        id: String,
+        name: String,
+    ) -> Result<media::MediaFile, FieldError> {
+        let mut input = Validator::default();
+        input.text("name", &name, MAX_NAME_CHARS);
+        input.finish()?;
+        update_media(context, owner, id, json!({ "name": name })).await
+    }
+    // Remove a file from the media library, refused with a MEDIA_IN_USE error
+    // while documents still use it. Returns whether it was in the library.
+    async fn delete_media(context: &Context, owner: Option<String>, id: String) -> Result<bool, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        media::delete(&*context.store, &owner_email, parse_media_id(&id)?).await
+    }
+    // Put a document from the trash back where it was deleted from, returns
+    // whether it was in the trash
+    async fn restore(context: &Context, owner: Option<String>, id: String) -> Result<bool, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let id = ObjectId::parse_str(&id).map_err(|err| FieldError::new(
+            "Invalid trash id",
+            graphql_value!({ "details": err.to_string() }),
+        ))?;
+        trash::restore(&*context.store, &owner_email, id)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to restore document",
+                graphql_value!({ "details": err.to_string() }),
+            ))
+    }
+    // Delete a document in the trash for good, returns whether it was in the trash
+    async fn purge(context: &Context, owner: Option<String>, id: String) -> Result<bool, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let id = ObjectId::parse_str(&id).map_err(|err| FieldError::new(
+            "Invalid trash id",
+            graphql_value!({ "details": err.to_string() }),
+        ))?;
+        trash::purge(&*context.store, &owner_email, id)
+            .await
+            .map_err(|err| FieldError::new(
+                "Failed to purge document",
+                graphql_value!({ "details": err.to_string() }),
+            ))
+    }
+    // Give the listed projects' variants of one locale ascending sortOrder values,
+    // returns how many were found
+    async fn reorder_projects(
+        context: &Context,
+        owner: Option<String>,
+        slugs: Vec<String>,
+        locale: Option<String>,
+    ) -> Result<i32, FieldError> {
+        let owner_email = context.owner_email(owner)?;
+        let mut input = Validator::default();
+        for slug in &slugs {
+            input.text("slugs", slug, MAX_SLUG_CHARS);
+        }
+        input.distinct("slugs", slugs.iter().map(String::as_str));
+        input.finish()?;
+        let collection = &config::collections().projects;
+        let mut updated = 0;
+        for (position, slug) in slugs.into_iter().enumerate() {
+            let filter = project_variant(&owner_email, &slug, locale.as_deref());
+            let found = context
+                .store
+                .update_one(collection, filter, json!({ "sortOrder": position as i32 }))
+                .await
+                .map_err(|err| FieldError::new(
+                    "Failed to reorder projects",
+                    graphql_value!({ "details": err.to_string() }),
+                ))?;
+            if found {
+                updated += 1;
+            }
+        }
+        Ok(updated)
+    }
+}
+
+// Most viewed slugs to look up before giving up on filling `limit`, as slugs of
+// drafts, deleted pages or mistyped URLs have no document
+const MAX_POPULAR_LOOKUPS: usize = 2 * MAX_PAGE_SIZE as usize;
+
+// The localized document of each slug of `ranked` matching `filter`, with its
+// views, up to `limit`. Slugs are looked up one at a time, most viewed first, so
+// only the documents shown are read.
+async fn popular_by_slug(
+    context: &Context,
+    collection: &str,
+    filter: Value,
+    ranked: Vec<(String, i32)>,
+    locales: &[String],
+    limit: usize,
+) -> Result<Vec<(Value, i32)>, store::StoreError> {
+    let mut found = Vec::new();
+    for (slug, views) in ranked.into_iter().take(MAX_POPULAR_LOOKUPS) {
+        if found.len() >= limit {
+            break;
+        }
+        let mut filter = filter.clone();
+        filter["slug"] = json!(slug);
+        let variants = context.store.find(collection, filter).await?;
+        if let Some(document) = i18n::localize(variants, locales).into_iter().next() {
+            found.push((document, views));
+        }
+    }
+    Ok(found)
+}
+
+// Published posts ranked by views, views of drafts, deleted posts or unknown slugs are skipped
+fn popular_blog_posts(paths: &[rollup::PathViews], posts: Vec<blog::BlogPost>, limit: usize) -> Vec<PopularBlogPost> {
+    analytics::views_by_slug(paths, &analytics::blog_path_prefix())
+        .into_iter()
+        .filter_map(|(slug, views)| {
+            let post = posts.iter().find(|post| post.slug == slug)?.clone();
+            Some(PopularBlogPost { post, views })
+        })
+        .take(limit)
+        .collect()
+}
+
+// Introduction variants of an experiment, sorted by variant name so assignment is stable
+async fn experiment_variants(
+    context: &Context,
+    owner_email: &str,
+    experiment_key: &str,
+) -> Result<Vec<Introduction>, FieldError> {
+    let collection = &config::collections().introductions;
+    let filter = json!({ "email": owner_email, "experimentKey": experiment_key });
+    let options = store::FindOptions {
+        sort: vec![("variant".to_string(), store::SortDirection::Ascending)],
+        ..Default::default()
+    };
+    match get_page_db(&*context.store, collection, filter, options).await {
+        Ok(values) => context.decode_all(collection, values),
+        Err(err) => Err(FieldError::new(
+            "Failed to fetch introductions",
+            graphql_value!({ "details": err.to_string() }),
+        )),
+    }
+}
+
+fn parse_media_id(id: &str) -> Result<ObjectId, FieldError> {
+    ObjectId::parse_str(id).map_err(|err| FieldError::new(
+        "Invalid media id",
+        graphql_value!({ "details": err.to_string() }),
+    ))
+}
+
+async fn update_media(
+    context: &Context,
+    owner: Option<String>,
+    id: String,
+    changes: Value,
+) -> Result<media::MediaFile, FieldError> {
+    let owner_email = context.owner_email(owner)?;
+    let updated = media::update(&*context.store, &owner_email, parse_media_id(&id)?, changes)
+        .await
+        .map_err(|err| FieldError::new(
+            "Failed to update media file",
+            graphql_value!({ "details": err.to_string() }),
+        ))?;
+    updated.ok_or_else(|| FieldError::new(
+        "Media file not found",
+        graphql_value!({ "details": "No media file with this id" }),
+    ))
+}
+
+// Today's date in UTC, e.g. 2024-03-01
+fn today() -> Result<String, FieldError> {
+    mongodb::bson::DateTime::now()
+        .try_to_rfc3339_string()
+        .map(|now| now[..10].to_string())
+        .map_err(|err| FieldError::new(
+            "Failed to read the current date",
+            graphql_value!({ "details": err.to_string() }),
+        ))
+}
+
+// Read one locale variant of a project, change its screenshots and write them back
+async fn update_screenshots(
+    context: &Context,
+    owner_email: &str,
+    slug: &str,
+    locale: Option<&str>,
+    change: impl FnOnce(&mut Vec<MediaAsset>),
+) -> Result<Project, FieldError> {
+    let collection = &config::collections().projects;
+    let filter = project_variant(owner_email, slug, locale);
+    let found: Option<Project> = get_one_db(&*context.store, collection, filter.clone())
+        .await
+        .and_then(|found| found.map(value_to_type).transpose())
+        .map_err(|err| FieldError::new(
+            "Failed to fetch project",
+            graphql_value!({ "details": err.to_string() }),
+        ))?;
+    let mut project = found
+        .ok_or_else(|| FieldError::new(
+            "Project not found",
+            graphql_value!({ "details": "No project with this slug and locale" }),
+        ))?;
+    change(&mut project.screenshots);
+    context
+        .store
+        .update_one(collection, filter, json!({ "screenshots": project.screenshots }))
+        .await
+        .map_err(|err| FieldError::new(
+            "Failed to update project",
+            graphql_value!({ "details": err.to_string() }),
+        ))?;
+    Ok(project)
+}
+
+#[tokio::main]
+async fn main() {
+    // Load .env.<APP_ENV> and .env
+    config::load_env_files();
+    let cli = cli::Cli::parse();
+    init_logging();
+    let _sentry = error_reporting::init();
+    // bench only talks to a running instance and serve and check connect as part
+    // of their configuration check, so they don't go through this
+    async fn connect() -> Arc<dyn store::DataStore> {
+        store::connect().await.expect("Failed to connect to the data store")
+    }
+    let result = match cli.command.unwrap_or(cli::Command::Serve) {
+        cli::Command::Serve => {
+            // the server only refuses to start over a broken environment, a store
+            // missing collections or down for now is reported and served anyway
+            let mut report = preflight::Report::environment();
+            let store = if report.has_errors() { None } else { report.connect().await };
+            if !report.all_ok() {
+                report.print();
+            }
+            match store {
+                Some(store) => {
+                    serve(store).await;
+                    Ok(())
+                }
+                None => Err("Configuration check failed".into()),
+            }
+        }
+        cli::Command::Seed { dir } => cli::seed(&*connect().await, dir).await,
+        cli::Command::Migrate => cli::migrate(&*connect().await).await,
+        cli::Command::Export { dir, owner } => cli::export(&*connect().await, dir, owner).await,
+        cli::Command::Check => cli::check().await,
+        cli::Command::CheckData { owner } => cli::check_data(&*connect().await, owner).await,
+        cli::Command::Rollup => rollup::run(&*connect().await).await,
+        cli::Command::Reindex => cli::reindex(&*connect().await).await,
+        cli::Command::Bench(args) => bench::run(args).await,
+    };
+    if let Err(e) = result {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+// Text or JSON log lines depending on the profile, filtered with RUST_LOG
+fn init_logging() {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    match config::app().log_format {
+        config::LogFormat::Json => subscriber.json().init(),
+        config::LogFormat::Text => subscriber.init(),
+    }
+}
+
+async fn serve(store: Arc<dyn store::DataStore>) {
+    let settings = config::app();
+    tracing::info!(profile = settings.profile.name(), "Starting portfolio API");
+    rollup::spawn(store.clone());
+    notion::spawn(store.clone());
+    books::spawn(store.clone());
+    git_content::spawn(store.clone());
+    search::spawn(store.clone());
+    let tenancy = tenant::Tenancy::from_env();
+    // missing indexes only cost query speed, and a unique index fails to build over
+    // existing duplicates, so the server starts regardless
+    if settings.ensure_indexes {
+        if let Err(e) = store::ensure_indexes(&*store).await {
+            eprintln!("Error creating indexes: {}", e);
+        }
+    }
+    // a cold cache only costs latency, so failing to warm it doesn't stop the server
+    if let Err(e) = warmup::run(&*store, &tenancy).await {
+        eprintln!("Error warming the cache: {}", e);
+    }
+    let allowed_origins = if settings.cors_origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::from(Any)
+    } else {
+        AllowOrigin::list(settings.cors_origins.iter().map(|origin| {
+            origin.parse().unwrap_or_else(|_| panic!("Invalid CORS origin {}", origin))
+        }))
+    };
+    let cors = CorsLayer::new()
+        .allow_methods(vec![Method::GET, Method::POST])
+        .allow_origin(allowed_origins)
+        .allow_headers(vec![
+            http::header::CONTENT_TYPE,
+            http::header::IF_NONE_MATCH,
+            HeaderName::from_static(api_keys::API_KEY_HEADER),
+            HeaderName::from_static(tenant::OWNER_HEADER),
+        ])
+        .expose_headers(vec![http::header::ETAG]);
+    let mut schema = Schema::new(
+        Query,
+        PublicMutation,
+        EmptySubscription::<Context>::new()
+     );
+    let mut admin_schema = AdminSchema::new(Query, Mutation, EmptySubscription::<Context>::new());
+    if !settings.introspection {
+        schema = schema.disable_introspection();
+        admin_schema = admin_schema.disable_introspection();
+    }
+    // admin routes are only reachable from the networks in ADMIN_ALLOWED_IPS,
+    // which the configuration check already required
+    let admin_allowlist = Arc::new(admin::AdminAllowlist::from_env().expect("Invalid ADMIN_ALLOWED_IPS"));
+    let admin_routes = Router::new()
+        .route("/admin/graphql", post(admin_graphql_handler))
+        .route("/admin/export.ndjson", get(ndjson::export))
+        .route("/admin/cache", get(status::cache))
+        .route_layer(middleware::from_fn_with_state(admin_allowlist, admin::require_allowed_ip));
+    // build our application with a route
+    let mut app = Router::new()
+        .route("/", get(root))
+        .route("/status", get(status::status))
+        // .route("/introduction", post(create_introduction))
+        // .route("/:collection_name", get(get_handler))
+        .route("/graphql", get(graphql_handler).post(graphql_handler))
+        .route("/blog/archive.ndjson", get(ndjson::blog_archive))
+        .route("/webhooks/git", post(git_content::webhook))
+        .merge(admin_routes);
+    if settings.playground {
+        app = app.route("/playground", get(playground("/graphql", None)));
+    }
+    let mut app = app.layer(middleware::from_fn(redirects::serve_redirects));
+    // inside the CORS layer so browsers can read the 503
+    if settings.max_in_flight_requests > 0 {
+        let in_flight = Arc::new(tokio::sync::Semaphore::new(settings.max_in_flight_requests));
+        app = app.layer(middleware::from_fn_with_state(in_flight, tuning::shed_load));
+    }
+    let app = app
+        .layer(cors)
+        .layer(middleware::from_fn(access_log::log_request))
+        // a Sentry hub per request, carrying the HTTP request details into events
+        .layer(SentryHttpLayer::with_transaction())
+        .layer(NewSentryLayer::<Request>::new_from_top())
+        .layer(Extension(store))
+        .layer(Extension(status::StartedAt(Instant::now())))
+        .layer(Extension(Arc::new(schema)))
+        .layer(Extension(Arc::new(admin_schema)))
+        .layer(Extension(Arc::new(tenancy)))
+        .layer(Extension(Arc::new(api_keys::ApiKeyGuard::from_env())));
+    // a socket handed over by systemd wins over the configured address
+    match systemd::inherited_listener() {
+        Some(systemd::InheritedListener::Tcp(listener)) => serve_tcp(app, listener).await,
+        Some(systemd::InheritedListener::Unix(listener)) => {
+            unix_socket::serve(app, listener).await;
+        }
+        None => match env::var("UNIX_SOCKET_PATH") {
+            Ok(socket_path) => unix_socket::serve(app, unix_socket::bind(&socket_path)).await,
+            Err(_) => {
+                let axum_address = env::var("AXUM_ADDRESS").unwrap_or_else(|_| {
+                    println!("AXUM_ADDRESS is not set, using 127.0.0.1");
+                    "127.0.0.1".to_string()
+                });
+                let app_port = env::var("PORT").unwrap_or_else(|_| {
+                    println!("PORT is not set, using 3000");
+                    "3000".to_string()
+                });
+                let axum_listener_address = format!("{}:{}", axum_address, app_port);
+                let address: SocketAddr = axum_listener_address.parse().expect("Invalid listener address");
+                let listener = tuning::bind(address).expect("Failed to bind to address");
+                serve_tcp(app, listener).await;
+            }
+        },
+    }
+}
+
+// Serve on a TCP listener, plus a plaintext listener on HTTP_ADDRESS next to
+// TLS for health checks and internal traffic
+async fn serve_tcp(app: Router, listener: std::net::TcpListener) {
+    // behind an ingress or load balancer every peer is the proxy, the client is
+    // in the trusted header. Requests without it keep the peer address.
+    let app = match admin::trusted_proxy_header().expect("Invalid TRUSTED_PROXY_HEADER") {
+        Some(header) => {
+            app.layer(middleware::from_fn_with_state(Arc::new(header), admin::connect_info_from_proxy))
+        }
+        None => app,
+    };
+    let mode = tls::TlsMode::from_env();
+    match env::var("HTTP_ADDRESS") {
+        Ok(http_address) if !matches!(mode, tls::TlsMode::Disabled) => {
+            let plain_address = http_address.parse().expect("Invalid HTTP_ADDRESS");
+            let plain_listener = tuning::bind(plain_address).expect("Failed to bind to HTTP_ADDRESS");
+            println!("Serving plain HTTP on {}", http_address);
+            tokio::join!(
+                tls::serve(app.clone(), plain_listener, tls::TlsMode::Disabled),
+                tls::serve(app, listener, mode),
+            );
+        }
+        _ => tls::serve(app, listener, mode).await,
+    }
+}
+
+// GraphQL handler for the public schema, mutations only over POST. Responses to
+// queries of the owner's content carry an ETag built from the content version and
+// the request, so unchanged content can be revalidated with If-None-Match without
+// running the query.
+async fn graphql_handler(
+    method: Method,
+    Extension(schema): Extension<Arc<Schema>>,
+    Extension(store): Extension<Arc<dyn store::DataStore>>,
+    Extension(tenancy): Extension<Arc<tenant::Tenancy>>,
+    Extension(key_guard): Extension<Arc<api_keys::ApiKeyGuard>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    JuniperRequest(request): JuniperRequest,
+) -> Result<Response, (StatusCode, &'static str)> {
+    // GET requests are cached and prefetched, so they mustn't change anything
+    if method == Method::GET && operation::mutates(&schema.schema, &request) {
+        return Err((StatusCode::METHOD_NOT_ALLOWED, "Mutations have to be sent with POST"));
+    }
+    let api_key = key_guard.authorize(&*store, &headers, api_keys::READ_SCOPE).await?;
+    let visitor = visitor::Visitor::new(connect_info.map(|ConnectInfo(peer)| peer.ip()), &headers);
+    let context = build_context(store, &tenancy, &headers, api_key, visitor, false).await;
+    error_reporting::tag_request(&request, context.owner_email.as_deref());
+    let revalidatable = operation::follows_content_version(&schema.schema, &request);
+    let etag = match &context.owner_email {
+        Some(owner_email) if revalidatable => store::content_version(&*context.store, owner_email)
+            .await
+            .ok()
+            .map(|version| {
+                let digest = Sha256::digest(format!("{:?}", request).as_bytes());
+                format!("W/\"{}-{:x}\"", version, digest)
+            }),
+        _ => None,
+    };
+    let client_has = |etag: &str| {
+        headers
+            .get(http::header::IF_NONE_MATCH)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.split(',').any(|candidate| candidate.trim() == etag))
+    };
+    if let Some(etag) = etag.as_deref().filter(|etag| client_has(etag)) {
+        return Ok((StatusCode::NOT_MODIFIED, [(http::header::ETAG, etag.to_string())]).into_response());
+    }
+    let response = request.execute(&*schema, &context).await;
+    let response = guardrails::GuardedResponse::new(response, &context);
+    // failed responses may succeed on a retry, so they aren't given an ETag
+    match etag.filter(|_| response.is_ok()) {
+        Some(etag) => Ok(([(http::header::ETAG, etag)], response).into_response()),
+        None => Ok(response.into_response()),
+    }
+}
+
+// GraphQL handler for the admin schema with mutations
+async fn admin_graphql_handler(
+    Extension(schema): Extension<Arc<AdminSchema>>,
+    Extension(store): Extension<Arc<dyn store::DataStore>>,
+    Extension(tenancy): Extension<Arc<tenant::Tenancy>>,
+    Extension(key_guard): Extension<Arc<api_keys::ApiKeyGuard>>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    JuniperRequest(request): JuniperRequest,
+) -> Result<guardrails::GuardedResponse, (StatusCode, &'static str)> {
+    let api_key = key_guard.authorize(&*store, &headers, api_keys::ADMIN_SCOPE).await?;
+    let visitor = visitor::Visitor::new(connect_info.map(|ConnectInfo(peer)| peer.ip()), &headers);
+    let context = build_context(store, &tenancy, &headers, api_key, visitor, true).await;
+    error_reporting::tag_request(&request, context.owner_email.as_deref());
+    let response = request.execute(&*schema, &context).await;
+    Ok(guardrails::GuardedResponse::new(response, &context))
+}
+
+// Resolve the owner for a request: API key first, then the owner header, then the Host
+async fn build_context(
+    store: Arc<dyn store::DataStore>,
+    tenancy: &tenant::Tenancy,
+    headers: &HeaderMap,
+    api_key: Option<api_keys::ApiKey>,
+    visitor: visitor::Visitor,
+    admin: bool,
+) -> Context {
+    let mut owner_email = match &api_key {
+        Some(key) if tenancy.is_multi() => Some(key.email.clone()),
+        _ => tenancy.resolve(headers),
+    };
+    let mut tenant = None;
+    // fall back to the tenant registered for the Host header
+    if tenancy.is_multi() && owner_email.is_none() {
+        if let Some(host) = tenant::request_host(headers) {
+            match tenant::find_tenant_by_host(&*store, &host).await {
+                Ok(found) => tenant = found,
+                Err(e) => eprintln!("Error resolving tenant for host {}: {}", host, e),
+            }
+        }
+        owner_email = tenant.as_ref().map(|tenant| tenant.email.clone());
+    }
+    let budget = Arc::new(guardrails::DocumentBudget::default());
+    let outage = Arc::new(guardrails::Outage::default());
+    Context {
+        store: Arc::new(guardrails::BudgetedStore::new(store, budget.clone(), outage.clone())),
+        owner_email,
+        multi_tenant: tenancy.is_multi(),
+        tenant,
+        api_key,
+        visitor,
+        admin,
+        accept_language: i18n::accept_language(headers),
+        locale_settings: Default::default(),
+        budget,
+        outage,
+        decode_warnings: Default::default(),
+    }
+}
+
+// basic handler that responds with a static string
+async fn root() -> &'static str {
+    "Hello, JM AAcera man!"
+}
+
+// Decode a stored document into a model, straight from BSON for Mongo documents
+fn value_to_type<T>(document: impl Into<store::StoredDocument>) -> Result<T, store::StoreError>
+where
     T: serde::de::DeserializeOwned,
 {
-    match serde_json::from_value(value) {
+    match document.into().decode() {
         Ok(result) => Ok(result),
-        Err(e) => Err(e.into()),
+        Err(e) => {
+            error_reporting::capture_deserialization_error::<T>(e.as_ref());
+            Err(e)
+        }
     }
 }
 
-async fn get_data_db(collection_name: String) -> Result<Vec<Value>, Error> {
-    // Connect to the database
-    let database = connect_to_database().await?;
+// Page size used when a paged query doesn't say otherwise, and the most a client can ask for
+const DEFAULT_PAGE_SIZE: i32 = 10;
+const MAX_PAGE_SIZE: i32 = 100;
 
-    // Fetch all documents from the "personals" collection
-    let values = find_all(&database, collection_name.as_str()).await?;
-    Ok(values)
+fn find_options(
+    limit: Option<i32>,
+    offset: Option<i32>,
+    sort: Vec<(String, store::SortDirection)>,
+) -> Result<store::FindOptions, FieldError> {
+    if limit.unwrap_or(0) < 0 || offset.unwrap_or(0) < 0 {
+        return Err(FieldError::new(
+            "Invalid pagination arguments",
+            graphql_value!({ "details": "limit and offset must not be negative" }),
+        ));
+    }
+    Ok(store::FindOptions {
+        sort,
+        offset: offset.unwrap_or(0) as u64,
+        limit: limit.map(|limit| limit.min(MAX_PAGE_SIZE) as u64),
+    })
 }
 
-
-pub struct MongoConnection {
-    client: Arc<Mutex<Client>>,
+async fn get_page_db(
+    store: &dyn store::DataStore,
+    collection_name: &str,
+    filter: Value,
+    options: store::FindOptions,
+) -> Result<Vec<store::StoredDocument>, store::StoreError> {
+    let result = store.find_raw(collection_name, filter, options).await;
+    if let Err(e) = &result {
+        error_reporting::capture_store_error(collection_name, e.as_ref());
+    }
+    result
 }
 
-impl MongoConnection {
-    pub async fn new() -> Result<Self, Error> {
-        let mongo_db_uri = env::var("MONGO_DB_URI")
-            .unwrap_or_else(|_| {
-                println!("MONGO_DB_URI is not set, using default value");
-                "default_value".to_string()
-            });
-        let client_options = ClientOptions::parse(mongo_db_uri).await?;
-        let client = Client::with_options(client_options)?;
+async fn get_one_db(
+    store: &dyn store::DataStore,
+    collection_name: &str,
+    filter: Value,
+) -> Result<Option<Value>, store::StoreError> {
+    let result = store.find_one(collection_name, filter).await;
+    if let Err(e) = &result {
+        error_reporting::capture_store_error(collection_name, e.as_ref());
+    }
+    result
+}
 
-        Ok(Self {
-            client: Arc::new(Mutex::new(client)),
-        })
+// A page of documents in the first of `locales` each is available in, plus how many
+// there are overall. When no document has a locale of its own, the default locale
+// is all there is to pick, so paging and counting happen in the store. Otherwise
+// variants are picked while streaming the matches, keeping only the page.
+async fn localized_page_db(
+    store: &dyn store::DataStore,
+    collection_name: &str,
+    filter: Value,
+    options: store::FindOptions,
+    locales: &[String],
+) -> Result<(Vec<store::StoredDocument>, u64), store::StoreError> {
+    let page = async {
+        let mut translated = false;
+        for locale in locales {
+            let mut with_locale = filter.clone();
+            with_locale[i18n::LOCALE_FIELD] = json!(locale);
+            if store.count(collection_name, with_locale).await? > 0 {
+                translated = true;
+                break;
+            }
+        }
+        if !translated {
+            let mut filter = filter;
+            filter[i18n::LOCALE_FIELD] = Value::Null;
+            let total_count = store.count(collection_name, filter.clone()).await?;
+            let page = store.find_raw(collection_name, filter, options).await?;
+            return Ok((page, total_count));
+        }
+        let store::FindOptions { sort, offset, limit } = options;
+        let sorted = store::FindOptions { sort, ..Default::default() };
+        let values = store.find_stream(collection_name, filter, sorted).await?;
+        let (page, total_count) = i18n::localize_page(values, locales, offset, limit).await?;
+        let page = page.into_iter().map(store::StoredDocument::from).collect();
+        Ok::<_, store::StoreError>((page, total_count))
+    };
+    let result = page.await;
+    if let Err(e) = &result {
+        error_reporting::capture_store_error(collection_name, e.as_ref());
     }
+    result
+}
 
-    pub fn db(&self, name: &str) -> Database {
-        self.client.lock().unwrap().database(name)
+async fn get_data_db(
+    store: &dyn store::DataStore,
+    collection_name: &str,
+    owner_email: &str,
+) -> Result<Vec<store::StoredDocument>, store::StoreError> {
+    // Fetch all documents of the owner from the collection
+    let filter = json!({ "email": owner_email });
+    let result = store.find_raw(collection_name, filter, Default::default()).await;
+    if let Err(e) = &result {
+        error_reporting::capture_store_error(collection_name, e.as_ref());
     }
-}
\ No newline at end of file
+    result
+}
+
+// Documents of the owner, each in the first of `locales` it is available in
+async fn get_localized_db(
+    store: &dyn store::DataStore,
+    collection_name: &str,
+    owner_email: &str,
+    locales: &[String],
+) -> Result<Vec<store::StoredDocument>, store::StoreError> {
+    let values = get_data_db(store, collection_name, owner_email).await?;
+    Ok(i18n::localize(values, locales))
+}
@@ -0,0 +1,149 @@
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use serde_json::Value;
+use sha1::{Digest, Sha1};
+use std::env;
+
+use super::{ImageVariant, MediaStorage, MediaUpload, UploadField};
+use crate::store::StoreError;
+
+const API_URL: &str = "https://api.cloudinary.com/v1_1";
+const DELIVERY_URL: &str = "https://res.cloudinary.com";
+// Cloudinary refuses signatures older than an hour
+const SIGNATURE_EXPIRY_SECS: i64 = 60 * 60;
+
+// Images hosted on Cloudinary, set up with the CLOUDINARY_URL its console shows,
+// cloudinary://<api key>:<api secret>@<cloud name>. Variants are made on the fly
+// from transformations in the URL.
+#[derive(Debug)]
+pub struct CloudinaryStorage {
+    cloud_name: String,
+    api_key: String,
+    api_secret: String,
+    client: reqwest::Client,
+}
+
+// Cloudinary appends the format to public ids itself, so keys lose their extension
+fn public_id(key: &str) -> &str {
+    let name_start = key.rfind('/').map_or(0, |slash| slash + 1);
+    match key[name_start..].rfind('.') {
+        Some(dot) if dot > 0 => &key[..name_start + dot],
+        _ => key,
+    }
+}
+
+impl CloudinaryStorage {
+    pub fn from_env() -> Result<Self, String> {
+        let url = env::var("CLOUDINARY_URL")
+            .map_err(|_| "CLOUDINARY_URL must be set for MEDIA_STORAGE=cloudinary".to_string())?;
+        let credentials = url
+            .strip_prefix("cloudinary://")
+            .and_then(|rest| rest.split_once('@'))
+            .and_then(|(keys, cloud_name)| Some((keys.split_once(':')?, cloud_name)));
+        let Some(((api_key, api_secret), cloud_name)) = credentials else {
+            let message = "CLOUDINARY_URL must look like cloudinary://<api key>:<api secret>@<cloud name>";
+            return Err(message.to_string());
+        };
+        Ok(Self {
+            cloud_name: cloud_name.to_string(),
+            api_key: api_key.to_string(),
+            api_secret: api_secret.to_string(),
+            client: reqwest::Client::new(),
+        })
+    }
+
+    // Signature of API parameters: sorted, joined like a query string without
+    // encoding, followed by the secret and hashed with SHA-1
+    fn sign(&self, params: &[(&str, String)]) -> String {
+        let mut params = params.to_vec();
+        params.sort();
+        let joined = params
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("&");
+        format!("{:x}", Sha1::digest(format!("{}{}", joined, self.api_secret).as_bytes()))
+    }
+
+    // Parameters of a signed API call on the image stored under `key`
+    fn signed_params(&self, key: &str) -> Vec<(&'static str, String)> {
+        let timestamp = Utc::now().timestamp();
+        let mut params = vec![("public_id", public_id(key).to_string()), ("timestamp", timestamp.to_string())];
+        let signature = self.sign(&params);
+        params.push(("api_key", self.api_key.clone()));
+        params.push(("signature", signature));
+        params
+    }
+}
+
+#[async_trait]
+impl MediaStorage for CloudinaryStorage {
+    fn name(&self) -> &'static str {
+        "cloudinary"
+    }
+
+    fn upload(&self, key: &str, _content_type: &str) -> Result<MediaUpload, StoreError> {
+        let params = self.signed_params(key);
+        Ok(MediaUpload {
+            method: "POST".to_string(),
+            url: format!("{}/{}/image/upload", API_URL, self.cloud_name),
+            headers: Vec::new(),
+            fields: params
+                .into_iter()
+                .map(|(name, value)| UploadField {
+                    name: name.to_string(),
+                    value,
+                })
+                .collect(),
+            storage_key: key.to_string(),
+            public_url: self.public_url(key),
+            expires_at: (Utc::now() + Duration::seconds(SIGNATURE_EXPIRY_SECS)).to_rfc3339(),
+        })
+    }
+
+    fn public_url(&self, key: &str) -> String {
+        format!("{}/{}/image/upload/{}", DELIVERY_URL, self.cloud_name, public_id(key))
+    }
+
+    // Scaled down to fit the size, never up, and in the format asked for or the
+    // best one the browser accepts
+    fn variant_url(&self, key: &str, variant: &ImageVariant) -> Option<String> {
+        let mut transformations = Vec::new();
+        if let Some(width) = variant.width {
+            transformations.push(format!("w_{}", width));
+        }
+        if let Some(height) = variant.height {
+            transformations.push(format!("h_{}", height));
+        }
+        if !transformations.is_empty() {
+            transformations.push("c_limit".to_string());
+        }
+        transformations.push(format!("f_{}", variant.format.as_deref().unwrap_or("auto")));
+        transformations.push("q_auto".to_string());
+        Some(format!(
+            "{}/{}/image/upload/{}/{}",
+            DELIVERY_URL,
+            self.cloud_name,
+            transformations.join(","),
+            public_id(key)
+        ))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StoreError> {
+        let params = self.signed_params(key);
+        let response: Value = self
+            .client
+            .post(format!("{}/{}/image/destroy", API_URL, self.cloud_name))
+            .form(&params)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        // deleting an image that is already gone is fine
+        match response["result"].as_str() {
+            Some("ok") | Some("not found") => Ok(()),
+            _ => Err(format!("Cloudinary didn't delete {}: {}", key, response).into()),
+        }
+    }
+}
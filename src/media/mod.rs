@@ -0,0 +1,359 @@
+use async_trait::async_trait;
+use juniper::{graphql_object, graphql_value, FieldError, Object, Value as GraphQLValue};
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::{env, fmt::Debug, sync::OnceLock};
+use tokio_stream::StreamExt;
+
+use crate::{
+    config,
+    store::{DataStore, FindOptions, StoreError},
+    validation::{self, Validator, MAX_NAME_CHARS},
+    Context,
+};
+
+mod cloudinary;
+mod s3;
+
+use cloudinary::CloudinaryStorage;
+use s3::S3Storage;
+
+// Formats images can be converted to by storages making variants
+const VARIANT_FORMATS: [&str; 5] = ["auto", "avif", "webp", "jpg", "png"];
+// Largest width or height of a variant, in pixels
+const MAX_VARIANT_SIZE: i32 = 4096;
+
+// An uploaded image or file, referenced from content by its public URL. The
+// file itself is stored wherever it was uploaded to, e.g. the media storage or
+// another host.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MediaFile {
+    #[serde(rename = "_id")]
+    pub id: ObjectId,
+    pub email: String,
+    pub url: String,
+    // Display name in the media library, renaming it leaves the URL as is
+    pub name: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(rename = "contentType", default)]
+    pub content_type: Option<String>,
+    // ISO 8601, e.g. 2024-03-01T09:00:00Z
+    #[serde(rename = "uploadedAt")]
+    pub uploaded_at: String,
+    // Where the media storage keeps the file, for files uploaded through it
+    #[serde(rename = "storageKey", default, skip_serializing_if = "Option::is_none")]
+    pub storage_key: Option<String>,
+}
+
+#[graphql_object(context = Context)]
+impl MediaFile {
+    fn id(&self) -> String {
+        self.id.to_hex()
+    }
+    fn url(&self) -> &str {
+        &self.url
+    }
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+    fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+    fn uploaded_at(&self) -> &str {
+        &self.uploaded_at
+    }
+    // URL of the image resized to fit `width` and `height` and converted to
+    // `format` (auto, avif, webp, jpg or png) by the media storage. Null for
+    // files it doesn't keep or when it can't make variants.
+    fn variant_url(
+        &self,
+        width: Option<i32>,
+        height: Option<i32>,
+        format: Option<String>,
+    ) -> Result<Option<String>, FieldError> {
+        let mut input = Validator::default();
+        if let Some(width) = width {
+            input.range("width", width, 1..=MAX_VARIANT_SIZE);
+        }
+        if let Some(height) = height {
+            input.range("height", height, 1..=MAX_VARIANT_SIZE);
+        }
+        if format.as_deref().is_some_and(|format| !VARIANT_FORMATS.contains(&format)) {
+            input.reject("format", "must be one of auto, avif, webp, jpg or png");
+        }
+        input.finish()?;
+        let (Some(key), Some(storage)) = (&self.storage_key, storage()) else {
+            return Ok(None);
+        };
+        let variant = ImageVariant {
+            width: width.map(|width| width as u32),
+            height: height.map(|height| height as u32),
+            format,
+        };
+        Ok(storage.variant_url(key, &variant))
+    }
+    // Portfolio documents using this file
+    async fn references(&self, context: &Context) -> Result<Vec<MediaReference>, FieldError> {
+        references(&*context.store, &self.email, &self.url).await.map_err(|err| {
+            FieldError::new(
+                "Failed to find media references",
+                graphql_value!({ "details": err.to_string() }),
+            )
+        })
+    }
+}
+
+// A portfolio document whose fields mention a media file's URL
+#[derive(Clone, Debug, juniper::GraphQLObject)]
+pub struct MediaReference {
+    pub collection: String,
+    // slug, title or name of the document, whichever it has
+    pub label: String,
+}
+
+// How the browser sends a new file straight to the media storage: a `method`
+// request to `url` with `headers` and the file as body, or, when there are
+// `fields`, a multipart form of them plus the file in a "file" field. The file
+// is added to the library by registering `publicUrl` with `storageKey`.
+#[derive(Debug, juniper::GraphQLObject)]
+pub struct MediaUpload {
+    pub method: String,
+    pub url: String,
+    pub headers: Vec<UploadField>,
+    pub fields: Vec<UploadField>,
+    pub storage_key: String,
+    pub public_url: String,
+    // ISO 8601, the upload is refused after this
+    pub expires_at: String,
+}
+
+#[derive(Debug, juniper::GraphQLObject)]
+pub struct UploadField {
+    pub name: String,
+    pub value: String,
+}
+
+// A resized or converted copy of an image, unset fields keep the original's
+#[derive(Debug, Default)]
+pub struct ImageVariant {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub format: Option<String>,
+}
+
+// Host for uploaded media files, files are addressed by the keys uploads are
+// signed for
+#[async_trait]
+pub trait MediaStorage: Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+    // A signed upload of a new file under `key`, valid for a limited time
+    fn upload(&self, key: &str, content_type: &str) -> Result<MediaUpload, StoreError>;
+    // Where the file under `key` is served from
+    fn public_url(&self, key: &str) -> String;
+    // URL of a variant of the image under `key`, None when the storage can't make them
+    fn variant_url(&self, _key: &str, _variant: &ImageVariant) -> Option<String> {
+        None
+    }
+    // Deleting a file that is already gone succeeds
+    async fn delete(&self, key: &str) -> Result<(), StoreError>;
+}
+
+static STORAGE: OnceLock<Option<Box<dyn MediaStorage>>> = OnceLock::new();
+
+// Set up the storage picked with MEDIA_STORAGE, s3 or cloudinary, at startup so
+// a misconfigured one is reported before anything is served. Returns its name,
+// None when files are hosted elsewhere and only registered in the library.
+pub fn init() -> Result<Option<&'static str>, String> {
+    let storage: Option<Box<dyn MediaStorage>> = match env::var("MEDIA_STORAGE").as_deref() {
+        Ok("s3") => Some(Box::new(S3Storage::from_env()?)),
+        Ok("cloudinary") => Some(Box::new(CloudinaryStorage::from_env()?)),
+        Ok(other) => return Err(format!("Unknown MEDIA_STORAGE {}, expected s3 or cloudinary", other)),
+        Err(_) => None,
+    };
+    Ok(STORAGE.get_or_init(|| storage).as_deref().map(|storage| storage.name()))
+}
+
+// The storage set up by init, None without one
+pub fn storage() -> Option<&'static dyn MediaStorage> {
+    STORAGE.get().and_then(Option::as_deref)
+}
+
+// Folder of the owner's uploads. Tenants share the storage, so keys outside it
+// belong to someone else.
+fn owner_prefix(owner_email: &str) -> String {
+    let hash = format!("{:x}", Sha256::digest(owner_email.as_bytes()));
+    format!("media/{}/", &hash[..16])
+}
+
+// Whether `key` is under the owner's folder, so the owner may register and delete it
+pub fn owns_key(owner_email: &str, key: &str) -> bool {
+    key.strip_prefix(&owner_prefix(owner_email))
+        .is_some_and(|rest| rest.split('/').all(|part| !part.is_empty() && part != ".."))
+}
+
+// Key for a new upload of `file_name` by the owner: unique, and keeping the name
+// readable in the storage's console
+pub fn upload_key(owner_email: &str, file_name: &str) -> String {
+    let (stem, extension) = match file_name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, Some(extension)),
+        _ => (file_name, None),
+    };
+    let mut name = validation::slugify(stem, MAX_NAME_CHARS);
+    if name.is_empty() {
+        name = "file".to_string();
+    }
+    let extension = extension
+        .map(|extension| extension.to_ascii_lowercase())
+        .filter(|extension| !extension.is_empty() && extension.chars().all(|c| c.is_ascii_alphanumeric()));
+    let prefix = owner_prefix(owner_email);
+    match extension {
+        Some(extension) => format!("{}{}/{}.{}", prefix, ObjectId::new().to_hex(), name, extension),
+        None => format!("{}{}/{}", prefix, ObjectId::new().to_hex(), name),
+    }
+}
+
+// ids are matched through their extended JSON form so every backend stores them alike
+fn file_filter(owner_email: &str, id: ObjectId) -> Value {
+    json!({ "_id": { "$oid": id.to_hex() }, "email": owner_email })
+}
+
+// Whether any string in `value`, at any depth, is `url`
+fn mentions(value: &Value, url: &str) -> bool {
+    match value {
+        Value::String(text) => text == url,
+        Value::Array(values) => values.iter().any(|value| mentions(value, url)),
+        Value::Object(fields) => fields.values().any(|value| mentions(value, url)),
+        _ => false,
+    }
+}
+
+// The owner's portfolio documents using `url`, e.g. as a project's background
+// image, a screenshot or an image block of a post. Every field is looked at, so
+// new places images are used in are counted without changes here. Documents in
+// the trash count too, restoring them would bring the reference back.
+pub async fn references(store: &dyn DataStore, owner_email: &str, url: &str) -> Result<Vec<MediaReference>, StoreError> {
+    let collections = config::collections();
+    let mut found = Vec::new();
+    for collection in collections.portfolio().into_iter().chain([collections.trash.as_str()]) {
+        let filter = json!({ "email": owner_email });
+        let mut documents = store.find_stream(collection, filter, FindOptions::default()).await?;
+        while let Some(document) = documents.next().await {
+            let document = document?;
+            if !mentions(&document, url) {
+                continue;
+            }
+            let label = ["slug", "title", "name", "label"]
+                .iter()
+                .find_map(|field| document.get(field).and_then(Value::as_str))
+                .unwrap_or_default();
+            found.push(MediaReference {
+                collection: collection.to_string(),
+                label: label.to_string(),
+            });
+        }
+    }
+    Ok(found)
+}
+
+// The owner's media files, of one tag when given, most recently uploaded first
+pub async fn list(store: &dyn DataStore, owner_email: &str, tag: Option<String>) -> Result<Vec<MediaFile>, StoreError> {
+    let mut filter = json!({ "email": owner_email });
+    if let Some(tag) = tag {
+        filter["tags"] = json!([tag]);
+    }
+    let mut files: Vec<MediaFile> = store
+        .find(&config::collections().media, filter)
+        .await?
+        .into_iter()
+        .map(serde_json::from_value)
+        .collect::<Result<_, _>>()?;
+    files.sort_by(|a, b| b.uploaded_at.cmp(&a.uploaded_at));
+    Ok(files)
+}
+
+// Add a file that was uploaded to the library
+pub async fn register(
+    store: &dyn DataStore,
+    owner_email: &str,
+    url: String,
+    name: String,
+    content_type: Option<String>,
+    tags: Vec<String>,
+    storage_key: Option<String>,
+) -> Result<MediaFile, StoreError> {
+    let file = MediaFile {
+        id: ObjectId::new(),
+        email: owner_email.to_string(),
+        url,
+        name,
+        tags,
+        content_type,
+        uploaded_at: DateTime::now().try_to_rfc3339_string()?,
+        storage_key,
+    };
+    store.insert_one(&config::collections().media, serde_json::to_value(&file)?).await?;
+    Ok(file)
+}
+
+pub async fn find(store: &dyn DataStore, owner_email: &str, id: ObjectId) -> Result<Option<MediaFile>, StoreError> {
+    let found = store.find_one(&config::collections().media, file_filter(owner_email, id)).await?;
+    Ok(found.map(serde_json::from_value).transpose()?)
+}
+
+// Change a file's name or tags, returns the file afterwards or None when there is no such file
+pub async fn update(
+    store: &dyn DataStore,
+    owner_email: &str,
+    id: ObjectId,
+    changes: Value,
+) -> Result<Option<MediaFile>, StoreError> {
+    if !store.update_one(&config::collections().media, file_filter(owner_email, id), changes).await? {
+        return Ok(None);
+    }
+    find(store, owner_email, id).await
+}
+
+// Remove a file from the library unless content still uses it. Files kept by the
+// media storage are deleted from it first, others are left where they were
+// uploaded to.
+pub async fn delete(store: &dyn DataStore, owner_email: &str, id: ObjectId) -> Result<bool, FieldError> {
+    let failed = |err: StoreError| {
+        FieldError::new(
+            "Failed to delete media file",
+            graphql_value!({ "details": err.to_string() }),
+        )
+    };
+    let Some(file) = find(store, owner_email, id).await.map_err(failed)? else {
+        return Ok(false);
+    };
+    let used_by = references(store, owner_email, &file.url).await.map_err(failed)?;
+    if !used_by.is_empty() {
+        let labels: Vec<GraphQLValue> = used_by
+            .iter()
+            .map(|reference| GraphQLValue::scalar(format!("{}: {}", reference.collection, reference.label)))
+            .collect();
+        let mut extensions = Object::with_capacity(2);
+        extensions.add_field("code", graphql_value!("MEDIA_IN_USE"));
+        extensions.add_field("references", GraphQLValue::list(labels));
+        return Err(FieldError::new(
+            format!("{} is used by {} documents", file.name, used_by.len()),
+            GraphQLValue::object(extensions),
+        ));
+    }
+    // keys outside the owner's folder, e.g. registered before uploads were kept
+    // apart, are left in the storage
+    let key = file.storage_key.as_deref().filter(|key| owns_key(owner_email, key));
+    if let (Some(key), Some(storage)) = (key, storage()) {
+        storage.delete(key).await.map_err(failed)?;
+    }
+    store
+        .delete_one(&config::collections().media, file_filter(owner_email, id))
+        .await
+        .map_err(failed)
+}
@@ -0,0 +1,159 @@
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use sha2::{Digest, Sha256};
+use std::env;
+
+use super::{MediaStorage, MediaUpload, UploadField};
+use crate::{
+    signing::{hex, hmac_sha256},
+    store::StoreError,
+};
+
+// How long a signed upload URL stays valid
+const UPLOAD_EXPIRY_SECS: i64 = 15 * 60;
+
+// A bucket on S3 or an S3 compatible service. S3_BUCKET, S3_ACCESS_KEY_ID and
+// S3_SECRET_ACCESS_KEY are required, S3_REGION defaults to us-east-1.
+// S3_ENDPOINT points at another service, e.g. R2 or MinIO, addressed path-style,
+// and S3_PUBLIC_URL is where files are served from, e.g. a CDN in front of the
+// bucket, the bucket itself by default. The bucket has to allow public reads.
+#[derive(Debug)]
+pub struct S3Storage {
+    bucket: String,
+    region: String,
+    endpoint: Option<String>,
+    public_url: Option<String>,
+    access_key_id: String,
+    secret_access_key: String,
+    client: reqwest::Client,
+}
+
+fn required(key: &str) -> Result<String, String> {
+    env::var(key).map_err(|_| format!("{} must be set for MEDIA_STORAGE=s3", key))
+}
+
+// Percent-encode everything but unreserved characters, and slashes when asked, as
+// SigV4 canonical requests expect
+fn uri_encode(text: &str, keep_slashes: bool) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(byte as char),
+            b'/' if keep_slashes => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+impl S3Storage {
+    pub fn from_env() -> Result<Self, String> {
+        Ok(Self {
+            bucket: required("S3_BUCKET")?,
+            region: env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+            endpoint: env::var("S3_ENDPOINT").ok().map(|url| url.trim_end_matches('/').to_string()),
+            public_url: env::var("S3_PUBLIC_URL").ok().map(|url| url.trim_end_matches('/').to_string()),
+            access_key_id: required("S3_ACCESS_KEY_ID")?,
+            secret_access_key: required("S3_SECRET_ACCESS_KEY")?,
+            client: reqwest::Client::new(),
+        })
+    }
+
+    // Scheme and host, plus the path of the object under `key`
+    fn location(&self, key: &str) -> (String, String) {
+        let key = uri_encode(key, true);
+        match &self.endpoint {
+            Some(endpoint) => (endpoint.clone(), format!("/{}/{}", uri_encode(&self.bucket, false), key)),
+            None => (
+                format!("https://{}.s3.{}.amazonaws.com", self.bucket, self.region),
+                format!("/{}", key),
+            ),
+        }
+    }
+
+    // URL `method` can be sent to on the object under `key` without credentials
+    // until it expires, signed with SigV4 in the query string. With a content
+    // type, requests have to send that Content-Type.
+    fn presign(&self, method: &str, key: &str, content_type: Option<&str>, expires_secs: i64) -> String {
+        let (origin, path) = self.location(key);
+        let host = origin.split("://").nth(1).unwrap_or(origin.as_str());
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let (canonical_headers, signed_headers) = match content_type {
+            Some(content_type) => (
+                format!("content-type:{}\nhost:{}\n", content_type, host),
+                "content-type;host",
+            ),
+            None => (format!("host:{}\n", host), "host"),
+        };
+        // already in the sorted order SigV4 asks for
+        let query = [
+            ("X-Amz-Algorithm", "AWS4-HMAC-SHA256".to_string()),
+            ("X-Amz-Credential", format!("{}/{}", self.access_key_id, scope)),
+            ("X-Amz-Date", amz_date.clone()),
+            ("X-Amz-Expires", expires_secs.to_string()),
+            ("X-Amz-SignedHeaders", signed_headers.to_string()),
+        ]
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, uri_encode(value, false)))
+        .collect::<Vec<_>>()
+        .join("&");
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\nUNSIGNED-PAYLOAD",
+            method, path, query, canonical_headers, signed_headers
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let signing_key = [date.as_str(), self.region.as_str(), "s3", "aws4_request"]
+            .iter()
+            .fold(format!("AWS4{}", self.secret_access_key).into_bytes(), |key, part| {
+                hmac_sha256(&key, part.as_bytes())
+            });
+        let signature = hex(&hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+        format!("{}{}?{}&X-Amz-Signature={}", origin, path, query, signature)
+    }
+}
+
+#[async_trait]
+impl MediaStorage for S3Storage {
+    fn name(&self) -> &'static str {
+        "s3"
+    }
+
+    fn upload(&self, key: &str, content_type: &str) -> Result<MediaUpload, StoreError> {
+        Ok(MediaUpload {
+            method: "PUT".to_string(),
+            url: self.presign("PUT", key, Some(content_type), UPLOAD_EXPIRY_SECS),
+            headers: vec![UploadField {
+                name: "Content-Type".to_string(),
+                value: content_type.to_string(),
+            }],
+            fields: Vec::new(),
+            storage_key: key.to_string(),
+            public_url: self.public_url(key),
+            expires_at: (Utc::now() + Duration::seconds(UPLOAD_EXPIRY_SECS)).to_rfc3339(),
+        })
+    }
+
+    fn public_url(&self, key: &str) -> String {
+        match &self.public_url {
+            Some(public_url) => format!("{}/{}", public_url, uri_encode(key, true)),
+            None => {
+                let (origin, path) = self.location(key);
+                format!("{}{}", origin, path)
+            }
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StoreError> {
+        let url = self.presign("DELETE", key, None, 60);
+        self.client.delete(url).send().await?.error_for_status()?;
+        Ok(())
+    }
+}
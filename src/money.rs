@@ -0,0 +1,115 @@
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::store::StoreError;
+
+// Digits after the decimal point of a currency's minor unit, e.g. cents for USD
+pub fn minor_digits(currency: &str) -> u32 {
+    match currency {
+        "BIF" | "CLP" | "ISK" | "JPY" | "KRW" | "PYG" | "UGX" | "VND" | "XAF" | "XOF" => 0,
+        "BHD" | "JOD" | "KWD" | "OMR" | "TND" => 3,
+        _ => 2,
+    }
+}
+
+fn symbol(currency: &str) -> Option<&'static str> {
+    match currency {
+        "USD" => Some("$"),
+        "EUR" => Some("€"),
+        "GBP" => Some("£"),
+        "JPY" => Some("¥"),
+        "PHP" => Some("₱"),
+        "KRW" => Some("₩"),
+        "INR" => Some("₹"),
+        _ => None,
+    }
+}
+
+// Format an amount in minor units for `locale`, e.g. 150000 PHP -> ₱1,500.00 in en
+pub fn format(amount_minor: i64, currency: &str, locale: &str) -> String {
+    let digits = minor_digits(currency);
+    let scale = 10_i64.pow(digits);
+    let (whole, fraction) = (amount_minor.abs() / scale, amount_minor.abs() % scale);
+    // thousands and decimal separators, and whether the symbol goes after the number
+    let language = locale.split('-').next().unwrap_or(locale);
+    let (group, decimal, symbol_after) = match language {
+        "de" | "es" | "it" | "pt" | "id" | "nl" => (".", ",", true),
+        "fr" => ("\u{202f}", ",", true),
+        _ => (",", ".", false),
+    };
+    let whole = whole.to_string();
+    let mut number = String::new();
+    for (index, digit) in whole.chars().enumerate() {
+        if index > 0 && (whole.len() - index) % 3 == 0 {
+            number.push_str(group);
+        }
+        number.push(digit);
+    }
+    if digits > 0 {
+        number.push_str(decimal);
+        number.push_str(&format!("{:0width$}", fraction, width = digits as usize));
+    }
+    let sign = if amount_minor < 0 { "-" } else { "" };
+    match (symbol(currency), symbol_after) {
+        (Some(symbol), false) => format!("{}{}{}", sign, symbol, number),
+        (Some(symbol), true) => format!("{}{} {}", sign, number, symbol),
+        (None, _) => format!("{}{} {}", sign, number, currency),
+    }
+}
+
+// Exchange rates against a base currency, as served by EXCHANGE_RATES_URL
+#[derive(Debug, Deserialize)]
+struct Rates {
+    base: String,
+    rates: HashMap<String, f64>,
+}
+
+impl Rates {
+    fn get(&self, currency: &str) -> Option<f64> {
+        if currency == self.base {
+            Some(1.0)
+        } else {
+            self.rates.get(currency).copied()
+        }
+    }
+}
+
+static RATES: Mutex<Option<(Instant, Arc<Rates>)>> = Mutex::new(None);
+
+// Rates are refetched once they are older than EXCHANGE_RATES_TTL seconds, an hour by default
+async fn rates() -> Result<Arc<Rates>, StoreError> {
+    let ttl = env::var("EXCHANGE_RATES_TTL")
+        .ok()
+        .and_then(|ttl| ttl.parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(3600));
+    let cached = RATES.lock().unwrap().clone();
+    if let Some((fetched, rates)) = cached {
+        if fetched.elapsed() < ttl {
+            return Ok(rates);
+        }
+    }
+    let url = env::var("EXCHANGE_RATES_URL").map_err(|_| "EXCHANGE_RATES_URL is not set")?;
+    let rates: Arc<Rates> = Arc::new(reqwest::get(&url).await?.error_for_status()?.json().await?);
+    *RATES.lock().unwrap() = Some((Instant::now(), rates.clone()));
+    Ok(rates)
+}
+
+// Convert an amount in minor units between currencies, rounding to the nearest minor unit
+pub async fn convert(amount_minor: i64, from: &str, to: &str) -> Result<i64, StoreError> {
+    if from == to {
+        return Ok(amount_minor);
+    }
+    let rates = rates().await?;
+    let (Some(from_rate), Some(to_rate)) = (rates.get(from), rates.get(to)) else {
+        return Err(format!("No exchange rate from {} to {}", from, to).into());
+    };
+    let amount = amount_minor as f64 / 10_f64.powi(minor_digits(from) as i32);
+    let converted = amount * to_rate / from_rate;
+    Ok((converted * 10_f64.powi(minor_digits(to) as i32)).round() as i64)
+}
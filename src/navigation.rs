@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+// Where a menu item is shown, hidden items are kept but left out of the navigation query
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize, juniper::GraphQLEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    #[default]
+    All,
+    Desktop,
+    Mobile,
+    Hidden,
+}
+
+// An entry of a header or footer menu, identified by its menu and href
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+pub struct NavigationItem {
+    #[graphql(ignore)]
+    #[serde(default)]
+    pub email: String,
+    // e.g. header or footer
+    pub menu: String,
+    pub label: String,
+    pub href: String,
+    #[serde(default)]
+    pub order: i32,
+    #[serde(default)]
+    pub visibility: Visibility,
+}
@@ -0,0 +1,121 @@
+use axum::{
+    body::{Body, Bytes},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension,
+};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::{
+    api_keys, blog, config,
+    store::{DataStore, FindOptions, SortDirection, StoreError},
+    tenant::{self, Tenancy},
+};
+
+// Documents read from the store per round trip while streaming
+const PAGE_SIZE: u64 = 100;
+
+// One collection to stream, lines are tagged with the collection name when `tagged`
+struct Source {
+    collection: String,
+    filter: Value,
+    sort: Vec<(String, SortDirection)>,
+    tagged: bool,
+}
+
+// Newline delimited JSON body, written a page at a time so the whole result is
+// never held in memory. A store error ends the body early.
+fn body(store: Arc<dyn DataStore>, sources: Vec<Source>) -> Response {
+    let (sender, receiver) = mpsc::channel::<Result<Bytes, StoreError>>(4);
+    tokio::spawn(async move {
+        for source in sources {
+            let mut offset = 0;
+            loop {
+                let options = FindOptions {
+                    sort: source.sort.clone(),
+                    offset,
+                    limit: Some(PAGE_SIZE),
+                };
+                let page = match store.find_with(&source.collection, source.filter.clone(), options).await {
+                    Ok(page) => page,
+                    Err(e) => {
+                        eprintln!("Error streaming {}: {}", source.collection, e);
+                        let _ = sender.send(Err(e)).await;
+                        return;
+                    }
+                };
+                let count = page.len() as u64;
+                for document in page {
+                    let line = if source.tagged {
+                        json!({ "collection": source.collection, "document": document })
+                    } else {
+                        document
+                    };
+                    let mut bytes = line.to_string().into_bytes();
+                    bytes.push(b'\n');
+                    // the client went away
+                    if sender.send(Ok(Bytes::from(bytes))).await.is_err() {
+                        return;
+                    }
+                }
+                if count < PAGE_SIZE {
+                    break;
+                }
+                offset += count;
+            }
+        }
+    });
+    (
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(ReceiverStream::new(receiver)),
+    )
+        .into_response()
+}
+
+// GET /blog/archive.ndjson: every published post of the owner, newest first
+pub async fn blog_archive(
+    Extension(store): Extension<Arc<dyn DataStore>>,
+    Extension(tenancy): Extension<Arc<Tenancy>>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, &'static str)> {
+    let owner_email = tenant::resolve_owner(&*store, &tenancy, &headers)
+        .await
+        .ok_or((StatusCode::NOT_FOUND, "Unknown portfolio owner"))?;
+    let sources = vec![Source {
+        collection: config::collections().blog_posts.clone(),
+        filter: blog::published_filter(&owner_email),
+        sort: vec![("publishedAt".to_string(), SortDirection::Descending)],
+        tagged: false,
+    }];
+    Ok(body(store, sources))
+}
+
+// GET /admin/export.ndjson: every portfolio document of the owner, one
+// { collection, document } object per line, like the export subcommand
+pub async fn export(
+    Extension(store): Extension<Arc<dyn DataStore>>,
+    Extension(tenancy): Extension<Arc<Tenancy>>,
+    Extension(key_guard): Extension<Arc<api_keys::ApiKeyGuard>>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, &'static str)> {
+    let api_key = key_guard.authorize(&*store, &headers, api_keys::ADMIN_SCOPE).await?;
+    let owner_email = match api_key {
+        Some(key) if tenancy.is_multi() => Some(key.email),
+        _ => tenant::resolve_owner(&*store, &tenancy, &headers).await,
+    }
+    .ok_or((StatusCode::NOT_FOUND, "Unknown portfolio owner"))?;
+    let sources = config::collections()
+        .portfolio()
+        .into_iter()
+        .map(|collection| Source {
+            collection: collection.to_string(),
+            filter: json!({ "email": owner_email }),
+            sort: Vec::new(),
+            tagged: true,
+        })
+        .collect();
+    Ok(body(store, sources))
+}
@@ -0,0 +1,51 @@
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+
+use crate::{
+    settings,
+    store::{DataStore, StoreError},
+};
+
+const DEFAULT_MESSAGE: &str = "This page doesn't exist.";
+
+// The `notFound` section of the site settings, a message and fun fact are
+// picked at random on every request so the 404 page rotates
+#[derive(Debug, Default, Deserialize)]
+struct NotFoundSettings {
+    #[serde(default)]
+    messages: Vec<String>,
+    #[serde(default)]
+    links: Vec<SuggestedLink>,
+    #[serde(rename = "funFacts", default)]
+    fun_facts: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, juniper::GraphQLObject)]
+pub struct SuggestedLink {
+    pub label: String,
+    pub href: String,
+}
+
+#[derive(Clone, Debug, juniper::GraphQLObject)]
+pub struct NotFoundContent {
+    pub message: String,
+    pub suggested_links: Vec<SuggestedLink>,
+    pub fun_fact: Option<String>,
+}
+
+pub async fn content(store: &dyn DataStore, owner_email: &str) -> Result<NotFoundContent, StoreError> {
+    let section = settings::load(store, owner_email)
+        .await?
+        .and_then(|mut settings| settings.get_mut("notFound").map(serde_json::Value::take));
+    let section: NotFoundSettings = section.map(serde_json::from_value).transpose()?.unwrap_or_default();
+    let mut rng = rand::thread_rng();
+    Ok(NotFoundContent {
+        message: section
+            .messages
+            .choose(&mut rng)
+            .cloned()
+            .unwrap_or_else(|| DEFAULT_MESSAGE.to_string()),
+        suggested_links: section.links,
+        fun_fact: section.fun_facts.choose(&mut rng).cloned(),
+    })
+}
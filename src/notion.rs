@@ -0,0 +1,350 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Map, Value};
+use std::{env, sync::Arc, time::Duration};
+
+use crate::{
+    blog::{BlogPost, BlogPostStatus},
+    config,
+    content::{CodeBlock, ContentBlock, HeadingBlock, ImageBlock, ListBlock, ParagraphBlock},
+    rules::{self, ContentRule},
+    store::{DataStore, StoreError},
+    validation::{self, MAX_SLUG_CHARS},
+};
+
+const API_URL: &str = "https://api.notion.com/v1";
+const API_VERSION: &str = "2022-06-28";
+
+// Where posts are pulled from: NOTION_TOKEN is the secret of an internal
+// integration the database is shared with, NOTION_DATABASE_ID the database and
+// NOTION_OWNER the portfolio owner the posts are created for, USER_EMAIL by default
+#[derive(Clone, Debug)]
+pub struct NotionConfig {
+    token: String,
+    database_id: String,
+    pub owner_email: String,
+}
+
+impl NotionConfig {
+    // None when Notion sync isn't set up
+    pub fn from_env() -> Option<Self> {
+        Some(Self {
+            token: env::var("NOTION_TOKEN").ok()?,
+            database_id: env::var("NOTION_DATABASE_ID").ok()?,
+            owner_email: env::var("NOTION_OWNER").or_else(|_| env::var("USER_EMAIL")).ok()?,
+        })
+    }
+}
+
+// A page of the database that couldn't be imported
+#[derive(Debug, juniper::GraphQLObject)]
+pub struct NotionSyncFailure {
+    pub page_id: String,
+    pub title: String,
+    pub message: String,
+}
+
+#[derive(Debug, Default, juniper::GraphQLObject)]
+pub struct NotionSyncResult {
+    pub created: i32,
+    pub updated: i32,
+    // Pages not edited since they were last imported
+    pub unchanged: i32,
+    pub failed: Vec<NotionSyncFailure>,
+}
+
+// One page of a paginated Notion API response
+#[derive(Deserialize)]
+struct Listing<T> {
+    results: Vec<T>,
+    has_more: bool,
+    next_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Page {
+    id: String,
+    last_edited_time: String,
+    properties: Map<String, Value>,
+}
+
+impl Page {
+    // The database's title property, whatever it is named
+    fn title(&self) -> String {
+        self.properties
+            .values()
+            .find(|property| property["type"] == "title")
+            .map(|property| plain_text(&property["title"]))
+            .unwrap_or_default()
+    }
+
+    // A text property such as Slug or Excerpt, None when missing or empty
+    fn text(&self, name: &str) -> Option<String> {
+        let text = plain_text(&self.properties.get(name)?["rich_text"]);
+        Some(text).filter(|text| !text.trim().is_empty())
+    }
+
+    // Names of the Tags multi-select
+    fn tags(&self) -> Vec<String> {
+        let options = self.properties.get("Tags").and_then(|tags| tags["multi_select"].as_array());
+        options
+            .into_iter()
+            .flatten()
+            .filter_map(|option| option["name"].as_str().map(str::to_string))
+            .collect()
+    }
+}
+
+fn plain_text(rich_text: &Value) -> String {
+    rich_text
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|part| part["plain_text"].as_str())
+        .collect()
+}
+
+// Content blocks of a page's top level blocks. Nested blocks and block types
+// without a counterpart, e.g. callouts, tables and embeds, are left out.
+fn content(blocks: &[Value]) -> Vec<ContentBlock> {
+    let mut content = Vec::new();
+    for block in blocks {
+        let kind = block["type"].as_str().unwrap_or_default();
+        let body = &block[kind];
+        let text = plain_text(&body["rich_text"]);
+        let converted = match kind {
+            "paragraph" if !text.trim().is_empty() => ContentBlock::Paragraph(ParagraphBlock { text }),
+            "heading_1" | "heading_2" | "heading_3" => ContentBlock::Heading(HeadingBlock {
+                text,
+                level: kind["heading_".len()..].parse().unwrap_or(2),
+            }),
+            "bulleted_list_item" | "numbered_list_item" => {
+                let ordered = kind == "numbered_list_item";
+                // Notion has no list block, consecutive items make up one list
+                if let Some(ContentBlock::List(list)) = content.last_mut() {
+                    if list.ordered == ordered {
+                        list.items.push(text);
+                        continue;
+                    }
+                }
+                ContentBlock::List(ListBlock { items: vec![text], ordered })
+            }
+            "code" => ContentBlock::Code(CodeBlock {
+                code: text,
+                language: body["language"]
+                    .as_str()
+                    .filter(|language| *language != "plain text")
+                    .map(str::to_string),
+            }),
+            "image" => {
+                // files uploaded to Notion have links expiring after an hour,
+                // link images from elsewhere to keep them showing
+                let Some(src) = body["external"]["url"].as_str().or(body["file"]["url"].as_str()) else {
+                    continue;
+                };
+                if body["type"] == "file" {
+                    tracing::warn!(src, "Imported an image hosted by Notion, its link expires");
+                }
+                let caption = plain_text(&body["caption"]);
+                ContentBlock::Image(ImageBlock {
+                    src: src.to_string(),
+                    alt: caption.clone(),
+                    caption: Some(caption).filter(|caption| !caption.is_empty()),
+                    width: None,
+                    height: None,
+                    blurhash: None,
+                })
+            }
+            _ => continue,
+        };
+        content.push(converted);
+    }
+    content
+}
+
+// Every result of a paginated endpoint, `request` sends the request for one cursor
+async fn fetch_all<T, F>(request: F) -> Result<Vec<T>, StoreError>
+where
+    T: DeserializeOwned,
+    F: Fn(Option<&str>) -> reqwest::RequestBuilder,
+{
+    let mut results = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let listing: Listing<T> = request(cursor.as_deref())
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        results.extend(listing.results);
+        match listing.next_cursor.filter(|_| listing.has_more) {
+            Some(next) => cursor = Some(next),
+            None => return Ok(results),
+        }
+    }
+}
+
+fn client(config: &NotionConfig) -> Result<reqwest::Client, StoreError> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert(reqwest::header::AUTHORIZATION, format!("Bearer {}", config.token).parse()?);
+    headers.insert("notion-version", API_VERSION.parse()?);
+    Ok(reqwest::Client::builder().default_headers(headers).build()?)
+}
+
+// Import the pages of the configured database as blog posts of its owner, found
+// again by page id on later syncs. New pages become drafts, edited pages
+// update their post's title, excerpt, tags and content but never its slug or
+// status, so posts are still published through this API. Pages deleted in
+// Notion leave their posts alone.
+pub async fn sync(store: &dyn DataStore, config: &NotionConfig) -> Result<NotionSyncResult, StoreError> {
+    let client = client(config)?;
+    let query_url = format!("{}/databases/{}/query", API_URL, config.database_id);
+    let pages: Vec<Page> = fetch_all(|cursor| {
+        let mut body = json!({ "page_size": 100 });
+        if let Some(cursor) = cursor {
+            body["start_cursor"] = json!(cursor);
+        }
+        client.post(&query_url).json(&body)
+    })
+    .await?;
+    let owner_rules = rules::load(store, &config.owner_email).await?;
+    let mut result = NotionSyncResult::default();
+    for page in pages {
+        match import(store, &client, config, &owner_rules, &page).await {
+            Ok(Imported::Created) => result.created += 1,
+            Ok(Imported::Updated) => result.updated += 1,
+            Ok(Imported::Unchanged) => result.unchanged += 1,
+            Err(e) => result.failed.push(NotionSyncFailure {
+                page_id: page.id.clone(),
+                title: page.title(),
+                message: e.to_string(),
+            }),
+        }
+    }
+    Ok(result)
+}
+
+enum Imported {
+    Created,
+    Updated,
+    Unchanged,
+}
+
+async fn import(
+    store: &dyn DataStore,
+    client: &reqwest::Client,
+    config: &NotionConfig,
+    owner_rules: &[ContentRule],
+    page: &Page,
+) -> Result<Imported, StoreError> {
+    let collection = &config::collections().blog_posts;
+    let filter = json!({ "email": config.owner_email, "notionPageId": page.id });
+    let existing = store.find_one(collection, filter.clone()).await?;
+    if existing
+        .as_ref()
+        .is_some_and(|post| post["notionEditedAt"] == page.last_edited_time.as_str())
+    {
+        return Ok(Imported::Unchanged);
+    }
+    let title = page.title();
+    if title.trim().is_empty() {
+        return Err("The page has no title".into());
+    }
+    let blocks_url = format!("{}/blocks/{}/children?page_size=100", API_URL, page.id);
+    let blocks: Vec<Value> = fetch_all(|cursor| match cursor {
+        Some(cursor) => client.get(format!("{}&start_cursor={}", blocks_url, cursor)),
+        None => client.get(&blocks_url),
+    })
+    .await?;
+    let now = DateTime::now().try_to_rfc3339_string()?;
+    let (mut post, created): (BlogPost, bool) = match existing {
+        Some(existing) => (serde_json::from_value(existing)?, false),
+        None => {
+            let slug = page.text("Slug").unwrap_or_else(|| validation::slugify(&title, MAX_SLUG_CHARS));
+            let variant = json!({ "email": config.owner_email, "slug": slug, "locale": null });
+            if slug.is_empty() || store.find_one(collection, variant).await?.is_some() {
+                return Err(format!("The slug {:?} is empty or used by another post, set a Slug", slug).into());
+            }
+            let post = BlogPost {
+                id: Some(ObjectId::new()),
+                email: config.owner_email.clone(),
+                title: String::new(),
+                slug,
+                excerpt: None,
+                // drafts keep their creation time as publishedAt
+                published_at: now.clone(),
+                status: BlogPostStatus::Draft.as_str().to_string(),
+                tags: Vec::new(),
+                content: Vec::new(),
+                locale: None,
+                created_at: Some(now.clone()),
+                updated_at: None,
+                working_copy: None,
+                syndication: Vec::new(),
+            };
+            (post, true)
+        }
+    };
+    post.title = title;
+    post.excerpt = page.text("Excerpt");
+    post.tags = page.tags();
+    post.content = content(&blocks);
+    post.updated_at = Some(now);
+    post.working_copy = None;
+    let mut document = serde_json::to_value(&post)?;
+    let broken: Vec<&str> = rules::broken(owner_rules, collection, &document)
+        .map(|rule| rule.name.as_str())
+        .collect();
+    if !broken.is_empty() {
+        return Err(format!("The post would break content rules: {}", broken.join(", ")).into());
+    }
+    document["notionPageId"] = json!(page.id);
+    document["notionEditedAt"] = json!(page.last_edited_time);
+    if created {
+        store.insert_one(collection, document).await?;
+        return Ok(Imported::Created);
+    }
+    let changes = json!({
+        "title": post.title,
+        "excerpt": post.excerpt,
+        "tags": post.tags,
+        "content": post.content,
+        "updatedAt": post.updated_at,
+        "workingCopy": null,
+        "notionEditedAt": page.last_edited_time,
+    });
+    store.update_one(collection, filter, changes).await?;
+    Ok(Imported::Updated)
+}
+
+// Sync every NOTION_SYNC_INTERVAL seconds, 15 minutes by default, when Notion is
+// configured. 0 leaves syncing to the syncNotion mutation.
+pub fn spawn(store: Arc<dyn DataStore>) {
+    let Some(config) = NotionConfig::from_env() else {
+        return;
+    };
+    let interval = env::var("NOTION_SYNC_INTERVAL")
+        .ok()
+        .map(|seconds| seconds.parse().expect("NOTION_SYNC_INTERVAL must be a number of seconds"))
+        .unwrap_or(15 * 60);
+    if interval == 0 {
+        println!("NOTION_SYNC_INTERVAL is 0, Notion is only synced on demand");
+        return;
+    }
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+            match sync(&*store, &config).await {
+                Ok(result) => tracing::info!(
+                    created = result.created,
+                    updated = result.updated,
+                    unchanged = result.unchanged,
+                    failed = result.failed.len(),
+                    "Synced blog posts from Notion"
+                ),
+                Err(e) => tracing::error!(error = %e, "Notion sync failed"),
+            }
+        }
+    });
+}
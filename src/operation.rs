@@ -0,0 +1,68 @@
+use juniper::{
+    http::GraphQLBatchRequest, parser::parse_document_source, DefaultScalarValue, Definition, OperationType,
+    SchemaType, Selection,
+};
+
+// Fields answered from visitor activity, page views, events and experiment
+// exposures, which change without raising the content version
+const ACTIVITY_FIELDS: [&str; 6] = [
+    "popularContent",
+    "dashboard",
+    "visitorsByCountry",
+    "trafficSources",
+    "eventCounts",
+    "experimentResults",
+];
+
+fn queries(request: &GraphQLBatchRequest) -> Vec<&str> {
+    match request {
+        GraphQLBatchRequest::Single(request) => vec![request.query.as_str()],
+        GraphQLBatchRequest::Batch(requests) => requests.iter().map(|request| request.query.as_str()).collect(),
+    }
+}
+
+// Whether any field of `selections` is one of `fields`. Named fragments are
+// definitions of the document and looked at on their own.
+fn selects(selections: &[Selection<'_, DefaultScalarValue>], fields: &[&str]) -> bool {
+    selections.iter().any(|selection| match selection {
+        Selection::Field(field) => {
+            fields.contains(&field.item.name.item)
+                || field.item.selection_set.as_deref().is_some_and(|nested| selects(nested, fields))
+        }
+        Selection::InlineFragment(fragment) => selects(&fragment.item.selection_set, fields),
+        Selection::FragmentSpread(_) => false,
+    })
+}
+
+// Whether a document of the request defines a mutation or subscription.
+// Documents that don't parse count as not, running them only reports the error.
+pub fn mutates(schema: &SchemaType<'_, DefaultScalarValue>, request: &GraphQLBatchRequest) -> bool {
+    queries(request).into_iter().any(|query| {
+        parse_document_source(query, schema).is_ok_and(|document| {
+            document.iter().any(|definition| {
+                matches!(definition, Definition::Operation(operation)
+                    if operation.item.operation_type != OperationType::Query)
+            })
+        })
+    })
+}
+
+// Whether the request only queries the owner's content, so its response changes
+// only with the content version and can be revalidated with an ETag
+pub fn follows_content_version(
+    schema: &SchemaType<'_, DefaultScalarValue>,
+    request: &GraphQLBatchRequest,
+) -> bool {
+    queries(request).into_iter().all(|query| {
+        let Ok(document) = parse_document_source(query, schema) else {
+            return false;
+        };
+        document.iter().all(|definition| match definition {
+            Definition::Operation(operation) => {
+                operation.item.operation_type == OperationType::Query
+                    && !selects(&operation.item.selection_set, &ACTIVITY_FIELDS)
+            }
+            Definition::Fragment(fragment) => !selects(&fragment.item.selection_set, &ACTIVITY_FIELDS),
+        })
+    })
+}
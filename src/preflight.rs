@@ -0,0 +1,234 @@
+use mongodb::options::ConnectionString;
+use std::{env, fmt, net::IpAddr, sync::Arc};
+
+use crate::{
+    admin, config, media,
+    store::{self, DataStore},
+    validation::looks_like_email,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Outcome {
+    Ok,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Outcome::Ok => "ok",
+            Outcome::Warning => "warn",
+            Outcome::Error => "error",
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Check {
+    subject: String,
+    outcome: Outcome,
+    message: String,
+}
+
+// What a deployment is missing to run, gathered into one report instead of
+// failing on the first problem
+#[derive(Debug, Default)]
+pub struct Report {
+    checks: Vec<Check>,
+}
+
+impl Report {
+    fn push(&mut self, subject: &str, outcome: Outcome, message: impl Into<String>) {
+        self.checks.push(Check {
+            subject: subject.to_string(),
+            outcome,
+            message: message.into(),
+        });
+    }
+
+    // The environment variables read at startup, checked without connecting to anything
+    pub fn environment() -> Self {
+        let mut report = Self::default();
+        match env::var("PORT") {
+            Ok(port) => match port.parse::<u16>() {
+                Ok(_) => report.push("PORT", Outcome::Ok, port),
+                Err(_) => {
+                    report.push("PORT", Outcome::Error, format!("{:?} is not a port number", port))
+                }
+            },
+            Err(_) => report.push("PORT", Outcome::Ok, "not set, using 3000"),
+        }
+        if let Ok(address) = env::var("AXUM_ADDRESS") {
+            match address.parse::<IpAddr>() {
+                Ok(_) => report.push("AXUM_ADDRESS", Outcome::Ok, address),
+                Err(_) => {
+                    let message = format!("{:?} is not an IP address", address);
+                    report.push("AXUM_ADDRESS", Outcome::Error, message)
+                }
+            }
+        }
+        report.data_store();
+        report.tenancy();
+        report.admin();
+        report.proxy();
+        report.visitor();
+        report.media();
+        report
+    }
+
+    fn data_store(&mut self) {
+        let uri = env::var("MONGO_DB_URI");
+        let backend = env::var("DATA_STORE")
+            .unwrap_or_else(|_| if uri.is_ok() { "mongo" } else { "sqlite" }.to_string());
+        match backend.as_str() {
+            "mongo" => match uri {
+                // the URI holds the password, so only whether it parsed is reported
+                Ok(uri) => match ConnectionString::parse(&uri) {
+                    Ok(_) => self.push("MONGO_DB_URI", Outcome::Ok, "valid connection string"),
+                    Err(e) => self.push(
+                        "MONGO_DB_URI",
+                        Outcome::Error,
+                        format!("not a valid connection string: {}", e.kind),
+                    ),
+                },
+                Err(_) => self.push("MONGO_DB_URI", Outcome::Error, "not set, but DATA_STORE is mongo"),
+            },
+            "postgres" if env::var("DATABASE_URL").is_err() => {
+                self.push("DATABASE_URL", Outcome::Error, "not set, but DATA_STORE is postgres")
+            }
+            "postgres" | "sqlite" | "file" => self.push("DATA_STORE", Outcome::Ok, backend),
+            other => self.push(
+                "DATA_STORE",
+                Outcome::Error,
+                format!("unknown backend {:?}, expected mongo, postgres, sqlite or file", other),
+            ),
+        }
+    }
+
+    fn tenancy(&mut self) {
+        match env::var("TENANT_MODE").as_deref() {
+            Ok("multi") => self.push("TENANT_MODE", Outcome::Ok, "multi"),
+            Ok("single") | Err(_) => match env::var("USER_EMAIL") {
+                Ok(email) if looks_like_email(&email) => self.push("USER_EMAIL", Outcome::Ok, email),
+                Ok(email) => {
+                    let message = format!("{:?} is not an email address", email);
+                    self.push("USER_EMAIL", Outcome::Error, message)
+                }
+                Err(_) => self.push(
+                    "USER_EMAIL",
+                    Outcome::Error,
+                    "not set, single tenant mode needs the portfolio owner's email",
+                ),
+            },
+            Ok(other) => self.push(
+                "TENANT_MODE",
+                Outcome::Error,
+                format!("unknown mode {:?}, expected single or multi", other),
+            ),
+        }
+    }
+
+    fn admin(&mut self) {
+        match admin::AdminAllowlist::from_env() {
+            Ok(_) => {
+                let networks = env::var("ADMIN_ALLOWED_IPS").unwrap_or_default();
+                self.push("ADMIN_ALLOWED_IPS", Outcome::Ok, networks)
+            }
+            Err(message) => self.push("ADMIN_ALLOWED_IPS", Outcome::Error, message),
+        }
+    }
+
+    fn proxy(&mut self) {
+        match admin::trusted_proxy_header() {
+            Ok(Some(header)) => self.push("TRUSTED_PROXY_HEADER", Outcome::Ok, header.as_str()),
+            Ok(None) => {}
+            Err(message) => self.push("TRUSTED_PROXY_HEADER", Outcome::Error, message),
+        }
+    }
+
+    fn visitor(&mut self) {
+        match env::var("VISITOR_SALT") {
+            Ok(salt) if !salt.is_empty() => self.push("VISITOR_SALT", Outcome::Ok, "set"),
+            _ => self.push(
+                "VISITOR_SALT",
+                Outcome::Warning,
+                "not set, visitors can endorse skills and like projects again after every restart",
+            ),
+        }
+    }
+
+    fn media(&mut self) {
+        match media::init() {
+            Ok(Some(storage)) => self.push("MEDIA_STORAGE", Outcome::Ok, storage),
+            Ok(None) => {}
+            Err(message) => self.push("MEDIA_STORAGE", Outcome::Error, message),
+        }
+    }
+
+    // Connect to the configured store and check it, None when the connection
+    // couldn't even be set up
+    pub async fn connect(&mut self) -> Option<Arc<dyn DataStore>> {
+        match store::connect().await {
+            Ok(store) => {
+                self.store(&*store).await;
+                Some(store)
+            }
+            Err(e) => {
+                self.push("data store", Outcome::Error, format!("failed to connect: {}", e));
+                None
+            }
+        }
+    }
+
+    // Whether the store answers and has the collections the API reads
+    async fn store(&mut self, store: &dyn DataStore) {
+        let database = config::database();
+        let names = match store.collection_names().await {
+            Ok(names) => names,
+            Err(e) => {
+                self.push(store.backend_name(), Outcome::Error, format!("unreachable: {}", e));
+                return;
+            }
+        };
+        self.push(
+            store.backend_name(),
+            Outcome::Ok,
+            format!("connected to {}", database.database_name),
+        );
+        for name in database.collections.portfolio() {
+            if names.iter().any(|existing| existing == name) {
+                self.push(name, Outcome::Ok, "exists");
+            } else {
+                // a fresh deployment has none of them until it is seeded
+                self.push(
+                    name,
+                    Outcome::Warning,
+                    "missing, seed it or check MONGO_DB_NAME and COLLECTION_* overrides",
+                );
+            }
+        }
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.checks.iter().any(|check| check.outcome == Outcome::Error)
+    }
+
+    pub fn all_ok(&self) -> bool {
+        self.checks.iter().all(|check| check.outcome == Outcome::Ok)
+    }
+
+    pub fn print(&self) {
+        println!("Configuration check:");
+        let width = self.checks.iter().map(|check| check.subject.len()).max().unwrap_or(0);
+        for check in &self.checks {
+            println!(
+                "  {:<5}  {:<width$}  {}",
+                check.outcome.to_string(),
+                check.subject,
+                check.message,
+                width = width
+            );
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use axum::{
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::Arc;
+
+use crate::{
+    config, error_reporting,
+    store::{DataStore, StoreError},
+    tenant::{self, Tenancy},
+};
+
+// A moved page, e.g. an old feed URL, pointing at where it lives now
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+pub struct Redirect {
+    #[serde(rename = "fromPath")]
+    pub from_path: String,
+    #[serde(rename = "toUrl")]
+    pub to_url: String,
+    // 301, 302, 303, 307 or 308, permanent (301) when unset
+    #[serde(rename = "statusCode", default)]
+    pub status_code: Option<i32>,
+}
+
+impl Redirect {
+    fn status(&self) -> StatusCode {
+        self.status_code
+            .and_then(|code| u16::try_from(code).ok())
+            .and_then(|code| StatusCode::from_u16(code).ok())
+            .filter(StatusCode::is_redirection)
+            .unwrap_or(StatusCode::MOVED_PERMANENTLY)
+    }
+}
+
+pub async fn find(store: &dyn DataStore, owner_email: &str, path: &str) -> Result<Option<Redirect>, StoreError> {
+    let filter = json!({ "email": owner_email, "fromPath": path });
+    let found = store.find_one(&config::collections().redirects, filter).await?;
+    Ok(found.map(serde_json::from_value).transpose()?)
+}
+
+// Middleware answering requests for paths the API doesn't serve (anymore) with the
+// owner's redirect for that path, so only requests that would 404 hit the store
+pub async fn serve_redirects(request: Request, next: Next) -> Response {
+    let path = request.uri().path().to_string();
+    let headers = request.headers().clone();
+    let store = request.extensions().get::<Arc<dyn DataStore>>().cloned();
+    let tenancy = request.extensions().get::<Arc<Tenancy>>().cloned();
+    let response = next.run(request).await;
+    if response.status() != StatusCode::NOT_FOUND {
+        return response;
+    }
+    let (Some(store), Some(tenancy)) = (store, tenancy) else {
+        return response;
+    };
+    let Some(owner_email) = tenant::resolve_owner(&*store, &tenancy, &headers).await else {
+        return response;
+    };
+    match find(&*store, &owner_email, &path).await {
+        Ok(Some(redirect)) => (redirect.status(), [(header::LOCATION, redirect.to_url)]).into_response(),
+        Ok(None) => response,
+        Err(err) => {
+            error_reporting::capture_store_error(&config::collections().redirects, err.as_ref());
+            response
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use serde::{Deserialize, Serialize};
+
+// A position held, identified by its company, title and start date
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkExperience {
+    #[graphql(ignore)]
+    #[serde(default)]
+    pub email: String,
+    pub company: String,
+    pub title: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub location: Option<String>,
+    // Year and month, e.g. 2021-03, or only the year, so they sort as text
+    pub start_date: String,
+    // Unset for the current positions
+    #[serde(default)]
+    pub end_date: Option<String>,
+    #[serde(default)]
+    pub locale: Option<String>,
+}
+
+// A school attended, identified by the school and degree
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+#[serde(rename_all = "camelCase")]
+pub struct Education {
+    #[graphql(ignore)]
+    #[serde(default)]
+    pub email: String,
+    pub school: String,
+    #[serde(default)]
+    pub degree: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub activities: Option<String>,
+    // Dates as on work experience
+    #[serde(default)]
+    pub start_date: Option<String>,
+    #[serde(default)]
+    pub end_date: Option<String>,
+    #[serde(default)]
+    pub locale: Option<String>,
+}
@@ -0,0 +1,235 @@
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    env,
+    sync::Arc,
+    time::Duration,
+};
+use tokio_stream::StreamExt;
+
+use crate::{
+    analytics::{self, CountryVisitors, PageView, TrafficSource},
+    config,
+    store::{DataStore, FindOptions, StoreError},
+};
+
+const DAY_MILLIS: i64 = 86_400_000;
+// Runs a little after midnight UTC so the previous day is complete
+const RUN_AFTER_MIDNIGHT_MILLIS: i64 = 15 * 60 * 1000;
+
+// Page views of one owner on one day, kept after the raw views are pruned
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+pub struct DailySummary {
+    pub email: String,
+    pub date: String,
+    pub views: i32,
+    pub visitors: i32,
+    pub paths: Vec<PathViews>,
+    pub sources: Vec<TrafficSource>,
+    pub countries: Vec<CountryVisitors>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+pub struct PathViews {
+    pub path: String,
+    pub views: i32,
+}
+
+fn summarize(email: &str, date: &str, views: &[PageView]) -> DailySummary {
+    let mut paths: Vec<PathViews> = Vec::new();
+    for view in views {
+        match paths.iter_mut().find(|path| path.path == view.path) {
+            Some(path) => path.views += 1,
+            None => paths.push(PathViews {
+                path: view.path.clone(),
+                views: 1,
+            }),
+        }
+    }
+    paths.sort_by(|a, b| b.views.cmp(&a.views).then_with(|| a.path.cmp(&b.path)));
+    DailySummary {
+        email: email.to_string(),
+        date: date.to_string(),
+        views: views.len() as i32,
+        visitors: analytics::distinct_visitors(views) as i32,
+        paths,
+        sources: analytics::traffic_sources(views),
+        countries: analytics::visitors_by_country(views),
+    }
+}
+
+fn date_of(millis: i64) -> Result<String, StoreError> {
+    Ok(DateTime::from_millis(millis).try_to_rfc3339_string()?[..10].to_string())
+}
+
+// Page views of a window of days folded together
+#[derive(Debug, Default)]
+pub struct WindowSummary {
+    pub views: i32,
+    // Distinct daily visitors, a visitor returning on another day counts again
+    pub visitors: i32,
+    // Most viewed first
+    pub paths: Vec<PathViews>,
+    pub sources: Vec<TrafficSource>,
+    pub countries: Vec<CountryVisitors>,
+}
+
+// Visitor hashes change every day, so the daily visitor counts simply add up
+fn combine(days: Vec<DailySummary>) -> WindowSummary {
+    let mut window = WindowSummary::default();
+    let mut paths: HashMap<String, i32> = HashMap::new();
+    let mut sources: HashMap<_, TrafficSource> = HashMap::new();
+    let mut countries: HashMap<Option<String>, CountryVisitors> = HashMap::new();
+    for day in days {
+        window.views += day.views;
+        window.visitors += day.visitors;
+        for path in day.paths {
+            *paths.entry(path.path).or_default() += path.views;
+        }
+        for source in day.sources {
+            let key = (source.source.clone(), source.medium.clone(), source.campaign.clone());
+            let entry = sources.entry(key).or_insert_with(|| TrafficSource {
+                views: 0,
+                visitors: 0,
+                ..source.clone()
+            });
+            entry.views += source.views;
+            entry.visitors += source.visitors;
+        }
+        for country in day.countries {
+            let entry = countries.entry(country.country.clone()).or_insert_with(|| CountryVisitors {
+                views: 0,
+                visitors: 0,
+                ..country.clone()
+            });
+            entry.views += country.views;
+            entry.visitors += country.visitors;
+        }
+    }
+    window.paths = paths.into_iter().map(|(path, views)| PathViews { path, views }).collect();
+    window.paths.sort_by(|a, b| b.views.cmp(&a.views).then_with(|| a.path.cmp(&b.path)));
+    window.sources = sources.into_values().collect();
+    window.sources.sort_by(|a, b| {
+        b.views
+            .cmp(&a.views)
+            .then_with(|| a.source.cmp(&b.source))
+            .then_with(|| a.medium.cmp(&b.medium))
+            .then_with(|| a.campaign.cmp(&b.campaign))
+    });
+    window.countries = countries.into_values().collect();
+    window.countries.sort_by(|a, b| b.visitors.cmp(&a.visitors).then_with(|| a.country.cmp(&b.country)));
+    window
+}
+
+// Page views of an owner on or after `since`, or all time, answered from the daily
+// summaries plus the raw views of the days the rollup hasn't reached yet: today,
+// and yesterday until the nightly run. Finished days before the latest summary
+// are covered by the summaries, raw views are only read by day.
+pub async fn window(
+    store: &dyn DataStore,
+    owner_email: &str,
+    since: Option<&str>,
+) -> Result<WindowSummary, StoreError> {
+    let collections = config::collections();
+    let filter = json!({ "email": owner_email });
+    let in_window = |day: &DailySummary| since.map_or(true, |since| day.date.as_str() >= since);
+    let mut days = analytics::collect_matching(store, &collections.analytics_daily, filter, in_window).await?;
+    let latest = days.iter().map(|day| day.date.clone()).max();
+    let now = DateTime::now().timestamp_millis();
+    for date in [date_of(now - DAY_MILLIS)?, date_of(now)?] {
+        let summarized = latest.as_ref().is_some_and(|latest| date <= *latest);
+        if summarized || since.is_some_and(|since| date.as_str() < since) {
+            continue;
+        }
+        let filter = json!({ "email": owner_email, "date": date });
+        let views = analytics::collect_matching(store, &collections.analytics, filter, |view: &PageView| {
+            !view.suspect
+        })
+        .await?;
+        days.push(summarize(owner_email, &date, &views));
+    }
+    Ok(combine(days))
+}
+
+// Summarize every finished day that has raw page views but no summary yet, then
+// delete the raw views older than ANALYTICS_RETENTION_DAYS (90 by default). Raw
+// views are streamed, only those of the days being summarized are kept in memory.
+pub async fn run(store: &dyn DataStore) -> Result<(), StoreError> {
+    let collections = config::collections();
+    let retention_days: i64 = env::var("ANALYTICS_RETENTION_DAYS")
+        .ok()
+        .and_then(|days| days.parse().ok())
+        .unwrap_or(90);
+    let now = DateTime::now().timestamp_millis();
+    let today = date_of(now)?;
+    let cutoff = date_of(now - retention_days * DAY_MILLIS)?;
+
+    let summarized: HashSet<(String, String)> =
+        analytics::collect_matching(store, &collections.analytics_daily, json!({}), |_: &DailySummary| true)
+            .await?
+            .into_iter()
+            .map(|day| (day.email, day.date))
+            .collect();
+    // views of the days to summarize, suspect ones are pruned with the rest but never counted
+    let mut pending: BTreeMap<(String, String), Vec<PageView>> = BTreeMap::new();
+    let mut expired: BTreeSet<(String, String)> = BTreeSet::new();
+    let mut views = store
+        .find_stream(&collections.analytics, json!({}), FindOptions::default())
+        .await?;
+    while let Some(view) = views.next().await {
+        let Ok(view) = serde_json::from_value::<PageView>(view?) else {
+            continue;
+        };
+        if view.date >= today {
+            continue;
+        }
+        let day = (view.email.clone(), view.date.clone());
+        if view.date < cutoff {
+            expired.insert(day.clone());
+        }
+        if summarized.contains(&day) {
+            continue;
+        }
+        let day_views = pending.entry(day).or_default();
+        if !view.suspect {
+            day_views.push(view);
+        }
+    }
+
+    for ((email, date), day_views) in &pending {
+        let filter = json!({ "email": email, "date": date });
+        // a run stopped between the two writes leaves no summary, so the day is redone
+        store.delete_many(&collections.analytics_daily, filter).await?;
+        let summary = serde_json::to_value(summarize(email, date, day_views))?;
+        store.insert_one(&collections.analytics_daily, summary).await?;
+    }
+    let mut pruned = 0;
+    for (email, date) in &expired {
+        pruned += store
+            .delete_many(&collections.analytics, json!({ "email": email, "date": date }))
+            .await?;
+    }
+    tracing::info!(days = pending.len(), pruned, "Rolled up analytics");
+    Ok(())
+}
+
+// Run the rollup at startup, catching up on days missed while the server was down,
+// then every night, unless ANALYTICS_ROLLUP=false (e.g. on all but one replica)
+pub fn spawn(store: Arc<dyn DataStore>) {
+    if env::var("ANALYTICS_ROLLUP").map_or(false, |value| value == "false" || value == "0") {
+        println!("ANALYTICS_ROLLUP is disabled, analytics won't be rolled up");
+        return;
+    }
+    tokio::spawn(async move {
+        loop {
+            if let Err(e) = run(&*store).await {
+                tracing::error!(error = %e, "Analytics rollup failed");
+            }
+            let now = DateTime::now().timestamp_millis();
+            let next = (now / DAY_MILLIS + 1) * DAY_MILLIS + RUN_AFTER_MIDNIGHT_MILLIS;
+            tokio::time::sleep(Duration::from_millis((next - now) as u64)).await;
+        }
+    });
+}
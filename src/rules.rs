@@ -0,0 +1,186 @@
+use juniper::{graphql_value, FieldError};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::{
+    config, settings,
+    store::{DataStore, StoreError},
+    validation::{Validator, MAX_NAME_CHARS},
+};
+
+// Field of the site settings the rules are stored in
+const SETTINGS_FIELD: &str = "contentRules";
+
+// A constraint the owner puts on their own content, e.g. "published posts have
+// an excerpt": documents of `collection` matching every condition must have at
+// least one of the `requireAny` fields set. Fields are named as stored, nested
+// ones with dots, e.g. screenshots or links.repo.
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentRule {
+    pub name: String,
+    pub collection: String,
+    #[serde(default)]
+    pub when: Vec<RuleCondition>,
+    pub require_any: Vec<String>,
+}
+
+// Matches documents whose `field` equals `equals`, e.g. status is published
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+pub struct RuleCondition {
+    pub field: String,
+    pub equals: String,
+}
+
+#[derive(Debug, juniper::GraphQLInputObject)]
+pub struct ContentRuleInput {
+    pub name: String,
+    pub collection: String,
+    #[graphql(default = Vec::new())]
+    pub when: Vec<RuleConditionInput>,
+    pub require_any: Vec<String>,
+}
+
+#[derive(Debug, juniper::GraphQLInputObject)]
+pub struct RuleConditionInput {
+    pub field: String,
+    pub equals: String,
+}
+
+impl ContentRuleInput {
+    pub fn validate(&self, input: &mut Validator) {
+        input.text("rules.name", &self.name, MAX_NAME_CHARS);
+        if !config::collections().portfolio().contains(&self.collection.as_str()) {
+            input.reject("rules.collection", "must be one of the portfolio collections");
+        }
+        for condition in &self.when {
+            input.text("rules.when.field", &condition.field, MAX_NAME_CHARS);
+            input.optional_text("rules.when.equals", Some(&condition.equals), MAX_NAME_CHARS);
+        }
+        if self.require_any.is_empty() {
+            input.reject("rules.requireAny", "must list at least one field");
+        }
+        for field in &self.require_any {
+            input.text("rules.requireAny", field, MAX_NAME_CHARS);
+        }
+    }
+}
+
+impl From<ContentRuleInput> for ContentRule {
+    fn from(rule: ContentRuleInput) -> Self {
+        Self {
+            name: rule.name,
+            collection: rule.collection,
+            when: rule
+                .when
+                .into_iter()
+                .map(|condition| RuleCondition {
+                    field: condition.field,
+                    equals: condition.equals,
+                })
+                .collect(),
+            require_any: rule.require_any,
+        }
+    }
+}
+
+impl ContentRule {
+    fn broken_by(&self, collection: &str, document: &Value) -> bool {
+        self.collection == collection
+            && self.when.iter().all(|condition| condition.matches(document))
+            && !self.require_any.iter().any(|field| is_set(field_value(document, field)))
+    }
+}
+
+impl RuleCondition {
+    fn matches(&self, document: &Value) -> bool {
+        match field_value(document, &self.field) {
+            Some(Value::String(value)) => *value == self.equals,
+            None | Some(Value::Null) => false,
+            // numbers and booleans compare as written, e.g. true or 3
+            Some(value) => value.to_string() == self.equals,
+        }
+    }
+}
+
+// The value at a dotted path like links.repo
+fn field_value<'a>(document: &'a Value, field: &str) -> Option<&'a Value> {
+    field.split('.').try_fold(document, |value, key| value.get(key))
+}
+
+// Blank strings and empty lists count as unset
+fn is_set(value: Option<&Value>) -> bool {
+    match value {
+        None | Some(Value::Null) => false,
+        Some(Value::String(value)) => !value.trim().is_empty(),
+        Some(Value::Array(values)) => !values.is_empty(),
+        Some(Value::Object(fields)) => !fields.is_empty(),
+        Some(_) => true,
+    }
+}
+
+// The rules `document` of `collection` breaks
+pub fn broken<'a>(
+    rules: &'a [ContentRule],
+    collection: &'a str,
+    document: &'a Value,
+) -> impl Iterator<Item = &'a ContentRule> {
+    rules.iter().filter(move |rule| rule.broken_by(collection, document))
+}
+
+fn parse(settings: Option<Value>) -> Result<Vec<ContentRule>, StoreError> {
+    let rules = settings.and_then(|mut settings| settings.get_mut(SETTINGS_FIELD).map(Value::take));
+    Ok(rules.map(serde_json::from_value).transpose()?.unwrap_or_default())
+}
+
+pub async fn load(store: &dyn DataStore, owner_email: &str) -> Result<Vec<ContentRule>, StoreError> {
+    parse(settings::load(store, owner_email).await?)
+}
+
+// Rules of every owner that has any, by owner email
+pub async fn load_all(store: &dyn DataStore) -> Result<HashMap<String, Vec<ContentRule>>, StoreError> {
+    let mut rules = HashMap::new();
+    for settings in store.find(&config::collections().settings, json!({})).await? {
+        let Some(email) = settings.get("email").and_then(Value::as_str).map(str::to_string) else {
+            continue;
+        };
+        let owner_rules = parse(Some(settings))?;
+        if !owner_rules.is_empty() {
+            rules.insert(email, owner_rules);
+        }
+    }
+    Ok(rules)
+}
+
+pub async fn save(store: &dyn DataStore, owner_email: &str, rules: &[ContentRule]) -> Result<(), StoreError> {
+    settings::save(store, owner_email, json!({ SETTINGS_FIELD: rules })).await
+}
+
+// Fail with INVALID_INPUT, naming the rules broken, when `document` as it is
+// about to be written breaks any of the owner's rules
+pub async fn enforce(
+    store: &dyn DataStore,
+    owner_email: &str,
+    collection: &str,
+    document: &impl Serialize,
+) -> Result<(), FieldError> {
+    let checked = async {
+        let rules = load(store, owner_email).await?;
+        let document = serde_json::to_value(document)?;
+        let mut input = Validator::default();
+        for rule in broken(&rules, collection, &document) {
+            input.reject(&rule.require_any.join(" or "), format!("must be set, see rule {:?}", rule.name));
+        }
+        Ok::<_, StoreError>(input)
+    };
+    checked
+        .await
+        .map_err(|err| {
+            FieldError::new(
+                "Failed to check content rules",
+                graphql_value!({ "details": err.to_string() }),
+            )
+        })?
+        .finish()
+}
@@ -0,0 +1,196 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use std::{
+    env,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use super::{SearchHit, SearchKind, SearchProvider};
+use crate::store::{DataStore, StoreError};
+
+// Hosted search engine holding the searchable documents of every owner in the
+// SEARCH_INDEX index ("portfolio" by default)
+#[derive(Debug)]
+pub enum ExternalEngine {
+    // MEILISEARCH_URL and MEILISEARCH_API_KEY, the key can be left out on an
+    // instance without a master key
+    Meilisearch {
+        url: String,
+        api_key: Option<String>,
+        index: String,
+        client: reqwest::Client,
+        configured: AtomicBool,
+    },
+    // ALGOLIA_APP_ID and ALGOLIA_API_KEY, a key allowed to write and search
+    Algolia {
+        app_id: String,
+        api_key: String,
+        index: String,
+        client: reqwest::Client,
+        configured: AtomicBool,
+    },
+}
+
+fn required(key: &str) -> String {
+    env::var(key).unwrap_or_else(|_| panic!("{} must be set for SEARCH_ENGINE", key))
+}
+
+// Quote a value inside a filter expression
+fn quoted(value: &str, quote: char) -> String {
+    let escaped = value.replace('\\', "\\\\").replace(quote, &format!("\\{}", quote));
+    format!("{}{}{}", quote, escaped, quote)
+}
+
+impl ExternalEngine {
+    pub fn meilisearch(index: String) -> Self {
+        ExternalEngine::Meilisearch {
+            url: required("MEILISEARCH_URL").trim_end_matches('/').to_string(),
+            api_key: env::var("MEILISEARCH_API_KEY").ok(),
+            index,
+            client: reqwest::Client::new(),
+            configured: AtomicBool::new(false),
+        }
+    }
+
+    pub fn algolia(index: String) -> Self {
+        ExternalEngine::Algolia {
+            app_id: required("ALGOLIA_APP_ID"),
+            api_key: required("ALGOLIA_API_KEY"),
+            index,
+            client: reqwest::Client::new(),
+            configured: AtomicBool::new(false),
+        }
+    }
+
+    // A request to `path` of the engine's API with its credentials
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        match self {
+            ExternalEngine::Meilisearch { url, api_key, index, client, .. } => {
+                let request = client.request(method, format!("{}/indexes/{}{}", url, index, path));
+                match api_key {
+                    Some(api_key) => request.bearer_auth(api_key),
+                    None => request,
+                }
+            }
+            ExternalEngine::Algolia { app_id, api_key, index, client, .. } => client
+                .request(method, format!("https://{}.algolia.net/1/indexes/{}{}", app_id, index, path))
+                .header("X-Algolia-Application-Id", app_id)
+                .header("X-Algolia-API-Key", api_key),
+        }
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<Value, StoreError> {
+        Ok(request.send().await?.error_for_status()?.json().await?)
+    }
+
+    // Make owner, kind and locale filterable, once per process
+    async fn configure(&self) -> Result<(), StoreError> {
+        let (ExternalEngine::Meilisearch { configured, .. } | ExternalEngine::Algolia { configured, .. }) = self;
+        if configured.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let request = match self {
+            ExternalEngine::Meilisearch { .. } => self
+                .request(reqwest::Method::PATCH, "/settings")
+                .json(&json!({ "filterableAttributes": ["email", "kind", "locale"] })),
+            ExternalEngine::Algolia { .. } => self.request(reqwest::Method::PUT, "/settings").json(&json!({
+                "attributesForFaceting": ["filterOnly(email)", "filterOnly(kind)", "filterOnly(locale)"]
+            })),
+        };
+        self.send(request).await?;
+        configured.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+// Meilisearch names the primary key id rather than Algolia's objectID
+fn meilisearch_document(hit: &SearchHit) -> Value {
+    let mut document = json!(hit);
+    document["id"] = document["objectID"].take();
+    if let Value::Object(fields) = &mut document {
+        fields.remove("objectID");
+    }
+    document
+}
+
+#[async_trait]
+impl SearchProvider for ExternalEngine {
+    fn name(&self) -> &'static str {
+        match self {
+            ExternalEngine::Meilisearch { .. } => "meilisearch",
+            ExternalEngine::Algolia { .. } => "algolia",
+        }
+    }
+
+    // Both engines apply the writes of an index in order, so searches see the
+    // old or the new set
+    async fn replace(&self, owner_email: &str, kind: SearchKind, hits: Vec<SearchHit>) -> Result<(), StoreError> {
+        self.configure().await?;
+        match self {
+            ExternalEngine::Meilisearch { .. } => {
+                let filter = format!("email = {} AND kind = {}", quoted(owner_email, '\''), kind.as_str());
+                let delete = self
+                    .request(reqwest::Method::POST, "/documents/delete")
+                    .json(&json!({ "filter": filter }));
+                self.send(delete).await?;
+                if !hits.is_empty() {
+                    let add = self
+                        .request(reqwest::Method::POST, "/documents?primaryKey=id")
+                        .json(&hits.iter().map(meilisearch_document).collect::<Vec<_>>());
+                    self.send(add).await?;
+                }
+            }
+            ExternalEngine::Algolia { .. } => {
+                let filters = format!("email:{} AND kind:{}", quoted(owner_email, '"'), kind.as_str());
+                let delete = self
+                    .request(reqwest::Method::POST, "/deleteByQuery")
+                    .json(&json!({ "filters": filters }));
+                self.send(delete).await?;
+                if !hits.is_empty() {
+                    let requests: Vec<Value> = hits
+                        .iter()
+                        .map(|hit| json!({ "action": "updateObject", "body": hit }))
+                        .collect();
+                    let add = self
+                        .request(reqwest::Method::POST, "/batch")
+                        .json(&json!({ "requests": requests }));
+                    self.send(add).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<(), StoreError> {
+        self.configure().await?;
+        let request = match self {
+            ExternalEngine::Meilisearch { .. } => self.request(reqwest::Method::DELETE, "/documents"),
+            ExternalEngine::Algolia { .. } => self.request(reqwest::Method::POST, "/clear"),
+        };
+        self.send(request).await?;
+        Ok(())
+    }
+
+    async fn search(
+        &self,
+        _store: &dyn DataStore,
+        owner_email: &str,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>, StoreError> {
+        let request = match self {
+            ExternalEngine::Meilisearch { .. } => self.request(reqwest::Method::POST, "/search").json(&json!({
+                "q": query,
+                "filter": format!("email = {}", quoted(owner_email, '\'')),
+                "limit": limit,
+            })),
+            ExternalEngine::Algolia { .. } => self.request(reqwest::Method::POST, "/query").json(&json!({
+                "query": query,
+                "filters": format!("email:{}", quoted(owner_email, '"')),
+                "hitsPerPage": limit,
+            })),
+        };
+        let mut response = self.send(request).await?;
+        Ok(serde_json::from_value(response["hits"].take())?)
+    }
+}
@@ -0,0 +1,148 @@
+use async_trait::async_trait;
+use std::{fmt, sync::Mutex};
+use tantivy::{
+    collector::TopDocs,
+    query::{BooleanQuery, Query, QueryParser, TermQuery},
+    schema::{Field, IndexRecordOption, Schema, Value as _, STORED, STRING, TEXT},
+    Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term,
+};
+
+use super::{SearchHit, SearchKind, SearchProvider};
+use crate::store::{DataStore, StoreError};
+
+// Smallest indexing buffer tantivy accepts for a writer thread
+const WRITER_HEAP_BYTES: usize = 15_000_000;
+
+struct Fields {
+    // owner and kind of the document, what replace swaps documents by
+    group: Field,
+    email: Field,
+    title: Field,
+    summary: Field,
+    tags: Field,
+    // the SearchHit as JSON, returned as is
+    hit: Field,
+}
+
+// Tantivy index in the server's memory, ranking matches with BM25 without running
+// a search engine. It starts empty and is filled from the store when the server
+// starts, see search::spawn.
+pub struct MemoryIndex {
+    index: Index,
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+    fields: Fields,
+}
+
+impl fmt::Debug for MemoryIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryIndex").finish_non_exhaustive()
+    }
+}
+
+fn group(owner_email: &str, kind: SearchKind) -> String {
+    format!("{}\n{}", owner_email, kind.as_str())
+}
+
+impl MemoryIndex {
+    pub fn new() -> Result<Self, StoreError> {
+        let mut schema = Schema::builder();
+        let fields = Fields {
+            group: schema.add_text_field("group", STRING),
+            email: schema.add_text_field("email", STRING),
+            title: schema.add_text_field("title", TEXT),
+            summary: schema.add_text_field("summary", TEXT),
+            tags: schema.add_text_field("tags", TEXT),
+            hit: schema.add_text_field("hit", STORED),
+        };
+        let index = Index::create_in_ram(schema.build());
+        let writer = index.writer_with_num_threads(1, WRITER_HEAP_BYTES)?;
+        let reader = index.reader_builder().reload_policy(ReloadPolicy::Manual).try_into()?;
+        Ok(Self {
+            index,
+            reader,
+            writer: Mutex::new(writer),
+            fields,
+        })
+    }
+
+    // Apply `change` and make it visible to searches
+    fn commit(&self, change: impl FnOnce(&mut IndexWriter, &Fields) -> Result<(), StoreError>) -> Result<(), StoreError> {
+        let mut writer = self.writer.lock().unwrap();
+        change(&mut writer, &self.fields)?;
+        writer.commit()?;
+        self.reader.reload()?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SearchProvider for MemoryIndex {
+    fn name(&self) -> &'static str {
+        "tantivy"
+    }
+
+    fn in_memory(&self) -> bool {
+        true
+    }
+
+    async fn replace(&self, owner_email: &str, kind: SearchKind, hits: Vec<SearchHit>) -> Result<(), StoreError> {
+        let group = group(owner_email, kind);
+        self.commit(|writer, fields| {
+            writer.delete_term(Term::from_field_text(fields.group, &group));
+            for hit in hits {
+                let mut document = TantivyDocument::new();
+                document.add_text(fields.group, &group);
+                document.add_text(fields.email, &hit.email);
+                document.add_text(fields.title, &hit.title);
+                if let Some(summary) = &hit.summary {
+                    document.add_text(fields.summary, summary);
+                }
+                for tag in &hit.tags {
+                    document.add_text(fields.tags, tag);
+                }
+                document.add_text(fields.hit, serde_json::to_string(&hit)?);
+                writer.add_document(document)?;
+            }
+            Ok(())
+        })
+    }
+
+    async fn clear(&self) -> Result<(), StoreError> {
+        self.commit(|writer, _| {
+            writer.delete_all_documents()?;
+            Ok(())
+        })
+    }
+
+    async fn search(
+        &self,
+        _store: &dyn DataStore,
+        owner_email: &str,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>, StoreError> {
+        let fields = &self.fields;
+        let parser = QueryParser::for_index(&self.index, vec![fields.title, fields.summary, fields.tags]);
+        // visitors type plain words, so syntax errors are ignored rather than reported
+        let (text, _) = parser.parse_query_lenient(query);
+        let owner: Box<dyn Query> = Box::new(TermQuery::new(
+            Term::from_field_text(fields.email, owner_email),
+            IndexRecordOption::Basic,
+        ));
+        let query = BooleanQuery::intersection(vec![text, owner]);
+        let searcher = self.reader.searcher();
+        searcher
+            .search(&query, &TopDocs::with_limit(limit))?
+            .into_iter()
+            .map(|(_, address)| {
+                let document: TantivyDocument = searcher.doc(address)?;
+                let hit = document
+                    .get_first(fields.hit)
+                    .and_then(|value| value.as_str())
+                    .ok_or("Indexed document without its search hit")?;
+                Ok(serde_json::from_str(hit)?)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,325 @@
+use async_trait::async_trait;
+use juniper::{graphql_object, graphql_value, FieldError};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::Debug,
+    sync::{Arc, OnceLock},
+};
+
+use crate::{
+    blog::{self, BlogPost},
+    config,
+    store::{DataStore, StoreError},
+    talks::Talk,
+    uses::UsesItem,
+    Context, Project,
+};
+
+mod external;
+mod memory;
+mod mongo;
+
+use external::ExternalEngine;
+use memory::MemoryIndex;
+use mongo::TextSearch;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, juniper::GraphQLEnum)]
+#[serde(rename_all = "camelCase")]
+pub enum SearchKind {
+    BlogPost,
+    Project,
+    Talk,
+    UsesItem,
+}
+
+impl SearchKind {
+    const ALL: [SearchKind; 4] = [SearchKind::BlogPost, SearchKind::Project, SearchKind::Talk, SearchKind::UsesItem];
+
+    // Kind of the documents of a collection, None for collections that aren't searched
+    pub fn of(collection: &str) -> Option<Self> {
+        let collections = config::collections();
+        if collection == collections.blog_posts {
+            Some(SearchKind::BlogPost)
+        } else if collection == collections.projects {
+            Some(SearchKind::Project)
+        } else if collection == collections.talks {
+            Some(SearchKind::Talk)
+        } else if collection == collections.uses {
+            Some(SearchKind::UsesItem)
+        } else {
+            None
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            SearchKind::BlogPost => "blogPost",
+            SearchKind::Project => "project",
+            SearchKind::Talk => "talk",
+            SearchKind::UsesItem => "usesItem",
+        }
+    }
+
+    fn collection(self) -> &'static str {
+        match self {
+            SearchKind::BlogPost => &config::collections().blog_posts,
+            SearchKind::Project => &config::collections().projects,
+            SearchKind::Talk => &config::collections().talks,
+            SearchKind::UsesItem => &config::collections().uses,
+        }
+    }
+
+    // Field naming the document among the owner's of its kind, uses items have no page of their own
+    fn key_field(self) -> &'static str {
+        match self {
+            SearchKind::UsesItem => "name",
+            _ => "slug",
+        }
+    }
+
+    fn title_field(self) -> &'static str {
+        match self {
+            SearchKind::UsesItem => "name",
+            _ => "title",
+        }
+    }
+
+    fn summary_field(self) -> &'static str {
+        match self {
+            SearchKind::BlogPost => "excerpt",
+            _ => "description",
+        }
+    }
+
+    // Fields matched against the query, those of the text indexes
+    fn text_fields(self) -> &'static [&'static str] {
+        match self {
+            SearchKind::BlogPost => &["title", "excerpt", "tags"],
+            SearchKind::Project => &["title", "description", "tags"],
+            SearchKind::Talk => &["title", "description", "event", "tags"],
+            SearchKind::UsesItem => &["name", "description", "category"],
+        }
+    }
+
+    // Filter matching the searchable documents of the kind, only published posts
+    // are. Without an owner it matches those of every owner.
+    fn filter(self, owner_email: Option<&str>) -> Value {
+        let mut filter = match (self, owner_email) {
+            (SearchKind::BlogPost, Some(owner_email)) => blog::published_filter(owner_email),
+            (SearchKind::BlogPost, None) => json!({ "status": blog::PUBLISHED }),
+            _ => json!({}),
+        };
+        if let Some(owner_email) = owner_email {
+            filter["email"] = json!(owner_email);
+        }
+        filter
+    }
+}
+
+// What a search provider stores of a post, project, talk or uses item, one per
+// locale variant
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    #[serde(rename = "objectID", alias = "id")]
+    pub id: String,
+    pub email: String,
+    pub kind: SearchKind,
+    // Slug of the page, the name of a uses item
+    pub slug: String,
+    pub locale: Option<String>,
+    // Title, the name of a uses item
+    pub title: String,
+    // Excerpt of a post, description of the others
+    pub summary: Option<String>,
+    pub tags: Vec<String>,
+}
+
+// The document a search hit stands for
+#[derive(juniper::GraphQLUnion)]
+#[graphql(context = Context)]
+pub enum SearchResult {
+    BlogPost(BlogPost),
+    Project(Project),
+    Talk(Talk),
+    UsesItem(UsesItem),
+}
+
+#[graphql_object(context = Context)]
+impl SearchHit {
+    fn kind(&self) -> SearchKind {
+        self.kind
+    }
+    fn slug(&self) -> &str {
+        &self.slug
+    }
+    fn locale(&self) -> Option<&str> {
+        self.locale.as_deref()
+    }
+    fn title(&self) -> &str {
+        &self.title
+    }
+    fn summary(&self) -> Option<&str> {
+        self.summary.as_deref()
+    }
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+    // The post, project, talk or uses item itself, None when it was deleted or
+    // unpublished after it was indexed
+    async fn item(&self, context: &Context) -> Result<Option<SearchResult>, FieldError> {
+        let collection = self.kind.collection();
+        let mut filter = self.kind.filter(Some(&self.email));
+        filter[self.kind.key_field()] = json!(self.slug);
+        filter["locale"] = json!(self.locale);
+        let found = context.store.find_one(collection, filter).await.map_err(|err| {
+            FieldError::new(
+                "Failed to fetch search result",
+                graphql_value!({ "details": err.to_string() }),
+            )
+        })?;
+        Ok(match self.kind {
+            SearchKind::BlogPost => context.decode_one(collection, found)?.map(SearchResult::BlogPost),
+            SearchKind::Project => context.decode_one(collection, found)?.map(SearchResult::Project),
+            SearchKind::Talk => context.decode_one(collection, found)?.map(SearchResult::Talk),
+            SearchKind::UsesItem => context.decode_one(collection, found)?.map(SearchResult::UsesItem),
+        })
+    }
+}
+
+impl SearchHit {
+    // None for documents without a slug, they have no page to link to, or uses
+    // items without a name
+    fn from_document(kind: SearchKind, document: &Value) -> Option<Self> {
+        let text = |field: &str| document.get(field).and_then(Value::as_str).map(str::to_string);
+        let (email, slug, locale) = (text("email")?, text(kind.key_field())?, text("locale"));
+        // ids only allow letters, digits, - and _ on Meilisearch, so the key is hashed
+        let key = format!("{}\n{}\n{}\n{}", email, kind.as_str(), slug, locale.as_deref().unwrap_or_default());
+        let id = format!("{:x}", Sha256::digest(key.as_bytes()));
+        let summary = text(kind.summary_field());
+        Some(Self {
+            id,
+            email,
+            kind,
+            slug,
+            locale,
+            title: text(kind.title_field()).unwrap_or_default(),
+            summary: summary.filter(|summary| !summary.is_empty()),
+            tags: document
+                .get("tags")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(|tag| tag.as_str().map(str::to_string))
+                .collect(),
+        })
+    }
+}
+
+// Answers the search query. Providers keeping their own copy of the searchable
+// documents get the owner's documents of a kind again after every write to them,
+// see store::SearchSyncStore, so a provider can be swapped for another by
+// configuration and a reindex, without touching the stored documents.
+#[async_trait]
+pub trait SearchProvider: Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+    // False for providers reading the store directly, nothing is pushed to them
+    fn keeps_copy(&self) -> bool {
+        true
+    }
+    // True when the copy lives in this process, it's rebuilt when the server starts
+    fn in_memory(&self) -> bool {
+        false
+    }
+    // Replace the owner's documents of one kind with `hits`
+    async fn replace(&self, owner_email: &str, kind: SearchKind, hits: Vec<SearchHit>) -> Result<(), StoreError>;
+    // Drop every document
+    async fn clear(&self) -> Result<(), StoreError>;
+    // The owner's searchable documents matching `query`, most relevant first
+    async fn search(
+        &self,
+        store: &dyn DataStore,
+        owner_email: &str,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>, StoreError>;
+}
+
+static PROVIDER: OnceLock<Box<dyn SearchProvider>> = OnceLock::new();
+
+// The provider picked with SEARCH_ENGINE: mongo (the default) searches the store,
+// tantivy an index in memory, meilisearch and algolia a hosted engine keeping
+// the documents in the SEARCH_INDEX index, "portfolio" by default.
+pub fn provider() -> &'static dyn SearchProvider {
+    PROVIDER
+        .get_or_init(|| -> Box<dyn SearchProvider> {
+            let engine = env::var("SEARCH_ENGINE").unwrap_or_else(|_| "mongo".to_string());
+            let index = env::var("SEARCH_INDEX").unwrap_or_else(|_| "portfolio".to_string());
+            match engine.as_str() {
+                "mongo" => Box::new(TextSearch),
+                "tantivy" => Box::new(MemoryIndex::new().expect("Failed to create the search index")),
+                "meilisearch" => Box::new(ExternalEngine::meilisearch(index)),
+                "algolia" => Box::new(ExternalEngine::algolia(index)),
+                other => panic!(
+                    "Unknown SEARCH_ENGINE {}, expected mongo, tantivy, meilisearch or algolia",
+                    other
+                ),
+            }
+        })
+        .as_ref()
+}
+
+// Push the owner's searchable documents of `collection` to the provider again
+pub async fn sync_owner(
+    store: &dyn DataStore,
+    provider: &dyn SearchProvider,
+    owner_email: &str,
+    collection: &str,
+) -> Result<(), StoreError> {
+    let Some(kind) = SearchKind::of(collection) else {
+        return Ok(());
+    };
+    let documents = store.find(collection, kind.filter(Some(owner_email))).await?;
+    let hits = documents
+        .iter()
+        .filter_map(|document| SearchHit::from_document(kind, document))
+        .collect();
+    provider.replace(owner_email, kind, hits).await
+}
+
+// Rebuild the provider's copy from the store, returning how many documents it holds
+pub async fn reindex(store: &dyn DataStore, provider: &dyn SearchProvider) -> Result<usize, StoreError> {
+    provider.clear().await?;
+    let mut total = 0;
+    for kind in SearchKind::ALL {
+        let mut owners: BTreeMap<String, Vec<SearchHit>> = BTreeMap::new();
+        for document in store.find(kind.collection(), kind.filter(None)).await? {
+            if let Some(hit) = SearchHit::from_document(kind, &document) {
+                owners.entry(hit.email.clone()).or_default().push(hit);
+            }
+        }
+        for (owner_email, hits) in owners {
+            total += hits.len();
+            provider.replace(&owner_email, kind, hits).await?;
+        }
+    }
+    Ok(total)
+}
+
+// Fill an in memory provider from the store in the background
+pub fn spawn(store: Arc<dyn DataStore>) {
+    let provider = provider();
+    if !provider.in_memory() {
+        return;
+    }
+    tokio::spawn(async move {
+        match reindex(&*store, provider).await {
+            Ok(documents) => tracing::info!(documents, provider = provider.name(), "Built the search index"),
+            Err(e) => tracing::error!(error = %e, "Failed to build the search index"),
+        }
+    });
+}
@@ -0,0 +1,53 @@
+use async_trait::async_trait;
+
+use super::{SearchHit, SearchKind, SearchProvider};
+use crate::store::{DataStore, StoreError};
+
+// Search over the store itself, nothing to keep in sync. Mongo answers with the
+// text indexes store::ensure_indexes creates, the other backends by counting the
+// query words each document contains.
+#[derive(Debug)]
+pub struct TextSearch;
+
+#[async_trait]
+impl SearchProvider for TextSearch {
+    fn name(&self) -> &'static str {
+        "mongo"
+    }
+
+    fn keeps_copy(&self) -> bool {
+        false
+    }
+
+    async fn replace(&self, _owner_email: &str, _kind: SearchKind, _hits: Vec<SearchHit>) -> Result<(), StoreError> {
+        Ok(())
+    }
+
+    async fn clear(&self) -> Result<(), StoreError> {
+        Ok(())
+    }
+
+    // Posts and projects are searched separately, then merged by score
+    async fn search(
+        &self,
+        store: &dyn DataStore,
+        owner_email: &str,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>, StoreError> {
+        let mut scored = Vec::new();
+        for kind in SearchKind::ALL {
+            let filter = kind.filter(Some(owner_email));
+            let found = store
+                .text_search(kind.collection(), filter, kind.text_fields(), query, limit as u64)
+                .await?;
+            scored.extend(
+                found
+                    .into_iter()
+                    .filter_map(|(score, document)| Some((score, SearchHit::from_document(kind, &document)?))),
+            );
+        }
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        Ok(scored.into_iter().take(limit).map(|(_, hit)| hit).collect())
+    }
+}
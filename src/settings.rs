@@ -0,0 +1,25 @@
+use serde_json::{json, Value};
+
+use crate::{
+    config,
+    store::{DataStore, StoreError},
+};
+
+// The owner's site settings document, if they have one
+pub async fn load(store: &dyn DataStore, owner_email: &str) -> Result<Option<Value>, StoreError> {
+    store
+        .find_one(&config::collections().settings, json!({ "email": owner_email }))
+        .await
+}
+
+// Set top level fields of the owner's site settings, creating the document when missing
+pub async fn save(store: &dyn DataStore, owner_email: &str, changes: Value) -> Result<(), StoreError> {
+    let collection = &config::collections().settings;
+    let filter = json!({ "email": owner_email });
+    if !store.update_one(collection, filter.clone(), changes.clone()).await? {
+        let mut document = changes;
+        document["email"] = filter["email"].clone();
+        store.insert_one(collection, document).await?;
+    }
+    Ok(())
+}
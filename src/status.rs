@@ -0,0 +1,77 @@
+use axum::{
+    http::{HeaderMap, StatusCode},
+    Extension, Json,
+};
+use serde::Serialize;
+use std::{sync::Arc, time::Instant};
+
+use crate::{
+    api_keys, config,
+    store::{CacheStats, DataStore},
+};
+
+// When the server started, for the uptime on /status
+#[derive(Clone, Copy, Debug)]
+pub struct StartedAt(pub Instant);
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Status {
+    version: &'static str,
+    git_commit: &'static str,
+    // seconds since the Unix epoch, unless BUILD_TIME was given at build time
+    build_time: &'static str,
+    uptime_seconds: u64,
+    database: DatabaseStatus,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DatabaseStatus {
+    backend: &'static str,
+    connected: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+// GET /status, answering 503 while the database can't be reached
+pub async fn status(
+    Extension(store): Extension<Arc<dyn DataStore>>,
+    Extension(StartedAt(started)): Extension<StartedAt>,
+) -> (StatusCode, Json<Status>) {
+    let ping = store.ping().await;
+    let code = if ping.is_ok() { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    let status = Status {
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: env!("GIT_COMMIT"),
+        build_time: env!("BUILD_TIME"),
+        uptime_seconds: started.elapsed().as_secs(),
+        database: DatabaseStatus {
+            backend: store.backend_name(),
+            connected: ping.is_ok(),
+            error: ping.err().map(|e| e.to_string()),
+        },
+    };
+    (code, Json(status))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStatus {
+    // 0 when reads aren't cached
+    ttl_seconds: u64,
+    collections: Vec<CacheStats>,
+}
+
+// GET /admin/cache, the read cache counters per collection. Cached results are
+// dropped with the purgeCache mutation.
+pub async fn cache(
+    Extension(store): Extension<Arc<dyn DataStore>>,
+    Extension(key_guard): Extension<Arc<api_keys::ApiKeyGuard>>,
+    headers: HeaderMap,
+) -> Result<Json<CacheStatus>, (StatusCode, &'static str)> {
+    key_guard.authorize(&*store, &headers, api_keys::ADMIN_SCOPE).await?;
+    Ok(Json(CacheStatus {
+        ttl_seconds: config::app().cache_ttl,
+        collections: store.cache_stats(),
+    }))
+}
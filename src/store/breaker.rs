@@ -0,0 +1,208 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use std::{
+    collections::VecDeque,
+    fmt,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use super::{retry, DataStore, DocumentStream, FindOptions, StoreError, StoreTimeout, StoredDocument};
+use crate::config;
+
+// Outcomes the failure threshold is counted over
+const WINDOW: usize = 20;
+
+// Error returned without calling the backend while the breaker is open
+#[derive(Debug)]
+pub struct CircuitOpen {
+    pub retry_after: Duration,
+}
+
+impl fmt::Display for CircuitOpen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Data store unavailable, retry in {}s", self.retry_after.as_secs().max(1))
+    }
+}
+
+impl std::error::Error for CircuitOpen {}
+
+// The open breaker's error, when `error` is one
+pub fn circuit_open(error: &StoreError) -> Option<&CircuitOpen> {
+    error.downcast_ref::<CircuitOpen>()
+}
+
+#[derive(Debug)]
+enum State {
+    // Outcomes of the latest calls, true for a failure
+    Closed(VecDeque<bool>),
+    Open(Instant),
+    // One call is let through after the cooldown to probe the backend, another
+    // one if it hasn't finished within a cooldown, e.g. because it was cancelled
+    Probing(Instant),
+}
+
+// Store failing calls straight away once breaker_threshold of the last 20 calls
+// failed on transient backend errors or server side timeouts, rather than waiting for the driver's
+// timeouts on every request. After breaker_cooldown seconds one call probes the
+// backend, closing the breaker again when it succeeds.
+#[derive(Debug)]
+pub struct BreakerStore {
+    inner: Arc<dyn DataStore>,
+    state: Mutex<State>,
+}
+
+impl BreakerStore {
+    pub fn new(inner: Arc<dyn DataStore>) -> Self {
+        Self {
+            inner,
+            state: Mutex::new(State::Closed(VecDeque::with_capacity(WINDOW))),
+        }
+    }
+
+    fn admit(&self) -> Result<(), StoreError> {
+        let cooldown = Duration::from_secs(config::app().breaker_cooldown);
+        let mut state = self.state.lock().unwrap();
+        match &*state {
+            State::Closed(_) => Ok(()),
+            State::Open(since) | State::Probing(since) if since.elapsed() >= cooldown => {
+                *state = State::Probing(Instant::now());
+                Ok(())
+            }
+            State::Open(since) | State::Probing(since) => Err(CircuitOpen {
+                retry_after: cooldown.saturating_sub(since.elapsed()),
+            }
+            .into()),
+        }
+    }
+
+    fn record(&self, failed: bool) {
+        let threshold = config::app().breaker_threshold as usize;
+        let mut state = self.state.lock().unwrap();
+        match &mut *state {
+            State::Closed(outcomes) => {
+                if outcomes.len() == WINDOW {
+                    outcomes.pop_front();
+                }
+                outcomes.push_back(failed);
+                if outcomes.iter().filter(|failed| **failed).count() >= threshold {
+                    tracing::error!(threshold, "Data store failing, opening the circuit breaker");
+                    *state = State::Open(Instant::now());
+                }
+            }
+            State::Probing(_) if failed => *state = State::Open(Instant::now()),
+            State::Probing(_) => {
+                tracing::info!("Data store recovered, closing the circuit breaker");
+                *state = State::Closed(VecDeque::with_capacity(WINDOW));
+            }
+            // calls admitted before the breaker opened finishing late
+            State::Open(_) => {}
+        }
+    }
+
+    async fn call<T>(&self, operation: impl Future<Output = Result<T, StoreError>>) -> Result<T, StoreError> {
+        self.admit()?;
+        let result = operation.await;
+        self.record(result.as_ref().is_err_and(|e| retry::transient(e) || e.is::<StoreTimeout>()));
+        result
+    }
+}
+
+#[async_trait]
+impl DataStore for BreakerStore {
+    fn backend_name(&self) -> &'static str {
+        self.inner.backend_name()
+    }
+
+    async fn ping(&self) -> Result<(), StoreError> {
+        self.call(self.inner.ping()).await
+    }
+
+    async fn find_with(
+        &self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<Vec<Value>, StoreError> {
+        self.call(self.inner.find_with(collection, filter, options)).await
+    }
+
+    async fn find_raw(
+        &self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<Vec<StoredDocument>, StoreError> {
+        self.call(self.inner.find_raw(collection, filter, options)).await
+    }
+
+    async fn find_stream<'a>(
+        &'a self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<DocumentStream<'a>, StoreError> {
+        self.call(self.inner.find_stream(collection, filter, options)).await
+    }
+
+    async fn count(&self, collection: &str, filter: Value) -> Result<u64, StoreError> {
+        self.call(self.inner.count(collection, filter)).await
+    }
+
+    async fn find_one(&self, collection: &str, filter: Value) -> Result<Option<Value>, StoreError> {
+        self.call(self.inner.find_one(collection, filter)).await
+    }
+
+    async fn insert_one(&self, collection: &str, document: Value) -> Result<(), StoreError> {
+        self.call(self.inner.insert_one(collection, document)).await
+    }
+
+    async fn delete_one(&self, collection: &str, filter: Value) -> Result<bool, StoreError> {
+        self.call(self.inner.delete_one(collection, filter)).await
+    }
+
+    async fn delete_many(&self, collection: &str, filter: Value) -> Result<u64, StoreError> {
+        self.call(self.inner.delete_many(collection, filter)).await
+    }
+
+    async fn update_one(&self, collection: &str, filter: Value, changes: Value) -> Result<bool, StoreError> {
+        self.call(self.inner.update_one(collection, filter, changes)).await
+    }
+
+    async fn insert_if_absent(
+        &self,
+        collection: &str,
+        filter: Value,
+        document: Value,
+    ) -> Result<bool, StoreError> {
+        self.call(self.inner.insert_if_absent(collection, filter, document)).await
+    }
+
+    async fn insert_many_atomic(&self, documents: Vec<(String, Value)>) -> Result<(), StoreError> {
+        self.call(self.inner.insert_many_atomic(documents)).await
+    }
+
+    async fn ensure_index(&self, collection: &str, fields: &[&str], unique: bool) -> Result<(), StoreError> {
+        self.call(self.inner.ensure_index(collection, fields, unique)).await
+    }
+
+    async fn ensure_text_index(&self, collection: &str, fields: &[&str]) -> Result<(), StoreError> {
+        self.call(self.inner.ensure_text_index(collection, fields)).await
+    }
+
+    async fn text_search(
+        &self,
+        collection: &str,
+        filter: Value,
+        fields: &[&str],
+        query: &str,
+        limit: u64,
+    ) -> Result<Vec<(f64, Value)>, StoreError> {
+        self.call(self.inner.text_search(collection, filter, fields, query, limit)).await
+    }
+
+    async fn collection_names(&self) -> Result<Vec<String>, StoreError> {
+        self.call(self.inner.collection_names()).await
+    }
+}
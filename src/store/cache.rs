@@ -0,0 +1,368 @@
+use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use super::{circuit_open, DataStore, DocumentStream, FindOptions, StoreError};
+use crate::config;
+
+// Results of one find, with when they were read
+#[derive(Debug)]
+struct Entry {
+    values: Vec<Value>,
+    fetched: Instant,
+    // when the entry was last served, the least recently used are dropped first
+    used: Instant,
+    // set while a background refresh of a stale entry is running
+    refreshing: bool,
+    // size of the key and results serialized as JSON, an estimate of the memory held
+    bytes: usize,
+}
+
+#[derive(Debug, Default)]
+struct CollectionCache {
+    entries: HashMap<String, Entry>,
+    // bumped on every write so refreshes started before it don't store old results
+    generation: u64,
+    hits: u64,
+    stale_hits: u64,
+    misses: u64,
+}
+
+impl CollectionCache {
+    // Drop results past `lifetime`, their TTL plus stale window, then the least
+    // recently read ones beyond cache_max_entries
+    fn evict(&mut self, lifetime: Duration) {
+        self.entries.retain(|_, entry| entry.fetched.elapsed() < lifetime);
+        let max_entries = config::app().cache_max_entries;
+        while self.entries.len() > max_entries {
+            let least_used = self.entries.iter().min_by_key(|(_, entry)| entry.used);
+            let Some(key) = least_used.map(|(key, _)| key.clone()) else {
+                break;
+            };
+            self.entries.remove(&key);
+        }
+    }
+}
+
+// Read cache counters of one collection since the server started
+#[derive(Clone, Debug, Serialize, juniper::GraphQLObject)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    pub collection: String,
+    pub entries: i32,
+    // Approximate, from the size of the cached results as JSON
+    pub approx_bytes: i32,
+    // Reads answered from the cache, including stale ones
+    pub hits: i32,
+    // Reads answered with expired results while they were refreshed
+    pub stale_hits: i32,
+    pub misses: i32,
+    pub hit_rate: f64,
+}
+
+fn saturating_i32(count: u64) -> i32 {
+    i32::try_from(count).unwrap_or(i32::MAX)
+}
+
+type Entries = Arc<Mutex<HashMap<String, CollectionCache>>>;
+
+// Read through cache in front of another store. Finds are served from memory for
+// CACHE_TTL seconds, then for the collection's stale-while-revalidate window the
+// stale results are still served while a background read refreshes them. Results
+// past that window are dropped when read or when the collection caches new ones,
+// and each collection keeps at most cache_max_entries results, dropping the least
+// recently read. Writes through this store drop the cached results of the
+// collections they touch, and while the circuit breaker is open results still
+// cached are served whatever their age. Raw finds go through the cached finds,
+// so their documents are the cached JSON.
+#[derive(Debug)]
+pub struct CachedStore {
+    inner: Arc<dyn DataStore>,
+    entries: Entries,
+    ttl: Duration,
+}
+
+impl CachedStore {
+    pub fn new(inner: Arc<dyn DataStore>, ttl: Duration) -> Self {
+        Self {
+            inner,
+            entries: Default::default(),
+            ttl,
+        }
+    }
+
+    fn stale_window(collection: &str) -> Duration {
+        let settings = config::app();
+        let seconds = settings
+            .cache_swr
+            .get(collection)
+            .or_else(|| settings.cache_swr.get("default"))
+            .copied()
+            .unwrap_or(0);
+        Duration::from_secs(seconds)
+    }
+
+    // Drop the cached results of a collection, returns how many there were
+    fn invalidate(&self, collection: &str) -> u64 {
+        let mut entries = self.entries.lock().unwrap();
+        let cache = entries.entry(collection.to_string()).or_default();
+        let dropped = cache.entries.len() as u64;
+        cache.entries.clear();
+        cache.generation += 1;
+        dropped
+    }
+
+    // Results past their stale window, served while the circuit breaker is open
+    fn expired(&self, collection: &str, key: &str) -> Option<Vec<Value>> {
+        let entries = self.entries.lock().unwrap();
+        let values = entries.get(collection)?.entries.get(key)?.values.clone();
+        tracing::warn!(collection, "Serving expired cached results while the data store is unavailable");
+        Some(values)
+    }
+
+    async fn fetch(
+        inner: &dyn DataStore,
+        entries: &Entries,
+        collection: &str,
+        key: String,
+        filter: Value,
+        options: FindOptions,
+        lifetime: Duration,
+    ) -> Result<Vec<Value>, StoreError> {
+        let generation = entries.lock().unwrap().get(collection).map_or(0, |cache| cache.generation);
+        let result = inner.find_with(collection, filter, options).await;
+        let mut entries = entries.lock().unwrap();
+        let cache = entries.entry(collection.to_string()).or_default();
+        match &result {
+            Ok(values) if cache.generation == generation => {
+                let bytes = key.len() + serde_json::to_vec(values).map_or(0, |json| json.len());
+                let now = Instant::now();
+                let entry = Entry {
+                    values: values.clone(),
+                    fetched: now,
+                    used: now,
+                    refreshing: false,
+                    bytes,
+                };
+                cache.entries.insert(key, entry);
+                cache.evict(lifetime);
+            }
+            // a failed refresh leaves the stale entry to be retried by the next read
+            _ => {
+                if let Some(entry) = cache.entries.get_mut(&key) {
+                    entry.refreshing = false;
+                }
+            }
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl DataStore for CachedStore {
+    fn backend_name(&self) -> &'static str {
+        self.inner.backend_name()
+    }
+
+    async fn ping(&self) -> Result<(), StoreError> {
+        self.inner.ping().await
+    }
+
+    async fn find_with(
+        &self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<Vec<Value>, StoreError> {
+        let key = format!("{}|{:?}|{}|{:?}", filter, options.sort, options.offset, options.limit);
+        let lifetime = self.ttl + Self::stale_window(collection);
+        let cached = {
+            let mut entries = self.entries.lock().unwrap();
+            let cache = entries.entry(collection.to_string()).or_default();
+            let cached = cache.entries.get_mut(&key).and_then(|entry| {
+                let age = entry.fetched.elapsed();
+                if age >= lifetime {
+                    return None;
+                }
+                entry.used = Instant::now();
+                if age < self.ttl {
+                    Some((entry.values.clone(), false, false))
+                } else {
+                    // only the first stale read starts a refresh
+                    let refresh = !entry.refreshing;
+                    entry.refreshing = true;
+                    Some((entry.values.clone(), true, refresh))
+                }
+            });
+            // expired results are only kept to be served while the breaker is open
+            if cached.is_none() && !config::app().breaker_serve_stale {
+                cache.entries.remove(&key);
+            }
+            match &cached {
+                Some((_, stale, _)) => {
+                    cache.hits += 1;
+                    if *stale {
+                        cache.stale_hits += 1;
+                    }
+                }
+                None => cache.misses += 1,
+            }
+            cached
+        };
+        match cached {
+            Some((values, _, refresh)) => {
+                if refresh {
+                    let inner = self.inner.clone();
+                    let entries = self.entries.clone();
+                    let collection = collection.to_string();
+                    tokio::spawn(async move {
+                        let refreshed =
+                            Self::fetch(&*inner, &entries, &collection, key, filter, options, lifetime).await;
+                        if let Err(e) = refreshed {
+                            eprintln!("Error refreshing cached {} results: {}", collection, e);
+                        }
+                    });
+                }
+                Ok(values)
+            }
+            None => {
+                let fetched = Self::fetch(
+                    &*self.inner,
+                    &self.entries,
+                    collection,
+                    key.clone(),
+                    filter,
+                    options,
+                    lifetime,
+                )
+                .await;
+                match fetched {
+                    Err(e) if circuit_open(&e).is_some() && config::app().breaker_serve_stale => {
+                        self.expired(collection, &key).ok_or(e)
+                    }
+                    fetched => fetched,
+                }
+            }
+        }
+    }
+
+    // streams are for reads too large to keep, so they bypass the cache
+    async fn find_stream<'a>(
+        &'a self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<DocumentStream<'a>, StoreError> {
+        self.inner.find_stream(collection, filter, options).await
+    }
+
+    async fn count(&self, collection: &str, filter: Value) -> Result<u64, StoreError> {
+        self.inner.count(collection, filter).await
+    }
+
+    fn cache_stats(&self) -> Vec<CacheStats> {
+        let entries = self.entries.lock().unwrap();
+        let mut stats: Vec<CacheStats> = entries
+            .iter()
+            .map(|(collection, cache)| {
+                let reads = cache.hits + cache.misses;
+                CacheStats {
+                    collection: collection.clone(),
+                    entries: saturating_i32(cache.entries.len() as u64),
+                    approx_bytes: saturating_i32(cache.entries.values().map(|entry| entry.bytes as u64).sum()),
+                    hits: saturating_i32(cache.hits),
+                    stale_hits: saturating_i32(cache.stale_hits),
+                    misses: saturating_i32(cache.misses),
+                    hit_rate: if reads == 0 { 0.0 } else { cache.hits as f64 / reads as f64 },
+                }
+            })
+            .collect();
+        stats.sort_by(|a, b| a.collection.cmp(&b.collection));
+        stats
+    }
+
+    fn purge_cache(&self, collection: Option<&str>) -> u64 {
+        let collections: Vec<String> = match collection {
+            Some(collection) => vec![collection.to_string()],
+            None => self.entries.lock().unwrap().keys().cloned().collect(),
+        };
+        collections.iter().map(|collection| self.invalidate(collection)).sum()
+    }
+
+    async fn find_one(&self, collection: &str, filter: Value) -> Result<Option<Value>, StoreError> {
+        self.inner.find_one(collection, filter).await
+    }
+
+    async fn insert_one(&self, collection: &str, document: Value) -> Result<(), StoreError> {
+        let result = self.inner.insert_one(collection, document).await;
+        self.invalidate(collection);
+        result
+    }
+
+    async fn delete_one(&self, collection: &str, filter: Value) -> Result<bool, StoreError> {
+        let result = self.inner.delete_one(collection, filter).await;
+        self.invalidate(collection);
+        result
+    }
+
+    async fn delete_many(&self, collection: &str, filter: Value) -> Result<u64, StoreError> {
+        let result = self.inner.delete_many(collection, filter).await;
+        self.invalidate(collection);
+        result
+    }
+
+    async fn update_one(&self, collection: &str, filter: Value, changes: Value) -> Result<bool, StoreError> {
+        let result = self.inner.update_one(collection, filter, changes).await;
+        self.invalidate(collection);
+        result
+    }
+
+    async fn insert_if_absent(
+        &self,
+        collection: &str,
+        filter: Value,
+        document: Value,
+    ) -> Result<bool, StoreError> {
+        let result = self.inner.insert_if_absent(collection, filter, document).await;
+        self.invalidate(collection);
+        result
+    }
+
+    async fn insert_many_atomic(&self, documents: Vec<(String, Value)>) -> Result<(), StoreError> {
+        let mut collections: Vec<String> = documents.iter().map(|(collection, _)| collection.clone()).collect();
+        collections.dedup();
+        let result = self.inner.insert_many_atomic(documents).await;
+        for collection in collections {
+            self.invalidate(&collection);
+        }
+        result
+    }
+
+    async fn ensure_index(&self, collection: &str, fields: &[&str], unique: bool) -> Result<(), StoreError> {
+        self.inner.ensure_index(collection, fields, unique).await
+    }
+
+    async fn ensure_text_index(&self, collection: &str, fields: &[&str]) -> Result<(), StoreError> {
+        self.inner.ensure_text_index(collection, fields).await
+    }
+
+    async fn text_search(
+        &self,
+        collection: &str,
+        filter: Value,
+        fields: &[&str],
+        query: &str,
+        limit: u64,
+    ) -> Result<Vec<(f64, Value)>, StoreError> {
+        self.inner.text_search(collection, filter, fields, query, limit).await
+    }
+
+    async fn collection_names(&self) -> Result<Vec<String>, StoreError> {
+        self.inner.collection_names().await
+    }
+}
@@ -0,0 +1,66 @@
+use mongodb::bson::{self, RawBsonRef, RawDocumentBuf};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use super::StoreError;
+
+// A document as the backend returned it, decoded into a model only when read.
+// Mongo documents stay BSON so models are deserialized straight from their bytes.
+#[derive(Clone, Debug)]
+pub enum StoredDocument {
+    Json(Value),
+    Bson(RawDocumentBuf),
+}
+
+impl StoredDocument {
+    pub fn decode<T: DeserializeOwned>(self) -> Result<T, StoreError> {
+        Ok(match self {
+            StoredDocument::Json(value) => serde_json::from_value(value)?,
+            StoredDocument::Bson(document) => bson::from_slice(document.as_bytes())?,
+        })
+    }
+
+    // The _id as a string, ObjectIds in their hex form
+    pub fn id(&self) -> Option<String> {
+        match self {
+            StoredDocument::Json(value) => match value.get("_id")? {
+                Value::String(id) => Some(id.clone()),
+                Value::Number(id) => Some(id.to_string()),
+                id => id.get("$oid").and_then(Value::as_str).map(str::to_string),
+            },
+            StoredDocument::Bson(document) => match document.get("_id").ok()?? {
+                RawBsonRef::ObjectId(id) => Some(id.to_hex()),
+                RawBsonRef::String(id) => Some(id.to_string()),
+                RawBsonRef::Int32(id) => Some(id.to_string()),
+                RawBsonRef::Int64(id) => Some(id.to_string()),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl From<Value> for StoredDocument {
+    fn from(value: Value) -> Self {
+        StoredDocument::Json(value)
+    }
+}
+
+// Top level string fields, read without decoding the rest of the document
+pub trait DocumentFields {
+    fn get_str(&self, field: &str) -> Option<&str>;
+}
+
+impl DocumentFields for Value {
+    fn get_str(&self, field: &str) -> Option<&str> {
+        self.get(field).and_then(Value::as_str)
+    }
+}
+
+impl DocumentFields for StoredDocument {
+    fn get_str(&self, field: &str) -> Option<&str> {
+        match self {
+            StoredDocument::Json(value) => value.get_str(field),
+            StoredDocument::Bson(document) => document.get_str(field).ok(),
+        }
+    }
+}
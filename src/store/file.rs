@@ -0,0 +1,179 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use std::{cmp::Ordering, collections::HashMap, env, fs, path::Path};
+
+use super::{DataStore, FindOptions, SortDirection, StoreError};
+
+// Read only backend serving collections loaded from <DATA_DIR>/<collection>.json,
+// each file holding a JSON array of documents
+#[derive(Clone, Debug)]
+pub struct FileStore {
+    collections: HashMap<String, Vec<Value>>,
+}
+
+impl FileStore {
+    pub fn load() -> Result<Self, StoreError> {
+        let directory = env::var("DATA_DIR").unwrap_or_else(|_| "data".to_string());
+        Self::load_dir(Path::new(&directory))
+    }
+
+    pub fn load_dir(directory: &Path) -> Result<Self, StoreError> {
+        let mut collections = HashMap::new();
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("json") {
+                continue;
+            }
+            let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            let documents: Vec<Value> = serde_json::from_str(&fs::read_to_string(&path)?)
+                .map_err(|e| format!("Invalid JSON in {}: {}", path.display(), e))?;
+            collections.insert(name, documents);
+        }
+        println!("Loaded {} collections from {}", collections.len(), directory.display());
+        Ok(Self { collections })
+    }
+
+    pub fn collections(&self) -> &HashMap<String, Vec<Value>> {
+        &self.collections
+    }
+
+    fn documents<'a>(&'a self, collection: &str, filter: &'a Value) -> impl Iterator<Item = &'a Value> {
+        self.collections
+            .get(collection)
+            .into_iter()
+            .flatten()
+            .filter(move |document| matches(document, filter))
+    }
+}
+
+// Every field of the filter must be present with an equal value, nested objects match recursively
+fn matches(document: &Value, filter: &Value) -> bool {
+    match (document, filter) {
+        (Value::Object(document), Value::Object(filter)) => filter.iter().all(|(key, expected)| {
+            document
+                .get(key)
+                .map(|actual| match (actual, expected) {
+                    (_, Value::Object(_)) => matches(actual, expected),
+                    (Value::Array(values), Value::Array(wanted)) => wanted.iter().all(|value| values.contains(value)),
+                    _ => actual == expected,
+                })
+                .unwrap_or(expected.is_null())
+        }),
+        _ => false,
+    }
+}
+
+// Order JSON scalars like the databases do: nulls first, then numbers, strings and booleans
+fn compare_values(a: &Value, b: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Null => 0,
+            Value::Number(_) => 1,
+            Value::String(_) => 2,
+            Value::Bool(_) => 3,
+            Value::Array(_) | Value::Object(_) => 4,
+        }
+    }
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a
+            .as_f64()
+            .partial_cmp(&b.as_f64())
+            .unwrap_or(Ordering::Equal),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+fn read_only() -> StoreError {
+    "The file data store is read only".into()
+}
+
+#[async_trait]
+impl DataStore for FileStore {
+    fn backend_name(&self) -> &'static str {
+        "file"
+    }
+
+    async fn ping(&self) -> Result<(), StoreError> {
+        Ok(())
+    }
+
+    async fn find_with(
+        &self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<Vec<Value>, StoreError> {
+        let mut documents: Vec<&Value> = self.documents(collection, &filter).collect();
+        // a stable sort keeps file order for equal keys
+        documents.sort_by(|a, b| {
+            options
+                .sort
+                .iter()
+                .map(|(field, direction)| {
+                    let ordering = compare_values(&a[field.as_str()], &b[field.as_str()]);
+                    match direction {
+                        SortDirection::Ascending => ordering,
+                        SortDirection::Descending => ordering.reverse(),
+                    }
+                })
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+        Ok(documents
+            .into_iter()
+            .skip(options.offset as usize)
+            .take(options.capped_limit() as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn count(&self, collection: &str, filter: Value) -> Result<u64, StoreError> {
+        Ok(self.documents(collection, &filter).count() as u64)
+    }
+
+    async fn find_one(&self, collection: &str, filter: Value) -> Result<Option<Value>, StoreError> {
+        Ok(self.documents(collection, &filter).next().cloned())
+    }
+
+    async fn insert_one(&self, _collection: &str, _document: Value) -> Result<(), StoreError> {
+        Err(read_only())
+    }
+
+    async fn delete_one(&self, _collection: &str, _filter: Value) -> Result<bool, StoreError> {
+        Err(read_only())
+    }
+
+    async fn delete_many(&self, _collection: &str, _filter: Value) -> Result<u64, StoreError> {
+        Err(read_only())
+    }
+
+    async fn update_one(&self, _collection: &str, _filter: Value, _changes: Value) -> Result<bool, StoreError> {
+        Err(read_only())
+    }
+
+    async fn insert_if_absent(
+        &self,
+        _collection: &str,
+        _filter: Value,
+        _document: Value,
+    ) -> Result<bool, StoreError> {
+        Err(read_only())
+    }
+
+    async fn insert_many_atomic(&self, _documents: Vec<(String, Value)>) -> Result<(), StoreError> {
+        Err(read_only())
+    }
+
+    async fn collection_names(&self) -> Result<Vec<String>, StoreError> {
+        Ok(self.collections.keys().cloned().collect())
+    }
+
+    async fn ensure_index(&self, _collection: &str, _fields: &[&str], _unique: bool) -> Result<(), StoreError> {
+        Ok(())
+    }
+}
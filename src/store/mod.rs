@@ -0,0 +1,366 @@
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use serde_json::Value;
+use std::{
+    collections::HashSet, env, error::Error as StdError, fmt::Debug, pin::Pin, sync::{Arc, OnceLock},
+    time::Duration,
+};
+use tokio_stream::Stream;
+
+use crate::{config, search};
+
+mod breaker;
+mod cache;
+mod document;
+mod file;
+mod mongo;
+mod postgres;
+mod retry;
+mod search_sync;
+mod sqlite;
+mod timeout;
+mod versioned;
+
+pub use breaker::{circuit_open, BreakerStore};
+pub use cache::{CacheStats, CachedStore};
+pub use document::{DocumentFields, StoredDocument};
+pub use file::FileStore;
+pub use mongo::MongoStore;
+pub use postgres::PostgresStore;
+pub use retry::RetryingStore;
+pub use search_sync::SearchSyncStore;
+pub use sqlite::SqliteStore;
+pub use timeout::{within, StoreTimeout};
+pub use versioned::{content_version, VersionedStore};
+
+pub type StoreError = Box<dyn StdError + Send + Sync>;
+
+// Documents yielded one at a time, see DataStore::find_stream
+pub type DocumentStream<'a> = Pin<Box<dyn Stream<Item = Result<Value, StoreError>> + Send + 'a>>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+// Ordering and paging applied to a find, on top of the filter.
+// Documents are sorted by the top level fields in `sort`, then by insertion order.
+#[derive(Clone, Debug, Default)]
+pub struct FindOptions {
+    pub sort: Vec<(String, SortDirection)>,
+    pub offset: u64,
+    pub limit: Option<u64>,
+}
+
+static MAX_DOCUMENTS: OnceLock<u64> = OnceLock::new();
+
+impl FindOptions {
+    // Limit pushed down to the backend for finds collected into memory, never above
+    // STORE_MAX_DOCUMENTS (10000 by default) so one large collection can't exhaust it
+    pub fn capped_limit(&self) -> u64 {
+        let cap = *MAX_DOCUMENTS.get_or_init(|| {
+            env::var("STORE_MAX_DOCUMENTS")
+                .ok()
+                .and_then(|max| max.parse().ok())
+                .unwrap_or(10_000)
+        });
+        self.limit.map_or(cap, |limit| limit.min(cap))
+    }
+}
+
+// Backend agnostic access to the portfolio documents.
+// Documents and filters are JSON objects, filters match on field equality,
+// except that an array in a filter matches arrays containing all of its values.
+#[async_trait]
+pub trait DataStore: Debug + Send + Sync {
+    fn backend_name(&self) -> &'static str;
+    // Cheap round trip to check the backend is reachable
+    async fn ping(&self) -> Result<(), StoreError>;
+    async fn find(&self, collection: &str, filter: Value) -> Result<Vec<Value>, StoreError> {
+        self.find_with(collection, filter, FindOptions::default()).await
+    }
+    async fn find_with(
+        &self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<Vec<Value>, StoreError>;
+    // Like find_with, but leaving the documents in the backend's own format until
+    // they are decoded into models
+    async fn find_raw(
+        &self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<Vec<StoredDocument>, StoreError> {
+        let documents = self.find_with(collection, filter, options).await?;
+        Ok(documents.into_iter().map(StoredDocument::Json).collect())
+    }
+    // Matching documents without the STORE_MAX_DOCUMENTS cap, for callers that fold
+    // over a whole collection. Backends that can't stream are read one page at a
+    // time as the stream is consumed. Finds order by insertion after the sort
+    // fields (ORDER BY id on the SQL backends), so pages neither skip nor repeat.
+    async fn find_stream<'a>(
+        &'a self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<DocumentStream<'a>, StoreError> {
+        let collection = collection.to_string();
+        let page_size = FindOptions::default().capped_limit();
+        // offset of the next page and how many documents are still wanted, None once read
+        let next = Some((options.offset, options.limit));
+        let pages = stream::unfold(next, move |next| {
+            let (collection, filter, sort) = (collection.clone(), filter.clone(), options.sort.clone());
+            async move {
+                let (offset, remaining) = next?;
+                let limit = remaining.map_or(page_size, |remaining| remaining.min(page_size));
+                if limit == 0 {
+                    return None;
+                }
+                let page = FindOptions {
+                    sort,
+                    offset,
+                    limit: Some(limit),
+                };
+                match self.find_with(&collection, filter, page).await {
+                    Ok(found) => {
+                        let read = found.len() as u64;
+                        let remaining = remaining.map(|remaining| remaining - read);
+                        Some((Ok(found), (read == limit).then_some((offset + read, remaining))))
+                    }
+                    Err(e) => Some((Err(e), None)),
+                }
+            }
+        });
+        Ok(Box::pin(pages.flat_map(|page| {
+            let documents: Vec<Result<Value, StoreError>> = match page {
+                Ok(found) => found.into_iter().map(Ok).collect(),
+                Err(e) => vec![Err(e)],
+            };
+            stream::iter(documents)
+        })))
+    }
+    async fn count(&self, collection: &str, filter: Value) -> Result<u64, StoreError>;
+    async fn find_one(&self, collection: &str, filter: Value) -> Result<Option<Value>, StoreError>;
+    async fn insert_one(&self, collection: &str, document: Value) -> Result<(), StoreError>;
+    async fn delete_one(&self, collection: &str, filter: Value) -> Result<bool, StoreError>;
+    // Delete every matching document, returns how many were deleted
+    async fn delete_many(&self, collection: &str, filter: Value) -> Result<u64, StoreError>;
+    // Set the top level fields in `changes` on the first matching document
+    async fn update_one(&self, collection: &str, filter: Value, changes: Value) -> Result<bool, StoreError>;
+    // Insert `document` unless a document matches `filter`, as one write so that
+    // concurrent callers insert it once. Returns whether it was inserted.
+    async fn insert_if_absent(
+        &self,
+        collection: &str,
+        filter: Value,
+        document: Value,
+    ) -> Result<bool, StoreError>;
+    // Insert documents across collections so that either all of them land or none do
+    async fn insert_many_atomic(&self, documents: Vec<(String, Value)>) -> Result<(), StoreError>;
+    // Index one or more top level fields, unique indexes skip documents without them.
+    // Mongo keeps documents without a locale, they are the default locale's.
+    async fn ensure_index(&self, collection: &str, fields: &[&str], unique: bool) -> Result<(), StoreError>;
+    // Full-text index over string fields, for backends that support text search
+    async fn ensure_text_index(&self, _collection: &str, _fields: &[&str]) -> Result<(), StoreError> {
+        Ok(())
+    }
+    // Documents matching `filter` and any word of `query`, with their scores, best
+    // first. Mongo matches the fields of the collection's text index, the other
+    // backends score documents by how many query words their `fields` contain.
+    async fn text_search(
+        &self,
+        collection: &str,
+        filter: Value,
+        fields: &[&str],
+        query: &str,
+        limit: u64,
+    ) -> Result<Vec<(f64, Value)>, StoreError> {
+        let query_words = words(query);
+        let mut scored: Vec<(f64, Value)> = self
+            .find(collection, filter)
+            .await?
+            .into_iter()
+            .filter_map(|document| {
+                let document_words: HashSet<String> = fields
+                    .iter()
+                    .filter_map(|field| document.get(*field))
+                    .flat_map(|value| match value {
+                        Value::String(text) => vec![text.as_str()],
+                        Value::Array(values) => values.iter().filter_map(Value::as_str).collect(),
+                        _ => Vec::new(),
+                    })
+                    .flat_map(words)
+                    .collect();
+                let score = query_words.iter().filter(|word| document_words.contains(*word)).count();
+                (score > 0).then_some((score as f64, document))
+            })
+            .collect();
+        // stable, so equal scores keep the store's order
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.truncate(limit as usize);
+        Ok(scored)
+    }
+    // Names of the collections holding documents, or created in Mongo
+    async fn collection_names(&self) -> Result<Vec<String>, StoreError>;
+    // Read cache counters per collection, empty when reads aren't cached
+    fn cache_stats(&self) -> Vec<CacheStats> {
+        Vec::new()
+    }
+    // Drop the cached reads of one collection, or of all of them when None.
+    // Returns how many cached results were dropped.
+    fn purge_cache(&self, _collection: Option<&str>) -> u64 {
+        0
+    }
+}
+
+// Connect to the backend picked by DATA_STORE (mongo, postgres, sqlite or file).
+// Without DATA_STORE, Mongo is used when MONGO_DB_URI is set and a local SQLite file otherwise.
+// Operations failing on a transient error are retried up to retry_attempts times,
+// a BreakerStore fails calls fast while the backend keeps failing, writes raise
+// the owner's content version, posts and projects are pushed to search
+// providers keeping a copy of them, and reads go through a CachedStore when the
+// cache_ttl setting is above zero.
+pub async fn connect() -> Result<Arc<dyn DataStore>, StoreError> {
+    let backend = env::var("DATA_STORE").unwrap_or_else(|_| {
+        if env::var("MONGO_DB_URI").is_ok() { "mongo" } else { "sqlite" }.to_string()
+    });
+    let store: Arc<dyn DataStore> = match backend.as_str() {
+        "mongo" => Arc::new(MongoStore::connect().await?),
+        "postgres" => Arc::new(PostgresStore::connect().await?),
+        "sqlite" => Arc::new(SqliteStore::connect().await?),
+        "file" => Arc::new(FileStore::load()?),
+        other => return Err(format!("Unknown DATA_STORE {}, expected mongo, postgres, sqlite or file", other).into()),
+    };
+    let store: Arc<dyn DataStore> = match config::app().retry_attempts {
+        0 | 1 => store,
+        _ => Arc::new(RetryingStore::new(store)),
+    };
+    let store: Arc<dyn DataStore> = match config::app().breaker_threshold {
+        0 => store,
+        _ => Arc::new(BreakerStore::new(store)),
+    };
+    let store: Arc<dyn DataStore> = Arc::new(VersionedStore::new(store));
+    let provider = search::provider();
+    let store: Arc<dyn DataStore> = if provider.keeps_copy() {
+        Arc::new(SearchSyncStore::new(store, provider))
+    } else {
+        store
+    };
+    match config::app().cache_ttl {
+        0 => Ok(store),
+        ttl => Ok(Arc::new(CachedStore::new(store, Duration::from_secs(ttl)))),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum IndexKind {
+    Lookup,
+    Unique,
+    Text,
+}
+
+// Unique tenant and API key lookups plus an email index on every portfolio collection.
+// Each index is created on its own: one that fails, e.g. a unique index over
+// existing duplicates, is logged and doesn't keep the others from being created.
+pub async fn ensure_indexes(store: &dyn DataStore) -> Result<(), StoreError> {
+    let collections = config::collections();
+    let index = |collection: &'static str, fields: &'static [&'static str], kind: IndexKind| {
+        (collection, fields, kind)
+    };
+    let mut indexes = vec![
+        index(&collections.tenants, &["email"], IndexKind::Unique),
+        index(&collections.tenants, &["host"], IndexKind::Unique),
+        index(&collections.api_keys, &["keyHash"], IndexKind::Unique),
+    ];
+    for collection_name in collections.portfolio() {
+        indexes.push(index(collection_name, &["email"], IndexKind::Lookup));
+    }
+    // project, blog post and talk pages are looked up by slug, translations of a
+    // page share its slug so the locale is part of the key
+    for collection_name in [&collections.projects, &collections.blog_posts, &collections.talks] {
+        indexes.push(index(collection_name, &["slug"], IndexKind::Lookup));
+        indexes.push(index(collection_name, &["email", "slug", "locale"], IndexKind::Unique));
+    }
+    indexes.extend([
+        // a book is listed once, however many sources list it
+        index(&collections.reading_list, &["email", "isbn"], IndexKind::Unique),
+        index(&collections.projects, &["title", "description", "tags"], IndexKind::Text),
+        index(&collections.blog_posts, &["title", "excerpt", "tags"], IndexKind::Text),
+        index(&collections.talks, &["title", "description", "event", "tags"], IndexKind::Text),
+        index(&collections.uses, &["name", "description", "category"], IndexKind::Text),
+        index(&collections.endorsements, &["email"], IndexKind::Lookup),
+        // a visitor endorses a skill and likes a project once
+        index(&collections.endorsements, &["email", "skill", "visitor"], IndexKind::Unique),
+        index(&collections.analytics, &["email"], IndexKind::Lookup),
+        // the days the rollup hasn't summarized yet are read by day
+        index(&collections.analytics, &["email", "date"], IndexKind::Lookup),
+        index(&collections.likes, &["email"], IndexKind::Lookup),
+        index(&collections.likes, &["email", "project", "visitor"], IndexKind::Unique),
+        index(&collections.experiments, &["email"], IndexKind::Lookup),
+        index(&collections.events, &["name"], IndexKind::Lookup),
+        index(&collections.analytics_daily, &["email"], IndexKind::Lookup),
+        index(&collections.trash, &["email"], IndexKind::Lookup),
+        index(&collections.media, &["email", "url"], IndexKind::Unique),
+        index(&collections.redirects, &["fromPath"], IndexKind::Lookup),
+        index(&collections.content_versions, &["email"], IndexKind::Unique),
+    ]);
+    let mut failed = 0;
+    for &(collection, fields, kind) in &indexes {
+        let created = match kind {
+            IndexKind::Text => store.ensure_text_index(collection, fields).await,
+            kind => store.ensure_index(collection, fields, kind == IndexKind::Unique).await,
+        };
+        if let Err(e) = created {
+            tracing::error!(error = %e, collection, ?fields, "Failed to create index");
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        return Err(format!("{} of {} indexes couldn't be created", failed, indexes.len()).into());
+    }
+    Ok(())
+}
+
+// ORDER BY clause for the SQL backends, `field_expression` turns a field name into
+// the expression extracting it from the JSON document
+fn order_by_clause(
+    sort: &[(String, SortDirection)],
+    field_expression: impl Fn(&str) -> String,
+) -> Result<String, StoreError> {
+    let mut terms = Vec::new();
+    for (field, direction) in sort {
+        let field = checked_identifier(field)?;
+        let direction = match direction {
+            SortDirection::Ascending => "ASC",
+            SortDirection::Descending => "DESC",
+        };
+        // missing fields sort lowest, as in Mongo
+        let nulls = match direction {
+            SortDirection::Ascending => "NULLS FIRST",
+            SortDirection::Descending => "NULLS LAST",
+        };
+        terms.push(format!("{} {} {}", field_expression(field), direction, nulls));
+    }
+    terms.push("id".to_string());
+    Ok(format!("ORDER BY {}", terms.join(", ")))
+}
+
+// Lowercased words of a text, for the text search of backends without text indexes
+fn words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+// Identifiers can't be bound as parameters, so only allow plain names in generated SQL
+fn checked_identifier(name: &str) -> Result<&str, StoreError> {
+    if !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        Ok(name)
+    } else {
+        Err(format!("Unsupported name in SQL: {}", name).into())
+    }
+}
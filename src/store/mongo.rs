@@ -0,0 +1,478 @@
+use async_trait::async_trait;
+use mongodb::{
+    bson::{doc, Bson, Document, RawDocumentBuf},
+    error::{Error as MongoError, ErrorKind},
+    options::{
+        Acknowledgment, ClientOptions, CountOptions, FindOneOptions, IndexOptions, ReadPreference,
+        ReadPreferenceOptions, SelectionCriteria, UpdateOptions, WriteConcern,
+    },
+    Client, Collection, Cursor, Database, IndexModel,
+};
+use serde_json::{json, Value};
+use std::{
+    env,
+    time::{Duration, Instant},
+};
+use tokio_stream::StreamExt;
+
+use super::{
+    timeout::store_timeout, DataStore, DocumentStream, FindOptions, SortDirection, StoreError,
+    StoreTimeout, StoredDocument,
+};
+use crate::config;
+
+// Server error code of a query that ran past its max_time
+const MAX_TIME_MS_EXPIRED: i32 = 50;
+// Server error code of a command on a collection that doesn't exist
+const NAMESPACE_NOT_FOUND: i32 = 26;
+
+#[derive(Clone, Debug)]
+pub struct MongoStore {
+    client: Client,
+    database: Database,
+    // finds slower than this are logged as warnings
+    slow_query_threshold: Duration,
+}
+
+impl MongoStore {
+    pub async fn connect() -> Result<Self, StoreError> {
+        let mongo_db_uri = env::var("MONGO_DB_URI")
+            .unwrap_or_else(|_| {
+                println!("MONGO_DB_URI is not set, using default value");
+                "default_value".to_string()
+            });
+        let mut client_options = ClientOptions::parse(mongo_db_uri).await?;
+        apply_replica_set_tuning(&mut client_options)?;
+        let client = Client::with_options(client_options)?;
+        println!("Connected to MongoDB");
+        Ok(Self {
+            database: client.database(&config::database().database_name),
+            client,
+            slow_query_threshold: Duration::from_millis(
+                env::var("SLOW_QUERY_MS").ok().and_then(|ms| ms.parse().ok()).unwrap_or(200),
+            ),
+        })
+    }
+
+    fn collection(&self, name: &str) -> Collection<Document> {
+        self.database.collection(name)
+    }
+
+    // Open a cursor over the matching documents, sorted and paged as asked.
+    // max_time bounds the server time spent on the whole find.
+    async fn cursor<T: Send + Sync>(
+        &self,
+        collection: &str,
+        filter: Value,
+        options: &FindOptions,
+        limit: Option<u64>,
+        max_time: Option<Duration>,
+    ) -> Result<Cursor<T>, StoreError> {
+        let mut sort = Document::new();
+        for (field, direction) in &options.sort {
+            let order = match direction {
+                SortDirection::Ascending => 1,
+                SortDirection::Descending => -1,
+            };
+            sort.insert(field.as_str(), order);
+        }
+        // _id keeps the order stable between pages
+        sort.insert("_id", 1);
+        let find_options = mongodb::options::FindOptions::builder()
+            .sort(sort)
+            .skip(options.offset)
+            .limit(limit.map(|limit| limit as i64))
+            .max_time(max_time)
+            .build();
+        self.database
+            .collection::<T>(collection)
+            .find(to_filter(filter)?, find_options)
+            .await
+            .map_err(|e| store_error("find", collection, e))
+    }
+
+    // A find capped by STORE_MAX_DOCUMENTS may have left documents out
+    fn warn_if_capped(collection: &str, options: &FindOptions, found: usize) {
+        let limit = options.capped_limit();
+        let capped = options.limit.map_or(true, |requested| requested > limit);
+        if capped && found as u64 == limit {
+            tracing::warn!(collection, limit, "Find stopped at STORE_MAX_DOCUMENTS");
+        }
+    }
+
+    fn warn_if_slow(&self, operation: &str, collection: &str, filter: &Value, started: Instant) {
+        let elapsed = started.elapsed();
+        if elapsed >= self.slow_query_threshold {
+            tracing::warn!(
+                operation,
+                collection,
+                filter = %filter_shape(filter),
+                duration_ms = elapsed.as_millis() as u64,
+                "Slow Mongo query",
+            );
+        }
+    }
+}
+
+// Queries the server gave up on after their max_time fail like the ones the
+// request timed out on
+// Name of the collection's text index when it covers other fields than `fields`
+async fn other_text_index(
+    collection: &Collection<Document>,
+    fields: &[&str],
+) -> Result<Option<String>, StoreError> {
+    let mut indexes = match collection.list_indexes(None).await {
+        Ok(indexes) => indexes,
+        Err(e) => match e.kind.as_ref() {
+            // a collection that doesn't exist yet has no indexes
+            ErrorKind::Command(command) if command.code == NAMESPACE_NOT_FOUND => return Ok(None),
+            _ => return Err(e.into()),
+        },
+    };
+    while let Some(index) = indexes.next().await {
+        let index = index?;
+        // text indexes are keyed on _fts, the fields are their weights
+        if index.keys.get_str("_fts") != Ok("text") {
+            continue;
+        }
+        let options = index.options.unwrap_or_default();
+        let mut indexed: Vec<&str> = options
+            .weights
+            .iter()
+            .flat_map(|weights| weights.keys())
+            .map(String::as_str)
+            .collect();
+        let mut wanted = fields.to_vec();
+        indexed.sort_unstable();
+        wanted.sort_unstable();
+        return Ok(options.name.filter(|_| indexed != wanted));
+    }
+    Ok(None)
+}
+
+fn store_error(operation: &str, collection: &str, error: MongoError) -> StoreError {
+    match error.kind.as_ref() {
+        ErrorKind::Command(command) if command.code == MAX_TIME_MS_EXPIRED => {
+            StoreTimeout::new(operation, collection).into()
+        }
+        _ => error.into(),
+    }
+}
+
+// The filter with its values replaced by their types, so logs show which fields
+// were queried without leaking the values themselves
+fn filter_shape(filter: &Value) -> Value {
+    match filter {
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| (key.clone(), filter_shape(value)))
+                .collect(),
+        ),
+        Value::Array(_) => Value::from("<array>"),
+        Value::String(_) => Value::from("<string>"),
+        Value::Number(_) => Value::from("<number>"),
+        Value::Bool(_) => Value::from("<bool>"),
+        Value::Null => Value::Null,
+    }
+}
+
+// Replica set settings from the environment, each one overriding what the URI says:
+// MONGO_READ_PREFERENCE (primary, primaryPreferred, secondary, secondaryPreferred, nearest),
+// MONGO_MAX_STALENESS_SECS, MONGO_WRITE_CONCERN (majority or a node count),
+// MONGO_RETRY_READS and MONGO_RETRY_WRITES (true or false)
+fn apply_replica_set_tuning(client_options: &mut ClientOptions) -> Result<(), StoreError> {
+    if let Ok(mode) = env::var("MONGO_READ_PREFERENCE") {
+        let mut options = ReadPreferenceOptions::default();
+        if let Ok(seconds) = env::var("MONGO_MAX_STALENESS_SECS") {
+            options.max_staleness = Some(Duration::from_secs(seconds.parse()?));
+        }
+        let read_preference = match mode.as_str() {
+            "primary" => ReadPreference::Primary,
+            "primaryPreferred" => ReadPreference::PrimaryPreferred { options },
+            "secondary" => ReadPreference::Secondary { options },
+            "secondaryPreferred" => ReadPreference::SecondaryPreferred { options },
+            "nearest" => ReadPreference::Nearest { options },
+            other => return Err(format!("Unknown MONGO_READ_PREFERENCE {}", other).into()),
+        };
+        client_options.selection_criteria = Some(SelectionCriteria::ReadPreference(read_preference));
+    }
+    if let Ok(w) = env::var("MONGO_WRITE_CONCERN") {
+        let acknowledgment = match w.parse::<u32>() {
+            Ok(nodes) => Acknowledgment::Nodes(nodes),
+            Err(_) if w == "majority" => Acknowledgment::Majority,
+            Err(_) => Acknowledgment::Custom(w),
+        };
+        client_options.write_concern = Some(WriteConcern::builder().w(acknowledgment).build());
+    }
+    if let Ok(retry_reads) = env::var("MONGO_RETRY_READS") {
+        client_options.retry_reads = Some(retry_reads.parse()?);
+    }
+    if let Ok(retry_writes) = env::var("MONGO_RETRY_WRITES") {
+        client_options.retry_writes = Some(retry_writes.parse()?);
+    }
+    Ok(())
+}
+
+// Filters and documents arrive as extended JSON, so {"$oid": ...} becomes an ObjectId
+fn to_document(value: Value) -> Result<Document, StoreError> {
+    match Bson::try_from(value)? {
+        Bson::Document(document) => Ok(document),
+        other => Err(format!("Expected a document, got {:?}", other.element_type()).into()),
+    }
+}
+
+// Arrays in a filter match arrays containing all of their values, which Mongo spells $all
+fn to_filter(filter: Value) -> Result<Document, StoreError> {
+    let filter = match filter {
+        Value::Object(fields) => Value::Object(
+            fields
+                .into_iter()
+                .map(|(key, value)| match value {
+                    Value::Array(values) => (key, json!({ "$all": values })),
+                    other => (key, other),
+                })
+                .collect(),
+        ),
+        other => other,
+    };
+    to_document(filter)
+}
+
+#[async_trait]
+impl DataStore for MongoStore {
+    fn backend_name(&self) -> &'static str {
+        "mongo"
+    }
+
+    async fn ping(&self) -> Result<(), StoreError> {
+        self.database.run_command(doc! { "ping": 1 }, None).await?;
+        Ok(())
+    }
+
+    async fn find_with(
+        &self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<Vec<Value>, StoreError> {
+        let started = Instant::now();
+        let limit = Some(options.capped_limit());
+        let mut cursor = self
+            .cursor::<Document>(collection, filter.clone(), &options, limit, store_timeout())
+            .await?;
+        let mut documents = Vec::new();
+
+        while cursor.advance().await.map_err(|e| store_error("find", collection, e))? {
+            match cursor.deserialize_current() {
+                Ok(document) => {
+                    // Convert the BSON Document into a serde_json::Value
+                    documents.push(Bson::Document(document).into());
+                }
+                Err(e) => eprintln!("Error deserializing document: {}", e),
+            }
+        }
+        self.warn_if_slow("find", collection, &filter, started);
+        Self::warn_if_capped(collection, &options, documents.len());
+        Ok(documents)
+    }
+
+    // The raw bytes of each document are kept, models are deserialized from them
+    async fn find_raw(
+        &self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<Vec<StoredDocument>, StoreError> {
+        let started = Instant::now();
+        let limit = Some(options.capped_limit());
+        let mut cursor = self
+            .cursor::<RawDocumentBuf>(collection, filter.clone(), &options, limit, store_timeout())
+            .await?;
+        let mut documents = Vec::new();
+        while cursor.advance().await.map_err(|e| store_error("find", collection, e))? {
+            documents.push(StoredDocument::Bson(cursor.current().to_raw_document_buf()));
+        }
+        self.warn_if_slow("find", collection, &filter, started);
+        Self::warn_if_capped(collection, &options, documents.len());
+        Ok(documents)
+    }
+
+    // Documents are converted as the cursor fetches its batches. Streams scan whole
+    // collections, so they aren't bound by store_timeout_ms.
+    async fn find_stream<'a>(
+        &'a self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<DocumentStream<'a>, StoreError> {
+        let cursor = self.cursor::<Document>(collection, filter, &options, options.limit, None).await?;
+        Ok(Box::pin(cursor.map(|document| {
+            document
+                .map(|document| Bson::Document(document).into())
+                .map_err(StoreError::from)
+        })))
+    }
+
+    async fn count(&self, collection: &str, filter: Value) -> Result<u64, StoreError> {
+        let started = Instant::now();
+        let options = CountOptions::builder().max_time(store_timeout()).build();
+        let count = self
+            .collection(collection)
+            .count_documents(to_filter(filter.clone())?, options)
+            .await
+            .map_err(|e| store_error("count", collection, e))?;
+        self.warn_if_slow("count", collection, &filter, started);
+        Ok(count)
+    }
+
+    async fn find_one(&self, collection: &str, filter: Value) -> Result<Option<Value>, StoreError> {
+        let started = Instant::now();
+        let options = FindOneOptions::builder().max_time(store_timeout()).build();
+        let document = self
+            .collection(collection)
+            .find_one(to_filter(filter.clone())?, options)
+            .await
+            .map_err(|e| store_error("find_one", collection, e))?;
+        self.warn_if_slow("find_one", collection, &filter, started);
+        Ok(document.map(|document| Bson::Document(document).into()))
+    }
+
+    async fn insert_one(&self, collection: &str, document: Value) -> Result<(), StoreError> {
+        self.collection(collection).insert_one(to_document(document)?, None).await?;
+        Ok(())
+    }
+
+    async fn delete_one(&self, collection: &str, filter: Value) -> Result<bool, StoreError> {
+        let result = self.collection(collection).delete_one(to_filter(filter)?, None).await?;
+        Ok(result.deleted_count > 0)
+    }
+
+    async fn delete_many(&self, collection: &str, filter: Value) -> Result<u64, StoreError> {
+        let result = self.collection(collection).delete_many(to_filter(filter)?, None).await?;
+        Ok(result.deleted_count)
+    }
+
+    async fn update_one(&self, collection: &str, filter: Value, changes: Value) -> Result<bool, StoreError> {
+        let result = self
+            .collection(collection)
+            .update_one(to_filter(filter)?, doc! { "$set": to_document(changes)? }, None)
+            .await?;
+        Ok(result.matched_count > 0)
+    }
+
+    // The server retries an upsert that races another one on a unique index over
+    // the filter's fields, so concurrent callers don't fail on a duplicate key
+    async fn insert_if_absent(
+        &self,
+        collection: &str,
+        filter: Value,
+        document: Value,
+    ) -> Result<bool, StoreError> {
+        let options = UpdateOptions::builder().upsert(true).build();
+        let result = self
+            .collection(collection)
+            .update_one(to_filter(filter)?, doc! { "$setOnInsert": to_document(document)? }, options)
+            .await?;
+        Ok(result.upserted_id.is_some())
+    }
+
+    // Needs a replica set or sharded cluster, standalone servers reject transactions
+    async fn insert_many_atomic(&self, documents: Vec<(String, Value)>) -> Result<(), StoreError> {
+        let mut session = self.client.start_session(None).await?;
+        session.start_transaction(None).await?;
+        for (collection, document) in documents {
+            let inserted = match to_document(document) {
+                Ok(document) => self
+                    .collection(&collection)
+                    .insert_one_with_session(document, None, &mut session)
+                    .await
+                    .map_err(StoreError::from),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = inserted {
+                session.abort_transaction().await?;
+                return Err(e);
+            }
+        }
+        session.commit_transaction().await?;
+        Ok(())
+    }
+
+    async fn ensure_index(&self, collection: &str, fields: &[&str], unique: bool) -> Result<(), StoreError> {
+        let mut keys = Document::new();
+        for field in fields {
+            keys.insert(*field, 1);
+        }
+        // A sparse compound index still holds documents having any one of its fields,
+        // so unique ones are partial over documents where the others are set. The
+        // locale stays out of the filter: Mongo indexes a missing locale as null, so
+        // legacy documents without one collide with the default locale's as they should.
+        let mut options = IndexOptions::builder().unique(unique).build();
+        if unique {
+            let mut present = Document::new();
+            for field in fields.iter().filter(|field| **field != "locale") {
+                present.insert(*field, doc! { "$type": "string" });
+            }
+            options.partial_filter_expression = Some(present);
+        }
+        self.collection(collection)
+            .create_index(IndexModel::builder().keys(keys).options(options).build(), None)
+            .await?;
+        Ok(())
+    }
+
+    async fn collection_names(&self) -> Result<Vec<String>, StoreError> {
+        Ok(self.database.list_collection_names(None).await?)
+    }
+
+    // Matches on the collection's text index, `fields` is what the index was built over
+    async fn text_search(
+        &self,
+        collection: &str,
+        filter: Value,
+        _fields: &[&str],
+        query: &str,
+        limit: u64,
+    ) -> Result<Vec<(f64, Value)>, StoreError> {
+        let started = Instant::now();
+        let mut text_filter = to_filter(filter.clone())?;
+        text_filter.insert("$text", doc! { "$search": query });
+        let options = mongodb::options::FindOptions::builder()
+            .projection(doc! { "score": { "$meta": "textScore" } })
+            .sort(doc! { "score": { "$meta": "textScore" } })
+            .limit(limit as i64)
+            .max_time(store_timeout())
+            .build();
+        let mut cursor = self
+            .collection(collection)
+            .find(text_filter, options)
+            .await
+            .map_err(|e| store_error("text search", collection, e))?;
+        let mut documents = Vec::new();
+        while cursor.advance().await.map_err(|e| store_error("text search", collection, e))? {
+            let mut document = cursor.deserialize_current()?;
+            let score = document.remove("score").and_then(|score| score.as_f64()).unwrap_or_default();
+            documents.push((score, Bson::Document(document).into()));
+        }
+        self.warn_if_slow("text search", collection, &filter, started);
+        Ok(documents)
+    }
+
+    // Mongo allows one text index per collection, so one over other fields, e.g.
+    // from before the fields changed, is dropped first
+    async fn ensure_text_index(&self, collection: &str, fields: &[&str]) -> Result<(), StoreError> {
+        let mut keys = Document::new();
+        for field in fields {
+            keys.insert(*field, "text");
+        }
+        let collection = self.collection(collection);
+        if let Some(name) = other_text_index(&collection, fields).await? {
+            collection.drop_index(name, None).await?;
+        }
+        collection
+            .create_index(IndexModel::builder().keys(keys).build(), None)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,233 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use sqlx::{postgres::PgPoolOptions, types::Json, PgPool};
+use std::env;
+
+use super::{checked_identifier, order_by_clause, DataStore, FindOptions, StoreError};
+
+// Every collection lives in one JSONB table, keyed by the collection name
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS documents (
+        id BIGSERIAL PRIMARY KEY,
+        collection TEXT NOT NULL,
+        data JSONB NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS documents_collection_email
+        ON documents (collection, (data->>'email'));
+";
+
+// Condition matching `filter`, with the filter to bind as $2. JSON containment only
+// matches an explicit null, while the other backends also match documents without
+// the field, so top level nulls are taken out and checked as missing or null.
+fn filter_clause(filter: Value) -> Result<(String, Value), StoreError> {
+    let Value::Object(mut fields) = filter else {
+        return Err("Filters must be JSON objects".into());
+    };
+    let nulls: Vec<String> = fields
+        .iter()
+        .filter(|(_, value)| value.is_null())
+        .map(|(field, _)| field.clone())
+        .collect();
+    let mut clause = String::from("data @> $2");
+    for field in nulls {
+        fields.remove(&field);
+        let field = checked_identifier(&field)?;
+        clause.push_str(&format!(" AND COALESCE(data->'{}', 'null'::jsonb) = 'null'::jsonb", field));
+    }
+    Ok((clause, Value::Object(fields)))
+}
+
+#[derive(Clone, Debug)]
+pub struct PostgresStore {
+    pool: PgPool,
+}
+
+impl PostgresStore {
+    pub async fn connect() -> Result<Self, StoreError> {
+        let database_url = env::var("DATABASE_URL")
+            .map_err(|_| "DATABASE_URL must be set when DATA_STORE is postgres")?;
+        let pool = PgPoolOptions::new().connect(&database_url).await?;
+        sqlx::raw_sql(SCHEMA).execute(&pool).await?;
+        println!("Connected to Postgres");
+        Ok(Self { pool })
+    }
+}
+
+#[async_trait]
+impl DataStore for PostgresStore {
+    fn backend_name(&self) -> &'static str {
+        "postgres"
+    }
+
+    async fn ping(&self) -> Result<(), StoreError> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn find_with(
+        &self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<Vec<Value>, StoreError> {
+        let (matches, filter) = filter_clause(filter)?;
+        let order_by = order_by_clause(&options.sort, |field| format!("(data->'{}')", field))?;
+        let sql = format!(
+            "SELECT data FROM documents WHERE collection = $1 AND {} {} LIMIT $3 OFFSET $4",
+            matches, order_by
+        );
+        let rows: Vec<Json<Value>> = sqlx::query_scalar(&sql)
+            .bind(collection)
+            .bind(Json(filter))
+            .bind(options.capped_limit() as i64)
+            .bind(options.offset as i64)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.into_iter().map(|Json(data)| data).collect())
+    }
+
+    async fn count(&self, collection: &str, filter: Value) -> Result<u64, StoreError> {
+        let (matches, filter) = filter_clause(filter)?;
+        let sql = format!("SELECT COUNT(*) FROM documents WHERE collection = $1 AND {}", matches);
+        let count: i64 = sqlx::query_scalar(&sql)
+            .bind(collection)
+            .bind(Json(filter))
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count as u64)
+    }
+
+    async fn find_one(&self, collection: &str, filter: Value) -> Result<Option<Value>, StoreError> {
+        let (matches, filter) = filter_clause(filter)?;
+        let sql = format!(
+            "SELECT data FROM documents WHERE collection = $1 AND {} ORDER BY id LIMIT 1",
+            matches
+        );
+        let row: Option<Json<Value>> = sqlx::query_scalar(&sql)
+            .bind(collection)
+            .bind(Json(filter))
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|Json(data)| data))
+    }
+
+    async fn insert_one(&self, collection: &str, document: Value) -> Result<(), StoreError> {
+        sqlx::query("INSERT INTO documents (collection, data) VALUES ($1, $2)")
+            .bind(collection)
+            .bind(Json(document))
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_one(&self, collection: &str, filter: Value) -> Result<bool, StoreError> {
+        let (matches, filter) = filter_clause(filter)?;
+        let sql = format!(
+            "DELETE FROM documents WHERE id = (
+                SELECT id FROM documents WHERE collection = $1 AND {} ORDER BY id LIMIT 1
+            )",
+            matches
+        );
+        let result = sqlx::query(&sql)
+            .bind(collection)
+            .bind(Json(filter))
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_many(&self, collection: &str, filter: Value) -> Result<u64, StoreError> {
+        let (matches, filter) = filter_clause(filter)?;
+        let sql = format!("DELETE FROM documents WHERE collection = $1 AND {}", matches);
+        let result = sqlx::query(&sql)
+            .bind(collection)
+            .bind(Json(filter))
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn update_one(&self, collection: &str, filter: Value, changes: Value) -> Result<bool, StoreError> {
+        let (matches, filter) = filter_clause(filter)?;
+        let sql = format!(
+            "UPDATE documents SET data = data || $3 WHERE id = (
+                SELECT id FROM documents WHERE collection = $1 AND {} ORDER BY id LIMIT 1
+            )",
+            matches
+        );
+        let result = sqlx::query(&sql)
+            .bind(collection)
+            .bind(Json(filter))
+            .bind(Json(changes))
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn insert_if_absent(
+        &self,
+        collection: &str,
+        filter: Value,
+        document: Value,
+    ) -> Result<bool, StoreError> {
+        let (matches, filter) = filter_clause(filter)?;
+        // the unique index over the filter's fields settles concurrent inserts the
+        // NOT EXISTS check lets through
+        let sql = format!(
+            "INSERT INTO documents (collection, data) SELECT $1, $3 WHERE NOT EXISTS (
+                SELECT 1 FROM documents WHERE collection = $1 AND {}
+            ) ON CONFLICT DO NOTHING",
+            matches
+        );
+        let result = sqlx::query(&sql)
+            .bind(collection)
+            .bind(Json(filter))
+            .bind(Json(document))
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn insert_many_atomic(&self, documents: Vec<(String, Value)>) -> Result<(), StoreError> {
+        // dropping the transaction on an early return rolls it back
+        let mut transaction = self.pool.begin().await?;
+        for (collection, document) in documents {
+            sqlx::query("INSERT INTO documents (collection, data) VALUES ($1, $2)")
+                .bind(collection)
+                .bind(Json(document))
+                .execute(&mut *transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn collection_names(&self) -> Result<Vec<String>, StoreError> {
+        let names: Vec<String> = sqlx::query_scalar("SELECT DISTINCT collection FROM documents")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(names)
+    }
+
+    async fn ensure_index(&self, collection: &str, fields: &[&str], unique: bool) -> Result<(), StoreError> {
+        let collection = checked_identifier(collection)?;
+        let fields = fields
+            .iter()
+            .map(|field| checked_identifier(field))
+            .collect::<Result<Vec<_>, _>>()?;
+        let keys: Vec<String> = fields
+            .iter()
+            .map(|field| format!("(data->>'{}')", field))
+            .collect();
+        // rows without the fields have NULL keys, which unique indexes ignore
+        let statement = format!(
+            "CREATE {unique} INDEX IF NOT EXISTS documents_{collection}_{name}
+                ON documents ({keys}) WHERE collection = '{collection}'",
+            unique = if unique { "UNIQUE" } else { "" },
+            name = fields.join("_"),
+            keys = keys.join(", "),
+        );
+        sqlx::query(&statement).execute(&self.pool).await?;
+        Ok(())
+    }
+}
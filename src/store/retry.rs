@@ -0,0 +1,245 @@
+use async_trait::async_trait;
+use mongodb::error::{Error as MongoError, ErrorKind, RETRYABLE_WRITE_ERROR, TRANSIENT_TRANSACTION_ERROR};
+use rand::Rng;
+use serde_json::Value;
+use std::{future::Future, sync::Arc, time::Duration};
+
+use super::{DataStore, DocumentStream, FindOptions, StoreError, StoredDocument};
+use crate::config;
+
+// Longest wait between two attempts, whatever the attempt number
+const MAX_BACKOFF: Duration = Duration::from_secs(2);
+// Server error codes of a replica set changing primary or a node going away,
+// the ones the driver itself retries reads on
+const TRANSIENT_CODES: [i32; 13] = [
+    11600, 11602, 10107, 13435, 13436, 189, 91, 7, 6, 89, 9001, 134, 262,
+];
+
+fn mongo_error(error: &StoreError) -> Option<&MongoError> {
+    error.downcast_ref::<MongoError>()
+}
+
+// Failures that may well succeed on another attempt: network errors, no
+// reachable primary during an election, and nodes stepping down or shutting down
+pub(super) fn transient(error: &StoreError) -> bool {
+    let Some(error) = mongo_error(error) else {
+        return false;
+    };
+    if never_sent(error) || error.contains_label(RETRYABLE_WRITE_ERROR) {
+        return true;
+    }
+    match error.kind.as_ref() {
+        ErrorKind::Io(_) => true,
+        ErrorKind::Command(command) => TRANSIENT_CODES.contains(&command.code),
+        _ => false,
+    }
+}
+
+// Failures that happened before the operation reached a server, so a write that
+// isn't idempotent can be sent again without applying it twice
+fn never_sent(error: &MongoError) -> bool {
+    matches!(
+        error.kind.as_ref(),
+        ErrorKind::ServerSelection { .. } | ErrorKind::ConnectionPoolCleared { .. }
+    )
+}
+
+fn insert_retryable(error: &StoreError) -> bool {
+    mongo_error(error).is_some_and(never_sent)
+}
+
+// A transaction aborted as a whole, so running it again is safe
+fn transaction_retryable(error: &StoreError) -> bool {
+    mongo_error(error)
+        .is_some_and(|error| never_sent(error) || error.contains_label(TRANSIENT_TRANSACTION_ERROR))
+}
+
+// Store retrying operations that failed on a transient backend error, waiting a
+// random time up to retry_backoff_ms doubled on every attempt in between.
+// Inserts are only retried when they never reached a server.
+#[derive(Debug)]
+pub struct RetryingStore {
+    inner: Arc<dyn DataStore>,
+}
+
+impl RetryingStore {
+    pub fn new(inner: Arc<dyn DataStore>) -> Self {
+        Self { inner }
+    }
+
+    async fn retry<T, F, Fut>(
+        &self,
+        operation: &str,
+        collection: &str,
+        retryable: fn(&StoreError) -> bool,
+        call: F,
+    ) -> Result<T, StoreError>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<T, StoreError>>,
+    {
+        let settings = config::app();
+        let mut attempt = 1;
+        loop {
+            match call().await {
+                Err(e) if attempt < settings.retry_attempts && retryable(&e) => {
+                    let ceiling = Duration::from_millis(settings.retry_backoff_ms)
+                        .saturating_mul(1 << (attempt - 1).min(16))
+                        .min(MAX_BACKOFF);
+                    let delay = rand::thread_rng().gen_range(Duration::ZERO..=ceiling);
+                    tracing::warn!(
+                        operation,
+                        collection,
+                        attempt,
+                        delay_ms = delay.as_millis() as u64,
+                        error = %e,
+                        "Retrying store operation after a transient error"
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl DataStore for RetryingStore {
+    fn backend_name(&self) -> &'static str {
+        self.inner.backend_name()
+    }
+
+    async fn ping(&self) -> Result<(), StoreError> {
+        self.retry("ping", "", transient, || self.inner.ping()).await
+    }
+
+    async fn find_with(
+        &self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<Vec<Value>, StoreError> {
+        self.retry("find", collection, transient, || {
+            self.inner.find_with(collection, filter.clone(), options.clone())
+        })
+        .await
+    }
+
+    async fn find_raw(
+        &self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<Vec<StoredDocument>, StoreError> {
+        self.retry("find", collection, transient, || {
+            self.inner.find_raw(collection, filter.clone(), options.clone())
+        })
+        .await
+    }
+
+    // Only opening the stream is retried, a stream failing halfway fails its reader
+    async fn find_stream<'a>(
+        &'a self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<DocumentStream<'a>, StoreError> {
+        self.retry("find", collection, transient, || {
+            self.inner.find_stream(collection, filter.clone(), options.clone())
+        })
+        .await
+    }
+
+    async fn count(&self, collection: &str, filter: Value) -> Result<u64, StoreError> {
+        self.retry("count", collection, transient, || self.inner.count(collection, filter.clone()))
+            .await
+    }
+
+    async fn find_one(&self, collection: &str, filter: Value) -> Result<Option<Value>, StoreError> {
+        self.retry("find_one", collection, transient, || {
+            self.inner.find_one(collection, filter.clone())
+        })
+        .await
+    }
+
+    async fn insert_one(&self, collection: &str, document: Value) -> Result<(), StoreError> {
+        self.retry("insert_one", collection, insert_retryable, || {
+            self.inner.insert_one(collection, document.clone())
+        })
+        .await
+    }
+
+    async fn delete_one(&self, collection: &str, filter: Value) -> Result<bool, StoreError> {
+        self.retry("delete_one", collection, transient, || {
+            self.inner.delete_one(collection, filter.clone())
+        })
+        .await
+    }
+
+    async fn delete_many(&self, collection: &str, filter: Value) -> Result<u64, StoreError> {
+        self.retry("delete_many", collection, transient, || {
+            self.inner.delete_many(collection, filter.clone())
+        })
+        .await
+    }
+
+    async fn update_one(&self, collection: &str, filter: Value, changes: Value) -> Result<bool, StoreError> {
+        self.retry("update_one", collection, transient, || {
+            self.inner.update_one(collection, filter.clone(), changes.clone())
+        })
+        .await
+    }
+
+    async fn insert_if_absent(
+        &self,
+        collection: &str,
+        filter: Value,
+        document: Value,
+    ) -> Result<bool, StoreError> {
+        // the filter keeps a second attempt from inserting again
+        self.retry("insert_if_absent", collection, transient, || {
+            self.inner.insert_if_absent(collection, filter.clone(), document.clone())
+        })
+        .await
+    }
+
+    async fn insert_many_atomic(&self, documents: Vec<(String, Value)>) -> Result<(), StoreError> {
+        self.retry("insert_many_atomic", "", transaction_retryable, || {
+            self.inner.insert_many_atomic(documents.clone())
+        })
+        .await
+    }
+
+    async fn ensure_index(&self, collection: &str, fields: &[&str], unique: bool) -> Result<(), StoreError> {
+        self.retry("ensure_index", collection, transient, || {
+            self.inner.ensure_index(collection, fields, unique)
+        })
+        .await
+    }
+
+    async fn ensure_text_index(&self, collection: &str, fields: &[&str]) -> Result<(), StoreError> {
+        self.retry("ensure_text_index", collection, transient, || {
+            self.inner.ensure_text_index(collection, fields)
+        })
+        .await
+    }
+
+    async fn text_search(
+        &self,
+        collection: &str,
+        filter: Value,
+        fields: &[&str],
+        query: &str,
+        limit: u64,
+    ) -> Result<Vec<(f64, Value)>, StoreError> {
+        self.retry("find", collection, transient, || {
+            self.inner.text_search(collection, filter.clone(), fields, query, limit)
+        })
+        .await
+    }
+
+    async fn collection_names(&self) -> Result<Vec<String>, StoreError> {
+        self.retry("collection_names", "", transient, || self.inner.collection_names()).await
+    }
+}
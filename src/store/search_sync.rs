@@ -0,0 +1,164 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use std::sync::Arc;
+
+use super::{DataStore, DocumentStream, FindOptions, StoreError, StoredDocument};
+use crate::search::{self, SearchKind, SearchProvider};
+
+// Store pushing an owner's searchable documents to the search provider again after
+// a write to them, taking the owner from the email of the document or filter. The
+// push runs in the background, a failing provider is logged and never fails the write.
+#[derive(Debug)]
+pub struct SearchSyncStore {
+    inner: Arc<dyn DataStore>,
+    provider: &'static dyn SearchProvider,
+}
+
+impl SearchSyncStore {
+    pub fn new(inner: Arc<dyn DataStore>, provider: &'static dyn SearchProvider) -> Self {
+        Self { inner, provider }
+    }
+
+    fn changed(&self, collection: &str, document: &Value) {
+        if SearchKind::of(collection).is_none() {
+            return;
+        }
+        let Some(owner_email) = document.get("email").and_then(Value::as_str) else {
+            return;
+        };
+        let (store, provider) = (self.inner.clone(), self.provider);
+        let (owner_email, collection) = (owner_email.to_string(), collection.to_string());
+        tokio::spawn(async move {
+            if let Err(e) = search::sync_owner(&*store, provider, &owner_email, &collection).await {
+                tracing::error!(error = %e, owner_email, collection, "Failed to update the search index");
+            }
+        });
+    }
+}
+
+#[async_trait]
+impl DataStore for SearchSyncStore {
+    fn backend_name(&self) -> &'static str {
+        self.inner.backend_name()
+    }
+
+    async fn ping(&self) -> Result<(), StoreError> {
+        self.inner.ping().await
+    }
+
+    async fn find_with(
+        &self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<Vec<Value>, StoreError> {
+        self.inner.find_with(collection, filter, options).await
+    }
+
+    async fn find_raw(
+        &self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<Vec<StoredDocument>, StoreError> {
+        self.inner.find_raw(collection, filter, options).await
+    }
+
+    async fn find_stream<'a>(
+        &'a self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<DocumentStream<'a>, StoreError> {
+        self.inner.find_stream(collection, filter, options).await
+    }
+
+    async fn count(&self, collection: &str, filter: Value) -> Result<u64, StoreError> {
+        self.inner.count(collection, filter).await
+    }
+
+    async fn find_one(&self, collection: &str, filter: Value) -> Result<Option<Value>, StoreError> {
+        self.inner.find_one(collection, filter).await
+    }
+
+    async fn insert_one(&self, collection: &str, document: Value) -> Result<(), StoreError> {
+        self.inner.insert_one(collection, document.clone()).await?;
+        self.changed(collection, &document);
+        Ok(())
+    }
+
+    async fn delete_one(&self, collection: &str, filter: Value) -> Result<bool, StoreError> {
+        let deleted = self.inner.delete_one(collection, filter.clone()).await?;
+        if deleted {
+            self.changed(collection, &filter);
+        }
+        Ok(deleted)
+    }
+
+    async fn delete_many(&self, collection: &str, filter: Value) -> Result<u64, StoreError> {
+        let deleted = self.inner.delete_many(collection, filter.clone()).await?;
+        if deleted > 0 {
+            self.changed(collection, &filter);
+        }
+        Ok(deleted)
+    }
+
+    async fn update_one(&self, collection: &str, filter: Value, changes: Value) -> Result<bool, StoreError> {
+        let updated = self.inner.update_one(collection, filter.clone(), changes).await?;
+        if updated {
+            self.changed(collection, &filter);
+        }
+        Ok(updated)
+    }
+
+    async fn insert_if_absent(
+        &self,
+        collection: &str,
+        filter: Value,
+        document: Value,
+    ) -> Result<bool, StoreError> {
+        let inserted = self.inner.insert_if_absent(collection, filter, document.clone()).await?;
+        if inserted {
+            self.changed(collection, &document);
+        }
+        Ok(inserted)
+    }
+
+    async fn insert_many_atomic(&self, documents: Vec<(String, Value)>) -> Result<(), StoreError> {
+        let mut changed: Vec<(String, Value)> = documents
+            .iter()
+            .filter(|(collection, _)| SearchKind::of(collection).is_some())
+            .filter_map(|(collection, document)| Some((collection.clone(), document.get("email")?.clone())))
+            .collect();
+        changed.sort_by(|a, b| (&a.0, a.1.as_str()).cmp(&(&b.0, b.1.as_str())));
+        changed.dedup();
+        self.inner.insert_many_atomic(documents).await?;
+        for (collection, email) in changed {
+            self.changed(&collection, &serde_json::json!({ "email": email }));
+        }
+        Ok(())
+    }
+
+    async fn ensure_index(&self, collection: &str, fields: &[&str], unique: bool) -> Result<(), StoreError> {
+        self.inner.ensure_index(collection, fields, unique).await
+    }
+
+    async fn ensure_text_index(&self, collection: &str, fields: &[&str]) -> Result<(), StoreError> {
+        self.inner.ensure_text_index(collection, fields).await
+    }
+
+    async fn text_search(
+        &self,
+        collection: &str,
+        filter: Value,
+        fields: &[&str],
+        query: &str,
+        limit: u64,
+    ) -> Result<Vec<(f64, Value)>, StoreError> {
+        self.inner.text_search(collection, filter, fields, query, limit).await
+    }
+
+    async fn collection_names(&self) -> Result<Vec<String>, StoreError> {
+        self.inner.collection_names().await
+    }
+}
@@ -0,0 +1,276 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use sqlx::{
+    query::QueryScalar,
+    sqlite::{Sqlite, SqliteArguments, SqliteConnectOptions, SqlitePoolOptions},
+    SqlitePool,
+};
+use std::{env, str::FromStr};
+
+use super::{checked_identifier, order_by_clause, DataStore, FindOptions, StoreError};
+
+// Same single table layout as the Postgres backend, with the JSON kept as text
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS documents (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        collection TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS documents_collection_email
+        ON documents (collection, json_extract(data, '$.email'));
+";
+
+#[derive(Clone, Debug)]
+pub struct SqliteStore {
+    pool: SqlitePool,
+}
+
+impl SqliteStore {
+    // Opens (and creates if needed) the database file at SQLITE_PATH
+    pub async fn connect() -> Result<Self, StoreError> {
+        let path = env::var("SQLITE_PATH").unwrap_or_else(|_| "portfolio.db".to_string());
+        let options = SqliteConnectOptions::from_str(&format!("sqlite://{}", path))?
+            .create_if_missing(true);
+        let pool = SqlitePoolOptions::new().connect_with(options).await?;
+        sqlx::raw_sql(SCHEMA).execute(&pool).await?;
+        println!("Using SQLite database at {}", path);
+        Ok(Self { pool })
+    }
+}
+
+// A value compared against a JSON path in the filter clause
+enum Bind {
+    Text(String),
+    Integer(i64),
+    Real(f64),
+    Null,
+}
+
+// How the value at a JSON path is compared with a bound value
+enum Match {
+    Equals,
+    // the path holds an array containing the value
+    Contains,
+}
+
+fn scalar_bind(key: &str, value: &Value) -> Result<Bind, StoreError> {
+    Ok(match value {
+        Value::String(text) => Bind::Text(text.clone()),
+        Value::Bool(flag) => Bind::Integer(i64::from(*flag)),
+        Value::Number(number) => match number.as_i64() {
+            Some(integer) => Bind::Integer(integer),
+            None => Bind::Real(number.as_f64().unwrap_or_default()),
+        },
+        Value::Null => Bind::Null,
+        Value::Array(_) | Value::Object(_) => {
+            return Err(format!("Nested array filters are not supported for {}", key).into())
+        }
+    })
+}
+
+// Flatten a JSON filter into json_extract paths and the scalar each one is matched against
+fn flatten_filter(prefix: &str, filter: &Value, out: &mut Vec<(String, Match, Bind)>) -> Result<(), StoreError> {
+    let fields = match filter {
+        Value::Object(fields) => fields,
+        _ => return Err("Filters must be JSON objects".into()),
+    };
+    for (key, value) in fields {
+        if key.contains('"') {
+            return Err(format!("Unsupported filter key {}", key).into());
+        }
+        let path = format!("{}.\"{}\"", prefix, key);
+        match value {
+            Value::Object(_) => flatten_filter(&path, value, out)?,
+            Value::Array(values) => {
+                for value in values {
+                    out.push((path.clone(), Match::Contains, scalar_bind(key, value)?));
+                }
+            }
+            _ => out.push((path, Match::Equals, scalar_bind(key, value)?)),
+        }
+    }
+    Ok(())
+}
+
+// Build the WHERE clause for a collection and filter, along with its bound values
+fn where_clause(filter: &Value) -> Result<(String, Vec<(String, Match, Bind)>), StoreError> {
+    let mut binds = Vec::new();
+    flatten_filter("$", filter, &mut binds)?;
+    let mut clause = String::from("collection = ?");
+    for (_, how, _) in &binds {
+        clause.push_str(match how {
+            Match::Equals => " AND json_extract(data, ?) IS ?",
+            Match::Contains => " AND EXISTS (SELECT 1 FROM json_each(data, ?) WHERE json_each.value IS ?)",
+        });
+    }
+    Ok((clause, binds))
+}
+
+fn bind_filter<'q, O>(
+    mut query: QueryScalar<'q, Sqlite, O, SqliteArguments<'q>>,
+    collection: &'q str,
+    binds: Vec<(String, Match, Bind)>,
+) -> QueryScalar<'q, Sqlite, O, SqliteArguments<'q>> {
+    query = query.bind(collection);
+    for (path, _, value) in binds {
+        query = query.bind(path);
+        query = match value {
+            Bind::Text(text) => query.bind(text),
+            Bind::Integer(integer) => query.bind(integer),
+            Bind::Real(real) => query.bind(real),
+            Bind::Null => query.bind(Option::<String>::None),
+        };
+    }
+    query
+}
+
+#[async_trait]
+impl DataStore for SqliteStore {
+    fn backend_name(&self) -> &'static str {
+        "sqlite"
+    }
+
+    async fn ping(&self) -> Result<(), StoreError> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn find_with(
+        &self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<Vec<Value>, StoreError> {
+        let (clause, binds) = where_clause(&filter)?;
+        let order_by = order_by_clause(&options.sort, |field| format!("json_extract(data, '$.{}')", field))?;
+        let sql = format!("SELECT data FROM documents WHERE {} {} LIMIT ? OFFSET ?", clause, order_by);
+        let rows: Vec<String> = bind_filter(sqlx::query_scalar(&sql), collection, binds)
+            .bind(options.capped_limit() as i64)
+            .bind(options.offset as i64)
+            .fetch_all(&self.pool)
+            .await?;
+        rows.iter()
+            .map(|data| serde_json::from_str(data).map_err(StoreError::from))
+            .collect()
+    }
+
+    async fn count(&self, collection: &str, filter: Value) -> Result<u64, StoreError> {
+        let (clause, binds) = where_clause(&filter)?;
+        let sql = format!("SELECT COUNT(*) FROM documents WHERE {}", clause);
+        let count: i64 = bind_filter(sqlx::query_scalar(&sql), collection, binds)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count as u64)
+    }
+
+    async fn find_one(&self, collection: &str, filter: Value) -> Result<Option<Value>, StoreError> {
+        let (clause, binds) = where_clause(&filter)?;
+        let sql = format!("SELECT data FROM documents WHERE {} ORDER BY id LIMIT 1", clause);
+        let row: Option<String> = bind_filter(sqlx::query_scalar(&sql), collection, binds)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|data| serde_json::from_str(&data)).transpose()?)
+    }
+
+    async fn insert_one(&self, collection: &str, document: Value) -> Result<(), StoreError> {
+        sqlx::query("INSERT INTO documents (collection, data) VALUES (?, ?)")
+            .bind(collection)
+            .bind(document.to_string())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_one(&self, collection: &str, filter: Value) -> Result<bool, StoreError> {
+        let (clause, binds) = where_clause(&filter)?;
+        let sql = format!(
+            "DELETE FROM documents WHERE id = (SELECT id FROM documents WHERE {} ORDER BY id LIMIT 1) RETURNING id",
+            clause
+        );
+        let deleted: Option<i64> = bind_filter(sqlx::query_scalar(&sql), collection, binds)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(deleted.is_some())
+    }
+
+    async fn delete_many(&self, collection: &str, filter: Value) -> Result<u64, StoreError> {
+        let (clause, binds) = where_clause(&filter)?;
+        let sql = format!("DELETE FROM documents WHERE {} RETURNING id", clause);
+        let deleted: Vec<i64> = bind_filter(sqlx::query_scalar(&sql), collection, binds)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(deleted.len() as u64)
+    }
+
+    async fn update_one(&self, collection: &str, filter: Value, changes: Value) -> Result<bool, StoreError> {
+        let (clause, binds) = where_clause(&filter)?;
+        let sql = format!(
+            "UPDATE documents SET data = json_patch(data, ?) WHERE id = (SELECT id FROM documents WHERE {} ORDER BY id LIMIT 1) RETURNING id",
+            clause
+        );
+        let updated: Option<i64> = bind_filter(sqlx::query_scalar(&sql).bind(changes.to_string()), collection, binds)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(updated.is_some())
+    }
+
+    async fn insert_if_absent(
+        &self,
+        collection: &str,
+        filter: Value,
+        document: Value,
+    ) -> Result<bool, StoreError> {
+        let (clause, binds) = where_clause(&filter)?;
+        // SQLite runs one write at a time, so the check and the insert can't interleave
+        let sql = format!(
+            "INSERT OR IGNORE INTO documents (collection, data) SELECT ?, ? WHERE NOT EXISTS (SELECT 1 FROM documents WHERE {}) RETURNING id",
+            clause
+        );
+        let query = sqlx::query_scalar(&sql).bind(collection).bind(document.to_string());
+        let inserted: Option<i64> = bind_filter(query, collection, binds).fetch_optional(&self.pool).await?;
+        Ok(inserted.is_some())
+    }
+
+    async fn insert_many_atomic(&self, documents: Vec<(String, Value)>) -> Result<(), StoreError> {
+        // dropping the transaction on an early return rolls it back
+        let mut transaction = self.pool.begin().await?;
+        for (collection, document) in documents {
+            sqlx::query("INSERT INTO documents (collection, data) VALUES (?, ?)")
+                .bind(collection)
+                .bind(document.to_string())
+                .execute(&mut *transaction)
+                .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn collection_names(&self) -> Result<Vec<String>, StoreError> {
+        let names: Vec<String> = sqlx::query_scalar("SELECT DISTINCT collection FROM documents")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(names)
+    }
+
+    async fn ensure_index(&self, collection: &str, fields: &[&str], unique: bool) -> Result<(), StoreError> {
+        let collection = checked_identifier(collection)?;
+        let fields = fields
+            .iter()
+            .map(|field| checked_identifier(field))
+            .collect::<Result<Vec<_>, _>>()?;
+        let keys: Vec<String> = fields
+            .iter()
+            .map(|field| format!("json_extract(data, '$.{}')", field))
+            .collect();
+        // rows without the fields have NULL keys, which unique indexes ignore
+        let statement = format!(
+            "CREATE {unique} INDEX IF NOT EXISTS documents_{collection}_{name}
+                ON documents ({keys}) WHERE collection = '{collection}'",
+            unique = if unique { "UNIQUE" } else { "" },
+            name = fields.join("_"),
+            keys = keys.join(", "),
+        );
+        sqlx::query(&statement).execute(&self.pool).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+use serde::Serialize;
+use std::{fmt, future::Future, time::Duration};
+
+use super::StoreError;
+use crate::config;
+
+// Error of a store call that took longer than store_timeout_ms
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoreTimeout {
+    pub operation: String,
+    pub collection: String,
+    pub timeout_ms: u64,
+}
+
+impl fmt::Display for StoreTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} on {} timed out after {}ms",
+            self.operation, self.collection, self.timeout_ms
+        )
+    }
+}
+
+impl std::error::Error for StoreTimeout {}
+
+impl StoreTimeout {
+    pub fn new(operation: &str, collection: &str) -> Self {
+        Self {
+            operation: operation.to_string(),
+            collection: collection.to_string(),
+            timeout_ms: config::app().store_timeout_ms,
+        }
+    }
+}
+
+// The time limit of a store call, None when store_timeout_ms is 0
+pub fn store_timeout() -> Option<Duration> {
+    match config::app().store_timeout_ms {
+        0 => None,
+        ms => Some(Duration::from_millis(ms)),
+    }
+}
+
+// Run a store call, failing with a StoreTimeout once it takes longer than the limit
+pub async fn within<T>(
+    operation: &str,
+    collection: &str,
+    call: impl Future<Output = Result<T, StoreError>>,
+) -> Result<T, StoreError> {
+    let Some(limit) = store_timeout() else {
+        return call.await;
+    };
+    match tokio::time::timeout(limit, call).await {
+        Ok(result) => result,
+        Err(_) => Err(StoreTimeout::new(operation, collection).into()),
+    }
+}
@@ -0,0 +1,194 @@
+use async_trait::async_trait;
+use mongodb::bson::DateTime;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+use super::{DataStore, DocumentStream, FindOptions, StoreError, StoredDocument};
+use crate::config;
+
+// Version of an owner's content, raised by every write to one of their portfolio
+// collections or to the endorsements and likes counted on them. ETags embed it so
+// clients and CDNs can revalidate cheaply.
+pub async fn content_version(store: &dyn DataStore, owner_email: &str) -> Result<i64, StoreError> {
+    let found = store
+        .find_one(&config::collections().content_versions, json!({ "email": owner_email }))
+        .await?;
+    Ok(found
+        .and_then(|document| document.get("version").and_then(Value::as_i64))
+        .unwrap_or(0))
+}
+
+// The next version is at least the current time in milliseconds, so concurrent
+// bumps that read the same version still end up above it
+async fn bump(store: &dyn DataStore, owner_email: &str) -> Result<(), StoreError> {
+    let collection = &config::collections().content_versions;
+    let version = (content_version(store, owner_email).await? + 1).max(DateTime::now().timestamp_millis());
+    let filter = json!({ "email": owner_email });
+    if !store.update_one(collection, filter, json!({ "version": version })).await? {
+        store
+            .insert_one(collection, json!({ "email": owner_email, "version": version }))
+            .await?;
+    }
+    Ok(())
+}
+
+fn owner_of<'a>(collection: &str, document: &'a Value) -> Option<&'a str> {
+    let collections = config::collections();
+    let counted = [collections.endorsements.as_str(), collections.likes.as_str()];
+    if !collections.portfolio().contains(&collection) && !counted.contains(&collection) {
+        return None;
+    }
+    document.get("email").and_then(Value::as_str)
+}
+
+// Store raising the content version of the owner a write to a versioned
+// collection belongs to, taken from the email of the document or filter
+#[derive(Debug)]
+pub struct VersionedStore {
+    inner: Arc<dyn DataStore>,
+}
+
+impl VersionedStore {
+    pub fn new(inner: Arc<dyn DataStore>) -> Self {
+        Self { inner }
+    }
+
+    async fn changed(&self, collection: &str, document: &Value) {
+        if let Some(owner_email) = owner_of(collection, document) {
+            self.bump(owner_email).await;
+        }
+    }
+
+    async fn bump(&self, owner_email: &str) {
+        if let Err(e) = bump(&*self.inner, owner_email).await {
+            eprintln!("Error updating the content version of {}: {}", owner_email, e);
+        }
+    }
+}
+
+#[async_trait]
+impl DataStore for VersionedStore {
+    fn backend_name(&self) -> &'static str {
+        self.inner.backend_name()
+    }
+
+    async fn ping(&self) -> Result<(), StoreError> {
+        self.inner.ping().await
+    }
+
+    async fn find_with(
+        &self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<Vec<Value>, StoreError> {
+        self.inner.find_with(collection, filter, options).await
+    }
+
+    async fn find_raw(
+        &self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<Vec<StoredDocument>, StoreError> {
+        self.inner.find_raw(collection, filter, options).await
+    }
+
+    async fn find_stream<'a>(
+        &'a self,
+        collection: &str,
+        filter: Value,
+        options: FindOptions,
+    ) -> Result<DocumentStream<'a>, StoreError> {
+        self.inner.find_stream(collection, filter, options).await
+    }
+
+    async fn count(&self, collection: &str, filter: Value) -> Result<u64, StoreError> {
+        self.inner.count(collection, filter).await
+    }
+
+    async fn find_one(&self, collection: &str, filter: Value) -> Result<Option<Value>, StoreError> {
+        self.inner.find_one(collection, filter).await
+    }
+
+    async fn insert_one(&self, collection: &str, document: Value) -> Result<(), StoreError> {
+        self.inner.insert_one(collection, document.clone()).await?;
+        self.changed(collection, &document).await;
+        Ok(())
+    }
+
+    async fn delete_one(&self, collection: &str, filter: Value) -> Result<bool, StoreError> {
+        let deleted = self.inner.delete_one(collection, filter.clone()).await?;
+        if deleted {
+            self.changed(collection, &filter).await;
+        }
+        Ok(deleted)
+    }
+
+    async fn delete_many(&self, collection: &str, filter: Value) -> Result<u64, StoreError> {
+        let deleted = self.inner.delete_many(collection, filter.clone()).await?;
+        if deleted > 0 {
+            self.changed(collection, &filter).await;
+        }
+        Ok(deleted)
+    }
+
+    async fn update_one(&self, collection: &str, filter: Value, changes: Value) -> Result<bool, StoreError> {
+        let updated = self.inner.update_one(collection, filter.clone(), changes).await?;
+        if updated {
+            self.changed(collection, &filter).await;
+        }
+        Ok(updated)
+    }
+
+    async fn insert_if_absent(
+        &self,
+        collection: &str,
+        filter: Value,
+        document: Value,
+    ) -> Result<bool, StoreError> {
+        let inserted = self.inner.insert_if_absent(collection, filter, document.clone()).await?;
+        if inserted {
+            self.changed(collection, &document).await;
+        }
+        Ok(inserted)
+    }
+
+    async fn insert_many_atomic(&self, documents: Vec<(String, Value)>) -> Result<(), StoreError> {
+        let mut owners: Vec<String> = documents
+            .iter()
+            .filter_map(|(collection, document)| owner_of(collection, document))
+            .map(str::to_string)
+            .collect();
+        owners.sort();
+        owners.dedup();
+        self.inner.insert_many_atomic(documents).await?;
+        for owner_email in &owners {
+            self.bump(owner_email).await;
+        }
+        Ok(())
+    }
+
+    async fn ensure_index(&self, collection: &str, fields: &[&str], unique: bool) -> Result<(), StoreError> {
+        self.inner.ensure_index(collection, fields, unique).await
+    }
+
+    async fn ensure_text_index(&self, collection: &str, fields: &[&str]) -> Result<(), StoreError> {
+        self.inner.ensure_text_index(collection, fields).await
+    }
+
+    async fn text_search(
+        &self,
+        collection: &str,
+        filter: Value,
+        fields: &[&str],
+        query: &str,
+        limit: u64,
+    ) -> Result<Vec<(f64, Value)>, StoreError> {
+        self.inner.text_search(collection, filter, fields, query, limit).await
+    }
+
+    async fn collection_names(&self) -> Result<Vec<String>, StoreError> {
+        self.inner.collection_names().await
+    }
+}
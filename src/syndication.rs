@@ -0,0 +1,208 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::env;
+
+use crate::{
+    blog::BlogPost,
+    config,
+    store::{DataStore, StoreError},
+};
+
+const BLUESKY_SERVICE: &str = "https://bsky.social";
+// Longest announcements, Bluesky counts graphemes, which are never more than chars
+const MASTODON_MAX_CHARS: usize = 500;
+const BLUESKY_MAX_CHARS: usize = 300;
+// Posts published longer ago than this, e.g. back-dated or imported ones, aren't announced
+const MAX_AGE_HOURS: i64 = 24;
+
+// A copy of a post announced on a social network
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+#[serde(rename_all = "camelCase")]
+pub struct Syndication {
+    // mastodon or bluesky
+    pub network: String,
+    pub url: String,
+    // ISO 8601, e.g. 2024-03-01T09:00:00Z
+    pub posted_at: String,
+}
+
+// Where published posts are announced. SYNDICATION_POST_URL turns it on, the
+// address of a post on the site with {slug} in place of its slug. Mastodon
+// needs MASTODON_URL, the instance, and MASTODON_ACCESS_TOKEN of an app with
+// write:statuses. Bluesky needs BLUESKY_IDENTIFIER, the handle, and
+// BLUESKY_APP_PASSWORD, BLUESKY_SERVICE is the PDS, bsky.social by default.
+// Only posts of SYNDICATION_OWNER, USER_EMAIL by default, are announced.
+#[derive(Clone, Debug)]
+struct SyndicationConfig {
+    post_url: String,
+    owner_email: String,
+    mastodon: Option<(String, String)>,
+    bluesky: Option<(String, String, String)>,
+}
+
+impl SyndicationConfig {
+    // None when syndication isn't set up
+    fn from_env() -> Option<Self> {
+        let mastodon = env::var("MASTODON_URL")
+            .ok()
+            .zip(env::var("MASTODON_ACCESS_TOKEN").ok())
+            .map(|(url, token)| (url.trim_end_matches('/').to_string(), token));
+        let bluesky = env::var("BLUESKY_IDENTIFIER")
+            .ok()
+            .zip(env::var("BLUESKY_APP_PASSWORD").ok())
+            .map(|(identifier, password)| {
+                let service = env::var("BLUESKY_SERVICE").unwrap_or_else(|_| BLUESKY_SERVICE.to_string());
+                (service.trim_end_matches('/').to_string(), identifier, password)
+            });
+        if mastodon.is_none() && bluesky.is_none() {
+            return None;
+        }
+        Some(Self {
+            post_url: env::var("SYNDICATION_POST_URL").ok()?,
+            owner_email: env::var("SYNDICATION_OWNER").or_else(|_| env::var("USER_EMAIL")).ok()?,
+            mastodon,
+            bluesky,
+        })
+    }
+}
+
+// Title, excerpt and link on separate lines, the excerpt shortened to fit `max_chars`
+fn announcement(post: &BlogPost, link: &str, max_chars: usize) -> String {
+    let fixed = post.title.chars().count() + link.chars().count() + 2;
+    let excerpt = post.excerpt.as_deref().map(str::trim).filter(|excerpt| !excerpt.is_empty());
+    match excerpt {
+        Some(excerpt) if fixed + 2 + excerpt.chars().count() <= max_chars => {
+            format!("{}\n\n{}\n\n{}", post.title, excerpt, link)
+        }
+        // too short to be worth keeping once shortened
+        Some(excerpt) if fixed + 2 + 40 <= max_chars => {
+            let kept: String = excerpt.chars().take(max_chars - fixed - 3).collect();
+            format!("{}\n\n{}…\n\n{}", post.title, kept.trim_end(), link)
+        }
+        _ => format!("{}\n\n{}", post.title, link),
+    }
+}
+
+// URL of the status posted on the Mastodon instance
+async fn post_to_mastodon(
+    client: &reqwest::Client,
+    (instance, token): &(String, String),
+    post: &BlogPost,
+    link: &str,
+) -> Result<String, StoreError> {
+    let status: Value = client
+        .post(format!("{}/api/v1/statuses", instance))
+        .bearer_auth(token)
+        // a retried request doesn't post twice
+        .header("Idempotency-Key", format!("{}:{}", post.email, post.slug))
+        .json(&json!({ "status": announcement(post, link, MASTODON_MAX_CHARS), "visibility": "public" }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    match status["url"].as_str() {
+        Some(url) => Ok(url.to_string()),
+        None => Err(format!("Mastodon returned no status URL: {}", status).into()),
+    }
+}
+
+// URL of the post made on Bluesky, with the link as a link facet and a card
+async fn post_to_bluesky(
+    client: &reqwest::Client,
+    (service, identifier, password): &(String, String, String),
+    post: &BlogPost,
+    link: &str,
+) -> Result<String, StoreError> {
+    let session: Value = client
+        .post(format!("{}/xrpc/com.atproto.server.createSession", service))
+        .json(&json!({ "identifier": identifier, "password": password }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let (Some(token), Some(did)) = (session["accessJwt"].as_str(), session["did"].as_str()) else {
+        return Err("Bluesky returned no session".into());
+    };
+    let text = announcement(post, link, BLUESKY_MAX_CHARS);
+    // facets address the text in UTF-8 bytes, the link ends it
+    let record = json!({
+        "$type": "app.bsky.feed.post",
+        "text": text,
+        "createdAt": Utc::now().to_rfc3339(),
+        "facets": [{
+            "index": { "byteStart": text.len() - link.len(), "byteEnd": text.len() },
+            "features": [{ "$type": "app.bsky.richtext.facet#link", "uri": link }],
+        }],
+        "embed": {
+            "$type": "app.bsky.embed.external",
+            "external": { "uri": link, "title": post.title, "description": post.excerpt.as_deref().unwrap_or_default() },
+        },
+    });
+    let created: Value = client
+        .post(format!("{}/xrpc/com.atproto.repo.createRecord", service))
+        .bearer_auth(token)
+        .json(&json!({ "repo": did, "collection": "app.bsky.feed.post", "record": record }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    // at://<did>/app.bsky.feed.post/<record key>
+    match created["uri"].as_str().and_then(|uri| uri.rsplit('/').next()) {
+        Some(key) => Ok(format!("https://bsky.app/profile/{}/post/{}", did, key)),
+        None => Err(format!("Bluesky returned no record: {}", created).into()),
+    }
+}
+
+// Announce a post that was just published on the configured networks and
+// record the copies on the post. Networks the post was already announced on,
+// e.g. before it was unpublished, are skipped, as are translations, posts of
+// other owners and posts published more than a day ago. Failures are only
+// logged, publishing never fails because of a network.
+pub async fn announce(store: &dyn DataStore, post: &BlogPost) {
+    let Some(config) = SyndicationConfig::from_env() else {
+        return;
+    };
+    let recent = DateTime::parse_from_rfc3339(&post.published_at)
+        .is_ok_and(|published_at| Utc::now() - published_at.to_utc() < Duration::hours(MAX_AGE_HOURS));
+    if post.email != config.owner_email || post.locale.is_some() || !recent {
+        return;
+    }
+    let announced = |network: &str| post.syndication.iter().any(|copy| copy.network == network);
+    let link = config.post_url.replace("{slug}", &post.slug);
+    let client = reqwest::Client::new();
+    let mut copies = post.syndication.clone();
+    if let Some(mastodon) = config.mastodon.as_ref().filter(|_| !announced("mastodon")) {
+        match post_to_mastodon(&client, mastodon, post, &link).await {
+            Ok(url) => copies.push(Syndication {
+                network: "mastodon".to_string(),
+                url,
+                posted_at: Utc::now().to_rfc3339(),
+            }),
+            Err(e) => tracing::error!(error = %e, slug = %post.slug, "Failed to announce the post on Mastodon"),
+        }
+    }
+    if let Some(bluesky) = config.bluesky.as_ref().filter(|_| !announced("bluesky")) {
+        match post_to_bluesky(&client, bluesky, post, &link).await {
+            Ok(url) => copies.push(Syndication {
+                network: "bluesky".to_string(),
+                url,
+                posted_at: Utc::now().to_rfc3339(),
+            }),
+            Err(e) => tracing::error!(error = %e, slug = %post.slug, "Failed to announce the post on Bluesky"),
+        }
+    }
+    if copies.len() == post.syndication.len() {
+        return;
+    }
+    let filter = json!({ "email": post.email, "slug": post.slug, "locale": null });
+    let recorded = store
+        .update_one(&config::collections().blog_posts, filter, json!({ "syndication": copies }))
+        .await;
+    if let Err(e) = recorded {
+        tracing::error!(error = %e, slug = %post.slug, "Failed to record the announcements of the post");
+    }
+}
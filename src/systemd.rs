@@ -0,0 +1,26 @@
+use listenfd::ListenFd;
+use std::{net::TcpListener, os::unix::net::UnixListener};
+
+// A listening socket passed in by systemd socket activation
+pub enum InheritedListener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+// The first socket from LISTEN_FDS, if the service was socket activated
+pub fn inherited_listener() -> Option<InheritedListener> {
+    let mut fds = ListenFd::from_env();
+    if fds.len() == 0 {
+        return None;
+    }
+    if let Ok(Some(listener)) = fds.take_tcp_listener(0) {
+        println!("Using TCP socket inherited from systemd");
+        return Some(InheritedListener::Tcp(listener));
+    }
+    if let Ok(Some(listener)) = fds.take_unix_listener(0) {
+        println!("Using Unix socket inherited from systemd");
+        return Some(InheritedListener::Unix(listener));
+    }
+    eprintln!("LISTEN_FDS is set but the first descriptor is not a listening socket, binding normally");
+    None
+}
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Context;
+
+// A talk given at a conference or meetup, identified by its slug
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+#[graphql(context = Context)]
+#[serde(rename_all = "camelCase")]
+pub struct Talk {
+    #[graphql(ignore)]
+    #[serde(default)]
+    pub email: String,
+    pub title: String,
+    pub slug: String,
+    // Conference or meetup, e.g. RustConf 2024
+    pub event: String,
+    // ISO 8601 date, e.g. 2024-09-12
+    pub date: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub location: Option<String>,
+    #[serde(default)]
+    pub slides_url: Option<String>,
+    #[serde(default)]
+    pub video_url: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub locale: Option<String>,
+}
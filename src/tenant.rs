@@ -0,0 +1,160 @@
+use axum::http::{header, HeaderMap};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::env;
+
+use crate::{
+    config,
+    store::{self, DataStore, StoreError},
+};
+
+// Header clients can send in multi-tenant mode to pick whose portfolio to read
+pub const OWNER_HEADER: &str = "x-portfolio-owner";
+
+// Whether one deployment serves a single owner or several
+#[derive(Clone, Debug)]
+pub enum Tenancy {
+    // Every request reads the portfolio of USER_EMAIL
+    Single { owner_email: String },
+    // The owner is resolved per request from the GraphQL argument, header or Host
+    Multi,
+}
+
+impl Tenancy {
+    pub fn from_env() -> Self {
+        let mode = env::var("TENANT_MODE").unwrap_or_else(|_| "single".to_string());
+        match mode.as_str() {
+            "multi" => Tenancy::Multi,
+            "single" => {
+                let owner_email = env::var("USER_EMAIL").unwrap_or_else(|_| {
+                    println!("USER_EMAIL is not set, using default value");
+                    "default_value".to_string()
+                });
+                Tenancy::Single { owner_email }
+            }
+            other => panic!("Unknown TENANT_MODE {}, expected single or multi", other),
+        }
+    }
+
+    pub fn is_multi(&self) -> bool {
+        matches!(self, Tenancy::Multi)
+    }
+
+    // Owner known before the GraphQL arguments are looked at
+    pub fn resolve(&self, headers: &HeaderMap) -> Option<String> {
+        match self {
+            Tenancy::Single { owner_email } => Some(owner_email.clone()),
+            Tenancy::Multi => headers
+                .get(OWNER_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty()),
+        }
+    }
+}
+
+// A portfolio owner served by a multi-tenant deployment, stored in the tenants collection
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+pub struct Tenant {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    pub email: String,
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+}
+
+// A Host header value without its port. IPv6 literals keep their brackets,
+// [::1]:8080 is [::1].
+fn without_port(host: &str) -> &str {
+    if host.starts_with('[') {
+        return host.find(']').map_or(host, |end| &host[..=end]);
+    }
+    match host.rsplit_once(':') {
+        Some((name, port)) if !name.contains(':') && port.bytes().all(|byte| byte.is_ascii_digit()) => name,
+        _ => host,
+    }
+}
+
+// Host name of the request without the port, lowercased to match the stored records
+pub fn request_host(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| without_port(value.trim()).to_ascii_lowercase())
+        .filter(|value| !value.is_empty())
+}
+
+// Owner of a request outside GraphQL: the deployment owner or owner header,
+// then in multi-tenant mode the tenant registered for the Host header
+pub async fn resolve_owner(store: &dyn DataStore, tenancy: &Tenancy, headers: &HeaderMap) -> Option<String> {
+    if let Some(owner_email) = tenancy.resolve(headers) {
+        return Some(owner_email);
+    }
+    if !tenancy.is_multi() {
+        return None;
+    }
+    let host = request_host(headers)?;
+    match find_tenant_by_host(store, &host).await {
+        Ok(tenant) => tenant.map(|tenant| tenant.email),
+        Err(e) => {
+            eprintln!("Error resolving tenant for host {}: {}", host, e);
+            None
+        }
+    }
+}
+
+// Look up the tenant registered for a host
+pub async fn find_tenant_by_host(store: &dyn DataStore, host: &str) -> Result<Option<Tenant>, StoreError> {
+    let found = store
+        .find_one(&config::collections().tenants, json!({ "host": host }))
+        .await?;
+    Ok(found.map(serde_json::from_value).transpose()?)
+}
+
+// Register a new owner and seed the documents the frontend expects to exist
+pub async fn provision(
+    store: &dyn DataStore,
+    email: String,
+    display_name: String,
+    host: Option<String>,
+) -> Result<Tenant, StoreError> {
+    let collections = config::collections();
+    store::ensure_indexes(store).await?;
+
+    let tenant = Tenant {
+        host: host.map(|host| host.trim().to_ascii_lowercase()),
+        email: email.clone(),
+        display_name: display_name.clone(),
+    };
+    let defaults: [(&str, Value); 4] = [
+        (collections.personals.as_str(), json!({
+            "email": email,
+        })),
+        (collections.users.as_str(), json!({
+            "email": email,
+            "fullName": display_name,
+        })),
+        (collections.settings.as_str(), json!({
+            "email": email,
+            "displayName": display_name,
+        })),
+        (collections.projects.as_str(), json!({
+            "email": email,
+            "title": "Example project",
+            "slug": "example-project",
+            "description": "Replace this with one of your own projects.",
+            "status": "active",
+        })),
+    ];
+    // the tenant record and its defaults are written together so a failed
+    // onboarding never leaves a tenant without its documents
+    let mut documents = vec![(collections.tenants.clone(), serde_json::to_value(&tenant)?)];
+    documents.extend(
+        defaults
+            .into_iter()
+            .map(|(collection_name, document)| (collection_name.to_string(), document)),
+    );
+    store.insert_many_atomic(documents).await?;
+    println!("Provisioned tenant {}", email);
+    Ok(tenant)
+}
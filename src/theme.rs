@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    settings,
+    store::{DataStore, StoreError},
+};
+
+// Look of the frontend, stored in the `theme` field of the site settings.
+// Unset values leave the frontend's own defaults in place.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, juniper::GraphQLObject)]
+pub struct Theme {
+    #[serde(default)]
+    pub palette: Palette,
+    #[serde(default)]
+    pub fonts: Fonts,
+    // Named layout switches the frontend understands, e.g. wideContent
+    #[serde(default)]
+    pub layout: Vec<LayoutFlag>,
+}
+
+// CSS colors, e.g. #1d4ed8
+#[derive(Clone, Debug, Default, Deserialize, Serialize, juniper::GraphQLObject)]
+pub struct Palette {
+    pub primary: Option<String>,
+    pub secondary: Option<String>,
+    pub accent: Option<String>,
+    pub background: Option<String>,
+    pub text: Option<String>,
+}
+
+// Font family names
+#[derive(Clone, Debug, Default, Deserialize, Serialize, juniper::GraphQLObject)]
+pub struct Fonts {
+    pub heading: Option<String>,
+    pub body: Option<String>,
+    pub mono: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+pub struct LayoutFlag {
+    pub name: String,
+    pub enabled: bool,
+}
+
+#[derive(Debug, juniper::GraphQLInputObject)]
+pub struct ThemeInput {
+    pub palette: Option<PaletteInput>,
+    pub fonts: Option<FontsInput>,
+    pub layout: Option<Vec<LayoutFlagInput>>,
+}
+
+#[derive(Debug, juniper::GraphQLInputObject)]
+pub struct PaletteInput {
+    pub primary: Option<String>,
+    pub secondary: Option<String>,
+    pub accent: Option<String>,
+    pub background: Option<String>,
+    pub text: Option<String>,
+}
+
+#[derive(Debug, juniper::GraphQLInputObject)]
+pub struct FontsInput {
+    pub heading: Option<String>,
+    pub body: Option<String>,
+    pub mono: Option<String>,
+}
+
+#[derive(Debug, juniper::GraphQLInputObject)]
+pub struct LayoutFlagInput {
+    pub name: String,
+    pub enabled: bool,
+}
+
+impl ThemeInput {
+    // Apply the sections given to `theme`, sections left out keep their current values
+    pub fn apply(self, theme: &mut Theme) -> Result<(), String> {
+        if let Some(palette) = self.palette {
+            let colors = [
+                &palette.primary,
+                &palette.secondary,
+                &palette.accent,
+                &palette.background,
+                &palette.text,
+            ];
+            if let Some(invalid) = colors.into_iter().flatten().find(|color| !is_hex_color(color)) {
+                return Err(format!("{} is not a hex color like #1d4ed8", invalid));
+            }
+            theme.palette = Palette {
+                primary: palette.primary,
+                secondary: palette.secondary,
+                accent: palette.accent,
+                background: palette.background,
+                text: palette.text,
+            };
+        }
+        if let Some(fonts) = self.fonts {
+            theme.fonts = Fonts {
+                heading: fonts.heading,
+                body: fonts.body,
+                mono: fonts.mono,
+            };
+        }
+        if let Some(layout) = self.layout {
+            theme.layout = layout
+                .into_iter()
+                .map(|flag| LayoutFlag { name: flag.name, enabled: flag.enabled })
+                .collect();
+        }
+        Ok(())
+    }
+}
+
+// #rgb, #rrggbb or #rrggbbaa
+fn is_hex_color(color: &str) -> bool {
+    color.strip_prefix('#').is_some_and(|hex| {
+        matches!(hex.len(), 3 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit())
+    })
+}
+
+pub async fn load(store: &dyn DataStore, owner_email: &str) -> Result<Theme, StoreError> {
+    let theme = settings::load(store, owner_email)
+        .await?
+        .and_then(|mut settings| settings.get_mut("theme").map(serde_json::Value::take));
+    Ok(theme.map(serde_json::from_value).transpose()?.unwrap_or_default())
+}
+
+pub async fn save(store: &dyn DataStore, owner_email: &str, theme: &Theme) -> Result<(), StoreError> {
+    settings::save(store, owner_email, json!({ "theme": theme })).await
+}
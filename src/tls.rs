@@ -0,0 +1,108 @@
+use axum::Router;
+use rustls_acme::{caches::DirCache, AcmeConfig};
+use axum_server::tls_rustls::RustlsConfig;
+use std::{
+    env,
+    net::{SocketAddr, TcpListener},
+};
+use tokio_stream::StreamExt;
+
+use crate::tuning;
+
+// How the server terminates TLS, picked from the environment at startup
+pub enum TlsMode {
+    // Plain HTTP, the default when nothing TLS related is set
+    Disabled,
+    // Certificate and private key read from PEM files on disk
+    Files { cert_path: String, key_path: String },
+    // Certificates requested and renewed automatically through ACME (Let's Encrypt)
+    Acme {
+        domains: Vec<String>,
+        contacts: Vec<String>,
+        cache_dir: Option<String>,
+        production: bool,
+    },
+}
+
+impl TlsMode {
+    pub fn from_env() -> Self {
+        let acme_domains = list_from_env("ACME_DOMAINS");
+        if !acme_domains.is_empty() {
+            return TlsMode::Acme {
+                domains: acme_domains,
+                contacts: list_from_env("ACME_CONTACT"),
+                cache_dir: env::var("ACME_CACHE_DIR").ok(),
+                production: env::var("ACME_PRODUCTION")
+                    .map(|value| value == "true" || value == "1")
+                    .unwrap_or(false),
+            };
+        }
+        match (env::var("TLS_CERT_PATH"), env::var("TLS_KEY_PATH")) {
+            (Ok(cert_path), Ok(key_path)) => TlsMode::Files { cert_path, key_path },
+            (Ok(_), Err(_)) | (Err(_), Ok(_)) => {
+                panic!("TLS_CERT_PATH and TLS_KEY_PATH must be set together")
+            }
+            _ => TlsMode::Disabled,
+        }
+    }
+}
+
+// Serve the app on an already bound listener, wrapping it in TLS when configured
+pub async fn serve(app: Router, listener: TcpListener, mode: TlsMode) {
+    listener.set_nonblocking(true).expect("Failed to make the listener non-blocking");
+    match mode {
+        TlsMode::Disabled => {
+            let listener = tokio::net::TcpListener::from_std(listener)
+                .expect("Failed to register the listener");
+            tuning::serve(app, listener).await;
+        }
+        TlsMode::Files { cert_path, key_path } => {
+            let config = RustlsConfig::from_pem_file(&cert_path, &key_path)
+                .await
+                .expect("Failed to load TLS certificate or key");
+            println!("Serving HTTPS with certificate {}", cert_path);
+            let mut server = axum_server::from_tcp_rustls(listener, config);
+            tuning::configure(server.http_builder());
+            server
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+                .unwrap();
+        }
+        TlsMode::Acme { domains, contacts, cache_dir, production } => {
+            let mut state = AcmeConfig::new(domains)
+                .contact(contacts.iter().map(|contact| format!("mailto:{}", contact)))
+                .cache_option(cache_dir.map(DirCache::new))
+                .directory_lets_encrypt(production)
+                .state();
+            let acceptor = state.axum_acceptor(state.default_rustls_config());
+            // Drive certificate ordering and renewal in the background
+            tokio::spawn(async move {
+                while let Some(event) = state.next().await {
+                    match event {
+                        Ok(ok) => println!("ACME event: {:?}", ok),
+                        Err(err) => eprintln!("ACME error: {:?}", err),
+                    }
+                }
+            });
+            println!("Serving HTTPS with ACME certificates");
+            let mut server = axum_server::from_tcp(listener).acceptor(acceptor);
+            tuning::configure(server.http_builder());
+            server
+                .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+                .unwrap();
+        }
+    }
+}
+
+fn list_from_env(key: &str) -> Vec<String> {
+    env::var(key)
+        .map(|value| {
+            value
+                .split(',')
+                .map(|item| item.trim().to_string())
+                .filter(|item| !item.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
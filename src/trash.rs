@@ -0,0 +1,142 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::{
+    config,
+    store::{DataStore, StoreError},
+};
+
+// A portfolio document deleted through the admin API. Deleted documents are
+// moved to the trash collection rather than flagged where they are, so they
+// drop out of every query and don't hold on to their slug in the unique indexes.
+#[derive(Debug, Deserialize, juniper::GraphQLObject)]
+pub struct TrashedDocument {
+    #[serde(rename = "_id", deserialize_with = "object_id_hex")]
+    pub id: String,
+    pub collection: String,
+    // ISO 8601, e.g. 2024-03-01T09:00:00Z
+    #[serde(rename = "deletedAt")]
+    pub deleted_at: String,
+    // slug, title or name of the document, whichever it has
+    #[serde(default)]
+    pub label: String,
+    // The document as it was stored, as JSON
+    #[serde(deserialize_with = "as_json")]
+    pub document: String,
+}
+
+fn object_id_hex<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(ObjectId::deserialize(deserializer)?.to_hex())
+}
+
+fn as_json<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(Value::deserialize(deserializer)?.to_string())
+}
+
+// ids are matched through their extended JSON form so every backend stores them alike
+fn entry_filter(owner_email: &str, id: ObjectId) -> Value {
+    json!({ "_id": { "$oid": id.to_hex() }, "email": owner_email })
+}
+
+// Move the owner's document matching `filter` from `collection` to the trash,
+// returning it. None when there is no such document.
+pub async fn discard(
+    store: &dyn DataStore,
+    owner_email: &str,
+    collection: &str,
+    filter: Value,
+) -> Result<Option<Value>, StoreError> {
+    let Some(document) = store.find_one(collection, filter.clone()).await? else {
+        return Ok(None);
+    };
+    let label = ["slug", "title", "name"]
+        .iter()
+        .find_map(|field| document.get(field).and_then(Value::as_str))
+        .unwrap_or_default();
+    let entry = json!({
+        "_id": ObjectId::new(),
+        "email": owner_email,
+        "collection": collection,
+        "deletedAt": DateTime::now().try_to_rfc3339_string()?,
+        "label": label,
+        "document": document,
+    });
+    // written to the trash first, so a failure in between leaves a copy rather than nothing
+    store.insert_one(&config::collections().trash, entry).await?;
+    store.delete_one(collection, filter).await?;
+    Ok(Some(document))
+}
+
+// The owner's trashed documents, of one collection when given, most recently deleted first
+pub async fn list(
+    store: &dyn DataStore,
+    owner_email: &str,
+    collection: Option<&str>,
+) -> Result<Vec<TrashedDocument>, StoreError> {
+    let mut filter = json!({ "email": owner_email });
+    if let Some(collection) = collection {
+        filter["collection"] = json!(collection);
+    }
+    let mut entries: Vec<TrashedDocument> = store
+        .find(&config::collections().trash, filter)
+        .await?
+        .into_iter()
+        .map(serde_json::from_value)
+        .collect::<Result<_, _>>()?;
+    entries.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at));
+    Ok(entries)
+}
+
+// Put a trashed document back into its collection, returns whether it was in the trash
+pub async fn restore(store: &dyn DataStore, owner_email: &str, id: ObjectId) -> Result<bool, StoreError> {
+    let trash = &config::collections().trash;
+    let filter = entry_filter(owner_email, id);
+    let Some(mut entry) = store.find_one(trash, filter.clone()).await? else {
+        return Ok(false);
+    };
+    let collection = entry["collection"].as_str().unwrap_or_default().to_string();
+    if !config::collections().portfolio().contains(&collection.as_str()) {
+        return Err(format!("Trashed document is from unknown collection {:?}", collection).into());
+    }
+    store.insert_one(&collection, entry["document"].take()).await?;
+    store.delete_one(trash, filter).await
+}
+
+// Delete a trashed document for good, returns whether it was in the trash. Likes
+// and endorsements go with the last variant of their project or skill.
+pub async fn purge(store: &dyn DataStore, owner_email: &str, id: ObjectId) -> Result<bool, StoreError> {
+    let collections = config::collections();
+    let filter = entry_filter(owner_email, id);
+    let Some(entry) = store.find_one(&collections.trash, filter.clone()).await? else {
+        return Ok(false);
+    };
+    store.delete_one(&collections.trash, filter).await?;
+    // the label is the slug of projects and the name of skills
+    let label = &entry["label"];
+    let dependents = match entry["collection"].as_str() {
+        Some(name) if name == collections.projects => Some((
+            &collections.projects,
+            json!({ "email": owner_email, "slug": label }),
+            &collections.likes,
+            json!({ "email": owner_email, "project": label }),
+        )),
+        Some(name) if name == collections.skills => Some((
+            &collections.skills,
+            json!({ "email": owner_email, "name": label }),
+            &collections.endorsements,
+            json!({ "email": owner_email, "skill": label }),
+        )),
+        _ => None,
+    };
+    if let Some((collection, variants, dependent, references)) = dependents {
+        // other locale variants, live or still in the trash, keep them
+        let trashed = json!({ "email": owner_email, "collection": collection, "label": label });
+        let in_use = store.find_one(collection, variants).await?.is_some()
+            || store.find_one(&collections.trash, trashed).await?.is_some();
+        if !in_use {
+            store.delete_many(dependent, references).await?;
+        }
+    }
+    Ok(true)
+}
@@ -0,0 +1,102 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router,
+};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo, TokioTimer},
+    server::conn::auto::Builder,
+    service::TowerToHyperService,
+};
+use std::{io, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{
+    net::{TcpListener, TcpSocket},
+    sync::{OwnedSemaphorePermit, Semaphore},
+};
+use tower_http::add_extension::AddExtension;
+
+use crate::config;
+
+// Bind a TCP listener with the configured listen backlog
+pub fn bind(address: SocketAddr) -> io::Result<std::net::TcpListener> {
+    let socket = if address.is_ipv4() { TcpSocket::new_v4()? } else { TcpSocket::new_v6()? };
+    socket.set_reuseaddr(true)?;
+    socket.bind(address)?;
+    socket.listen(config::app().listen_backlog)?.into_std()
+}
+
+// Apply the keep-alive setting to a connection builder. HTTP/2 connections stay
+// open until the client closes them.
+pub fn configure(builder: &mut Builder<TokioExecutor>) {
+    let keep_alive_timeout = config::app().keep_alive_timeout;
+    let mut http1 = builder.http1();
+    if keep_alive_timeout == 0 {
+        http1.keep_alive(false);
+    } else {
+        http1
+            .timer(TokioTimer::new())
+            .header_read_timeout(Duration::from_secs(keep_alive_timeout));
+    }
+}
+
+// Slots for open connections, None when they aren't limited
+pub fn connection_slots() -> Option<Arc<Semaphore>> {
+    match config::app().max_connections {
+        0 => None,
+        max_connections => Some(Arc::new(Semaphore::new(max_connections))),
+    }
+}
+
+// Wait for a free connection slot. Connections aren't accepted meanwhile, so new
+// ones queue in the listen backlog.
+pub async fn acquire(slots: &Option<Arc<Semaphore>>) -> Option<OwnedSemaphorePermit> {
+    match slots {
+        Some(slots) => slots.clone().acquire_owned().await.ok(),
+        None => None,
+    }
+}
+
+// Serve plain HTTP on a TCP listener
+pub async fn serve(app: Router, listener: TcpListener) {
+    let slots = connection_slots();
+    let mut builder = Builder::new(TokioExecutor::new());
+    configure(&mut builder);
+    let builder = Arc::new(builder);
+    loop {
+        let permit = acquire(&slots).await;
+        let (socket, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                eprintln!("Error accepting connection: {}", e);
+                continue;
+            }
+        };
+        let service = TowerToHyperService::new(AddExtension::new(app.clone(), ConnectInfo(peer)));
+        let builder = builder.clone();
+        tokio::spawn(async move {
+            if let Err(e) = builder
+                .serve_connection_with_upgrades(TokioIo::new(socket), service)
+                .await
+            {
+                eprintln!("Error serving connection from {}: {}", peer, e);
+            }
+            drop(permit);
+        });
+    }
+}
+
+// Answer 503 right away once MAX_IN_FLIGHT_REQUESTS are being handled, so a
+// traffic spike is turned away cheaply instead of queueing behind slow requests
+pub async fn shed_load(State(in_flight): State<Arc<Semaphore>>, request: Request, next: Next) -> Response {
+    match in_flight.try_acquire() {
+        Ok(_permit) => next.run(request).await,
+        Err(_) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            [(header::RETRY_AFTER, "1")],
+            "Server is busy, try again shortly",
+        )
+            .into_response(),
+    }
+}
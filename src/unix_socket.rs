@@ -0,0 +1,68 @@
+use axum::{middleware, Router};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto::Builder,
+    service::TowerToHyperService,
+};
+use std::{env, fs, os::unix::fs::PermissionsExt, path::Path, sync::Arc};
+use tokio::net::UnixListener;
+
+use crate::{admin, tuning};
+
+// Bind a Unix domain socket, for running behind a local nginx or caddy
+pub fn bind(path: &str) -> std::os::unix::net::UnixListener {
+    // a socket left behind by a previous run would make bind fail
+    if Path::new(path).exists() {
+        fs::remove_file(path).expect("Failed to remove stale Unix socket");
+    }
+    let listener = std::os::unix::net::UnixListener::bind(path).expect("Failed to bind to Unix socket");
+    // UNIX_SOCKET_MODE, e.g. 660, lets the proxy user connect
+    if let Ok(mode) = env::var("UNIX_SOCKET_MODE") {
+        let mode = u32::from_str_radix(&mode, 8).expect("UNIX_SOCKET_MODE must be an octal mode");
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))
+            .expect("Failed to set Unix socket permissions");
+    }
+    println!("Listening on Unix socket {}", path);
+    listener
+}
+
+pub async fn serve(app: Router, listener: std::os::unix::net::UnixListener) {
+    listener.set_nonblocking(true).expect("Failed to make the listener non-blocking");
+    let listener = UnixListener::from_std(listener).expect("Failed to register the listener");
+    // there is no peer address on a Unix socket, the proxy passes the client in the
+    // trusted header. Without one clients stay unknown and admin routes reject them.
+    let app = match admin::trusted_proxy_header().expect("Invalid TRUSTED_PROXY_HEADER") {
+        Some(header) => {
+            app.layer(middleware::from_fn_with_state(Arc::new(header), admin::connect_info_from_proxy))
+        }
+        None => {
+            println!("TRUSTED_PROXY_HEADER is not set, admin routes reject every Unix socket client");
+            app
+        }
+    };
+    let slots = tuning::connection_slots();
+    let mut builder = Builder::new(TokioExecutor::new());
+    tuning::configure(&mut builder);
+    let builder = Arc::new(builder);
+    loop {
+        let permit = tuning::acquire(&slots).await;
+        let (socket, _) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                eprintln!("Error accepting Unix socket connection: {}", e);
+                continue;
+            }
+        };
+        let service = TowerToHyperService::new(app.clone());
+        let builder = builder.clone();
+        tokio::spawn(async move {
+            if let Err(e) = builder
+                .serve_connection_with_upgrades(TokioIo::new(socket), service)
+                .await
+            {
+                eprintln!("Error serving Unix socket connection: {}", e);
+            }
+            drop(permit);
+        });
+    }
+}
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Context;
+
+// Hardware, software or a service listed on the /uses page, identified by its name
+#[derive(Clone, Debug, Deserialize, Serialize, juniper::GraphQLObject)]
+#[graphql(context = Context)]
+#[serde(rename_all = "camelCase")]
+pub struct UsesItem {
+    #[graphql(ignore)]
+    #[serde(default)]
+    pub email: String,
+    pub name: String,
+    // e.g. Desk, Editor, Terminal
+    pub category: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    // Position in the category, items without one come last
+    #[serde(default)]
+    pub order: Option<i32>,
+    #[serde(default)]
+    pub locale: Option<String>,
+}
@@ -0,0 +1,202 @@
+use chrono::NaiveDate;
+use juniper::{graphql_value, FieldError, Object, Value};
+use std::{collections::HashSet, ops::RangeInclusive};
+
+use crate::store::DataStore;
+
+// Longest names, labels and titles, in characters
+pub const MAX_NAME_CHARS: usize = 100;
+// Longest free text such as captions and alt texts
+pub const MAX_TEXT_CHARS: usize = 1000;
+// Longest descriptions and other multi-paragraph text
+pub const MAX_DESCRIPTION_CHARS: usize = 10_000;
+pub const MAX_SLUG_CHARS: usize = 100;
+pub const MAX_URL_CHARS: usize = 2048;
+// Longest host name DNS allows
+const MAX_HOST_CHARS: usize = 253;
+
+#[derive(Debug)]
+struct Violation {
+    field: String,
+    message: String,
+}
+
+// Collects what is wrong with a mutation's arguments so they are all reported
+// at once, as an INVALID_INPUT error listing each field with its problem,
+// before anything is written
+#[derive(Debug, Default)]
+pub struct Validator {
+    violations: Vec<Violation>,
+}
+
+impl Validator {
+    pub fn reject(&mut self, field: &str, message: impl Into<String>) {
+        self.violations.push(Violation {
+            field: field.to_string(),
+            message: message.into(),
+        });
+    }
+
+    // Non-blank text of at most `max` characters
+    pub fn text(&mut self, field: &str, value: &str, max: usize) {
+        if value.trim().is_empty() {
+            self.reject(field, "must not be blank");
+        } else {
+            self.optional_text(field, Some(value), max);
+        }
+    }
+
+    pub fn optional_text(&mut self, field: &str, value: Option<&str>, max: usize) {
+        if value.is_some_and(|value| value.chars().count() > max) {
+            self.reject(field, format!("must be at most {} characters", max));
+        }
+    }
+
+    // Lowercase letters and digits in words joined by single hyphens, e.g. my-project-2
+    pub fn slug(&mut self, field: &str, value: &str) {
+        if value.len() > MAX_SLUG_CHARS {
+            self.reject(field, format!("must be at most {} characters", MAX_SLUG_CHARS));
+        } else if !is_slug(value) {
+            self.reject(field, "must be lowercase letters, digits and single hyphens, e.g. my-project");
+        }
+    }
+
+    // Where an image or page lives: an absolute http(s) URL or a path on this site
+    pub fn url(&mut self, field: &str, value: &str) {
+        if value.chars().count() > MAX_URL_CHARS {
+            self.reject(field, format!("must be at most {} characters", MAX_URL_CHARS));
+        } else if !is_site_path(value) && !is_web_url(value) {
+            self.reject(field, "must be an http(s) URL or a path starting with /");
+        }
+    }
+
+    // Target of a link, like url but also an #anchor, mailto: or tel: link
+    pub fn href(&mut self, field: &str, value: &str) {
+        let other_link = ["#", "mailto:", "tel:"].iter().any(|prefix| value.starts_with(prefix))
+            && !value.contains(char::is_whitespace);
+        if !other_link {
+            self.url(field, value);
+        }
+    }
+
+    pub fn email(&mut self, field: &str, value: &str) {
+        if !looks_like_email(value) {
+            self.reject(field, "must be an email address");
+        }
+    }
+
+    // A host name like portfolio.example.com, without scheme or port
+    pub fn host(&mut self, field: &str, value: &str) {
+        let labels_ok = value.split('.').all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+        if value.len() > MAX_HOST_CHARS || !labels_ok {
+            self.reject(field, "must be a host name like portfolio.example.com");
+        }
+    }
+
+    // ISO 8601 date, e.g. 2024-03-01
+    pub fn date(&mut self, field: &str, value: &str) {
+        if NaiveDate::parse_from_str(value, "%Y-%m-%d").is_err() {
+            self.reject(field, "must be a date like 2024-03-01");
+        }
+    }
+
+    pub fn range(&mut self, field: &str, value: i32, range: RangeInclusive<i32>) {
+        if !range.contains(&value) {
+            self.reject(field, format!("must be between {} and {}", range.start(), range.end()));
+        }
+    }
+
+    // Lists where each value may only appear once, e.g. the slugs of a reordering
+    pub fn distinct<'a>(&mut self, field: &str, values: impl IntoIterator<Item = &'a str>) {
+        let mut seen = HashSet::new();
+        let duplicates: Vec<&str> = values.into_iter().filter(|value| !seen.insert(*value)).collect();
+        if !duplicates.is_empty() {
+            self.reject(field, format!("lists {} more than once", duplicates.join(", ")));
+        }
+    }
+
+    // Ok when nothing was rejected, otherwise one error carrying every violation
+    // in extensions.fields
+    pub fn finish(self) -> Result<(), FieldError> {
+        if self.violations.is_empty() {
+            return Ok(());
+        }
+        let details = self
+            .violations
+            .iter()
+            .map(|violation| format!("{} {}", violation.field, violation.message))
+            .collect::<Vec<_>>()
+            .join("; ");
+        let fields = self
+            .violations
+            .into_iter()
+            .map(|violation| graphql_value!({ "field": violation.field, "message": violation.message }))
+            .collect();
+        let mut extensions = Object::with_capacity(3);
+        extensions.add_field("code", graphql_value!("INVALID_INPUT"));
+        extensions.add_field("details", Value::scalar(details));
+        extensions.add_field("fields", Value::list(fields));
+        Err(FieldError::new("Invalid input", Value::object(extensions)))
+    }
+}
+
+// Slug made of the words of `text`, e.g. "Hello, World!" becomes hello-world.
+// Empty when the text has no ASCII letters or digits.
+pub fn slugify(text: &str, max: usize) -> String {
+    let mut slug = String::new();
+    let words = text.split(|c: char| !c.is_ascii_alphanumeric()).filter(|word| !word.is_empty());
+    for word in words {
+        if !slug.is_empty() {
+            if slug.len() + 1 + word.len() > max {
+                break;
+            }
+            slug.push('-');
+        }
+        slug.push_str(&word.to_ascii_lowercase());
+    }
+    slug.truncate(max);
+    slug
+}
+
+// Whether a document matching `filter` exists already, to reject duplicates
+// before inserting
+pub async fn exists(store: &dyn DataStore, collection: &str, filter: serde_json::Value) -> Result<bool, FieldError> {
+    match store.find_one(collection, filter).await {
+        Ok(found) => Ok(found.is_some()),
+        Err(err) => Err(FieldError::new(
+            "Failed to check for duplicates",
+            graphql_value!({ "details": err.to_string() }),
+        )),
+    }
+}
+
+fn is_slug(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .split('-')
+            .all(|word| !word.is_empty() && word.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()))
+}
+
+// A path like /uploads/cover.png, but not a protocol relative //host/...
+fn is_site_path(value: &str) -> bool {
+    value.starts_with('/') && !value.starts_with("//") && !value.contains(char::is_whitespace)
+}
+
+fn is_web_url(value: &str) -> bool {
+    reqwest::Url::parse(value)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.host_str().is_some())
+}
+
+// Loose on purpose, the owner's email is only used to find their documents
+pub fn looks_like_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+    let labels_ok = domain.split('.').count() > 1 && domain.split('.').all(|label| !label.is_empty());
+    !local.is_empty() && !domain.contains('@') && labels_ok && !email.contains(char::is_whitespace)
+}
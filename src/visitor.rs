@@ -0,0 +1,63 @@
+use axum::http::{header, HeaderMap};
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
+use std::{env, net::IpAddr, sync::OnceLock};
+
+static SALT: OnceLock<String> = OnceLock::new();
+
+// Secret mixed into visitor hashes so they can't be reversed into IP addresses.
+// Without VISITOR_SALT a random one is used, so hashes change on every restart.
+fn salt() -> &'static str {
+    SALT.get_or_init(|| {
+        env::var("VISITOR_SALT").ok().filter(|salt| !salt.is_empty()).unwrap_or_else(|| {
+            tracing::warn!(
+                "VISITOR_SALT is not set, using a random value: endorsements, likes and unique \
+                 visitor counts won't recognize returning visitors after a restart"
+            );
+            rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(32)
+                .map(char::from)
+                .collect()
+        })
+    })
+}
+
+// Anonymous identity of whoever sent the request, derived without cookies
+#[derive(Clone, Debug, Default)]
+pub struct Visitor {
+    ip: Option<IpAddr>,
+    user_agent: String,
+}
+
+impl Visitor {
+    pub fn new(ip: Option<IpAddr>, headers: &HeaderMap) -> Self {
+        let user_agent = headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        Self { ip, user_agent }
+    }
+
+    pub fn ip(&self) -> Option<IpAddr> {
+        self.ip
+    }
+
+    pub fn user_agent(&self) -> &str {
+        &self.user_agent
+    }
+
+    // Salted hash identifying the visitor within `scope`, the same visitor hashes
+    // differently in different scopes so hashes can't be joined across features
+    pub fn hash(&self, scope: &str) -> String {
+        let ip = self.ip.map(|ip| ip.to_string()).unwrap_or_default();
+        hash_id(scope, &format!("{}|{}", ip, self.user_agent))
+    }
+}
+
+// Salted hash of an identifier within `scope`, for ids that shouldn't be stored as is
+pub fn hash_id(scope: &str, id: &str) -> String {
+    let input = format!("{}|{}|{}", salt(), scope, id);
+    format!("{:x}", Sha256::digest(input.as_bytes()))
+}
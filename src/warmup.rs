@@ -0,0 +1,47 @@
+use serde_json::json;
+use std::time::Instant;
+
+use crate::{
+    blog, config, project_sort,
+    store::{DataStore, FindOptions, StoreError},
+    tenant::Tenancy,
+};
+
+// Read what the home page and blog index need for every owner so the first
+// visitor after a deploy is served from the cache. The reads match the ones
+// the resolvers make, cached results are keyed by filter and options.
+pub async fn run(store: &dyn DataStore, tenancy: &Tenancy) -> Result<(), StoreError> {
+    if config::app().cache_ttl == 0 {
+        return Ok(());
+    }
+    let started = Instant::now();
+    let collections = config::collections();
+    let owners: Vec<String> = match tenancy {
+        Tenancy::Single { owner_email } => vec![owner_email.clone()],
+        Tenancy::Multi => store
+            .find(&collections.tenants, json!({}))
+            .await?
+            .into_iter()
+            .filter_map(|tenant| tenant.get("email")?.as_str().map(str::to_string))
+            .collect(),
+    };
+    for owner_email in &owners {
+        for collection in [&collections.introductions, &collections.personals, &collections.skills] {
+            store.find(collection, json!({ "email": owner_email })).await?;
+        }
+        let options = FindOptions {
+            sort: project_sort(None),
+            ..Default::default()
+        };
+        store
+            .find_with(&collections.projects, json!({ "email": owner_email }), options)
+            .await?;
+        store.find(&collections.blog_posts, blog::published_filter(owner_email)).await?;
+    }
+    tracing::info!(
+        owners = owners.len(),
+        elapsed_ms = started.elapsed().as_millis() as u64,
+        "Warmed the cache"
+    );
+    Ok(())
+}